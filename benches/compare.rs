@@ -0,0 +1,18 @@
+//! Runs the comparison harness and prints its report.
+//!
+//! `cargo bench --bench compare --no-default-features -- [n] [load_factor] [seed]`,
+//! with defaults of 1,000,000 keys, a load factor of 0.95 and seed 0.
+
+use rb_elastic_hash::compare::{compare, Workload};
+
+fn main() {
+    // `cargo bench` passes `--bench` along with the arguments.
+    let args: Vec<String> = std::env::args().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    let arg = |i: usize, default: &str| args.get(i).map_or(default, String::as_str).to_string();
+    let workload = Workload {
+        n: arg(0, "1000000").parse().expect("n must be an integer"),
+        load_factor: arg(1, "0.95").parse().expect("load_factor must be a number"),
+        seed: arg(2, "0").parse().expect("seed must be an integer"),
+    };
+    print!("{}", compare(&workload));
+}
@@ -0,0 +1,5 @@
+fn main() {
+    // napi-rs needs extra linker arguments for the Node addon.
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
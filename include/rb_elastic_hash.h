@@ -0,0 +1,171 @@
+/* C API of rb_elastic_hash, built with the `capi` feature. Generated by cbindgen; do not edit. */
+
+#ifndef RB_ELASTIC_HASH_H
+#define RB_ELASTIC_HASH_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * `eh_*_insert` succeeded.
+ */
+#define EH_OK 0
+
+/**
+ * `eh_*_insert` scanned the last subarray without finding a free slot.
+ */
+#define EH_FULL 1
+
+/**
+ * `eh_*_insert` was turned away by every subarray's probe budget.
+ */
+#define EH_NO_SLOT 2
+
+/**
+ * An opaque handle to a table with `double` values.
+ */
+typedef struct EhFloatTable EhFloatTable;
+
+/**
+ * An opaque handle to a table with `int64_t` values.
+ */
+typedef struct EhIntTable EhIntTable;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a table with `capacity` slots and elasticity `delta`. Returns
+ * NULL unless `delta` lies strictly between 0 and 1. Free the table with
+ * `eh_int_table_free`.
+ */
+EhIntTable *eh_int_table_new(size_t capacity, double delta);
+
+/**
+ * Frees a table. Does nothing for NULL.
+ *
+ * # Safety
+ *
+ * `table` must be NULL or a table from `eh_int_table_new` that was not freed yet.
+ */
+void eh_int_table_free(EhIntTable *table);
+
+/**
+ * Inserts or updates `key`. Returns `EH_OK`, or `EH_FULL` or `EH_NO_SLOT` if
+ * a new key could not be placed.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+int32_t eh_int_table_insert(EhIntTable *table, uint64_t key, int64_t value);
+
+/**
+ * Looks `key` up. Returns whether it is stored, and if so writes its value
+ * to `*value`.
+ *
+ * # Safety
+ *
+ * `table` must be a live table and `value` valid for a write.
+ */
+bool eh_int_table_get(const EhIntTable *table, uint64_t key, int64_t *value);
+
+/**
+ * Removes `key`. Returns whether it was stored.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+bool eh_int_table_remove(EhIntTable *table, uint64_t key);
+
+/**
+ * Number of stored entries.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+size_t eh_int_table_len(const EhIntTable *table);
+
+/**
+ * Number of slots the table was created with.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+size_t eh_int_table_capacity(const EhIntTable *table);
+
+/**
+ * Creates a table with `capacity` slots and elasticity `delta`. Returns
+ * NULL unless `delta` lies strictly between 0 and 1. Free the table with
+ * `eh_float_table_free`.
+ */
+EhFloatTable *eh_float_table_new(size_t capacity, double delta);
+
+/**
+ * Frees a table. Does nothing for NULL.
+ *
+ * # Safety
+ *
+ * `table` must be NULL or a table from `eh_float_table_new` that was not freed yet.
+ */
+void eh_float_table_free(EhFloatTable *table);
+
+/**
+ * Inserts or updates `key`. Returns `EH_OK`, or `EH_FULL` or `EH_NO_SLOT` if
+ * a new key could not be placed.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+int32_t eh_float_table_insert(EhFloatTable *table, uint64_t key, double value);
+
+/**
+ * Looks `key` up. Returns whether it is stored, and if so writes its value
+ * to `*value`.
+ *
+ * # Safety
+ *
+ * `table` must be a live table and `value` valid for a write.
+ */
+bool eh_float_table_get(const EhFloatTable *table, uint64_t key, double *value);
+
+/**
+ * Removes `key`. Returns whether it was stored.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+bool eh_float_table_remove(EhFloatTable *table, uint64_t key);
+
+/**
+ * Number of stored entries.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+size_t eh_float_table_len(const EhFloatTable *table);
+
+/**
+ * Number of slots the table was created with.
+ *
+ * # Safety
+ *
+ * `table` must be a live table.
+ */
+size_t eh_float_table_capacity(const EhFloatTable *table);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RB_ELASTIC_HASH_H */
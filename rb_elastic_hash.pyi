@@ -0,0 +1,2628 @@
+# Generated by `cargo run -p xtask -- stubs` from the #[pymethods] blocks in src/; do not edit.
+
+import os
+from typing import Any, Generic, Iterable, Iterator, Mapping, Sequence, TypeVar
+
+_K = TypeVar("_K")
+_V = TypeVar("_V")
+
+
+class ElasticHashError(ValueError):
+    """Base class of the failures specific to elastic hash tables."""
+
+
+class TableFullError(ElasticHashError):
+    """Raised when an insert scanned the last subarray completely without finding a free slot."""
+
+
+class InsertProbeLimitError(ElasticHashError):
+    """Raised when every subarray was skipped or exhausted its probe budget during an insert."""
+
+
+class CorruptedDataError(ElasticHashError):
+    """Raised when a saved table, checkpoint or mapped file fails validation."""
+
+
+class TableClosedError(ElasticHashError):
+    """Raised when a table is used after `close()`."""
+
+
+class LoadFactorWarning(UserWarning):
+    """Issued when a table's load factor crosses the threshold of `set_load_alert()`."""
+
+
+class ElasticTable(Generic[_K, _V]):
+    def __init__(self, capacity: int, delta: float = 0.05, intern: bool = False, weak: bool = False, ordered: bool = False, growth_factor: float | None = None, grow_at: float | None = None, max_capacity: int | None = None, value_type: Any | None = None) -> None:
+        """Create a new ElasticTable with specified capacity and delta parameter.
+
+        Args:
+            capacity: Total number of slots in the hash table
+            delta: Elasticity parameter (default: 0.05). Target load factor = 1 - delta.
+                   Lower delta = higher load factor but may increase probe count.
+                   Recommended range: 0.05 to 0.20
+            intern: Store each distinct value once and have slots refer to it
+                    (default: False). Worth it when many keys share few values.
+            weak: Hold values through weak references (default: False), so the
+                  table does not keep them alive. Entries whose value has been
+                  collected count as missing.
+            ordered: Remember insertion order, like dict (default: False), so
+                     `keys()`, `values()`, `items()` and iteration follow it.
+                     Costs 8 bytes per slot.
+            growth_factor: Grow the table by this factor, instead of raising
+                           TableFullError, when it fills up (default: None,
+                           fixed capacity). Must be greater than 1.
+            grow_at: Load factor an insert must not take the table past
+                     before it grows (default: 1 - delta).
+            max_capacity: Capacity the table never grows past (default: None,
+                          no ceiling). Inserts fail as usual once it is reached.
+            value_type: A type or tuple of types every value must be an instance
+                        of, or a callable returning whether a value is acceptable
+                        (default: None, any value). Inserts of other values raise
+                        TypeError, as does a callable that raises it.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90, intern: bool = False, weak: bool = False, ordered: bool = False, value_type: Any | None = None) -> ElasticTable:
+        """Create an ElasticTable sized for a specific number of expected items.
+
+        Args:
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90). Must be between 0.5 and 0.98.
+                         Higher values = more space-efficient but slightly more probes.
+                         Recommended: 0.85-0.95
+
+        Example:
+            table = ElasticTable.for_items(1000000)  # Stores ~1M items at 90% load
+            table = ElasticTable.for_items(1000000, 0.95)  # More space-efficient
+        """
+
+    @staticmethod
+    def benchmark(n: int = 1_000_000, load_factor: float = 0.90) -> dict[Any, Any]:
+        """Time a standard workload on this machine, against a dict.
+        Inserts `n` distinct pseudo-random keys into a table sized for them at
+        `load_factor`, looks each one up again, then looks up `n` keys that are
+        absent, and repeats the same operations on a dict. Both are driven from
+        native code, so the cost of calling them from Python is left out.
+        Returns a dict with "n", the reached "load_factor" and "capacity";
+        "elastic" and "dict" dicts giving the seconds each phase took
+        ("insert_seconds", "hit_seconds", "miss_seconds") and the same per
+        operation in nanoseconds ("insert_ns", ...); and "probes", with the mean
+        and max probes of the table's inserts, hits and misses.
+        """
+
+    @staticmethod
+    def compare(n: int = 1_000_000, load_factor: float = 0.95, seed: int = 0) -> dict[Any, Any]:
+        """Run one workload through elastic hashing, plain, with `two_choices`
+        and with `robin_hood`, funnel hashing, Rust's `HashMap` and a dict,
+        for comparing them on the current machine.
+        `n` distinct keys derived from `seed` are inserted into tables sized
+        for them at `load_factor`, looked up again, and then `n` absent keys
+        are looked up; the same arguments always give the same keys. Returns a
+        dict with "n", "load_factor", "capacity", "seed", "tables", mapping
+        each table's name to its "insert_ns", "hit_ns", "miss_ns",
+        "failed_inserts" and, for the open addressing tables,
+        "insert_probes", "hit_probes" and "miss_probes" (each a dict of "mean"
+        and "max", otherwise None), and "report", the same as printable text.
+        """
+
+    @staticmethod
+    def from_json(data: str, load_factor: float = 0.90, intern: bool = False, weak: bool = False, ordered: bool = False) -> ElasticTable:
+        """Build a table from a JSON object mapping decimal keys to values, such as
+        the output of `to_json()`. Takes the same options as `for_items`.
+        """
+
+    def to_json(self, indent: int | None = None) -> str:
+        """Serialize the contents as a JSON object mapping each key, as a decimal
+        string, to its value. Keys appear in ascending order, so the output is
+        stable enough for golden-file tests. Values must be JSON-serializable.
+        """
+
+    @property
+    def ordered(self) -> bool:
+        """Whether the table remembers insertion order."""
+
+    @property
+    def value_type(self) -> Any | None:
+        """The `value_type` values are checked against, or None."""
+
+    def keys(self) -> _KeysView:
+        """A live view of the keys, in insertion order for ordered tables and in
+        slot order otherwise. Like a dict's, it reflects later changes and is
+        set-like: `&`, `|`, `-` and `^` take any iterable and return a set.
+        """
+
+    def values(self) -> _ValuesView:
+        """A live view of the values, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, value)` pairs, in the order of `keys()`."""
+
+    def bulk(self, buffer: bool = False) -> _BulkBlock:
+        """Start a block of inserts, for use as `with table.bulk():`.
+        Until the block ends, the tracer set with `set_tracer` is called once,
+        at the end, instead of after every insert. With `buffer=True`, `insert`
+        and `insert_many` also hold their entries back and return 0, and the
+        end of the block inserts them in one batch with the GIL released;
+        lookups do not see them until then.
+        `delete` and `delete_many` first insert what was held back, and
+        `clear` discards it. The block ends, and applies its inserts, even if
+        it raises.
+        """
+
+    def transaction(self) -> _Transaction:
+        """Open a transaction, so that a group of changes can be undone together.
+        Until the returned transaction's `commit()` or `rollback()`, the table
+        remembers the value every key held before the transaction first
+        changed it. `rollback()` puts those values back and removes keys the
+        transaction added; `commit()` keeps the changes. Used as
+        `with table.transaction():`, it commits when the block completes and
+        rolls back when it raises. The journal costs one entry per distinct key
+        changed. Only one transaction can be open at a time; values restored
+        by a rollback move to the end of an ordered table.
+        """
+
+    def close(self) -> None:
+        """Release every stored value now, and make later use of the table raise
+        `TableClosedError`.
+        A table that is simply dropped frees its values whenever its last
+        reference goes away, which for tens of millions of objects is a long
+        pause at an unpredictable point. `close()` frees them where it is
+        called, in chunks, and lets other threads run between chunks. Values
+        whose finalizers look at the table find it closed already. An open
+        transaction or `bulk()` block is discarded. Closing a closed table does
+        nothing. Used as `with table:`, the table is closed when the block ends.
+        """
+
+    @property
+    def closed(self) -> bool:
+        """Whether `close()` was called."""
+
+    def __enter__(self) -> ElasticTable: ...
+
+    def __exit__(self, _exc_type: type | None, _exc_value: Any | None, _traceback: Any | None) -> bool:
+        """Closes the table, whether or not the block raised."""
+
+    def __iter__(self) -> _TableIterator:
+        """Iterate over the keys, in the order of `keys()`. Like a `dict`, the
+        table must not gain or lose entries meanwhile: the iterator then
+        raises `RuntimeError`. Unordered tables are walked in place, without
+        copying their keys.
+        """
+
+    def sample(self, n: int, items: bool = False, seed: int | None = None) -> Any:
+        """`n` distinct keys chosen uniformly at random, or every key if the table
+        holds fewer, as a list in random order. With `items=True` the list holds
+        `(key, value)` pairs instead. Passing a `seed` makes the sample
+        repeatable for the same contents.
+        Well-filled tables are sampled by drawing random slots, so the cost
+        grows with `n` rather than with the table; only tables that are mostly
+        empty, or samples of most of the entries, take a pass over every slot.
+        """
+
+    def entry_at(self, index: int) -> tuple[int, Any]:
+        """The entry at position `index` of slot order, as a `(key, value)` pair.
+        Negative indices count from the end.
+        Positions number the entries subarray by subarray, in the order an
+        unordered table's `keys()` lists them, and only shift when entries are
+        inserted or removed. Finding one skips whole subarrays by their counts
+        and then counts control bytes, without visiting the entries before it.
+        """
+
+    def entry_range(self, start: int, stop: int | None = None) -> list[tuple[int, Any]]:
+        """The entries at positions `start` up to `stop` of slot order (see
+        `entry_at`), as `(key, value)` pairs, so workers can each export one
+        chunk of a table. `stop` defaults to, and is capped at, the number of entries.
+        """
+
+    def subarray_lens(self) -> list[int]:
+        """Number of entries in each subarray, in slot order, so that
+        `entry_range` chunks can follow subarray boundaries.
+        """
+
+    def insert(self, key: int, value: Any) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Sequence[Any]) -> int:
+        """Insert many key/value pairs in a single call.
+        Args:
+            keys: Sequence of integer keys, or a uint64 numpy array
+            values: Sequence of values, same length as `keys`
+        Returns the total number of probes used across all insertions.
+        Pairs are inserted in order; if one fails, the pairs before it stay inserted.
+        The GIL is released while probing.
+        """
+
+    def insert_all(self, keys: Iterable[int], values: Sequence[Any]) -> int:
+        """Insert many key/value pairs, all or none.
+        Takes the same arguments as `insert_many`. If some key cannot be
+        placed, the pairs already inserted are taken out again, the values they
+        replaced are put back, and the error is raised with the table as it was
+        before the call. Remembering the replaced values costs one lookup per
+        key. Returns the total probes used.
+        """
+
+    def get(self, key: int, default: Any | None = None) -> Any:
+        """The value stored under `key`, or `default` if the table does not hold
+        the key. A stored None comes back as None too; `table[key]` and
+        `key in table` tell it apart from a missing key.
+        """
+
+    def __getitem__(self, key: Any) -> Any:
+        """`table[key]`: the value stored under `key`. Raises KeyError if the
+        table does not hold the key.
+        """
+
+    def __contains__(self, key: Any) -> bool:
+        """`key in table`, which is True for keys holding None too."""
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call.
+        Accepts a sequence of integer keys or a uint64 numpy array.
+        Returns the number of keys that were actually present.
+        The GIL is released while probing.
+        """
+
+    def get_many(self, keys: Iterable[int], default: Any | None = None, mask: bool = False, defaults: Sequence[Any] | None = None) -> Any:
+        """Look up many keys in a single call.
+        Args:
+            keys: Sequence of integer keys, or a uint64 numpy array
+            default: Value returned for keys that are not present (default: None).
+                Always a single value: a list is returned as it is.
+            mask: Also return which keys were found (default: False)
+            defaults: Sequence holding a value for each key, returned in place
+                of that key's value when it is not present; exclusive with `default`.
+                Every table class takes per-key values this way.
+        Returns a list of values in the same order as `keys`. With `mask`,
+        returns a `(values, found)` pair, where `found` flags the keys present:
+        a numpy bool array if `keys` is a numpy array, a list otherwise.
+        The GIL is released while probing; large batches are probed in parallel.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Accepts a sequence of integer keys or a uint64 numpy array.
+        Returns a list of booleans in the same order as `keys`.
+        The GIL is released while probing; large batches are probed in parallel.
+        """
+
+    def merge(self, other: Any, on_conflict: str = "replace") -> None:
+        """Insert every entry of `other`: an ElasticTable, a dict or other mapping
+        with int keys, or an iterable of `(key, value)` pairs, read as
+        `dict.update` reads them.
+        `on_conflict` decides what happens to keys both hold: "replace" (the
+        default) stores the value from `other`, "keep" leaves the stored value,
+        and "error" raises KeyError for the first such key before anything is
+        inserted. Values are inserted in bulk with the GIL released. As with
+        `insert_many`, entries inserted before a failed insert stay in the table.
+        """
+
+    def __or__(self, other: ElasticTable | dict[Any, Any]) -> ElasticTable:
+        """`table | other`: a new table holding the entries of both, with the
+        values of `other` for keys both hold. The new table has the same `delta`,
+        `intern`, `weak` and `ordered` settings, and enough capacity for both
+        tables.
+        """
+
+    def __ior__(self, other: Any) -> ElasticTable:
+        """`table |= other`: `merge(other)` in place. Like `dict |=`, and unlike
+        `|`, it takes anything `merge` does, not only dicts and ElasticTables.
+        """
+
+    def intersection_keys(self, other: ElasticTable | IntTable | FloatTable) -> list[int]:
+        """Keys of this table that `other` also holds, in ascending order.
+        `other` can be a table of any class, as only keys are compared. The
+        comparison runs in Rust with the GIL released, so two large key sets
+        are compared without building Python sets.
+        """
+
+    def difference_keys(self, other: ElasticTable | IntTable | FloatTable) -> list[int]:
+        """Keys of this table that `other` does not hold, in ascending order.
+        See `intersection_keys`.
+        """
+
+    def split(self, n: int) -> list[ElasticTable]:
+        """Partition the entries into `n` new tables by key hash.
+        Key `k` goes to part `ElasticTable.shard_of(k, n)`, the shard a
+        `ShardedElasticTable` with `n` shards stores it in, so a table built
+        once can be split across worker processes that each serve one part.
+        The parts keep this table's `delta`, `intern`, `weak` and `ordered`
+        settings, and each gets an `n`-th of its capacity, or more if its
+        entries need it.
+        """
+
+    @staticmethod
+    def shard_of(key: int, n: int) -> int:
+        """The part of `split(n)`, or shard of a `ShardedElasticTable` with `n`
+        shards, that holds `key`.
+        """
+
+    @staticmethod
+    def tag_key(tag: int, key: int, tag_bits: int = 8) -> int:
+        """`key` with `tag` packed into its top `tag_bits` bits, so that one table
+        can host several logical keyspaces: namespace `tag` holds the keys
+        tagged with it. Raises ValueError if the tag or the key does not fit.
+        """
+
+    @staticmethod
+    def untag_key(key: int, tag_bits: int = 8) -> tuple[int, int]:
+        """The `(tag, key)` pair `tag_key` packed into `key`."""
+
+    def namespace_keys(self, tag: int, tag_bits: int = 8) -> list[int]:
+        """The keys in namespace `tag`, without the tag, in the order of `keys()`.
+        Takes a pass over the table.
+        """
+
+    def namespace_items(self, tag: int, tag_bits: int = 8) -> list[tuple[int, Any]]:
+        """The `(key, value)` pairs in namespace `tag`, keys without the tag, in
+        the order of `keys()`.
+        """
+
+    def delete_namespace(self, tag: int, tag_bits: int = 8) -> int:
+        """Remove every key in namespace `tag`, as `delete_many` would. Returns
+        how many were removed.
+        """
+
+    def rebalance(self) -> None:
+        """Redistribute the entries across the subarrays, keeping the capacity.
+        Skewed insertion orders and many deletes can leave some subarrays far
+        fuller than the insertion algorithm intends and the table littered with
+        tombstones. Rebalancing takes every entry out and inserts it again into
+        the emptied subarrays, which restores the intended occupancy profile and
+        drops the tombstones, without allocating a larger table. Takes a pass
+        over every slot plus an insert per entry.
+        """
+
+    def clear(self) -> None:
+        """Remove every entry, keeping the allocated capacity.
+        Interning tables also release their pool of distinct values, unless a
+        transaction is open: the values it may put back still refer to it.
+        """
+
+    def purge(self) -> int:
+        """Remove entries whose weakly held value has been collected.
+        Returns the number of entries removed (always 0 unless the table is weak).
+        """
+
+    def enable_bloom_filter(self, bits_per_key: int = 10) -> None:
+        """Keep a Bloom filter over the keys, so most lookups of missing keys are
+        answered without probing the subarrays.
+        The filter is sized for the table's capacity at `bits_per_key` bits per
+        slot; at the default of 10 about 1% of misses still probe. It is updated
+        by every insert and rebuilt as deleted keys accumulate, and is not saved
+        with the table.
+        """
+
+    def disable_bloom_filter(self) -> None:
+        """Drop the Bloom filter, if any."""
+
+    @property
+    def bloom_filter(self) -> int | None:
+        """Bits per slot of the Bloom filter, or None without one."""
+
+    def enable_auto_shrink(self, threshold: float = 0.25, min_capacity: int = 0) -> None:
+        """Shrink the table once deletes leave its entries filling less than
+        `threshold` of the capacity, so a cache that once held many more
+        entries gives the memory back.
+        After a `delete`, `delete_many` or `purge` that leaves the table that
+        sparse, its entries move to a new table sized so they fill twice
+        `threshold`, but no smaller than `min_capacity` slots, with the GIL
+        released. That takes a pass over the slots and an insert per entry,
+        and later inserts have only the smaller capacity to fill: pick
+        `min_capacity` for the size the table must always accommodate.
+        `clear()` keeps the capacity, and no table shrinks while a transaction
+        is open. `threshold` must lie in (0, 0.5).
+        """
+
+    def disable_auto_shrink(self) -> None:
+        """Stop shrinking the table after deletes."""
+
+    @property
+    def auto_shrink(self) -> float | None:
+        """The `threshold` of `enable_auto_shrink`, or None if the table does not
+        shrink.
+        """
+
+    @property
+    def track_probes(self) -> bool:
+        """Whether probe lengths are recorded for `probe_histograms()` (default: False).
+        Recording costs a few atomic increments per insert and lookup; turning
+        it off discards the histograms.
+        """
+    @track_probes.setter
+    def track_probes(self, enabled: bool) -> None: ...
+
+    @property
+    def adaptive(self) -> bool:
+        """Whether the insert probe budget adapts to the workload (default: False).
+        An insert probes a subarray for at most `c * log2(1 / epsilon)^2`
+        groups before moving on to the next one, with `c` = 2 by default. While
+        adaptive, the table widens `c` when more than 5% of a window of 1024
+        inserts run out of budget somewhere, or when an insert fails, and
+        narrows it when fewer than 1% do, within 0.25 to 16. `detailed_stats()`
+        reports the tuned budget and a recommended `delta` for the workload.
+        Turning it off returns to the default budget.
+        """
+    @adaptive.setter
+    def adaptive(self, enabled: bool) -> None: ...
+
+    @property
+    def two_choices(self) -> bool:
+        """Whether inserts place keys by two choices (default: False).
+        A comparison point for adversarial key sets: every key gets a second
+        probe sequence in each subarray, and an insert follows whichever of its
+        two sequences starts at the group with more free slots, falling back to
+        the other. Lookups probe both sequences, so misses cost about twice as
+        much, and inserts of new keys look them up first. Turning it off
+        rebalances the table.
+        """
+    @two_choices.setter
+    def two_choices(self, enabled: bool) -> None: ...
+
+    @property
+    def robin_hood(self) -> bool:
+        """Whether new keys are placed by Robin Hood displacement (default: False).
+        Walking its probe sequence in the subarray the insertion algorithm
+        picked, a new key takes the slot of the first entry it meets that sits
+        fewer probes from the start of its own sequence than the key does,
+        and the displaced entry moves on the same way until one reaches a
+        free slot. Lookups are unchanged, but entries sit at more even probe
+        distances, which trims the probes the longest lookups spend in the
+        subarray holding their key, at the cost of slower inserts. Keys stored
+        before it was turned on stay put until `rebalance()`. Cannot be
+        combined with `two_choices`; "relocations" in `op_stats()` counts the
+        entries displaced.
+        """
+    @robin_hood.setter
+    def robin_hood(self, enabled: bool) -> None: ...
+
+    @property
+    def cuckoo_kicks(self) -> int:
+        """How many stored entries an insert may try to move aside before it
+        spills to the next subarray (default: 0, off).
+        When an insert exhausts its probe budget in a subarray, it tries the
+        entries along its probe sequence in turn, and the first one that has a
+        free slot within the same budget along its own sequence moves there,
+        freeing its slot for the new key. Kicks never cascade, and lookups
+        find moved entries as before, so this only trades insert probes for
+        fewer keys in the deeper subarrays. `op_stats()` counts the moves as
+        "relocations".
+        """
+    @cuckoo_kicks.setter
+    def cuckoo_kicks(self, kicks: int) -> None: ...
+
+    def probe_histograms(self) -> dict[Any, Any] | None:
+        """Probe-length histograms recorded since `track_probes` was enabled, or
+        None if it is off.
+        Returns a dict with "insert" and "get" lists, where entry `i` counts the
+        operations that used `i` probes in total (the last entry also counts
+        longer ones), and a "subarrays" list holding the same two lists for each
+        subarray: the inserts placed there and the lookups that found their key
+        there, by the probes spent in that subarray. Failed inserts are not counted.
+        """
+
+    def op_stats(self) -> dict[Any, Any]:
+        """Counts of the operations applied since the table was created or
+        `reset_stats()` was last called.
+        Returns a dict with "gets", "hits" and "misses" (every key looked up by
+        `get`, `get_many` and `contains_many`), "inserts" (successful inserts,
+        including "overwrites" that replaced a stored key's value in its slot),
+        "failed_inserts" and "relocations" (entries moved to make room for an
+        insert, see `cuckoo_kicks` and `robin_hood`). Counting is always on
+        and costs two atomic updates per lookup call.
+        """
+
+    def reset_stats(self) -> None:
+        """Zero the counts of `op_stats()`, the fallthroughs of `detailed_stats()`
+        and any probe histograms being recorded.
+        """
+
+    def interned_count(self) -> int:
+        """Number of distinct values held by the interning pool (0 if not interning)."""
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """Number of entries that can still be inserted before the table holds
+        `(1 - delta) * capacity()` of them, the most elastic hashing guarantees
+        to place. For a table made by `for_items`, that is `expected_items`.
+        Inserts past it may still succeed, but can start failing.
+        """
+
+    def is_full(self) -> bool:
+        """Whether `remaining_capacity()` is 0, so the table should be replaced by
+        a larger one before more keys arrive.
+        """
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def set_tracer(self, callback: Any | None) -> None:
+        """Call `callback(key, steps, inserted)` after every insert, to follow the
+        decisions of the insertion algorithm on a problematic workload.
+        `steps` lists the subarrays the insert visited, in order, as
+        `(subarray, case, limit, probes)` tuples: the case of the paper's
+        analysis that applied (see `detailed_stats`), the groups the insert could
+        probe there and the probes it used. When `inserted` is True the key was
+        placed in the subarray of the last step; otherwise the insert failed.
+        Batch inserts report their keys after the whole batch. An exception raised
+        by the callback propagates from the insert, which has already taken
+        effect. Pass None to stop tracing. Tracing slows inserts down considerably.
+        """
+
+    def add_observer(self, callback: Any) -> None:
+        """Call `callback(event, key, old, new)` with every change to the table,
+        for change-data-capture without wrapping every call site.
+        `event` is "insert", "update" or "delete", and `old` and `new` are the
+        key's values before and after the change, None where it had none.
+        Observers are called in the order they were added, once the call that
+        made the change took effect: batch calls report their changes in batch
+        order after the whole batch, a `bulk()` block as its buffer is applied,
+        and `clear()` a delete per entry. Undoing changes, by rolling back a
+        transaction or a failed `insert_all`, is reported as changes too. The
+        table is still busy with the call meanwhile, so observers cannot use
+        it. An exception raised by an observer propagates from the call, which
+        has already taken effect, and skips the calls left. While a table has
+        observers, every change costs a lookup of the value it replaces.
+        """
+
+    def remove_observer(self, callback: Any) -> None:
+        """Stop calling `callback` with changes. Raises ValueError if it is not
+        an observer of the table.
+        """
+
+    def set_load_alert(self, threshold: float | None, callback: Any | None = None, subarray_threshold: float | None = None) -> None:
+        """Get an early warning before inserts start failing: after an insert takes
+        the table's load factor to `threshold` or past it, call
+        `callback(load_factor, None)`, or issue a `LoadFactorWarning` without a
+        callback.
+        With `subarray_threshold`, the load factor of every subarray is watched
+        too, and `callback(load_factor, subarray)` is called for each subarray
+        that reaches it. Each alert fires once when its load crosses the
+        threshold, and again only after deletes took the load back below it.
+        Loads are checked after every insert call, after the whole batch for
+        batch inserts and at the end of a `bulk()` block. An exception raised
+        by the callback propagates from the insert, which has already taken
+        effect. Pass None as `threshold` to remove the alert.
+        """
+
+    @property
+    def load_alert(self) -> float | None:
+        """The `threshold` of `set_load_alert`, or None without an alert."""
+
+    def metrics_text(self, prefix: str = "elastic_table", labels: dict[str, str] | None = None) -> str:
+        """Render the table's metrics in the Prometheus text exposition format.
+        Covers the `op_stats()` counters, occupancy and memory gauges (overall
+        and per subarray), and, while `track_probes` is on, the probe histograms.
+        Every metric name starts with `prefix`, and every sample carries the
+        constant `labels`, such as `{"table": "users"}`. Each call renders
+        complete metric families, so tables exposed together need distinct prefixes.
+        """
+
+    def probe_bounds(self, tolerance: float = 4.0) -> dict[Any, Any]:
+        """Compare the probe counts recorded while `track_probes` is on with the
+        paper's bounds: O(1) probes amortized and O(log 1/delta) in the worst case.
+        The bounds are asymptotic, so `tolerance` stands in for their constants:
+        the amortized bound is `tolerance` probes and the worst-case bound
+        `tolerance * log2(1 / delta)`. Probes are counted in groups of 16 slots.
+        Returns a dict with "insert_count", "insert_amortized_probes" and
+        "insert_worst_case_probes", the same three for "get", the bounds used
+        ("log_inv_delta", "amortized_bound", "worst_case_bound"), and a list of
+        "deviations" describing every bound exceeded. Lookups of missing keys
+        fall outside the paper's analysis and may show up as worst-case deviations.
+        """
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes allocated for the table, broken down by subarray.
+        Returns a dict with a "subarrays" list holding, per subarray, its
+        "slots" (padding included), the "control_bytes", "key_bytes" and
+        "value_bytes" they take, their "total_bytes", and the number of
+        "references": values held as references to Python objects rather than
+        inline. The objects themselves are not measured. "filter_bytes" counts
+        the Bloom filter, "order_bytes" the insertion order of an ordered table,
+        and "total_bytes" covers everything listed.
+        """
+
+    def occupancy(self, packed: bool = False) -> list[Any]:
+        """Which slots of every subarray are occupied, for visualizing clustering.
+        Returns one entry per subarray, numbered as in `stats()`, covering its
+        slots in order: a numpy bool array, or with `packed`, a `bytes` bitmap
+        holding slot `j` in bit `j % 8` of byte `j // 8`, which
+        `numpy.unpackbits(..., bitorder="little")` expands. Padding slots are
+        left out, and slots of deleted entries count as free.
+        """
+
+    def items_in_subarray(self, i: int) -> list[tuple[int, Any]]:
+        """The `(key, value)` pairs stored in subarray `i`, in slot order.
+        For debugging high spill rates: the keys found in the later, overflow
+        subarrays are those the first ones had no room for. Subarrays are
+        numbered as in `stats()`; raises IndexError for one the table does not
+        have.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`.
+        Returns a dict with the table's "capacity", "count", "load_factor",
+        "delta" and "probe_budget" (the constant `c` of the insert probe budget,
+        see `adaptive`), a "tuning" dict while `adaptive` is on, and a
+        "subarrays" list with one dict per subarray:
+        - index, capacity, count, tombstones, load_factor
+        - epsilon: the free fraction of the subarray
+        - insert_case: which case of the paper's analysis the next insert reaching
+          the subarray falls in: 1 probes a bounded number of groups, 2 skips the
+          nearly full subarray, 3 scans all of it because the next one is nearly full
+        - insert_probe_limit: groups that insert may probe (0 when skipping)
+        - lookup_probe_limit: groups a lookup probes at most
+        - fallthroughs: the inserts that moved on to the next subarray from
+          this one, by the case that sent them on: "exhausted" (case 1, the
+          probe budget ran out), "skipped" (case 2) and "full" (case 3, a scan
+          found no free slot). Passing over an empty subarray is not counted,
+          and `reset_stats()` zeroes them.
+        Many "exhausted" fallthroughs from subarrays that still have room point
+        at keys that cluster, while "skipped" ones from full subarrays are the
+        algorithm working as intended, a `delta` too small for the load if they
+        end in failed inserts.
+        The "tuning" dict counts the "inserts" observed, the "fallthroughs"
+        among them that ran out of budget in some subarray and the
+        "adjustments" made to the budget, and reports the "peak_load" reached,
+        the load an insert first failed at as "failure_load" (or None), and a
+        "recommended_delta" for tables serving the same workload. That is a
+        quarter more slack than the failure left, or, once the table filled to
+        `1 - delta` without failing, half of `delta` if the budget did not have
+        to grow and `delta` otherwise; it is None before either happens.
+        """
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants.
+        Verifies that every subarray's counts match its occupied and deleted
+        slots, that every stored key is found again from its hash, that no key
+        is stored twice and that the Bloom filter, if enabled, admits every
+        stored key. Returns a list describing each violation; an empty list
+        means the table is consistent. Takes a pass over every slot.
+        """
+
+
+class ConcurrentElasticTable:
+    """A thread-safe `ElasticTable`.
+    Lookups from different threads run concurrently; inserts and deletes are
+    exclusive. Batch methods release the GIL, so other threads keep running
+    (and reading) while a large batch is probed.
+    """
+
+    def __init__(self, capacity: int, delta: float = 0.05) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90) -> ConcurrentElasticTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    def insert(self, key: int, value: Any) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Sequence[Any]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used.
+        Pairs are inserted in order; if one fails, the pairs before it stay inserted.
+        """
+
+    def get(self, key: int, default: Any | None = None) -> Any:
+        """The value stored under `key`, or `default` if the table does not hold
+        the key. See ElasticTable.get.
+        """
+
+    def __getitem__(self, key: Any) -> Any:
+        """`table[key]`: the value stored under `key`. Raises KeyError if the
+        table does not hold the key.
+        """
+
+    def __contains__(self, key: Any) -> bool:
+        """`key in table`, which is True for keys holding None too."""
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns the number of keys that were present."""
+
+    def get_many(self, keys: Iterable[int], default: Any | None = None, mask: bool = False, defaults: Sequence[Any] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` (or the matching
+        item of `defaults`) for missing keys. With `mask`, returns a
+        `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call."""
+
+    def clear(self) -> None:
+        """Remove every entry, keeping the allocated capacity."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+
+class ShardedElasticTable:
+    """A thread-safe `ElasticTable` split into independently locked shards.
+    Unlike `ConcurrentElasticTable`, inserts into different shards proceed in
+    parallel, which suits insert-heavy workloads from many threads.
+    """
+
+    def __init__(self, capacity: int, delta: float = 0.05, shards: int = 16) -> None:
+        """Create a table with `capacity` slots split evenly across `shards` shards.
+        See ElasticTable for the meaning of `delta`.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90, shards: int = 16) -> ShardedElasticTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    @property
+    def shards(self) -> int:
+        """Number of shards."""
+
+    def insert(self, key: int, value: Any) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Sequence[Any]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used.
+        Pairs are grouped by shard and each shard is locked once. Within a shard
+        pairs are inserted in order; if an insertion fails, other shards may
+        already hold their part of the batch.
+        """
+
+    def get(self, key: int, default: Any | None = None) -> Any:
+        """The value stored under `key`, or `default` if the table does not hold
+        the key. See ElasticTable.get.
+        """
+
+    def __getitem__(self, key: Any) -> Any:
+        """`table[key]`: the value stored under `key`. Raises KeyError if the
+        table does not hold the key.
+        """
+
+    def __contains__(self, key: Any) -> bool:
+        """`key in table`, which is True for keys holding None too."""
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns the number of keys that were present."""
+
+    def get_many(self, keys: Iterable[int], default: Any | None = None, mask: bool = False, defaults: Sequence[Any] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` (or the matching
+        item of `defaults`) for missing keys. With `mask`, returns a
+        `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call."""
+
+    def clear(self) -> None:
+        """Remove every entry, keeping the allocated capacity."""
+
+    def stats(self) -> list[list[tuple[int, int, float]]]:
+        """Per-shard subarray stats, in the format of `ElasticTable.stats()`."""
+
+
+class ReadMostlyElasticTable:
+    """An `ElasticTable` whose lookups never take a lock.
+    Meant for read-mostly serving: gets and batch lookups from any number of
+    threads proceed without waiting on writers, so lookup latency is not
+    affected by occasional inserts. Writers serialize among themselves and
+    each change is applied to two internal copies, so the table uses twice
+    the memory of an `ElasticTable`.
+    """
+
+    def __init__(self, capacity: int, delta: float = 0.05) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90) -> ReadMostlyElasticTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    def insert(self, key: int, value: Any) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Sequence[Any]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used.
+        Pairs are inserted in order; if one fails, the pairs before it stay inserted.
+        """
+
+    def get(self, key: int, default: Any | None = None) -> Any:
+        """The value stored under `key`, or `default` if the table does not hold
+        the key. See ElasticTable.get.
+        """
+
+    def __getitem__(self, key: Any) -> Any:
+        """`table[key]`: the value stored under `key`. Raises KeyError if the
+        table does not hold the key.
+        """
+
+    def __contains__(self, key: Any) -> bool:
+        """`key in table`, which is True for keys holding None too."""
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns the number of keys that were present."""
+
+    def get_many(self, keys: Iterable[int], default: Any | None = None, mask: bool = False, defaults: Sequence[Any] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` (or the matching
+        item of `defaults`) for missing keys. With `mask`, returns a
+        `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call."""
+
+    def clear(self) -> None:
+        """Remove every entry, keeping the allocated capacity."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+
+class IntTable:
+    """An elastic hash table mapping u64 keys to int64 values stored in Rust."""
+
+    def __init__(self, capacity: int, delta: float = 0.05, growth_factor: float | None = None, grow_at: float | None = None, max_capacity: int | None = None) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90) -> IntTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    @staticmethod
+    def from_numpy(keys: Iterable[int], values: Iterable[int], load_factor: float = 0.90) -> IntTable:
+        """Build a table from parallel key and value arrays.
+        Args:
+            keys: uint64 numpy array (or sequence of ints)
+            values: numpy array of the table's value type, same length as `keys`
+            load_factor: Target load factor used to size the table (default: 0.90)
+        Arrays are read in place; no Python object is created per element.
+        """
+
+    @staticmethod
+    def from_arrow(keys: Any, values: Any, load_factor: float = 0.90) -> IntTable:
+        """Build a table from parallel Arrow key and value arrays.
+        Args:
+            keys: uint64 pyarrow array or chunked array
+            values: pyarrow array of the table's value type, same length as `keys`
+            load_factor: Target load factor used to size the table (default: 0.90)
+        Null-free arrays are read in place, as with `from_numpy`; arrays with
+        nulls are rejected.
+        """
+
+    @staticmethod
+    def from_pandas(df: Any, key_col: str, value_col: str, load_factor: float = 0.90) -> IntTable:
+        """Build a table from two columns of a pandas DataFrame.
+        Args:
+            df: The DataFrame
+            key_col: Name of the uint64 key column
+            value_col: Name of the value column, of the table's value type
+            load_factor: Target load factor used to size the table (default: 0.90)
+        Columns are read in place, as with `from_numpy`; columns with missing
+        values are rejected.
+        """
+
+    @staticmethod
+    def create_shared(expected_items: int, load_factor: float = 0.90, name: str | None = None) -> IntTable:
+        """Create an empty table whose slots live in a new shared memory segment.
+        Args:
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90)
+            name: Segment name (default: a random name chosen by Python)
+        Other processes attach to the same slots with `open_shared(name)`.
+        The creator should call `table.shared_memory.unlink()` once the
+        segment is no longer needed.
+        """
+
+    @staticmethod
+    def open_shared(name: str) -> IntTable:
+        """Attach to a table created by `create_shared`, without copying it.
+        Entries are recounted on attach. Writes made through one handle are
+        visible in the other processes' lookups, but handles do not
+        coordinate: write while no other process is reading, and only from
+        the process that created the table.
+        """
+
+    @property
+    def shared_memory(self) -> Any | None:
+        """The `multiprocessing.shared_memory.SharedMemory` holding the slots,
+        or None for tables in private memory.
+        """
+
+    @staticmethod
+    def create_mmap(path: str | os.PathLike[str], expected_items: int, load_factor: float = 0.90) -> IntTable:
+        """Create an empty table whose slots live in a memory-mapped file.
+        Args:
+            path: File to create (an existing file is overwritten)
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90)
+        The OS pages slots in and out on demand, so the table may be larger
+        than RAM. Changes reach the file when the OS writes the pages back,
+        or at the latest on `flush()`.
+        """
+
+    @staticmethod
+    def open_mmap(path: str | os.PathLike[str]) -> IntTable:
+        """Reopen a table file written by `create_mmap`, without rebuilding it."""
+
+    @staticmethod
+    def create_tiered(path: str | os.PathLike[str], expected_items: int, load_factor: float = 0.90, hot_subarrays: int = 1) -> IntTable:
+        """Create an empty table whose first subarrays stay in memory while
+        the tail subarrays live in a memory-mapped scratch file.
+        Args:
+            path: Scratch file to create (an existing file is overwritten)
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90)
+            hot_subarrays: Subarrays kept in memory (default: 1)
+        Later subarrays only hold the overflow of the earlier ones and are
+        probed less often, so the OS can write their pages back to the file
+        and drop them under memory pressure. The file is removed with the
+        table and cannot be reopened.
+        """
+
+    @property
+    def hot_subarrays(self) -> int | None:
+        """Subarrays kept in memory by a table from `create_tiered`, or None."""
+
+    def flush(self) -> None:
+        """Write modified slots of a memory-mapped table back to its file,
+        so that reopening it skips recounting entries. Does nothing for
+        other tables.
+        """
+
+    def save(self, path: str | os.PathLike[str], wal: bool = False) -> None:
+        """Write a snapshot of the table to `path`.
+        The snapshot uses the same format as `create_mmap` files, so it can
+        also be reopened for writing with `open_mmap`. It keeps the table's
+        growth settings, which `load` restores.
+        With `wal=True`, every later change is also appended to a log at
+        `<path>.wal`, which `load(path)` replays. A table that already logs
+        to that file starts a fresh log, since the snapshot now holds its
+        changes.
+        Saving replaces the log and checkpoints of any snapshot previously
+        saved at `path`.
+        """
+
+    def checkpoint(self) -> int:
+        """Write the changes logged since the last checkpoint to the next
+        checkpoint file, `<path>.v<n>`, and empty the log. Returns `n`.
+        Each checkpoint holds only the keys changed since the previous one,
+        so long-running jobs can checkpoint often without rewriting the
+        snapshot. Requires a table that logs its changes (see `save`).
+        """
+
+    @staticmethod
+    def versions(path: str | os.PathLike[str]) -> list[int]:
+        """Versions of the snapshot at `path` that `load` can restore: 0 for the
+        snapshot itself, followed by the number of every checkpoint.
+        """
+
+    def commit(self) -> None:
+        """Force logged changes to disk, so they survive a machine crash;
+        changes already survive the process crashing. Does nothing for
+        tables without a log.
+        """
+
+    def start_recording(self, path: str | os.PathLike[str]) -> None:
+        """Record every change made to the table from now on to `path`,
+        along with its parameters, so that `replay(path)` can repeat them.
+        The recording starts with the entries the table holds, and
+        inserts that fail are recorded too, so a recording started on an
+        empty table replays into a table identical slot for slot, for
+        reproducing corruption or probe explosions. Raises RuntimeError
+        if the table is already recording.
+        """
+
+    def stop_recording(self) -> None:
+        """Stop recording, handing the recording to the OS. Does nothing
+        for a table that is not recording.
+        """
+
+    @property
+    def recording(self) -> str | None:
+        """Path of the recording `start_recording` is writing, or None."""
+
+    @staticmethod
+    def replay(path: str | os.PathLike[str]) -> IntTable:
+        """Build a fresh table from a recording written by
+        `start_recording`, with the recorded parameters, and repeat the
+        recorded changes on it. Inserts that failed when recorded are
+        expected to fail again and are skipped; replay stops at a torn
+        tail.
+        """
+
+    @staticmethod
+    def load(path: str | os.PathLike[str], mmap: bool = False, wal: bool = False, version: int | None = None, verify: bool = True) -> IntTable:
+        """Load a snapshot written by `save`.
+        Args:
+            path: Snapshot file
+            mmap: Map the file read-only instead of reading it (default: False).
+                  Pages are then shared with other processes mapping the
+                  file, but the table rejects inserts, deletes and `clear()`.
+            wal: Keep appending changes to the snapshot's log (default: False)
+            version: Restore this version (see `versions`) instead of the latest
+            verify: Check the snapshot's checksums before using it (default:
+                    True). Checking reads the whole file; with `mmap=True`,
+                    pass False to start in milliseconds regardless of table
+                    size, loading pages on first access, on a file known to
+                    be intact.
+        By default the snapshot's checkpoints are applied, followed by the
+        changes recorded in `<path>.wal` up to the first record a crash left
+        incomplete. An explicit `version` applies checkpoints up to that one
+        and ignores the log.
+        A snapshot written by an incompatible version of this library, or
+        one whose header, data or checkpoints fail their checksums, raises
+        ValueError.
+        """
+
+    def freeze(self, load_factor: float = 0.90, perfect: bool = False) -> FrozenIntTable:
+        """Build a read-only copy of the table tuned for lookups.
+        The copy is sized for the current entries at `load_factor` and holds
+        no tombstones. Each of its subarrays also records the longest probe
+        sequence among its keys, so lookups of missing keys stop early and
+        empty subarrays are skipped.
+        With `perfect=True`, the copy instead uses a minimal perfect hash over
+        the current keys: entries fill exactly one slot each and every lookup
+        inspects a single slot. Building it takes longer, and `load_factor`
+        does not apply.
+        """
+
+    @property
+    def read_only(self) -> bool:
+        """Whether the table rejects modification (tables loaded with `mmap=True`)."""
+
+    def insert(self, key: int, value: int) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Iterable[int]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used."""
+
+    def insert_all(self, keys: Iterable[int], values: Iterable[int]) -> int:
+        """Insert many key/value pairs, all or none. See
+        `ElasticTable.insert_all`. On a logged table, the pairs taken
+        out again are logged as removed.
+        """
+
+    def get(self, key: int) -> int | None: ...
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def get_many(self, keys: Iterable[int], default: int | None = None, mask: bool = False, defaults: Iterable[int] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` for missing keys,
+        or the matching item of `defaults`, a sequence or array holding one
+        value for each key. Large batches are probed in parallel. With
+        `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def to_numpy(self) -> tuple[Any, Any]:
+        """Export the contents as a `(keys, values)` pair of numpy arrays."""
+
+    def to_arrow(self) -> tuple[Any, Any]:
+        """Export the contents as a `(keys, values)` pair of pyarrow arrays,
+        which share memory with the arrays `to_numpy()` returns.
+        """
+
+    def to_pandas(self, key_col: str = "key", value_col: str = "value") -> Any:
+        """Export the contents as a pandas DataFrame with one row per entry,
+        built around the arrays `to_numpy()` returns.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. Typed tables
+        do not keep insertion order. See `ElasticTable.keys`.
+        """
+
+    def values(self) -> _ValuesView:
+        """A live view of the values, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, value)` pairs, in the order of `keys()`."""
+
+    def transaction(self) -> _Transaction:
+        """Open a transaction, so that a group of changes can be undone
+        together. See `ElasticTable.transaction`. A rolled back
+        transaction is undone in the change log too.
+        """
+
+    def bulk(self, buffer: bool = False) -> _BulkBlock:
+        """Start a block of inserts, for use as `with table.bulk():`.
+        See `ElasticTable.bulk`. Besides tracer calls, a logged table
+        hands its change log to the OS once, when the block ends.
+        """
+
+    def __iter__(self) -> _TableIterator:
+        """Iterate over the keys, in the order of `keys()`. Raises
+        `RuntimeError` if the table gains or loses entries meanwhile.
+        """
+
+    def sample(self, n: int, items: bool = False, seed: int | None = None) -> Any:
+        """`n` distinct keys chosen uniformly at random, or `(key, value)`
+        pairs with `items=True`. See `ElasticTable.sample`.
+        """
+
+    def entry_at(self, index: int) -> tuple[int, int]:
+        """The entry at position `index` of slot order, as a `(key, value)`
+        pair. See `ElasticTable.entry_at`.
+        """
+
+    def entry_range(self, start: int, stop: int | None = None) -> list[tuple[int, int]]:
+        """The entries at positions `start` up to `stop` of slot order.
+        See `ElasticTable.entry_range`.
+        """
+
+    def subarray_lens(self) -> list[int]:
+        """Number of entries in each subarray, in slot order."""
+
+    def to_json(self, indent: int | None = None) -> str:
+        """Serialize the contents as a JSON object mapping each key, as a decimal
+        string, to its value, in ascending key order.
+        """
+
+    @staticmethod
+    def from_json(data: str, load_factor: float = 0.90) -> IntTable:
+        """Build a table from a JSON object mapping decimal keys to values, such
+        as the output of `to_json()`.
+        """
+
+    def to_msgpack(self) -> bytes:
+        """Encode the contents as a msgpack map from key to value."""
+
+    @staticmethod
+    def from_msgpack(data: bytes, load_factor: float = 0.90) -> IntTable:
+        """Build a table from a msgpack map from non-negative integer keys to values,
+        such as the output of `to_msgpack()`.
+        """
+
+    def to_cbor(self) -> bytes:
+        """Encode the contents as a CBOR map from key to value."""
+
+    @staticmethod
+    def from_cbor(data: bytes, load_factor: float = 0.90) -> IntTable:
+        """Build a table from a CBOR map from non-negative integer keys to values,
+        such as the output of `to_cbor()`.
+        """
+
+    def clear(self) -> None:
+        """Remove every entry, keeping the allocated capacity."""
+
+    def merge(self, other: Any, on_conflict: str = "replace") -> None:
+        """Insert every entry of `other`, a table of the same class, a mapping
+        with int keys or an iterable of `(key, value)` pairs. See
+        ElasticTable.merge for `on_conflict`.
+        """
+
+    def __or__(self, other: IntTable | dict[Any, Any]) -> IntTable:
+        """`table | other`: a new table holding the entries of both, with the
+        values of `other` for keys both hold. It has the same `delta` and
+        enough capacity for both tables.
+        """
+
+    def __ior__(self, other: Any) -> IntTable:
+        """`table |= other`: `merge(other)` in place."""
+
+    def intersection_keys(self, other: ElasticTable | IntTable | FloatTable) -> list[int]:
+        """Keys of this table that `other`, a table of any class, also holds,
+        in ascending order. See ElasticTable.intersection_keys.
+        """
+
+    def difference_keys(self, other: ElasticTable | IntTable | FloatTable) -> list[int]:
+        """Keys of this table that `other` does not hold, in ascending order."""
+
+    def split(self, n: int) -> list[IntTable]:
+        """Partition the entries into `n` new tables by key hash. See
+        ElasticTable.split; the parts keep this table's `delta`.
+        """
+
+    @staticmethod
+    def shard_of(key: int, n: int) -> int:
+        """The part of `split(n)` that holds `key`. See ElasticTable.shard_of."""
+
+    @staticmethod
+    def tag_key(tag: int, key: int, tag_bits: int = 8) -> int:
+        """`key` with `tag` packed into its top `tag_bits` bits. See
+        ElasticTable.tag_key.
+        """
+
+    @staticmethod
+    def untag_key(key: int, tag_bits: int = 8) -> tuple[int, int]:
+        """The `(tag, key)` pair `tag_key` packed into `key`."""
+
+    def namespace_keys(self, tag: int, tag_bits: int = 8) -> list[int]:
+        """The keys in namespace `tag`, without the tag, in the order of `keys()`."""
+
+    def namespace_items(self, tag: int, tag_bits: int = 8) -> list[tuple[int, int]]:
+        """The `(key, value)` pairs in namespace `tag`, keys without the tag."""
+
+    def delete_namespace(self, tag: int, tag_bits: int = 8) -> int:
+        """Remove every key in namespace `tag`, as `delete_many` would.
+        Returns how many were removed.
+        """
+
+    def rebalance(self) -> None:
+        """Redistribute the entries across the subarrays, keeping the capacity.
+        See ElasticTable.rebalance. The GIL is released meanwhile.
+        """
+
+    def enable_bloom_filter(self, bits_per_key: int = 10) -> None:
+        """Keep a Bloom filter over the keys, so most lookups of missing keys
+        are answered without probing. See ElasticTable.enable_bloom_filter.
+        """
+
+    def disable_bloom_filter(self) -> None:
+        """Drop the Bloom filter, if any."""
+
+    @property
+    def bloom_filter(self) -> int | None:
+        """Bits per slot of the Bloom filter, or None without one."""
+
+    def enable_auto_shrink(self, threshold: float = 0.25, min_capacity: int = 0) -> None:
+        """Shrink the table once deletes leave it sparse. See
+        ElasticTable.enable_auto_shrink. Tables in shared memory or a
+        mapped file cannot shrink.
+        """
+
+    def disable_auto_shrink(self) -> None:
+        """Stop shrinking the table after deletes."""
+
+    @property
+    def auto_shrink(self) -> float | None:
+        """The `threshold` of `enable_auto_shrink`, or None if the table does
+        not shrink.
+        """
+
+    @property
+    def track_probes(self) -> bool:
+        """Whether probe lengths are recorded for `probe_histograms()` (default:
+        False). See ElasticTable.track_probes.
+        """
+    @track_probes.setter
+    def track_probes(self, enabled: bool) -> None: ...
+
+    @property
+    def adaptive(self) -> bool:
+        """Whether the insert probe budget adapts to the workload (default:
+        False). See ElasticTable.adaptive.
+        """
+    @adaptive.setter
+    def adaptive(self, enabled: bool) -> None: ...
+
+    def probe_histograms(self) -> dict[Any, Any] | None:
+        """Probe-length histograms recorded since `track_probes` was enabled, or
+        None if it is off. See ElasticTable.probe_histograms.
+        """
+
+    def op_stats(self) -> dict[Any, Any]:
+        """Counts of the operations applied since the table was created or loaded,
+        or `reset_stats()` was last called. See ElasticTable.op_stats.
+        """
+
+    def reset_stats(self) -> None:
+        """Zero the counts of `op_stats()`, the fallthroughs of `detailed_stats()`
+        and any probe histograms being recorded.
+        """
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def set_tracer(self, callback: Any | None) -> None:
+        """Call `callback(key, steps, inserted)` after every insert. See
+        ElasticTable.set_tracer.
+        """
+
+    def add_observer(self, callback: Any) -> None:
+        """Call `callback(event, key, old, new)` with every change to the
+        table. See ElasticTable.add_observer.
+        """
+
+    def remove_observer(self, callback: Any) -> None:
+        """Stop calling `callback` with changes. Raises ValueError if it
+        is not an observer of the table.
+        """
+
+    def set_load_alert(self, threshold: float | None, callback: Any | None = None, subarray_threshold: float | None = None) -> None:
+        """Call `callback(load_factor, subarray)`, or issue a
+        `LoadFactorWarning`, once the load factor crosses `threshold`.
+        See ElasticTable.set_load_alert.
+        """
+
+    @property
+    def load_alert(self) -> float | None:
+        """The `threshold` of `set_load_alert`, or None without an alert."""
+
+    def track_distinct_values(self, precision: int | None = ...) -> None:
+        """Estimate the number of distinct values inserted, reported as
+        "distinct_values" by `detailed_stats()`.
+        A HyperLogLog sketch of `2**precision` bytes (4 to 18, default
+        14 for 16 KiB and a typical error of 0.8%) takes in the values
+        stored now and every value inserted from then on. Values stay
+        counted once overwritten or deleted. `None` stops the estimate.
+        """
+
+    def metrics_text(self, prefix: str = "elastic_table", labels: dict[str, str] | None = None) -> str:
+        """Render the table's metrics in the Prometheus text exposition format.
+        See ElasticTable.metrics_text.
+        """
+
+    def probe_bounds(self, tolerance: float = 4.0) -> dict[Any, Any]:
+        """Compare the probe counts recorded while `track_probes` is on with the
+        paper's bounds. See ElasticTable.probe_bounds.
+        """
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes allocated for the table, broken down by subarray. See
+        ElasticTable.memory_usage; values are stored inline, so there are no
+        "references". Mapped tables count the bytes of their mapping.
+        """
+
+    def occupancy(self, packed: bool = False) -> list[Any]:
+        """Which slots of every subarray are occupied. See ElasticTable.occupancy."""
+
+    def items_in_subarray(self, i: int) -> list[tuple[int, int]]:
+        """The `(key, value)` pairs stored in subarray `i`, in slot order.
+        See ElasticTable.items_in_subarray.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats;
+        with `track_distinct_values`, "distinct_values" is the estimated
+        number of distinct values inserted, and None without it.
+        """
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class FloatTable:
+    """An elastic hash table mapping u64 keys to float64 values stored in Rust."""
+
+    def __init__(self, capacity: int, delta: float = 0.05, growth_factor: float | None = None, grow_at: float | None = None, max_capacity: int | None = None) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90) -> FloatTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    @staticmethod
+    def from_numpy(keys: Iterable[int], values: Iterable[float], load_factor: float = 0.90) -> FloatTable:
+        """Build a table from parallel key and value arrays.
+        Args:
+            keys: uint64 numpy array (or sequence of ints)
+            values: numpy array of the table's value type, same length as `keys`
+            load_factor: Target load factor used to size the table (default: 0.90)
+        Arrays are read in place; no Python object is created per element.
+        """
+
+    @staticmethod
+    def from_arrow(keys: Any, values: Any, load_factor: float = 0.90) -> FloatTable:
+        """Build a table from parallel Arrow key and value arrays.
+        Args:
+            keys: uint64 pyarrow array or chunked array
+            values: pyarrow array of the table's value type, same length as `keys`
+            load_factor: Target load factor used to size the table (default: 0.90)
+        Null-free arrays are read in place, as with `from_numpy`; arrays with
+        nulls are rejected.
+        """
+
+    @staticmethod
+    def from_pandas(df: Any, key_col: str, value_col: str, load_factor: float = 0.90) -> FloatTable:
+        """Build a table from two columns of a pandas DataFrame.
+        Args:
+            df: The DataFrame
+            key_col: Name of the uint64 key column
+            value_col: Name of the value column, of the table's value type
+            load_factor: Target load factor used to size the table (default: 0.90)
+        Columns are read in place, as with `from_numpy`; columns with missing
+        values are rejected.
+        """
+
+    @staticmethod
+    def create_shared(expected_items: int, load_factor: float = 0.90, name: str | None = None) -> FloatTable:
+        """Create an empty table whose slots live in a new shared memory segment.
+        Args:
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90)
+            name: Segment name (default: a random name chosen by Python)
+        Other processes attach to the same slots with `open_shared(name)`.
+        The creator should call `table.shared_memory.unlink()` once the
+        segment is no longer needed.
+        """
+
+    @staticmethod
+    def open_shared(name: str) -> FloatTable:
+        """Attach to a table created by `create_shared`, without copying it.
+        Entries are recounted on attach. Writes made through one handle are
+        visible in the other processes' lookups, but handles do not
+        coordinate: write while no other process is reading, and only from
+        the process that created the table.
+        """
+
+    @property
+    def shared_memory(self) -> Any | None:
+        """The `multiprocessing.shared_memory.SharedMemory` holding the slots,
+        or None for tables in private memory.
+        """
+
+    @staticmethod
+    def create_mmap(path: str | os.PathLike[str], expected_items: int, load_factor: float = 0.90) -> FloatTable:
+        """Create an empty table whose slots live in a memory-mapped file.
+        Args:
+            path: File to create (an existing file is overwritten)
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90)
+        The OS pages slots in and out on demand, so the table may be larger
+        than RAM. Changes reach the file when the OS writes the pages back,
+        or at the latest on `flush()`.
+        """
+
+    @staticmethod
+    def open_mmap(path: str | os.PathLike[str]) -> FloatTable:
+        """Reopen a table file written by `create_mmap`, without rebuilding it."""
+
+    @staticmethod
+    def create_tiered(path: str | os.PathLike[str], expected_items: int, load_factor: float = 0.90, hot_subarrays: int = 1) -> FloatTable:
+        """Create an empty table whose first subarrays stay in memory while
+        the tail subarrays live in a memory-mapped scratch file.
+        Args:
+            path: Scratch file to create (an existing file is overwritten)
+            expected_items: The number of items you plan to store
+            load_factor: Target load factor (default: 0.90)
+            hot_subarrays: Subarrays kept in memory (default: 1)
+        Later subarrays only hold the overflow of the earlier ones and are
+        probed less often, so the OS can write their pages back to the file
+        and drop them under memory pressure. The file is removed with the
+        table and cannot be reopened.
+        """
+
+    @property
+    def hot_subarrays(self) -> int | None:
+        """Subarrays kept in memory by a table from `create_tiered`, or None."""
+
+    def flush(self) -> None:
+        """Write modified slots of a memory-mapped table back to its file,
+        so that reopening it skips recounting entries. Does nothing for
+        other tables.
+        """
+
+    def save(self, path: str | os.PathLike[str], wal: bool = False) -> None:
+        """Write a snapshot of the table to `path`.
+        The snapshot uses the same format as `create_mmap` files, so it can
+        also be reopened for writing with `open_mmap`. It keeps the table's
+        growth settings, which `load` restores.
+        With `wal=True`, every later change is also appended to a log at
+        `<path>.wal`, which `load(path)` replays. A table that already logs
+        to that file starts a fresh log, since the snapshot now holds its
+        changes.
+        Saving replaces the log and checkpoints of any snapshot previously
+        saved at `path`.
+        """
+
+    def checkpoint(self) -> int:
+        """Write the changes logged since the last checkpoint to the next
+        checkpoint file, `<path>.v<n>`, and empty the log. Returns `n`.
+        Each checkpoint holds only the keys changed since the previous one,
+        so long-running jobs can checkpoint often without rewriting the
+        snapshot. Requires a table that logs its changes (see `save`).
+        """
+
+    @staticmethod
+    def versions(path: str | os.PathLike[str]) -> list[int]:
+        """Versions of the snapshot at `path` that `load` can restore: 0 for the
+        snapshot itself, followed by the number of every checkpoint.
+        """
+
+    def commit(self) -> None:
+        """Force logged changes to disk, so they survive a machine crash;
+        changes already survive the process crashing. Does nothing for
+        tables without a log.
+        """
+
+    def start_recording(self, path: str | os.PathLike[str]) -> None:
+        """Record every change made to the table from now on to `path`,
+        along with its parameters, so that `replay(path)` can repeat them.
+        The recording starts with the entries the table holds, and
+        inserts that fail are recorded too, so a recording started on an
+        empty table replays into a table identical slot for slot, for
+        reproducing corruption or probe explosions. Raises RuntimeError
+        if the table is already recording.
+        """
+
+    def stop_recording(self) -> None:
+        """Stop recording, handing the recording to the OS. Does nothing
+        for a table that is not recording.
+        """
+
+    @property
+    def recording(self) -> str | None:
+        """Path of the recording `start_recording` is writing, or None."""
+
+    @staticmethod
+    def replay(path: str | os.PathLike[str]) -> FloatTable:
+        """Build a fresh table from a recording written by
+        `start_recording`, with the recorded parameters, and repeat the
+        recorded changes on it. Inserts that failed when recorded are
+        expected to fail again and are skipped; replay stops at a torn
+        tail.
+        """
+
+    @staticmethod
+    def load(path: str | os.PathLike[str], mmap: bool = False, wal: bool = False, version: int | None = None, verify: bool = True) -> FloatTable:
+        """Load a snapshot written by `save`.
+        Args:
+            path: Snapshot file
+            mmap: Map the file read-only instead of reading it (default: False).
+                  Pages are then shared with other processes mapping the
+                  file, but the table rejects inserts, deletes and `clear()`.
+            wal: Keep appending changes to the snapshot's log (default: False)
+            version: Restore this version (see `versions`) instead of the latest
+            verify: Check the snapshot's checksums before using it (default:
+                    True). Checking reads the whole file; with `mmap=True`,
+                    pass False to start in milliseconds regardless of table
+                    size, loading pages on first access, on a file known to
+                    be intact.
+        By default the snapshot's checkpoints are applied, followed by the
+        changes recorded in `<path>.wal` up to the first record a crash left
+        incomplete. An explicit `version` applies checkpoints up to that one
+        and ignores the log.
+        A snapshot written by an incompatible version of this library, or
+        one whose header, data or checkpoints fail their checksums, raises
+        ValueError.
+        """
+
+    def freeze(self, load_factor: float = 0.90, perfect: bool = False) -> FrozenFloatTable:
+        """Build a read-only copy of the table tuned for lookups.
+        The copy is sized for the current entries at `load_factor` and holds
+        no tombstones. Each of its subarrays also records the longest probe
+        sequence among its keys, so lookups of missing keys stop early and
+        empty subarrays are skipped.
+        With `perfect=True`, the copy instead uses a minimal perfect hash over
+        the current keys: entries fill exactly one slot each and every lookup
+        inspects a single slot. Building it takes longer, and `load_factor`
+        does not apply.
+        """
+
+    @property
+    def read_only(self) -> bool:
+        """Whether the table rejects modification (tables loaded with `mmap=True`)."""
+
+    def insert(self, key: int, value: float) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Iterable[float]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used."""
+
+    def insert_all(self, keys: Iterable[int], values: Iterable[float]) -> int:
+        """Insert many key/value pairs, all or none. See
+        `ElasticTable.insert_all`. On a logged table, the pairs taken
+        out again are logged as removed.
+        """
+
+    def get(self, key: int) -> float | None: ...
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def get_many(self, keys: Iterable[int], default: float | None = None, mask: bool = False, defaults: Iterable[float] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` for missing keys,
+        or the matching item of `defaults`, a sequence or array holding one
+        value for each key. Large batches are probed in parallel. With
+        `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def to_numpy(self) -> tuple[Any, Any]:
+        """Export the contents as a `(keys, values)` pair of numpy arrays."""
+
+    def to_arrow(self) -> tuple[Any, Any]:
+        """Export the contents as a `(keys, values)` pair of pyarrow arrays,
+        which share memory with the arrays `to_numpy()` returns.
+        """
+
+    def to_pandas(self, key_col: str = "key", value_col: str = "value") -> Any:
+        """Export the contents as a pandas DataFrame with one row per entry,
+        built around the arrays `to_numpy()` returns.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. Typed tables
+        do not keep insertion order. See `ElasticTable.keys`.
+        """
+
+    def values(self) -> _ValuesView:
+        """A live view of the values, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, value)` pairs, in the order of `keys()`."""
+
+    def transaction(self) -> _Transaction:
+        """Open a transaction, so that a group of changes can be undone
+        together. See `ElasticTable.transaction`. A rolled back
+        transaction is undone in the change log too.
+        """
+
+    def bulk(self, buffer: bool = False) -> _BulkBlock:
+        """Start a block of inserts, for use as `with table.bulk():`.
+        See `ElasticTable.bulk`. Besides tracer calls, a logged table
+        hands its change log to the OS once, when the block ends.
+        """
+
+    def __iter__(self) -> _TableIterator:
+        """Iterate over the keys, in the order of `keys()`. Raises
+        `RuntimeError` if the table gains or loses entries meanwhile.
+        """
+
+    def sample(self, n: int, items: bool = False, seed: int | None = None) -> Any:
+        """`n` distinct keys chosen uniformly at random, or `(key, value)`
+        pairs with `items=True`. See `ElasticTable.sample`.
+        """
+
+    def entry_at(self, index: int) -> tuple[int, float]:
+        """The entry at position `index` of slot order, as a `(key, value)`
+        pair. See `ElasticTable.entry_at`.
+        """
+
+    def entry_range(self, start: int, stop: int | None = None) -> list[tuple[int, float]]:
+        """The entries at positions `start` up to `stop` of slot order.
+        See `ElasticTable.entry_range`.
+        """
+
+    def subarray_lens(self) -> list[int]:
+        """Number of entries in each subarray, in slot order."""
+
+    def to_json(self, indent: int | None = None) -> str:
+        """Serialize the contents as a JSON object mapping each key, as a decimal
+        string, to its value, in ascending key order.
+        """
+
+    @staticmethod
+    def from_json(data: str, load_factor: float = 0.90) -> FloatTable:
+        """Build a table from a JSON object mapping decimal keys to values, such
+        as the output of `to_json()`.
+        """
+
+    def to_msgpack(self) -> bytes:
+        """Encode the contents as a msgpack map from key to value."""
+
+    @staticmethod
+    def from_msgpack(data: bytes, load_factor: float = 0.90) -> FloatTable:
+        """Build a table from a msgpack map from non-negative integer keys to values,
+        such as the output of `to_msgpack()`.
+        """
+
+    def to_cbor(self) -> bytes:
+        """Encode the contents as a CBOR map from key to value."""
+
+    @staticmethod
+    def from_cbor(data: bytes, load_factor: float = 0.90) -> FloatTable:
+        """Build a table from a CBOR map from non-negative integer keys to values,
+        such as the output of `to_cbor()`.
+        """
+
+    def clear(self) -> None:
+        """Remove every entry, keeping the allocated capacity."""
+
+    def merge(self, other: Any, on_conflict: str = "replace") -> None:
+        """Insert every entry of `other`, a table of the same class, a mapping
+        with int keys or an iterable of `(key, value)` pairs. See
+        ElasticTable.merge for `on_conflict`.
+        """
+
+    def __or__(self, other: FloatTable | dict[Any, Any]) -> FloatTable:
+        """`table | other`: a new table holding the entries of both, with the
+        values of `other` for keys both hold. It has the same `delta` and
+        enough capacity for both tables.
+        """
+
+    def __ior__(self, other: Any) -> FloatTable:
+        """`table |= other`: `merge(other)` in place."""
+
+    def intersection_keys(self, other: ElasticTable | IntTable | FloatTable) -> list[int]:
+        """Keys of this table that `other`, a table of any class, also holds,
+        in ascending order. See ElasticTable.intersection_keys.
+        """
+
+    def difference_keys(self, other: ElasticTable | IntTable | FloatTable) -> list[int]:
+        """Keys of this table that `other` does not hold, in ascending order."""
+
+    def split(self, n: int) -> list[FloatTable]:
+        """Partition the entries into `n` new tables by key hash. See
+        ElasticTable.split; the parts keep this table's `delta`.
+        """
+
+    @staticmethod
+    def shard_of(key: int, n: int) -> int:
+        """The part of `split(n)` that holds `key`. See ElasticTable.shard_of."""
+
+    @staticmethod
+    def tag_key(tag: int, key: int, tag_bits: int = 8) -> int:
+        """`key` with `tag` packed into its top `tag_bits` bits. See
+        ElasticTable.tag_key.
+        """
+
+    @staticmethod
+    def untag_key(key: int, tag_bits: int = 8) -> tuple[int, int]:
+        """The `(tag, key)` pair `tag_key` packed into `key`."""
+
+    def namespace_keys(self, tag: int, tag_bits: int = 8) -> list[int]:
+        """The keys in namespace `tag`, without the tag, in the order of `keys()`."""
+
+    def namespace_items(self, tag: int, tag_bits: int = 8) -> list[tuple[int, float]]:
+        """The `(key, value)` pairs in namespace `tag`, keys without the tag."""
+
+    def delete_namespace(self, tag: int, tag_bits: int = 8) -> int:
+        """Remove every key in namespace `tag`, as `delete_many` would.
+        Returns how many were removed.
+        """
+
+    def rebalance(self) -> None:
+        """Redistribute the entries across the subarrays, keeping the capacity.
+        See ElasticTable.rebalance. The GIL is released meanwhile.
+        """
+
+    def enable_bloom_filter(self, bits_per_key: int = 10) -> None:
+        """Keep a Bloom filter over the keys, so most lookups of missing keys
+        are answered without probing. See ElasticTable.enable_bloom_filter.
+        """
+
+    def disable_bloom_filter(self) -> None:
+        """Drop the Bloom filter, if any."""
+
+    @property
+    def bloom_filter(self) -> int | None:
+        """Bits per slot of the Bloom filter, or None without one."""
+
+    def enable_auto_shrink(self, threshold: float = 0.25, min_capacity: int = 0) -> None:
+        """Shrink the table once deletes leave it sparse. See
+        ElasticTable.enable_auto_shrink. Tables in shared memory or a
+        mapped file cannot shrink.
+        """
+
+    def disable_auto_shrink(self) -> None:
+        """Stop shrinking the table after deletes."""
+
+    @property
+    def auto_shrink(self) -> float | None:
+        """The `threshold` of `enable_auto_shrink`, or None if the table does
+        not shrink.
+        """
+
+    @property
+    def track_probes(self) -> bool:
+        """Whether probe lengths are recorded for `probe_histograms()` (default:
+        False). See ElasticTable.track_probes.
+        """
+    @track_probes.setter
+    def track_probes(self, enabled: bool) -> None: ...
+
+    @property
+    def adaptive(self) -> bool:
+        """Whether the insert probe budget adapts to the workload (default:
+        False). See ElasticTable.adaptive.
+        """
+    @adaptive.setter
+    def adaptive(self, enabled: bool) -> None: ...
+
+    def probe_histograms(self) -> dict[Any, Any] | None:
+        """Probe-length histograms recorded since `track_probes` was enabled, or
+        None if it is off. See ElasticTable.probe_histograms.
+        """
+
+    def op_stats(self) -> dict[Any, Any]:
+        """Counts of the operations applied since the table was created or loaded,
+        or `reset_stats()` was last called. See ElasticTable.op_stats.
+        """
+
+    def reset_stats(self) -> None:
+        """Zero the counts of `op_stats()`, the fallthroughs of `detailed_stats()`
+        and any probe histograms being recorded.
+        """
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def set_tracer(self, callback: Any | None) -> None:
+        """Call `callback(key, steps, inserted)` after every insert. See
+        ElasticTable.set_tracer.
+        """
+
+    def add_observer(self, callback: Any) -> None:
+        """Call `callback(event, key, old, new)` with every change to the
+        table. See ElasticTable.add_observer.
+        """
+
+    def remove_observer(self, callback: Any) -> None:
+        """Stop calling `callback` with changes. Raises ValueError if it
+        is not an observer of the table.
+        """
+
+    def set_load_alert(self, threshold: float | None, callback: Any | None = None, subarray_threshold: float | None = None) -> None:
+        """Call `callback(load_factor, subarray)`, or issue a
+        `LoadFactorWarning`, once the load factor crosses `threshold`.
+        See ElasticTable.set_load_alert.
+        """
+
+    @property
+    def load_alert(self) -> float | None:
+        """The `threshold` of `set_load_alert`, or None without an alert."""
+
+    def track_distinct_values(self, precision: int | None = ...) -> None:
+        """Estimate the number of distinct values inserted, reported as
+        "distinct_values" by `detailed_stats()`.
+        A HyperLogLog sketch of `2**precision` bytes (4 to 18, default
+        14 for 16 KiB and a typical error of 0.8%) takes in the values
+        stored now and every value inserted from then on. Values stay
+        counted once overwritten or deleted. `None` stops the estimate.
+        """
+
+    def metrics_text(self, prefix: str = "elastic_table", labels: dict[str, str] | None = None) -> str:
+        """Render the table's metrics in the Prometheus text exposition format.
+        See ElasticTable.metrics_text.
+        """
+
+    def probe_bounds(self, tolerance: float = 4.0) -> dict[Any, Any]:
+        """Compare the probe counts recorded while `track_probes` is on with the
+        paper's bounds. See ElasticTable.probe_bounds.
+        """
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes allocated for the table, broken down by subarray. See
+        ElasticTable.memory_usage; values are stored inline, so there are no
+        "references". Mapped tables count the bytes of their mapping.
+        """
+
+    def occupancy(self, packed: bool = False) -> list[Any]:
+        """Which slots of every subarray are occupied. See ElasticTable.occupancy."""
+
+    def items_in_subarray(self, i: int) -> list[tuple[int, float]]:
+        """The `(key, value)` pairs stored in subarray `i`, in slot order.
+        See ElasticTable.items_in_subarray.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats;
+        with `track_distinct_values`, "distinct_values" is the estimated
+        number of distinct values inserted, and None without it.
+        """
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class FrozenIntTable:
+    """A read-only IntTable.
+    Frozen tables only offer lookups, so any number of threads can share
+    one without locking.
+    """
+
+    @staticmethod
+    def open(path: str | os.PathLike[str], verify: bool = True) -> FrozenIntTable:
+        """Map a snapshot written by `save` for serving lookups.
+        The file is mapped read-only, so every process that opens it (for
+        example each worker of a pre-forking web server) shares one physical
+        copy through the page cache. The snapshot's checksums are checked
+        first, which reads the whole file; with `verify=False`, opening
+        instead takes milliseconds regardless of table size. Snapshots
+        with checkpoints or a change log are refused; load and save them
+        again to fold those in.
+        """
+
+    @staticmethod
+    def open_shared(name: str) -> FrozenIntTable:
+        """Attach to the shared memory segment of a table created with
+        `create_shared`, for lookups only. The segment must not be modified
+        while it is attached.
+        """
+
+    def get(self, key: int) -> int | None: ...
+
+    def get_many(self, keys: Iterable[int], default: int | None = None, mask: bool = False, defaults: Iterable[int] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` for missing keys,
+        or the matching item of `defaults`, a sequence or array holding one
+        value for each key. Large batches are probed in parallel. With
+        `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def to_numpy(self) -> tuple[Any, Any]:
+        """Export the contents as a `(keys, values)` pair of numpy arrays."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+
+class FrozenFloatTable:
+    """A read-only FloatTable.
+    Frozen tables only offer lookups, so any number of threads can share
+    one without locking.
+    """
+
+    @staticmethod
+    def open(path: str | os.PathLike[str], verify: bool = True) -> FrozenFloatTable:
+        """Map a snapshot written by `save` for serving lookups.
+        The file is mapped read-only, so every process that opens it (for
+        example each worker of a pre-forking web server) shares one physical
+        copy through the page cache. The snapshot's checksums are checked
+        first, which reads the whole file; with `verify=False`, opening
+        instead takes milliseconds regardless of table size. Snapshots
+        with checkpoints or a change log are refused; load and save them
+        again to fold those in.
+        """
+
+    @staticmethod
+    def open_shared(name: str) -> FrozenFloatTable:
+        """Attach to the shared memory segment of a table created with
+        `create_shared`, for lookups only. The segment must not be modified
+        while it is attached.
+        """
+
+    def get(self, key: int) -> float | None: ...
+
+    def get_many(self, keys: Iterable[int], default: float | None = None, mask: bool = False, defaults: Iterable[float] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` for missing keys,
+        or the matching item of `defaults`, a sequence or array holding one
+        value for each key. Large batches are probed in parallel. With
+        `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def to_numpy(self) -> tuple[Any, Any]:
+        """Export the contents as a `(keys, values)` pair of numpy arrays."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+
+class IntTable32:
+    """An IntTable with 32-bit keys: 64-bit signed integer values stored inline, in 13 bytes per slot."""
+
+    def __init__(self, capacity: int, delta: float = 0.05) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90) -> IntTable32:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    def insert(self, key: int, value: int) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Iterable[int]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used."""
+
+    def get(self, key: int) -> int | None: ...
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def get_many(self, keys: Iterable[int], default: int | None = None, mask: bool = False, defaults: Iterable[int] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` for missing keys,
+        or the matching item of `defaults`, a sequence or array holding one
+        value for each key. Large batches are probed in parallel. With
+        `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. See ElasticTable.keys."""
+
+    def values(self) -> _ValuesView:
+        """A live view of the values, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, value)` pairs, in the order of `keys()`."""
+
+    def clear(self) -> None: ...
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes of memory the table takes. See ElasticTable.memory_usage;
+        keys take 4 bytes per slot and values are stored inline.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats."""
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class FloatTable32:
+    """A FloatTable with 32-bit keys: 64-bit float values stored inline, in 13 bytes per slot."""
+
+    def __init__(self, capacity: int, delta: float = 0.05) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90) -> FloatTable32:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    def insert(self, key: int, value: float) -> int: ...
+
+    def insert_many(self, keys: Iterable[int], values: Iterable[float]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used."""
+
+    def get(self, key: int) -> float | None: ...
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def get_many(self, keys: Iterable[int], default: float | None = None, mask: bool = False, defaults: Iterable[float] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` for missing keys,
+        or the matching item of `defaults`, a sequence or array holding one
+        value for each key. Large batches are probed in parallel. With
+        `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. See ElasticTable.keys."""
+
+    def values(self) -> _ValuesView:
+        """A live view of the values, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, value)` pairs, in the order of `keys()`."""
+
+    def clear(self) -> None: ...
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes of memory the table takes. See ElasticTable.memory_usage;
+        keys take 4 bytes per slot and values are stored inline.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats."""
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class BytesTable:
+    """A table of raw byte values."""
+
+    def __init__(self, capacity: int, delta: float = 0.05, compress_above: int | None = None, compression_level: int = 3, spill_above: int | None = None, spill_path: str | os.PathLike[str] | None = None) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        See ElasticTable for the meaning of the arguments.
+        With `compress_above`, values longer than that many bytes are stored
+        zstd-compressed at `compression_level` (1 to 22) when that makes
+        them smaller.
+        With `spill_above` and `spill_path`, values still longer than
+        `spill_above` bytes are written to a value log at `spill_path`, and
+        the table keeps only their offset. The log is scratch space: it is
+        truncated now and removed with the table.
+        """
+
+    @staticmethod
+    def for_items(expected_items: int, load_factor: float = 0.90, compress_above: int | None = None, compression_level: int = 3, spill_above: int | None = None, spill_path: str | os.PathLike[str] | None = None) -> BytesTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    @property
+    def compress_above(self) -> int | None:
+        """The size past which values are compressed, or None."""
+
+    @property
+    def spill_above(self) -> int | None:
+        """The size past which values are spilled to the value log, or None."""
+
+    @property
+    def spill_path(self) -> str | None:
+        """The path of the value log, or None."""
+
+    def insert(self, key: int, value: Any) -> int:
+        """Store a copy of the bytes of `value`, any object supporting the
+        buffer protocol. Returns the probes used.
+        """
+
+    def insert_many(self, keys: Iterable[int], values: Sequence[Any]) -> int:
+        """Insert many key/value pairs in a single call. Returns the total probes used."""
+
+    def get(self, key: int) -> bytes | None:
+        """The bytes stored under `key`, or None."""
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def get_many(self, keys: Iterable[int], default: Any | None = None, mask: bool = False, defaults: Sequence[Any] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` (or the matching
+        item of `defaults`) for missing keys.
+        Large batches are probed in parallel. With `mask`, returns a
+        `(values, found)` pair; see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. See ElasticTable.keys."""
+
+    def values(self) -> _ValuesView:
+        """A live view of the values as bytes, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, value)` pairs, in the order of `keys()`."""
+
+    def clear(self) -> None: ...
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes of memory the table takes. See ElasticTable.memory_usage; a slot
+        holds a pointer and length for its value, and "payload_bytes", also
+        counted in "total_bytes", gives the bytes of the values themselves, as
+        stored; "compressed_values" counts the values stored compressed.
+        With a value log, "spilled_values" and "spilled_bytes" give the values
+        in the log and their bytes, which are not in memory, and
+        "value_log_bytes" the size of the log, including overwritten values.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats."""
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class RecordTable:
+    """A table of fixed-width records described by a `struct` format string."""
+
+    def __init__(self, format: str, capacity: int, delta: float = 0.05) -> None:
+        """Create a table with the given slot capacity and delta parameter for
+        records of the `struct` format `format`. See ElasticTable for the
+        meaning of the other arguments.
+        """
+
+    @staticmethod
+    def for_items(format: str, expected_items: int, load_factor: float = 0.90) -> RecordTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    @property
+    def format(self) -> Any:
+        """The `struct` format string of the records."""
+
+    @property
+    def record_size(self) -> int:
+        """Bytes per record."""
+
+    def insert(self, key: int, record: Any) -> int:
+        """Pack `record`, a tuple of fields matching the format, and store it.
+        Returns the probes used.
+        """
+
+    def insert_many(self, keys: Iterable[int], records: Sequence[Any]) -> int:
+        """Insert many key/record pairs in a single call. Returns the total probes used."""
+
+    def get(self, key: int) -> Any | None:
+        """The record stored under `key` as a tuple, or None."""
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def get_many(self, keys: Iterable[int], default: Any | None = None, mask: bool = False, defaults: Sequence[Any] | None = None) -> Any:
+        """Look up many keys in a single call, using `default` (or the matching
+        item of `defaults`) for missing keys.
+        Large batches are probed in parallel, and the records found are
+        unpacked in one pass. With `mask`, returns a `(values, found)` pair;
+        see ElasticTable.get_many.
+        """
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. See ElasticTable.keys."""
+
+    def values(self) -> _ValuesView:
+        """A live view of the records as tuples, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, record)` pairs, in the order of `keys()`."""
+
+    def clear(self) -> None: ...
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes of memory the table takes. See ElasticTable.memory_usage; a slot
+        holds the index of its record, and "record_bytes", also counted in
+        "total_bytes", gives the bytes of the record buffer, including
+        records freed by deletes and not reused yet.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats."""
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class ColumnTable:
+    """A table of several named, typed value columns per key."""
+
+    def __init__(self, columns: Any, capacity: int, delta: float = 0.05) -> None:
+        """Create a table with the given slot capacity and delta parameter.
+        `columns` maps each column name to its type: "i32", "i64", "u32",
+        "u64", "f32" or "f64" (numpy names such as "float32" work too), as a
+        dict or a list of `(name, type)` pairs. See ElasticTable for the
+        meaning of the other arguments.
+        """
+
+    @staticmethod
+    def for_items(columns: Any, expected_items: int, load_factor: float = 0.90) -> ColumnTable:
+        """Create a table sized for `expected_items` at the target load factor."""
+
+    @property
+    def columns(self) -> list[tuple[str, str]]:
+        """The `(name, type)` pairs of the columns, in order."""
+
+    def insert(self, key: int, row: Any) -> int:
+        """Store a row under `key`: a dict with a value for every column, or a
+        sequence of values in column order. Returns the probes used.
+        """
+
+    def insert_many(self, keys: Iterable[int], columns: dict[Any, Any]) -> int:
+        """Insert many rows in a single call, column by column: `columns` maps
+        every column name to its values for `keys`, as a numpy array or a
+        sequence. Returns the total probes used.
+        """
+
+    def get(self, key: int) -> dict[Any, Any] | None:
+        """The row stored under `key` as a dict, or None."""
+
+    def get_many(self, keys: Iterable[int], columns: Sequence[str] | None = None, default: Any | None = None, mask: bool = False) -> Any:
+        """Look up many keys in a single call, returning a dict with the values
+        of every column, or of the columns named in `columns`, as numpy arrays,
+        with `default`, or zero, for missing keys. Large batches are probed in
+        parallel. With `mask`, returns a `(columns, found)` pair; see
+        ElasticTable.get_many.
+        """
+
+    def column(self, name: str) -> Any:
+        """The values of column `name` as a numpy array, in the order of `keys()`."""
+
+    def to_numpy(self) -> tuple[Any, dict[Any, Any]]:
+        """Export the contents as a `(keys, columns)` pair: a numpy array of the
+        keys and a dict mapping every column name to a numpy array of its values.
+        """
+
+    def delete(self, key: int) -> bool:
+        """Remove a key from the table. Returns True if the key was present."""
+
+    def delete_many(self, keys: Iterable[int]) -> int:
+        """Remove many keys in a single call. Returns how many were present."""
+
+    def contains_many(self, keys: Iterable[int]) -> list[bool]:
+        """Check membership for many keys in a single call.
+        Large batches are probed in parallel.
+        """
+
+    def keys(self) -> _KeysView:
+        """A live, set-like view of the keys, in slot order. See ElasticTable.keys."""
+
+    def values(self) -> _ValuesView:
+        """A live view of the rows as dicts, in the order of `keys()`."""
+
+    def items(self) -> _ItemsView:
+        """A live, set-like view of the `(key, row)` pairs, in the order of `keys()`."""
+
+    def clear(self) -> None: ...
+
+    def capacity(self) -> int:
+        """Number of slots the table was created with."""
+
+    def remaining_capacity(self) -> int:
+        """See `ElasticTable.remaining_capacity`."""
+
+    def is_full(self) -> bool:
+        """See `ElasticTable.is_full`."""
+
+    def stats(self) -> list[tuple[int, int, float]]: ...
+
+    def memory_usage(self) -> dict[Any, Any]:
+        """Bytes of memory the table takes. See ElasticTable.memory_usage; a slot
+        holds the index of its row, and "column_bytes", also counted in
+        "total_bytes", maps every column to the bytes of its values, including
+        rows freed by deletes and not reused yet.
+        """
+
+    def detailed_stats(self) -> dict[Any, Any]:
+        """A structured version of `stats()`. See ElasticTable.detailed_stats."""
+
+    def validate(self) -> list[str]:
+        """Check the table's internal invariants. See ElasticTable.validate."""
+
+
+class TableRegistry:
+    """A set of ElasticTables addressed by name."""
+
+    def __init__(self) -> None: ...
+
+    def create(self, name: str, *args: Any, **kwargs: Any) -> ElasticTable:
+        """Create a table named `name` and return it. The other arguments are
+        those of the ElasticTable constructor. Raises ValueError if the name is
+        taken.
+        """
+
+    def create_for_items(self, name: str, *args: Any, **kwargs: Any) -> ElasticTable:
+        """Like `create`, with the arguments of ElasticTable.for_items."""
+
+    def add(self, name: str, table: ElasticTable) -> None:
+        """Register an existing table under `name`."""
+
+    def get(self, name: str) -> ElasticTable | None:
+        """The table named `name`, or None."""
+
+    def drop(self, name: str) -> bool:
+        """Remove the table named `name` from the registry. Returns True if there
+        was one. The table itself stays usable through other references.
+        """
+
+    def names(self) -> list[str]:
+        """The names of the tables, sorted."""
+
+    def clear(self) -> None:
+        """Remove every table from the registry."""
+
+    def stats(self) -> dict[Any, Any]:
+        """Totals over all tables.
+        Returns a dict with the number of "tables", their combined "count",
+        "capacity" and "load_factor", the "total_bytes" of `memory_usage()`
+        summed over them, and "per_table", a dict from each name to its
+        "count", "capacity", "load_factor" and "total_bytes". Closed tables are
+        left out.
+        """
+
+    def __len__(self) -> int: ...
+
+    def __contains__(self, name: str) -> bool: ...
+
+    def __getitem__(self, name: str) -> ElasticTable: ...
+
+    def __delitem__(self, name: str) -> None: ...
+
+    def __iter__(self) -> Any: ...
+
+
+class _BulkBlock:
+    """Returned by `table.bulk()`; entering it starts the block and leaving it
+    ends it.
+    """
+
+    def __enter__(self) -> Any:
+        """Starts the block and returns the table."""
+
+    def __exit__(self, _exc_type: type | None, _exc_value: Any | None, _traceback: Any | None) -> bool:
+        """Ends the block, applying what it held back even if the block raised."""
+
+
+class _ItemsView:
+    """Returned by `table.items()`: a live, set-like view of the `(key, value)` pairs."""
+
+    def __len__(self) -> int: ...
+
+    def __iter__(self) -> _TableIterator: ...
+
+    def __contains__(self, element: Any) -> bool: ...
+
+    def __repr__(self) -> str: ...
+
+    def __eq__(self, other: Any) -> Any: ...
+
+    def __ne__(self, other: Any) -> Any: ...
+
+    def __lt__(self, other: Any) -> Any: ...
+
+    def __le__(self, other: Any) -> Any: ...
+
+    def __gt__(self, other: Any) -> Any: ...
+
+    def __ge__(self, other: Any) -> Any: ...
+
+    def __and__(self, other: Any) -> Any: ...
+
+    def __rand__(self, other: Any) -> Any: ...
+
+    def __or__(self, other: Any) -> Any: ...
+
+    def __ror__(self, other: Any) -> Any: ...
+
+    def __sub__(self, other: Any) -> Any: ...
+
+    def __rsub__(self, other: Any) -> Any: ...
+
+    def __xor__(self, other: Any) -> Any: ...
+
+    def __rxor__(self, other: Any) -> Any: ...
+
+    def isdisjoint(self, other: Any) -> bool:
+        """Whether the view and `other` have no element in common."""
+
+
+class _KeysView:
+    """Returned by `table.keys()`: a live, set-like view of the keys."""
+
+    def __len__(self) -> int: ...
+
+    def __iter__(self) -> _TableIterator: ...
+
+    def __contains__(self, element: Any) -> bool: ...
+
+    def __repr__(self) -> str: ...
+
+    def __eq__(self, other: Any) -> Any: ...
+
+    def __ne__(self, other: Any) -> Any: ...
+
+    def __lt__(self, other: Any) -> Any: ...
+
+    def __le__(self, other: Any) -> Any: ...
+
+    def __gt__(self, other: Any) -> Any: ...
+
+    def __ge__(self, other: Any) -> Any: ...
+
+    def __and__(self, other: Any) -> Any: ...
+
+    def __rand__(self, other: Any) -> Any: ...
+
+    def __or__(self, other: Any) -> Any: ...
+
+    def __ror__(self, other: Any) -> Any: ...
+
+    def __sub__(self, other: Any) -> Any: ...
+
+    def __rsub__(self, other: Any) -> Any: ...
+
+    def __xor__(self, other: Any) -> Any: ...
+
+    def __rxor__(self, other: Any) -> Any: ...
+
+    def isdisjoint(self, other: Any) -> bool:
+        """Whether the view and `other` have no element in common."""
+
+
+class _TableIterator:
+    def __iter__(self) -> _TableIterator: ...
+
+    def __next__(self) -> Any: ...
+
+
+class _Transaction:
+    """Returned by `table.transaction()`, which opens it."""
+
+    def commit(self) -> None:
+        """Keep the changes made since the transaction began."""
+
+    def rollback(self) -> None:
+        """Undo the changes made since the transaction began."""
+
+    @property
+    def finished(self) -> bool:
+        """Whether the transaction was committed or rolled back."""
+
+    def __enter__(self) -> _Transaction: ...
+
+    def __exit__(self, exc_type: type | None, _exc_value: Any | None, _traceback: Any | None) -> bool:
+        """Commits if the block completed and rolls back if it raised, unless
+        the block already finished the transaction itself.
+        """
+
+
+class _ValuesView:
+    """Returned by `table.values()`: a live view of the values. Like a dict's,
+    it is not set-like, since values need not be unique or hashable.
+    """
+
+    def __len__(self) -> int: ...
+
+    def __iter__(self) -> _TableIterator: ...
+
+    def __contains__(self, value: Any) -> bool: ...
+
+    def __repr__(self) -> str: ...
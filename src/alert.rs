@@ -0,0 +1,102 @@
+//! Alerts fired when a table's load factor crosses a threshold.
+//!
+//! Inserts start failing once a table, or the subarrays most inserts land in,
+//! fill up. A table with an alert checks its load factor after every insert
+//! call, and the first time the overall load, or that of a subarray, reaches
+//! its threshold, it calls the alert's callback or, without one, issues a
+//! `LoadFactorWarning`. An alert fires once per crossing: it fires again only
+//! after deletes have brought the load back below the threshold.
+
+use std::ffi::CString;
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::PyValueError;
+
+use crate::errors::LoadFactorWarning;
+use crate::map::ElasticMap;
+
+/// Validates a load factor threshold of `set_load_alert`.
+fn check_threshold(threshold: f64, name: &str) -> PyResult<()> {
+    if threshold <= 0.0 || threshold > 1.0 {
+        return Err(PyValueError::new_err(format!("{name} must be between 0 and 1")));
+    }
+    Ok(())
+}
+
+pub(crate) struct LoadAlert {
+    threshold: f64,
+    subarray_threshold: Option<f64>,
+    /// Called with the load factor and the subarray, or None for the whole
+    /// table; a warning is issued instead if not set.
+    callback: Option<PyObject>,
+    /// Whether the overall load, and that of each subarray, is at or past its
+    /// threshold since the alert last fired for it.
+    fired: bool,
+    fired_subarrays: Vec<bool>,
+}
+
+impl LoadAlert {
+    pub(crate) fn new(threshold: f64, subarray_threshold: Option<f64>, callback: Option<PyObject>) -> PyResult<Self> {
+        check_threshold(threshold, "threshold")?;
+        if let Some(threshold) = subarray_threshold {
+            check_threshold(threshold, "subarray_threshold")?;
+        }
+        Ok(LoadAlert { threshold, subarray_threshold, callback, fired: false, fired_subarrays: Vec::new() })
+    }
+
+    pub(crate) fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        match &self.callback {
+            Some(callback) => visit.call(callback),
+            None => Ok(()),
+        }
+    }
+
+    /// Fires for every load of `map` that crossed its threshold since the
+    /// last check, overall load first.
+    pub(crate) fn check<V>(&mut self, py: Python<'_>, map: &ElasticMap<V>) -> PyResult<()> {
+        let load = map.load_factor();
+        let rising = load >= self.threshold && !self.fired;
+        self.fired = load >= self.threshold;
+        if rising {
+            self.fire(py, load, None)?;
+        }
+        let Some(threshold) = self.subarray_threshold else { return Ok(()) };
+        let loads: Vec<f64> = map.subarray_loads().collect();
+        // Resizing changes the number of subarrays.
+        self.fired_subarrays.resize(loads.len(), false);
+        for (i, load) in loads.into_iter().enumerate() {
+            let rising = load >= threshold && !self.fired_subarrays[i];
+            self.fired_subarrays[i] = load >= threshold;
+            if rising {
+                self.fire(py, load, Some(i))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fire(&self, py: Python<'_>, load: f64, subarray: Option<usize>) -> PyResult<()> {
+        if let Some(callback) = &self.callback {
+            callback.call1(py, (load, subarray))?;
+            return Ok(());
+        }
+        let message = match subarray {
+            Some(i) => format!(
+                "subarray {i} is {:.1}% full, past the alert threshold of {:.1}%",
+                load * 100.0,
+                self.subarray_threshold.unwrap_or(1.0) * 100.0,
+            ),
+            None => format!(
+                "table is {:.1}% full, past the alert threshold of {:.1}%",
+                load * 100.0,
+                self.threshold * 100.0,
+            ),
+        };
+        let message = CString::new(message).expect("no NUL in the message");
+        PyErr::warn(py, &py.get_type::<LoadFactorWarning>(), &message, 1)
+    }
+}
@@ -0,0 +1,208 @@
+//! Argument types for the batch methods.
+
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+use pyo3::exceptions::PyValueError;
+use pyo3::buffer::{Element, PyBuffer};
+use numpy::IntoPyArray;
+use rayon::prelude::*;
+use std::borrow::Cow;
+
+use crate::hash::hash_keys;
+use crate::map::ElasticMap;
+
+/// Batches smaller than this are probed on the calling thread; splitting them
+/// across the rayon pool costs more than it saves.
+pub(crate) const PARALLEL_THRESHOLD: usize = 16 * 1024;
+
+/// A batch of keys or values: either a one-dimensional typed buffer (e.g. a numpy
+/// array), which is read in place, or any sequence of Python numbers.
+pub(crate) enum Batch<T: Element> {
+    Buffer(PyBuffer<T>),
+    Seq(Vec<T>),
+}
+
+/// Keys accepted by the batch methods.
+pub(crate) type KeyBatch = Batch<u64>;
+
+impl<'py, T> FromPyObject<'py> for Batch<T>
+where
+    T: Element + FromPyObject<'py>,
+{
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(buf) = PyBuffer::<T>::get(ob) {
+            if buf.dimensions() == 1 {
+                return Ok(Batch::Buffer(buf));
+            }
+        }
+        Ok(Batch::Seq(ob.extract()?))
+    }
+}
+
+impl<T: Element> Batch<T> {
+    /// Whether the items came as a buffer, such as a numpy array.
+    pub(crate) fn is_buffer(&self) -> bool {
+        matches!(self, Batch::Buffer(_))
+    }
+}
+
+impl<T: Element + Copy> Batch<T> {
+    /// Borrows the items as a slice. Contiguous buffers are not copied.
+    pub(crate) fn as_slice(&self, py: Python<'_>) -> PyResult<Cow<'_, [T]>> {
+        match self {
+            Batch::Buffer(buf) if buf.is_c_contiguous() => {
+                // SAFETY: the buffer is C-contiguous, holds `item_count` values of `T` and
+                // stays alive (and exported) for as long as `self` is borrowed.
+                let items = unsafe {
+                    std::slice::from_raw_parts(buf.buf_ptr() as *const T, buf.item_count())
+                };
+                Ok(Cow::Borrowed(items))
+            }
+            Batch::Buffer(buf) => Ok(Cow::Owned(buf.to_vec(py)?)),
+            Batch::Seq(items) => Ok(Cow::Borrowed(items)),
+        }
+    }
+}
+
+/// The fill values of a typed table's `get_many`: `default` for every
+/// missing key, or `defaults`, a batch holding one value per key.
+pub(crate) enum Fills<'a, T: Clone> {
+    One(T),
+    PerKey(Cow<'a, [T]>),
+}
+
+impl<'a, T: Element + Copy> Fills<'a, T> {
+    /// The fill values for a batch of `len` keys, or `None` if the call gave
+    /// neither; `defaults` must hold exactly `len` values.
+    pub(crate) fn new(py: Python<'_>, default: Option<T>, defaults: Option<&'a Batch<T>>, len: usize) -> PyResult<Option<Self>> {
+        match (default, defaults) {
+            (Some(_), Some(_)) => Err(PyValueError::new_err("pass default or defaults, not both")),
+            (Some(value), None) => Ok(Some(Fills::One(value))),
+            (None, Some(batch)) => {
+                let values = batch.as_slice(py)?;
+                check_fill_len(values.len(), len)?;
+                Ok(Some(Fills::PerKey(values)))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl<T: Copy> Fills<'_, T> {
+    /// The value for a missing `keys[index]`.
+    pub(crate) fn at(&self, index: usize) -> T {
+        match self {
+            Fills::One(value) => *value,
+            Fills::PerKey(values) => values[index],
+        }
+    }
+}
+
+/// The fill values of an object table's `get_many`: `default` for every
+/// missing key, or `defaults`, a sequence holding one object per key.
+pub(crate) enum ObjectFills {
+    One(PyObject),
+    PerKey(Vec<PyObject>),
+}
+
+impl ObjectFills {
+    pub(crate) fn new(
+        py: Python<'_>,
+        default: Option<PyObject>,
+        defaults: Option<Vec<PyObject>>,
+        len: usize,
+    ) -> PyResult<Self> {
+        let Some(values) = defaults else {
+            return Ok(ObjectFills::One(default.unwrap_or_else(|| py.None())));
+        };
+        if default.is_some() {
+            return Err(PyValueError::new_err("pass default or defaults, not both"));
+        }
+        check_fill_len(values.len(), len)?;
+        Ok(ObjectFills::PerKey(values))
+    }
+
+    /// The value for a missing `keys[index]`.
+    pub(crate) fn at(&self, py: Python<'_>, index: usize) -> PyObject {
+        match self {
+            ObjectFills::One(value) => value.clone_ref(py),
+            ObjectFills::PerKey(values) => values[index].clone_ref(py),
+        }
+    }
+}
+
+fn check_fill_len(fills: usize, keys: usize) -> PyResult<()> {
+    if fills != keys {
+        return Err(PyValueError::new_err(format!(
+            "got {fills} default values for {keys} keys"
+        )));
+    }
+    Ok(())
+}
+
+/// The result of a batch lookup: `values`, or, given the `found` flags of the
+/// keys, a `(values, found)` pair. Flags of keys that came as a buffer are
+/// returned as a numpy bool array, others as a list.
+pub(crate) fn lookup_result<'py>(
+    py: Python<'py>,
+    values: impl IntoPyObject<'py>,
+    found: Option<Vec<bool>>,
+    keys_buffer: bool,
+) -> PyResult<PyObject> {
+    let values = values.into_bound_py_any(py)?;
+    let Some(found) = found else { return Ok(values.unbind()) };
+    let found = if keys_buffer {
+        py.import("numpy")?;
+        found.into_pyarray(py).into_any()
+    } else {
+        found.into_bound_py_any(py)?
+    };
+    (values, found).into_py_any(py)
+}
+
+/// How many keys ahead of the current probe `map_keys` prefetches.
+const PREFETCH_DISTANCE: usize = 16;
+
+/// Maps every key of a lookup batch through `f(key, hash)`, in parallel for large
+/// batches. Results keep the order of `keys`.
+///
+/// The whole batch is hashed up front, which lets each worker prefetch the first
+/// probe of the key `PREFETCH_DISTANCE` positions ahead while probing the current one.
+pub(crate) fn map_keys<V, R, F>(map: &ElasticMap<V>, keys: &[u64], f: F) -> Vec<R>
+where
+    V: Sync,
+    R: Send,
+    F: Fn(u64, u64) -> R + Sync + Send,
+{
+    map_keys_with(keys, |hash| map.prefetch(hash), f)
+}
+
+/// `map_keys` for callers that spread keys over several maps; `prefetch(hash)`
+/// prefetches the first probe of `hash` in whichever map will be probed for it.
+pub(crate) fn map_keys_with<R, P, F>(keys: &[u64], prefetch: P, f: F) -> Vec<R>
+where
+    R: Send,
+    P: Fn(u64) + Sync + Send,
+    F: Fn(u64, u64) -> R + Sync + Send,
+{
+    let hashes = hash_keys(keys);
+    let probe_chunk = |keys: &[u64], hashes: &[u64]| -> Vec<R> {
+        let mut out = Vec::with_capacity(keys.len());
+        for i in 0..keys.len() {
+            if let Some(&ahead) = hashes.get(i + PREFETCH_DISTANCE) {
+                prefetch(ahead);
+            }
+            out.push(f(keys[i], hashes[i]));
+        }
+        out
+    };
+
+    if keys.len() < PARALLEL_THRESHOLD {
+        probe_chunk(keys, &hashes)
+    } else {
+        keys.par_chunks(PARALLEL_THRESHOLD)
+            .zip(hashes.par_chunks(PARALLEL_THRESHOLD))
+            .flat_map_iter(|(keys, hashes)| probe_chunk(keys, hashes))
+            .collect()
+    }
+}
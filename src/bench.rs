@@ -0,0 +1,197 @@
+//! `ElasticTable.benchmark()`: a standard workload timed against `dict`, and
+//! `ElasticTable.compare()`: the harness of `compare` with a `dict` added.
+//!
+//! The workload inserts `n` distinct pseudo-random keys into a table sized for
+//! them at the requested load factor, looks every key up again, and then looks
+//! up `n` keys that were never inserted. A `dict` runs the same operations on
+//! the same keys and values. Both are driven from native code, so the timings
+//! leave out the cost of calling into them from Python, which both pay alike.
+
+use std::time::Instant;
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+
+use crate::compare::{compare, Measurement, Probes, Workload};
+use crate::hash::hash_key;
+use crate::map::ElasticMap;
+use crate::table::sizing_for_items;
+use crate::value::PyValue;
+
+/// Seconds taken by each phase of the workload.
+struct Timings {
+    insert: f64,
+    hit: f64,
+    miss: f64,
+}
+
+impl Timings {
+    fn to_dict<'py>(&self, py: Python<'py>, n: usize) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("insert_seconds", self.insert)?;
+        dict.set_item("hit_seconds", self.hit)?;
+        dict.set_item("miss_seconds", self.miss)?;
+        let ns = |seconds: f64| seconds * 1e9 / n as f64;
+        dict.set_item("insert_ns", ns(self.insert))?;
+        dict.set_item("hit_ns", ns(self.hit))?;
+        dict.set_item("miss_ns", ns(self.miss))?;
+        Ok(dict)
+    }
+}
+
+/// Runs `f` and returns its result with the seconds it took.
+fn timed<R>(f: impl FnOnce() -> R) -> (R, f64) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed().as_secs_f64())
+}
+
+fn ints(py: Python<'_>, values: impl Iterator<Item = u64>) -> PyResult<Vec<Bound<'_, PyAny>>> {
+    values.map(|value| Ok(value.into_pyobject(py)?.into_any())).collect()
+}
+
+/// Runs the workload on a `dict` with the given keys and values.
+fn time_dict<'py>(
+    py: Python<'py>,
+    keys: &[Bound<'py, PyAny>],
+    missing: &[Bound<'py, PyAny>],
+    values: &[Bound<'py, PyAny>],
+) -> PyResult<Timings> {
+    let dict = PyDict::new(py);
+    let (result, insert) = timed(|| {
+        for (key, value) in keys.iter().zip(values) {
+            dict.set_item(key, value)?;
+        }
+        Ok::<_, PyErr>(())
+    });
+    result?;
+    let (result, hit) = timed(|| {
+        for key in keys {
+            drop(dict.get_item(key)?);
+        }
+        Ok::<_, PyErr>(())
+    });
+    result?;
+    let (result, miss) = timed(|| {
+        for key in missing {
+            drop(dict.get_item(key)?);
+        }
+        Ok::<_, PyErr>(())
+    });
+    result?;
+    Ok(Timings { insert, hit, miss })
+}
+
+/// Runs the workload for `n` keys and reports the timings of both tables and
+/// the probes the elastic table used.
+pub(crate) fn benchmark(py: Python<'_>, n: usize, load_factor: f64) -> PyResult<Bound<'_, PyDict>> {
+    if n == 0 {
+        return Err(PyValueError::new_err("n must be positive"));
+    }
+    let (capacity, delta) = sizing_for_items(n, load_factor)?;
+    // `hash_key` is a bijection, so these keys are distinct and look random.
+    let keys: Vec<u64> = (0..n as u64).map(hash_key).collect();
+    let missing: Vec<u64> = (n as u64..2 * n as u64).map(hash_key).collect();
+    let values = ints(py, 0..n as u64)?;
+    let key_objects = ints(py, keys.iter().copied())?;
+    let missing_objects = ints(py, missing.iter().copied())?;
+
+    let mut map = ElasticMap::new(capacity, delta);
+    let (probes, insert) = timed(|| {
+        let mut probes = Vec::with_capacity(n);
+        for (&key, value) in keys.iter().zip(&values) {
+            probes.push(map.insert(key, PyValue::new(value))?);
+        }
+        Ok::<_, PyErr>(probes)
+    });
+    let probes = probes?;
+    let ((), hit) = timed(|| {
+        for &key in &keys {
+            drop(map.get(key).map(|value| value.to_object(py, &[])));
+        }
+    });
+    let ((), miss) = timed(|| {
+        for &key in &missing {
+            drop(map.get(key).map(|value| value.to_object(py, &[])));
+        }
+    });
+    let elastic = Timings { insert, hit, miss };
+    let baseline = time_dict(py, &key_objects, &missing_objects, &values)?;
+
+    // Lookup probes are counted in an untimed pass, so recording them does not
+    // slow the timed one down.
+    map.track_probes(true);
+    let probe_stats = PyDict::new(py);
+    probe_stats.set_item("insert_mean", probes.iter().sum::<usize>() as f64 / n as f64)?;
+    probe_stats.set_item("insert_max", probes.iter().max().copied().unwrap_or(0))?;
+    for (name, lookups) in [("hit", &keys), ("miss", &missing)] {
+        map.reset_counts();
+        for &key in lookups {
+            map.get(key);
+        }
+        let gets = &map.histograms().unwrap().gets;
+        probe_stats.set_item(format!("{name}_mean"), gets.mean())?;
+        probe_stats.set_item(format!("{name}_max"), gets.longest())?;
+    }
+
+    let report = PyDict::new(py);
+    report.set_item("n", n)?;
+    report.set_item("load_factor", map.len() as f64 / capacity as f64)?;
+    report.set_item("capacity", capacity)?;
+    report.set_item("elastic", elastic.to_dict(py, n)?)?;
+    report.set_item("dict", baseline.to_dict(py, n)?)?;
+    report.set_item("probes", probe_stats)?;
+    Ok(report)
+}
+
+fn probes_to_dict(py: Python<'_>, probes: Option<Probes>) -> PyResult<PyObject> {
+    let Some(Probes { mean, max }) = probes else { return Ok(py.None()) };
+    let dict = PyDict::new(py);
+    dict.set_item("mean", mean)?;
+    dict.set_item("max", max)?;
+    Ok(dict.into_any().unbind())
+}
+
+fn measurement_to_dict<'py>(py: Python<'py>, m: &Measurement) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("insert_ns", m.insert_ns)?;
+    dict.set_item("hit_ns", m.hit_ns)?;
+    dict.set_item("miss_ns", m.miss_ns)?;
+    dict.set_item("failed_inserts", m.failed_inserts)?;
+    dict.set_item("insert_probes", probes_to_dict(py, m.insert_probes)?)?;
+    dict.set_item("hit_probes", probes_to_dict(py, m.hit_probes)?)?;
+    dict.set_item("miss_probes", probes_to_dict(py, m.miss_probes)?)?;
+    Ok(dict)
+}
+
+/// Runs the comparison harness and the same workload on a `dict`, and
+/// reports every table's results along with the harness's text report.
+pub(crate) fn compare_tables(py: Python<'_>, n: usize, load_factor: f64, seed: u64) -> PyResult<Bound<'_, PyDict>> {
+    if n == 0 {
+        return Err(PyValueError::new_err("n must be positive"));
+    }
+    if !(load_factor > 0.0 && load_factor < 1.0) {
+        return Err(PyValueError::new_err("load_factor must be between 0 and 1"));
+    }
+    let workload = Workload { n, load_factor, seed };
+    let mut comparison = py.allow_threads(|| compare(&workload));
+
+    let (keys, missing) = workload.keys();
+    let values = ints(py, 0..n as u64)?;
+    let timings = time_dict(py, &ints(py, keys.into_iter())?, &ints(py, missing.into_iter())?, &values)?;
+    comparison.results.push(Measurement::from_seconds("dict", n, timings.insert, timings.hit, timings.miss));
+
+    let tables = PyDict::new(py);
+    for m in &comparison.results {
+        tables.set_item(&m.name, measurement_to_dict(py, m)?)?;
+    }
+    let report = PyDict::new(py);
+    report.set_item("n", n)?;
+    report.set_item("load_factor", load_factor)?;
+    report.set_item("capacity", comparison.capacity)?;
+    report.set_item("seed", seed)?;
+    report.set_item("tables", tables)?;
+    report.set_item("report", comparison.to_string())?;
+    Ok(report)
+}
@@ -0,0 +1,90 @@
+//! `with table.bulk():` blocks, which batch the bookkeeping of many inserts.
+//!
+//! Inside a block, a table calls its tracer, and hands its change log to the
+//! OS, once when the block ends instead of after every call. With
+//! `buffer=True`, `insert` and `insert_many` only record their entries, and the
+//! end of the block inserts them in one batch, with the GIL released, in the
+//! order they were made. Sorting them by the group each key probes first was
+//! measured to cost more than the locality it buys, since most keys probe
+//! more than one subarray.
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::types::{PyAny, PyType};
+
+use crate::table::TableHandle;
+
+/// The state of a table inside a `bulk()` block.
+pub(crate) struct Bulk<V> {
+    /// Inserts held back until the block ends, when buffering.
+    buffer: Option<Vec<(u64, V)>>,
+}
+
+impl<V> Bulk<V> {
+    pub(crate) fn new(buffer: bool) -> Self {
+        Bulk { buffer: buffer.then(Vec::new) }
+    }
+
+    /// Where inserts go instead of the map, if the block buffers them.
+    pub(crate) fn buffer(&mut self) -> Option<&mut Vec<(u64, V)>> {
+        self.buffer.as_mut()
+    }
+
+    /// The values of the buffered inserts.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.buffer.iter().flatten().map(|(_, value)| value)
+    }
+
+    /// Takes the buffered inserts out, in the order they were made.
+    pub(crate) fn take(&mut self) -> (Vec<u64>, Vec<V>) {
+        self.buffer.as_mut().map_or_else(Default::default, |buffer| std::mem::take(buffer).into_iter().unzip())
+    }
+}
+
+/// Returned by `table.bulk()`; entering it starts the block and leaving it
+/// ends it.
+#[pyclass]
+pub(crate) struct BulkBlock {
+    table: TableHandle,
+    buffer: bool,
+}
+
+impl BulkBlock {
+    pub(crate) fn new(table: impl Into<TableHandle>, buffer: bool) -> Self {
+        BulkBlock { table: table.into(), buffer }
+    }
+}
+
+#[pymethods]
+impl BulkBlock {
+    /// Starts the block and returns the table.
+    fn __enter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match &self.table {
+            TableHandle::Elastic(table) => table.get().lock(py)?.begin_bulk(self.buffer)?,
+            TableHandle::Int(table) => table.get().lock(py)?.begin_bulk(self.buffer)?,
+            TableHandle::Float(table) => table.get().lock(py)?.begin_bulk(self.buffer)?,
+        }
+        Ok(self.table.object(py))
+    }
+
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        self.table.traverse(&visit)
+    }
+
+    /// Ends the block, applying what it held back even if the block raised.
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyType>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        match &self.table {
+            TableHandle::Elastic(table) => table.get().lock(py)?.end_bulk(py)?,
+            TableHandle::Int(table) => table.get().lock(py)?.end_bulk(py)?,
+            TableHandle::Float(table) => table.get().lock(py)?.end_bulk(py)?,
+        }
+        Ok(false)
+    }
+}
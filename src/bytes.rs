@@ -0,0 +1,541 @@
+//! `BytesTable`: a table storing values as raw bytes owned by Rust.
+//!
+//! Values can be any object supporting the buffer protocol (`bytes`,
+//! `bytearray`, `memoryview`, numpy arrays, ...). Their bytes are copied into
+//! the table, and lookups return them as `bytes`, so serialized payloads are
+//! kept without a Python object per entry and stay valid however the buffer
+//! they came from changes.
+//!
+//! With `compress_above` set, values longer than that many bytes are stored
+//! zstd-compressed, when compression makes them smaller, and decompressed on
+//! every lookup: CPU time traded for memory on compressible payloads. With
+//! `spill_above` set, values that are still longer are written to a value log
+//! on disk (see `spill`), so occasional huge blobs do not cost their size in
+//! memory.
+
+use std::borrow::Cow;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyBytes, PyDict, PyMemoryView};
+
+use crate::batch::{lookup_result, map_keys, KeyBatch, ObjectFills};
+#[cfg(feature = "zstd")]
+use crate::errors::CorruptedDataError;
+use crate::hash::hash_keys;
+use crate::lock::{TableGuard, TableLock};
+use crate::map::{ElasticMap, InsertError};
+use crate::spill::ValueLog;
+use crate::table::{check_delta, detailed_stats, memory_usage, sizing_for_items};
+use crate::views::{ItemsView, KeysView, ValuesView};
+
+/// Copies the bytes of a buffer-protocol object.
+fn buffer_bytes(value: &Bound<'_, PyAny>) -> PyResult<Box<[u8]>> {
+    if let Ok(bytes) = value.downcast::<PyBytes>() {
+        return Ok(bytes.as_bytes().into());
+    }
+    if let Ok(buffer) = PyBuffer::<u8>::get(value) {
+        return Ok(buffer.to_vec(value.py())?.into_boxed_slice());
+    }
+    // Buffers of other item types, such as float arrays, are copied byte for
+    // byte in C order.
+    let bytes = PyMemoryView::from(value)?.call_method0("tobytes")?;
+    Ok(bytes.downcast::<PyBytes>()?.as_bytes().into())
+}
+
+/// A stored value: its bytes, their zstd compression, or where the value
+/// log holds either.
+pub(crate) enum Payload {
+    Raw(Box<[u8]>),
+    #[cfg(feature = "zstd")]
+    Zstd(Box<[u8]>),
+    Spilled { offset: u64, len: usize, compressed: bool },
+}
+
+impl Payload {
+    /// Bytes the value takes in memory.
+    fn stored_len(&self) -> usize {
+        match self {
+            Payload::Raw(bytes) => bytes.len(),
+            #[cfg(feature = "zstd")]
+            Payload::Zstd(data) => data.len(),
+            Payload::Spilled { .. } => 0,
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        match self {
+            Payload::Raw(_) => false,
+            #[cfg(feature = "zstd")]
+            Payload::Zstd(_) => true,
+            &Payload::Spilled { compressed, .. } => compressed,
+        }
+    }
+
+    fn spilled_len(&self) -> Option<usize> {
+        match *self {
+            Payload::Spilled { len, .. } => Some(len),
+            _ => None,
+        }
+    }
+}
+
+/// Which values a table compresses, and how hard.
+#[derive(Clone, Copy)]
+struct Compression {
+    threshold: usize,
+    #[cfg_attr(not(feature = "zstd"), allow(dead_code))]
+    level: i32,
+}
+
+impl Compression {
+    fn new(compress_above: Option<usize>, level: i32) -> PyResult<Option<Self>> {
+        let Some(threshold) = compress_above else { return Ok(None) };
+        if cfg!(not(feature = "zstd")) {
+            return Err(PyValueError::new_err("compress_above requires a build with the zstd feature"));
+        }
+        if !(1..=22).contains(&level) {
+            return Err(PyValueError::new_err("compression_level must be between 1 and 22"));
+        }
+        Ok(Some(Compression { threshold, level }))
+    }
+}
+
+/// How a table turns values into payloads and back: which it compresses,
+/// and which it spills to its value log.
+struct Storage {
+    compression: Option<Compression>,
+    spill: Option<(usize, ValueLog)>,
+}
+
+impl Storage {
+    /// Compresses `bytes` if they are past the compression threshold and
+    /// compression makes them smaller, then spills the result if it is past
+    /// the spill threshold.
+    fn encode(&mut self, bytes: Box<[u8]>) -> io::Result<Payload> {
+        #[allow(unused_mut)]
+        let mut payload = Payload::Raw(bytes);
+        #[cfg(feature = "zstd")]
+        if let (Some(Compression { threshold, level }), Payload::Raw(bytes)) = (self.compression, &payload) {
+            if bytes.len() > threshold {
+                let compressed = zstd::bulk::compress(bytes, level)?;
+                if compressed.len() < bytes.len() {
+                    payload = Payload::Zstd(compressed.into_boxed_slice());
+                }
+            }
+        }
+        let Some((threshold, log)) = &mut self.spill else { return Ok(payload) };
+        let compressed = payload.is_compressed();
+        match payload {
+            Payload::Raw(data) if data.len() > *threshold => Self::spill(log, &data, compressed),
+            #[cfg(feature = "zstd")]
+            Payload::Zstd(data) if data.len() > *threshold => Self::spill(log, &data, compressed),
+            payload => Ok(payload),
+        }
+    }
+
+    fn spill(log: &mut ValueLog, data: &[u8], compressed: bool) -> io::Result<Payload> {
+        let offset = log.append(data)?;
+        Ok(Payload::Spilled { offset, len: data.len(), compressed })
+    }
+
+    /// The bytes of a value, read back and decompressed if need be.
+    fn decode<'a>(&self, payload: &'a Payload) -> PyResult<Cow<'a, [u8]>> {
+        match *payload {
+            Payload::Raw(ref bytes) => Ok(Cow::Borrowed(bytes)),
+            #[cfg(feature = "zstd")]
+            Payload::Zstd(ref data) => decompress(data).map(Cow::Owned),
+            Payload::Spilled { offset, len, compressed } => {
+                let (_, log) = self.spill.as_ref().expect("spilled values need a value log");
+                let data = log.read(offset, len)?;
+                #[cfg(feature = "zstd")]
+                if compressed {
+                    return decompress(&data).map(Cow::Owned);
+                }
+                debug_assert!(!compressed);
+                Ok(Cow::Owned(data))
+            }
+        }
+    }
+}
+
+/// A value that cannot be decompressed means the table's memory, or its
+/// value log, was damaged.
+#[cfg(feature = "zstd")]
+fn decompress(data: &[u8]) -> PyResult<Vec<u8>> {
+    zstd::decode_all(data)
+        .map_err(|err| CorruptedDataError::new_err(format!("cannot decompress a stored value: {err}")))
+}
+
+/// A table of raw byte values.
+#[pyclass(frozen)]
+pub(crate) struct BytesTable {
+    state: TableLock<BytesTableState>,
+}
+
+/// The state of a `BytesTable`, behind its lock.
+pub(crate) struct BytesTableState {
+    map: ElasticMap<Payload>,
+    storage: Storage,
+}
+
+impl From<BytesTableState> for BytesTable {
+    fn from(state: BytesTableState) -> Self {
+        BytesTable { state: TableLock::new(state) }
+    }
+}
+
+impl BytesTable {
+    /// The table's state, waiting for any call on another thread to finish.
+    pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, BytesTableState>> {
+        self.state.lock(py)
+    }
+}
+
+impl BytesTableState {
+    pub(crate) fn map(&self) -> &ElasticMap<Payload> {
+        &self.map
+    }
+
+    /// The value stored under `key` as bytes, without counting a lookup.
+    pub(crate) fn value_of(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+        self.map.get(key).map(|value| Ok(PyBytes::new(py, &self.storage.decode(value)?).into_any().unbind())).transpose()
+    }
+}
+
+impl BytesTableState {
+    fn new(
+        capacity: usize,
+        delta: f64,
+        compress_above: Option<usize>,
+        compression_level: i32,
+        spill_above: Option<usize>,
+        spill_path: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        check_delta(delta)?;
+        let compression = Compression::new(compress_above, compression_level)?;
+        let spill = match (spill_above, spill_path) {
+            (Some(threshold), Some(path)) => Some((threshold, ValueLog::create(path)?)),
+            (None, None) => None,
+            _ => return Err(PyValueError::new_err("spill_above and spill_path must be given together")),
+        };
+        Ok(BytesTableState { map: ElasticMap::new(capacity, delta), storage: Storage { compression, spill } })
+    }
+
+    fn for_items(
+        expected_items: usize,
+        load_factor: f64,
+        compress_above: Option<usize>,
+        compression_level: i32,
+        spill_above: Option<usize>,
+        spill_path: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(capacity, delta, compress_above, compression_level, spill_above, spill_path)
+    }
+
+    fn compress_above(&self) -> Option<usize> {
+        self.storage.compression.map(|compression| compression.threshold)
+    }
+
+    fn spill_above(&self) -> Option<usize> {
+        self.storage.spill.as_ref().map(|&(threshold, _)| threshold)
+    }
+
+    fn spill_path(&self) -> Option<&Path> {
+        self.storage.spill.as_ref().map(|(_, log)| log.path())
+    }
+
+    fn insert(&mut self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let bytes = buffer_bytes(value)?;
+        let storage = &mut self.storage;
+        let payload = py.allow_threads(|| storage.encode(bytes))?;
+        Ok(self.map.insert(key, payload)?)
+    }
+
+    fn insert_many(&mut self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        if keys.len() != values.len() {
+            return Err(PyValueError::new_err("keys and values must have the same length"));
+        }
+        let values = values.iter().map(buffer_bytes).collect::<PyResult<Vec<_>>>()?;
+        let (map, storage) = (&mut self.map, &mut self.storage);
+        let probes = py.allow_threads(|| {
+            let values = values.into_iter()
+                .map(|bytes| storage.encode(bytes))
+                .collect::<io::Result<Vec<_>>>()?;
+            let hashes = hash_keys(&keys);
+            keys.iter().zip(&hashes).zip(values)
+                .map(|((&key, &hash), value)| map.insert_hashed(key, hash, value))
+                .sum::<Result<usize, InsertError>>()
+                .map_err(PyErr::from)
+        });
+        probes
+    }
+
+    fn get<'py>(&self, py: Python<'py>, key: u64) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        let found = self.map.get(key);
+        self.map.count_lookups(1, found.is_some() as usize);
+        found.map(|value| Ok(PyBytes::new(py, &self.storage.decode(value)?))).transpose()
+    }
+
+    fn delete(&mut self, key: u64) -> bool {
+        self.map.remove(key).is_some()
+    }
+
+    fn delete_many(&mut self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        Ok(keys.iter().filter(|&&key| self.map.remove(key).is_some()).count())
+    }
+
+    fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<PyObject>, mask: bool, defaults: Option<Vec<PyObject>>) -> PyResult<PyObject> {
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let (map, storage) = (&self.map, &self.storage);
+        // Values are read back and decompressed in parallel too.
+        let found = py.allow_threads(|| {
+            map_keys(map, &keys, |key, hash| map.get_hashed(key, hash).map(|value| storage.decode(value)))
+        });
+        map.count_lookups(found.len(), found.iter().flatten().count());
+        let hits = mask.then(|| found.iter().map(Option::is_some).collect());
+        let fills = ObjectFills::new(py, default, defaults, keys.len())?;
+        let values = found.into_iter()
+            .enumerate().map(|(index, value)| match value {
+                Some(bytes) => Ok(PyBytes::new(py, &bytes?).into_any().unbind()),
+                None => Ok(fills.at(py, index)),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        lookup_result(py, values, hits, keys_buffer)
+    }
+
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        let keys = keys.as_slice(py)?;
+        let map = &self.map;
+        let found = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.contains_hashed(key, hash)));
+        map.count_lookups(found.len(), found.iter().filter(|&&hit| hit).count());
+        Ok(found)
+    }
+
+    fn clear(&mut self) -> PyResult<()> {
+        self.map.clear();
+        if let Some((_, log)) = &mut self.storage.spill {
+            log.clear()?;
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    fn remaining_capacity(&self) -> usize {
+        self.map.remaining_capacity()
+    }
+
+    fn is_full(&self) -> bool {
+        self.map.remaining_capacity() == 0
+    }
+
+    fn stats(&self) -> Vec<(usize, usize, f64)> {
+        self.map.stats()
+    }
+
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = memory_usage(py, &self.map, |_| None)?;
+        let payload: usize = self.map.iter().map(|(_, value)| value.stored_len()).sum();
+        let compressed = self.map.iter().filter(|(_, value)| value.is_compressed()).count();
+        let total: usize = dict.get_item("total_bytes")?.map_or(Ok(0), |total| total.extract())?;
+        dict.set_item("payload_bytes", payload)?;
+        dict.set_item("compressed_values", compressed)?;
+        if let Some((_, log)) = &self.storage.spill {
+            let spilled: Vec<usize> = self.map.iter().filter_map(|(_, value)| value.spilled_len()).collect();
+            dict.set_item("spilled_values", spilled.len())?;
+            dict.set_item("spilled_bytes", spilled.iter().sum::<usize>())?;
+            dict.set_item("value_log_bytes", log.len())?;
+        }
+        dict.set_item("total_bytes", total + payload)?;
+        Ok(dict)
+    }
+
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        detailed_stats(py, &self.map)
+    }
+
+    fn validate(&self, py: Python<'_>) -> Vec<String> {
+        py.allow_threads(|| self.map.validate())
+    }
+}
+
+#[pymethods]
+impl BytesTable {
+    /// Create a table with the given slot capacity and delta parameter.
+    /// See ElasticTable for the meaning of the arguments.
+    ///
+    /// With `compress_above`, values longer than that many bytes are stored
+    /// zstd-compressed at `compression_level` (1 to 22) when that makes
+    /// them smaller.
+    ///
+    /// With `spill_above` and `spill_path`, values still longer than
+    /// `spill_above` bytes are written to a value log at `spill_path`, and
+    /// the table keeps only their offset. The log is scratch space: it is
+    /// truncated now and removed with the table.
+    #[new]
+    #[pyo3(signature = (
+        capacity, delta=0.05, compress_above=None, compression_level=3, spill_above=None, spill_path=None,
+    ))]
+    fn new(
+        capacity: usize,
+        delta: f64,
+        compress_above: Option<usize>,
+        compression_level: i32,
+        spill_above: Option<usize>,
+        spill_path: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        BytesTableState::new(capacity, delta, compress_above, compression_level, spill_above, spill_path).map(Self::from)
+    }
+
+    /// Create a table sized for `expected_items` at the target load factor.
+    #[staticmethod]
+    #[pyo3(signature = (
+        expected_items, load_factor=0.90, compress_above=None, compression_level=3, spill_above=None, spill_path=None,
+    ))]
+    fn for_items(
+        expected_items: usize,
+        load_factor: f64,
+        compress_above: Option<usize>,
+        compression_level: i32,
+        spill_above: Option<usize>,
+        spill_path: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        BytesTableState::for_items(expected_items, load_factor, compress_above, compression_level, spill_above, spill_path)
+            .map(Self::from)
+    }
+
+    /// The size past which values are compressed, or None.
+    #[getter]
+    fn compress_above(&self, py: Python<'_>) -> PyResult<Option<usize>> {
+        Ok(self.lock(py)?.compress_above())
+    }
+
+    /// The size past which values are spilled to the value log, or None.
+    #[getter]
+    fn spill_above(&self, py: Python<'_>) -> PyResult<Option<usize>> {
+        Ok(self.lock(py)?.spill_above())
+    }
+
+    /// The path of the value log, or None.
+    #[getter]
+    fn spill_path(&self, py: Python<'_>) -> PyResult<Option<PathBuf>> {
+        Ok(self.lock(py)?.spill_path().map(Path::to_path_buf))
+    }
+
+    /// Store a copy of the bytes of `value`, any object supporting the
+    /// buffer protocol. Returns the probes used.
+    fn insert(&self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        self.lock(py)?.insert(py, key, value)
+    }
+
+    /// Insert many key/value pairs in a single call. Returns the total probes used.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.lock(py)?.insert_many(py, keys, values)
+    }
+
+    /// The bytes stored under `key`, or None.
+    fn get<'py>(&self, py: Python<'py>, key: u64) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        self.lock(py)?.get(py, key)
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        Ok(self.lock(py)?.delete(key))
+    }
+
+    /// Remove many keys in a single call. Returns how many were present.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        self.lock(py)?.delete_many(py, keys)
+    }
+
+    /// Look up many keys in a single call, using `default` (or the matching
+    /// item of `defaults`) for missing keys.
+    /// Large batches are probed in parallel. With `mask`, returns a
+    /// `(values, found)` pair; see ElasticTable.get_many.
+    #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+    fn get_many(
+        &self,
+        py: Python<'_>,
+        keys: KeyBatch,
+        default: Option<PyObject>,
+        mask: bool,
+        defaults: Option<Vec<PyObject>>,
+    ) -> PyResult<PyObject> {
+        self.lock(py)?.get_many(py, keys, default, mask, defaults)
+    }
+
+    /// Check membership for many keys in a single call.
+    /// Large batches are probed in parallel.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        self.lock(py)?.contains_many(py, keys)
+    }
+
+    /// A live, set-like view of the keys, in slot order. See ElasticTable.keys.
+    fn keys(slf: &Bound<'_, Self>) -> KeysView {
+        KeysView::new(slf.clone().unbind())
+    }
+
+    /// A live view of the values as bytes, in the order of `keys()`.
+    fn values(slf: &Bound<'_, Self>) -> ValuesView {
+        ValuesView::new(slf.clone().unbind())
+    }
+
+    /// A live, set-like view of the `(key, value)` pairs, in the order of `keys()`.
+    fn items(slf: &Bound<'_, Self>) -> ItemsView {
+        ItemsView::new(slf.clone().unbind())
+    }
+
+    fn clear(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.clear()
+    }
+
+    /// Number of slots the table was created with.
+    fn capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.capacity())
+    }
+
+    /// See `ElasticTable.remaining_capacity`.
+    fn remaining_capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.remaining_capacity())
+    }
+
+    /// See `ElasticTable.is_full`.
+    fn is_full(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(self.lock(py)?.is_full())
+    }
+
+    fn stats(&self, py: Python<'_>) -> PyResult<Vec<(usize, usize, f64)>> {
+        Ok(self.lock(py)?.stats())
+    }
+
+    /// Bytes of memory the table takes. See ElasticTable.memory_usage; a slot
+    /// holds a pointer and length for its value, and "payload_bytes", also
+    /// counted in "total_bytes", gives the bytes of the values themselves, as
+    /// stored; "compressed_values" counts the values stored compressed.
+    /// With a value log, "spilled_values" and "spilled_bytes" give the values
+    /// in the log and their bytes, which are not in memory, and
+    /// "value_log_bytes" the size of the log, including overwritten values.
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.memory_usage(py)
+    }
+
+    /// A structured version of `stats()`. See ElasticTable.detailed_stats.
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.detailed_stats(py)
+    }
+
+    /// Check the table's internal invariants. See ElasticTable.validate.
+    fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        Ok(self.lock(py)?.validate(py))
+    }
+}
@@ -0,0 +1,196 @@
+//! A C ABI for the typed tables, behind the `capi` feature.
+//!
+//! `EhIntTable` and `EhFloatTable` are opaque handles to the maps behind
+//! `IntTable` and `FloatTable`: `u64` keys with `int64_t` or `double` values.
+//! `include/rb_elastic_hash.h` declares the functions; it is generated with
+//! `cbindgen --config cbindgen.toml --output include/rb_elastic_hash.h src/capi.rs`.
+//! Handles must not be used from two threads at once.
+
+use crate::map::{ElasticMap, InsertError};
+
+/// `eh_*_insert` succeeded.
+pub const EH_OK: i32 = 0;
+/// `eh_*_insert` scanned the last subarray without finding a free slot.
+pub const EH_FULL: i32 = 1;
+/// `eh_*_insert` was turned away by every subarray's probe budget.
+pub const EH_NO_SLOT: i32 = 2;
+
+/// Stores `value` under `key`, updating a stored key in place.
+fn insert<V>(map: &mut ElasticMap<V>, key: u64, value: V) -> i32 {
+    match map.insert(key, value) {
+        Ok(_) => EH_OK,
+        Err(InsertError::Full) => EH_FULL,
+        Err(InsertError::NoSlot) => EH_NO_SLOT,
+    }
+}
+
+/// Writes the value of `key` to `*value` if it is stored.
+///
+/// # Safety
+///
+/// `value` must be valid for a write.
+unsafe fn get<V: Copy>(map: &ElasticMap<V>, key: u64, value: *mut V) -> bool {
+    match map.get(key) {
+        Some(&stored) => {
+            *value = stored;
+            true
+        }
+        None => false,
+    }
+}
+
+/// An opaque handle to a table with `int64_t` values.
+pub struct EhIntTable(ElasticMap<i64>);
+
+/// Creates a table with `capacity` slots and elasticity `delta`. Returns
+/// NULL unless `delta` lies strictly between 0 and 1. Free the table with
+/// `eh_int_table_free`.
+#[no_mangle]
+pub extern "C" fn eh_int_table_new(capacity: usize, delta: f64) -> *mut EhIntTable {
+    if !(delta > 0.0 && delta < 1.0) {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(EhIntTable(ElasticMap::new(capacity, delta))))
+}
+
+/// Frees a table. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `table` must be NULL or a table from `eh_int_table_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn eh_int_table_free(table: *mut EhIntTable) {
+    if !table.is_null() {
+        drop(Box::from_raw(table));
+    }
+}
+
+/// Inserts or updates `key`. Returns `EH_OK`, or `EH_FULL` or `EH_NO_SLOT` if
+/// a new key could not be placed.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_int_table_insert(table: *mut EhIntTable, key: u64, value: i64) -> i32 {
+    insert(&mut (*table).0, key, value)
+}
+
+/// Looks `key` up. Returns whether it is stored, and if so writes its value
+/// to `*value`.
+///
+/// # Safety
+///
+/// `table` must be a live table and `value` valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn eh_int_table_get(table: *const EhIntTable, key: u64, value: *mut i64) -> bool {
+    get(&(*table).0, key, value)
+}
+
+/// Removes `key`. Returns whether it was stored.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_int_table_remove(table: *mut EhIntTable, key: u64) -> bool {
+    (*table).0.remove(key).is_some()
+}
+
+/// Number of stored entries.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_int_table_len(table: *const EhIntTable) -> usize {
+    (*table).0.len()
+}
+
+/// Number of slots the table was created with.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_int_table_capacity(table: *const EhIntTable) -> usize {
+    (*table).0.capacity()
+}
+
+/// An opaque handle to a table with `double` values.
+pub struct EhFloatTable(ElasticMap<f64>);
+
+/// Creates a table with `capacity` slots and elasticity `delta`. Returns
+/// NULL unless `delta` lies strictly between 0 and 1. Free the table with
+/// `eh_float_table_free`.
+#[no_mangle]
+pub extern "C" fn eh_float_table_new(capacity: usize, delta: f64) -> *mut EhFloatTable {
+    if !(delta > 0.0 && delta < 1.0) {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(EhFloatTable(ElasticMap::new(capacity, delta))))
+}
+
+/// Frees a table. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `table` must be NULL or a table from `eh_float_table_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn eh_float_table_free(table: *mut EhFloatTable) {
+    if !table.is_null() {
+        drop(Box::from_raw(table));
+    }
+}
+
+/// Inserts or updates `key`. Returns `EH_OK`, or `EH_FULL` or `EH_NO_SLOT` if
+/// a new key could not be placed.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_float_table_insert(table: *mut EhFloatTable, key: u64, value: f64) -> i32 {
+    insert(&mut (*table).0, key, value)
+}
+
+/// Looks `key` up. Returns whether it is stored, and if so writes its value
+/// to `*value`.
+///
+/// # Safety
+///
+/// `table` must be a live table and `value` valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn eh_float_table_get(table: *const EhFloatTable, key: u64, value: *mut f64) -> bool {
+    get(&(*table).0, key, value)
+}
+
+/// Removes `key`. Returns whether it was stored.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_float_table_remove(table: *mut EhFloatTable, key: u64) -> bool {
+    (*table).0.remove(key).is_some()
+}
+
+/// Number of stored entries.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_float_table_len(table: *const EhFloatTable) -> usize {
+    (*table).0.len()
+}
+
+/// Number of slots the table was created with.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn eh_float_table_capacity(table: *const EhFloatTable) -> usize {
+    (*table).0.capacity()
+}
@@ -0,0 +1,300 @@
+//! Typed table contents in msgpack and CBOR.
+//!
+//! A table is encoded as a single map from key to value, the natural shape for
+//! any msgpack or CBOR library: integers use their shortest encoding and floats
+//! are written as 64-bit floats. Decoding accepts every integer and float
+//! encoding of both formats, so data written by other producers loads as well.
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Format {
+    MsgPack,
+    Cbor,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::MsgPack => "msgpack",
+            Format::Cbor => "CBOR",
+        }
+    }
+}
+
+/// A decoded number, before it is converted to the table's value type.
+#[derive(Clone, Copy)]
+pub(crate) enum Number {
+    Unsigned(u64),
+    Negative(i64),
+    Float(f64),
+}
+
+/// A value type that typed tables store and encode.
+pub(crate) trait Scalar: Copy {
+    fn encode(self, format: Format, out: &mut Vec<u8>);
+
+    /// Converts a decoded number, or returns `None` if it does not fit.
+    fn from_number(number: Number) -> Option<Self>;
+}
+
+impl Scalar for i64 {
+    fn encode(self, format: Format, out: &mut Vec<u8>) {
+        match format {
+            Format::MsgPack => msgpack_int(out, self),
+            Format::Cbor if self >= 0 => cbor_head(out, 0, self as u64),
+            Format::Cbor => cbor_head(out, 1, !self as u64),
+        }
+    }
+
+    fn from_number(number: Number) -> Option<Self> {
+        match number {
+            Number::Unsigned(n) => i64::try_from(n).ok(),
+            Number::Negative(n) => Some(n),
+            Number::Float(_) => None,
+        }
+    }
+}
+
+impl Scalar for f64 {
+    fn encode(self, format: Format, out: &mut Vec<u8>) {
+        out.push(match format {
+            Format::MsgPack => 0xcb,
+            Format::Cbor => 0xfb,
+        });
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn from_number(number: Number) -> Option<Self> {
+        match number {
+            Number::Unsigned(n) => Some(n as f64),
+            Number::Negative(n) => Some(n as f64),
+            Number::Float(f) => Some(f),
+        }
+    }
+}
+
+fn msgpack_uint(out: &mut Vec<u8>, n: u64) {
+    if n < 0x80 {
+        out.push(n as u8);
+    } else if let Ok(n) = u8::try_from(n) {
+        out.extend_from_slice(&[0xcc, n]);
+    } else if let Ok(n) = u16::try_from(n) {
+        out.push(0xcd);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else if let Ok(n) = u32::try_from(n) {
+        out.push(0xce);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else {
+        out.push(0xcf);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn msgpack_int(out: &mut Vec<u8>, n: i64) {
+    if n >= 0 {
+        msgpack_uint(out, n as u64);
+    } else if n >= -32 {
+        out.push(n as u8);
+    } else if let Ok(n) = i8::try_from(n) {
+        out.extend_from_slice(&[0xd0, n as u8]);
+    } else if let Ok(n) = i16::try_from(n) {
+        out.push(0xd1);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else if let Ok(n) = i32::try_from(n) {
+        out.push(0xd2);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else {
+        out.push(0xd3);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+/// Writes a CBOR item head: the major type and its argument.
+fn cbor_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if let Ok(arg) = u8::try_from(arg) {
+        out.extend_from_slice(&[major | 24, arg]);
+    } else if let Ok(arg) = u16::try_from(arg) {
+        out.push(major | 25);
+        out.extend_from_slice(&arg.to_be_bytes());
+    } else if let Ok(arg) = u32::try_from(arg) {
+        out.push(major | 26);
+        out.extend_from_slice(&arg.to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+/// Encodes `len` entries as a map from key to value.
+pub(crate) fn encode<V: Scalar>(
+    format: Format,
+    len: usize,
+    entries: impl Iterator<Item = (u64, V)>,
+) -> PyResult<Vec<u8>> {
+    let mut out = Vec::with_capacity(5 + len * 18);
+    match format {
+        Format::MsgPack => match u32::try_from(len) {
+            Ok(len) if len < 16 => out.push(0x80 | len as u8),
+            Ok(len) if len <= u16::MAX as u32 => {
+                out.push(0xde);
+                out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            Ok(len) => {
+                out.push(0xdf);
+                out.extend_from_slice(&len.to_be_bytes());
+            }
+            Err(_) => return Err(PyValueError::new_err("msgpack maps hold at most 2**32 - 1 entries")),
+        },
+        Format::Cbor => cbor_head(&mut out, 5, len as u64),
+    }
+    for (key, value) in entries {
+        match format {
+            Format::MsgPack => msgpack_uint(&mut out, key),
+            Format::Cbor => cbor_head(&mut out, 0, key),
+        }
+        value.encode(format, &mut out);
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    format: Format,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, what: &str) -> PyErr {
+        PyValueError::new_err(format!("invalid {} data: {what}", self.format.name()))
+    }
+
+    fn take<const N: usize>(&mut self) -> PyResult<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(self.error("unexpected end of data"));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn byte(&mut self) -> PyResult<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn msgpack_map_len(&mut self) -> PyResult<u64> {
+        match self.byte()? {
+            b @ 0x80..=0x8f => Ok((b & 0x0f) as u64),
+            0xde => Ok(u16::from_be_bytes(self.take()?) as u64),
+            0xdf => Ok(u32::from_be_bytes(self.take()?) as u64),
+            _ => Err(self.error("expected a map")),
+        }
+    }
+
+    fn msgpack_number(&mut self) -> PyResult<Number> {
+        let signed = |n: i64| if n >= 0 { Number::Unsigned(n as u64) } else { Number::Negative(n) };
+        Ok(match self.byte()? {
+            b @ 0x00..=0x7f => Number::Unsigned(b as u64),
+            b @ 0xe0..=0xff => Number::Negative(b as i8 as i64),
+            0xcc => Number::Unsigned(self.byte()? as u64),
+            0xcd => Number::Unsigned(u16::from_be_bytes(self.take()?) as u64),
+            0xce => Number::Unsigned(u32::from_be_bytes(self.take()?) as u64),
+            0xcf => Number::Unsigned(u64::from_be_bytes(self.take()?)),
+            0xd0 => signed(self.byte()? as i8 as i64),
+            0xd1 => signed(i16::from_be_bytes(self.take()?) as i64),
+            0xd2 => signed(i32::from_be_bytes(self.take()?) as i64),
+            0xd3 => signed(i64::from_be_bytes(self.take()?)),
+            0xca => Number::Float(f32::from_be_bytes(self.take()?) as f64),
+            0xcb => Number::Float(f64::from_be_bytes(self.take()?)),
+            _ => return Err(self.error("expected a number")),
+        })
+    }
+
+    /// Reads a CBOR item head. Returns the major type, the raw additional
+    /// information and the argument.
+    fn cbor_head(&mut self) -> PyResult<(u8, u8, u64)> {
+        let initial = self.byte()?;
+        let info = initial & 0x1f;
+        let arg = match info {
+            0..=23 => info as u64,
+            24 => self.byte()? as u64,
+            25 => u16::from_be_bytes(self.take()?) as u64,
+            26 => u32::from_be_bytes(self.take()?) as u64,
+            27 => u64::from_be_bytes(self.take()?),
+            _ => return Err(self.error("indefinite-length items are not supported")),
+        };
+        Ok((initial >> 5, info, arg))
+    }
+
+    fn cbor_map_len(&mut self) -> PyResult<u64> {
+        match self.cbor_head()? {
+            (5, _, len) => Ok(len),
+            _ => Err(self.error("expected a map")),
+        }
+    }
+
+    fn cbor_number(&mut self) -> PyResult<Number> {
+        Ok(match self.cbor_head()? {
+            (0, _, n) => Number::Unsigned(n),
+            (1, _, n) => match i64::try_from(n) {
+                Ok(n) => Number::Negative(-1 - n),
+                Err(_) => return Err(self.error("integer out of range")),
+            },
+            (7, 25, bits) => Number::Float(f16_to_f64(bits as u16)),
+            (7, 26, bits) => Number::Float(f32::from_bits(bits as u32) as f64),
+            (7, 27, bits) => Number::Float(f64::from_bits(bits)),
+            _ => return Err(self.error("expected a number")),
+        })
+    }
+
+    fn map_len(&mut self) -> PyResult<u64> {
+        match self.format {
+            Format::MsgPack => self.msgpack_map_len(),
+            Format::Cbor => self.cbor_map_len(),
+        }
+    }
+
+    fn number(&mut self) -> PyResult<Number> {
+        match self.format {
+            Format::MsgPack => self.msgpack_number(),
+            Format::Cbor => self.cbor_number(),
+        }
+    }
+}
+
+fn f16_to_f64(bits: u16) -> f64 {
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1024.0 + mantissa) * 2f64.powi(exponent as i32 - 25),
+    };
+    if bits & 0x8000 != 0 { -magnitude } else { magnitude }
+}
+
+/// Decodes a map from key to value written by `encode` or another producer.
+pub(crate) fn decode<V: Scalar>(format: Format, bytes: &[u8]) -> PyResult<(Vec<u64>, Vec<V>)> {
+    let mut reader = Reader { bytes, format };
+    let len = reader.map_len()?;
+    // Every entry takes at least two bytes, which bounds what a corrupt length can allocate.
+    let capacity = len.min(bytes.len() as u64 / 2) as usize;
+    let (mut keys, mut values) = (Vec::with_capacity(capacity), Vec::with_capacity(capacity));
+    for _ in 0..len {
+        let Number::Unsigned(key) = reader.number()? else {
+            return Err(reader.error("keys must be non-negative integers"));
+        };
+        let value = V::from_number(reader.number()?)
+            .ok_or_else(|| reader.error("value does not fit the table's value type"))?;
+        keys.push(key);
+        values.push(value);
+    }
+    if !reader.bytes.is_empty() {
+        return Err(reader.error("trailing bytes after the map"));
+    }
+    Ok((keys, values))
+}
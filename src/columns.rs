@@ -0,0 +1,595 @@
+//! `ColumnTable`: several named, typed value columns per key.
+//!
+//! Each key maps to a row index, and every column keeps its values for all
+//! rows in one vector of its type, so a column is stored, filled from and
+//! exported to numpy as a plain array, as in a feature store. Rows freed by
+//! deletes are reused by later inserts.
+
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::types::PyDict;
+use numpy::IntoPyArray;
+
+use crate::batch::{lookup_result, map_keys, Batch, KeyBatch};
+use crate::hash::{hash_key, hash_keys};
+use crate::lock::{TableGuard, TableLock};
+use crate::map::{ElasticMap, InsertError};
+use crate::table::{check_delta, detailed_stats, memory_usage, sizing_for_items};
+use crate::views::{ItemsView, KeysView, ValuesView};
+
+/// The values of one column, indexed by row.
+enum Column {
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+/// Runs `$body` with `$values` bound to the vector of whichever type `$column` holds.
+macro_rules! each_column {
+    ($column:expr, $values:ident => $body:expr) => {
+        match $column {
+            Column::I32($values) => $body,
+            Column::I64($values) => $body,
+            Column::U32($values) => $body,
+            Column::U64($values) => $body,
+            Column::F32($values) => $body,
+            Column::F64($values) => $body,
+        }
+    };
+}
+
+/// Extracts `value` as the item type of `values`.
+fn convert<'py, T: FromPyObject<'py>>(_values: &[T], value: &Bound<'py, PyAny>) -> PyResult<T> {
+    value.extract()
+}
+
+impl Column {
+    fn new(dtype: &str) -> PyResult<Self> {
+        Ok(match dtype {
+            "i32" | "int32" => Column::I32(Vec::new()),
+            "i64" | "int64" => Column::I64(Vec::new()),
+            "u32" | "uint32" => Column::U32(Vec::new()),
+            "u64" | "uint64" => Column::U64(Vec::new()),
+            "f32" | "float32" => Column::F32(Vec::new()),
+            "f64" | "float64" => Column::F64(Vec::new()),
+            _ => return Err(PyValueError::new_err(format!(
+                "unsupported column type {dtype:?}; use i32, i64, u32, u64, f32 or f64"
+            ))),
+        })
+    }
+
+    fn dtype(&self) -> &'static str {
+        match self {
+            Column::I32(_) => "i32",
+            Column::I64(_) => "i64",
+            Column::U32(_) => "u32",
+            Column::U64(_) => "u64",
+            Column::F32(_) => "f32",
+            Column::F64(_) => "f64",
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        each_column!(self, values => std::mem::size_of_val(values.as_slice()))
+    }
+
+    /// Raises unless `value` converts to the type of the column.
+    fn check(&self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        each_column!(self, values => convert(values, value).map(drop))
+    }
+
+    /// Adds a row, zeroed until it is set.
+    fn push(&mut self) {
+        each_column!(self, values => values.push(Default::default()))
+    }
+
+    /// Sets row `row` to `value`, which passed `check`.
+    fn set(&mut self, row: u32, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        each_column!(self, values => values[row as usize] = convert(values, value)?);
+        Ok(())
+    }
+
+    fn get<'py>(&self, py: Python<'py>, row: usize) -> PyResult<Bound<'py, PyAny>> {
+        each_column!(self, values => Ok(values[row].into_pyobject(py)?.into_any()))
+    }
+
+    /// The values of `rows` as a numpy array, `default`, or zero, for rows
+    /// that are None.
+    fn gather<'py>(&self, py: Python<'py>, rows: &[Option<u32>], default: Option<&Bound<'py, PyAny>>) -> PyResult<Bound<'py, PyAny>> {
+        each_column!(self, values => {
+            let default = default.map_or(Ok(Default::default()), |default| convert(values, default))?;
+            let gathered: Vec<_> = rows.iter().map(|row| row.map_or(default, |row| values[row as usize])).collect();
+            Ok(gathered.into_pyarray(py).into_any())
+        })
+    }
+
+    fn clear(&mut self) {
+        each_column!(self, values => values.clear())
+    }
+}
+
+/// Column values of an `insert_many` batch, typed like the column.
+enum ColumnBatch {
+    I32(Batch<i32>),
+    I64(Batch<i64>),
+    U32(Batch<u32>),
+    U64(Batch<u64>),
+    F32(Batch<f32>),
+    F64(Batch<f64>),
+}
+
+impl ColumnBatch {
+    fn extract(column: &Column, values: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(match column {
+            Column::I32(_) => ColumnBatch::I32(values.extract()?),
+            Column::I64(_) => ColumnBatch::I64(values.extract()?),
+            Column::U32(_) => ColumnBatch::U32(values.extract()?),
+            Column::U64(_) => ColumnBatch::U64(values.extract()?),
+            Column::F32(_) => ColumnBatch::F32(values.extract()?),
+            Column::F64(_) => ColumnBatch::F64(values.extract()?),
+        })
+    }
+
+    fn len(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(match self {
+            ColumnBatch::I32(batch) => batch.as_slice(py)?.len(),
+            ColumnBatch::I64(batch) => batch.as_slice(py)?.len(),
+            ColumnBatch::U32(batch) => batch.as_slice(py)?.len(),
+            ColumnBatch::U64(batch) => batch.as_slice(py)?.len(),
+            ColumnBatch::F32(batch) => batch.as_slice(py)?.len(),
+            ColumnBatch::F64(batch) => batch.as_slice(py)?.len(),
+        })
+    }
+
+    /// Writes the first `rows.len()` values of the batch to those rows of
+    /// `column`, which has its type.
+    fn store(&self, py: Python<'_>, column: &mut Column, rows: &[u32]) -> PyResult<()> {
+        fn scatter<T: Copy>(values: &mut [T], rows: &[u32], batch: &[T]) {
+            for (&row, &value) in rows.iter().zip(batch) {
+                values[row as usize] = value;
+            }
+        }
+        match (self, column) {
+            (ColumnBatch::I32(batch), Column::I32(values)) => scatter(values, rows, &batch.as_slice(py)?),
+            (ColumnBatch::I64(batch), Column::I64(values)) => scatter(values, rows, &batch.as_slice(py)?),
+            (ColumnBatch::U32(batch), Column::U32(values)) => scatter(values, rows, &batch.as_slice(py)?),
+            (ColumnBatch::U64(batch), Column::U64(values)) => scatter(values, rows, &batch.as_slice(py)?),
+            (ColumnBatch::F32(batch), Column::F32(values)) => scatter(values, rows, &batch.as_slice(py)?),
+            (ColumnBatch::F64(batch), Column::F64(values)) => scatter(values, rows, &batch.as_slice(py)?),
+            _ => unreachable!("batches are extracted with the type of their column"),
+        }
+        Ok(())
+    }
+}
+
+/// A table of several named, typed value columns per key.
+#[pyclass(frozen)]
+pub(crate) struct ColumnTable {
+    state: TableLock<ColumnTableState>,
+}
+
+/// The state of a `ColumnTable`, behind its lock.
+pub(crate) struct ColumnTableState {
+    map: ElasticMap<u32>,
+    names: Vec<String>,
+    columns: Vec<Column>,
+    /// Rows in use or freed, and the freed ones.
+    rows: usize,
+    free: Vec<u32>,
+}
+
+impl From<ColumnTableState> for ColumnTable {
+    fn from(state: ColumnTableState) -> Self {
+        ColumnTable { state: TableLock::new(state) }
+    }
+}
+
+impl ColumnTable {
+    /// The table's state, waiting for any call on another thread to finish.
+    pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, ColumnTableState>> {
+        self.state.lock(py)
+    }
+}
+
+impl ColumnTableState {
+    pub(crate) fn map(&self) -> &ElasticMap<u32> {
+        &self.map
+    }
+
+    /// The row stored under `key` as a dict, without counting a lookup.
+    pub(crate) fn value_of(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+        self.map.get(key).map(|&row| Ok(self.row_dict(py, row)?.into_any().unbind())).transpose()
+    }
+
+    fn column_index(&self, name: &str) -> PyResult<usize> {
+        self.names.iter().position(|column| column == name)
+            .ok_or_else(|| PyKeyError::new_err(format!("no column named {name:?}")))
+    }
+
+    /// The row of `key`, storing the key in a free or new row if it is not
+    /// in the table yet, and the probes used.
+    fn row_for(&mut self, key: u64, hash: u64) -> Result<(u32, usize), InsertError> {
+        if let Some(&row) = self.map.get_hashed(key, hash) {
+            return Ok((row, 0));
+        }
+        let row = match self.free.pop() {
+            Some(row) => row,
+            None => {
+                let row = u32::try_from(self.rows).map_err(|_| InsertError::Full)?;
+                self.columns.iter_mut().for_each(Column::push);
+                self.rows += 1;
+                row
+            }
+        };
+        match self.map.insert_hashed(key, hash, row) {
+            Ok(probes) => Ok((row, probes)),
+            Err(err) => {
+                self.free.push(row);
+                Err(err)
+            }
+        }
+    }
+
+    fn row_dict<'py>(&self, py: Python<'py>, row: u32) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (name, column) in self.names.iter().zip(&self.columns) {
+            dict.set_item(name, column.get(py, row as usize)?)?;
+        }
+        Ok(dict)
+    }
+}
+
+impl ColumnTableState {
+    fn new(columns: &Bound<'_, PyAny>, capacity: usize, delta: f64) -> PyResult<Self> {
+        check_delta(delta)?;
+        let pairs: Vec<(String, String)> = match columns.downcast::<PyDict>() {
+            Ok(dict) => dict.iter().map(|(name, dtype)| Ok((name.extract()?, dtype.extract()?))).collect::<PyResult<_>>()?,
+            Err(_) => columns.extract()?,
+        };
+        if pairs.is_empty() {
+            return Err(PyValueError::new_err("a ColumnTable needs at least one column"));
+        }
+        let mut names = Vec::with_capacity(pairs.len());
+        let mut typed = Vec::with_capacity(pairs.len());
+        for (name, dtype) in pairs {
+            if names.contains(&name) {
+                return Err(PyValueError::new_err(format!("duplicate column {name:?}")));
+            }
+            typed.push(Column::new(&dtype)?);
+            names.push(name);
+        }
+        Ok(ColumnTableState { map: ElasticMap::new(capacity, delta), names, columns: typed, rows: 0, free: Vec::new() })
+    }
+
+    fn for_items(columns: &Bound<'_, PyAny>, expected_items: usize, load_factor: f64) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(columns, capacity, delta)
+    }
+
+    fn columns(&self) -> Vec<(String, &'static str)> {
+        self.names.iter().cloned().zip(self.columns.iter().map(Column::dtype)).collect()
+    }
+
+    fn insert(&mut self, key: u64, row: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let values: Vec<Bound<'_, PyAny>> = match row.downcast::<PyDict>() {
+            Ok(dict) => {
+                if dict.len() != self.names.len() {
+                    return Err(PyValueError::new_err("a row needs exactly one value per column"));
+                }
+                self.names.iter()
+                    .map(|name| dict.get_item(name)?.ok_or_else(|| PyKeyError::new_err(format!("missing column {name:?}"))))
+                    .collect::<PyResult<_>>()?
+            }
+            Err(_) => row.try_iter()?.collect::<PyResult<_>>()?,
+        };
+        if values.len() != self.columns.len() {
+            return Err(PyValueError::new_err("a row needs exactly one value per column"));
+        }
+        // Convert every value before touching the table, so a bad one leaves it unchanged.
+        for (column, value) in self.columns.iter().zip(&values) {
+            column.check(value)?;
+        }
+        let (row, probes) = self.row_for(key, hash_key(key))?;
+        for (column, value) in self.columns.iter_mut().zip(&values) {
+            column.set(row, value)?;
+        }
+        Ok(probes)
+    }
+
+    fn insert_many(&mut self, py: Python<'_>, keys: KeyBatch, columns: &Bound<'_, PyDict>) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        if columns.len() != self.names.len() {
+            return Err(PyValueError::new_err("insert_many needs values for every column"));
+        }
+        let batches = self.names.iter().zip(&self.columns)
+            .map(|(name, column)| {
+                let values = columns.get_item(name)?.ok_or_else(|| PyKeyError::new_err(format!("missing column {name:?}")))?;
+                let batch = ColumnBatch::extract(column, &values)?;
+                if batch.len(py)? != keys.len() {
+                    return Err(PyValueError::new_err(format!("column {name:?} must have one value per key")));
+                }
+                Ok(batch)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let hashes = hash_keys(&keys);
+        let mut rows = Vec::with_capacity(keys.len());
+        let mut probes = 0;
+        let mut failed = None;
+        for (&key, &hash) in keys.iter().zip(&hashes) {
+            match self.row_for(key, hash) {
+                Ok((row, used)) => {
+                    rows.push(row);
+                    probes += used;
+                }
+                Err(err) => {
+                    failed = Some(err);
+                    break;
+                }
+            }
+        }
+        // The keys stored before a failure keep their values.
+        for (batch, column) in batches.iter().zip(self.columns.iter_mut()) {
+            batch.store(py, column, &rows)?;
+        }
+        match failed {
+            Some(err) => Err(err.into()),
+            None => Ok(probes),
+        }
+    }
+
+    fn get<'py>(&self, py: Python<'py>, key: u64) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let found = self.map.get(key);
+        self.map.count_lookups(1, found.is_some() as usize);
+        found.map(|&row| self.row_dict(py, row)).transpose()
+    }
+
+    fn get_many<'py>(
+        &self,
+        py: Python<'py>,
+        keys: KeyBatch,
+        columns: Option<Vec<String>>,
+        default: Option<&Bound<'py, PyAny>>,
+        mask: bool,
+    ) -> PyResult<PyObject> {
+        py.import("numpy")?;
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let map = &self.map;
+        let rows = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.get_hashed(key, hash).copied()));
+        map.count_lookups(rows.len(), rows.iter().flatten().count());
+        let dict = PyDict::new(py);
+        for name in columns.as_ref().unwrap_or(&self.names) {
+            dict.set_item(name, self.columns[self.column_index(name)?].gather(py, &rows, default)?)?;
+        }
+        let hits = mask.then(|| rows.iter().map(Option::is_some).collect());
+        lookup_result(py, dict, hits, keys_buffer)
+    }
+
+    fn column<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+        py.import("numpy")?;
+        let rows: Vec<Option<u32>> = self.map.iter().map(|(_, &row)| Some(row)).collect();
+        self.columns[self.column_index(name)?].gather(py, &rows, None)
+    }
+
+    fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyDict>)> {
+        py.import("numpy")?;
+        let (keys, rows): (Vec<u64>, Vec<Option<u32>>) = self.map.iter().map(|(key, &row)| (key, Some(row))).unzip();
+        let dict = PyDict::new(py);
+        for (name, column) in self.names.iter().zip(&self.columns) {
+            dict.set_item(name, column.gather(py, &rows, None)?)?;
+        }
+        Ok((keys.into_pyarray(py).into_any(), dict))
+    }
+
+    fn delete(&mut self, key: u64) -> bool {
+        let removed = self.map.remove(key);
+        removed.inspect(|&row| self.free.push(row)).is_some()
+    }
+
+    fn delete_many(&mut self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        Ok(keys.iter().filter(|&&key| self.delete(key)).count())
+    }
+
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        let keys = keys.as_slice(py)?;
+        let map = &self.map;
+        let found = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.contains_hashed(key, hash)));
+        map.count_lookups(found.len(), found.iter().filter(|&&hit| hit).count());
+        Ok(found)
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.columns.iter_mut().for_each(Column::clear);
+        self.rows = 0;
+        self.free.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    fn remaining_capacity(&self) -> usize {
+        self.map.remaining_capacity()
+    }
+
+    fn is_full(&self) -> bool {
+        self.map.remaining_capacity() == 0
+    }
+
+    fn stats(&self) -> Vec<(usize, usize, f64)> {
+        self.map.stats()
+    }
+
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = memory_usage(py, &self.map, |_| None)?;
+        let columns = PyDict::new(py);
+        for (name, column) in self.names.iter().zip(&self.columns) {
+            columns.set_item(name, column.bytes())?;
+        }
+        let bytes: usize = self.columns.iter().map(Column::bytes).sum::<usize>() + 4 * self.free.len();
+        let total: usize = dict.get_item("total_bytes")?.map_or(Ok(0), |total| total.extract())?;
+        dict.set_item("column_bytes", columns)?;
+        dict.set_item("total_bytes", total + bytes)?;
+        Ok(dict)
+    }
+
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        detailed_stats(py, &self.map)
+    }
+
+    fn validate(&self, py: Python<'_>) -> Vec<String> {
+        py.allow_threads(|| self.map.validate())
+    }
+}
+
+#[pymethods]
+impl ColumnTable {
+    /// Create a table with the given slot capacity and delta parameter.
+    ///
+    /// `columns` maps each column name to its type: "i32", "i64", "u32",
+    /// "u64", "f32" or "f64" (numpy names such as "float32" work too), as a
+    /// dict or a list of `(name, type)` pairs. See ElasticTable for the
+    /// meaning of the other arguments.
+    #[new]
+    #[pyo3(signature = (columns, capacity, delta=0.05))]
+    fn new(columns: &Bound<'_, PyAny>, capacity: usize, delta: f64) -> PyResult<Self> {
+        ColumnTableState::new(columns, capacity, delta).map(Self::from)
+    }
+
+    /// Create a table sized for `expected_items` at the target load factor.
+    #[staticmethod]
+    #[pyo3(signature = (columns, expected_items, load_factor=0.90))]
+    fn for_items(columns: &Bound<'_, PyAny>, expected_items: usize, load_factor: f64) -> PyResult<Self> {
+        ColumnTableState::for_items(columns, expected_items, load_factor).map(Self::from)
+    }
+
+    /// The `(name, type)` pairs of the columns, in order.
+    #[getter]
+    fn columns(&self, py: Python<'_>) -> PyResult<Vec<(String, &'static str)>> {
+        Ok(self.lock(py)?.columns())
+    }
+
+    /// Store a row under `key`: a dict with a value for every column, or a
+    /// sequence of values in column order. Returns the probes used.
+    fn insert(&self, py: Python<'_>, key: u64, row: &Bound<'_, PyAny>) -> PyResult<usize> {
+        self.lock(py)?.insert(key, row)
+    }
+
+    /// Insert many rows in a single call, column by column: `columns` maps
+    /// every column name to its values for `keys`, as a numpy array or a
+    /// sequence. Returns the total probes used.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, columns: &Bound<'_, PyDict>) -> PyResult<usize> {
+        self.lock(py)?.insert_many(py, keys, columns)
+    }
+
+    /// The row stored under `key` as a dict, or None.
+    fn get<'py>(&self, py: Python<'py>, key: u64) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.lock(py)?.get(py, key)
+    }
+
+    /// Look up many keys in a single call, returning a dict with the values
+    /// of every column, or of the columns named in `columns`, as numpy arrays,
+    /// with `default`, or zero, for missing keys. Large batches are probed in
+    /// parallel. With `mask`, returns a `(columns, found)` pair; see
+    /// ElasticTable.get_many.
+    #[pyo3(signature = (keys, columns=None, default=None, mask=false))]
+    fn get_many<'py>(
+        &self,
+        py: Python<'py>,
+        keys: KeyBatch,
+        columns: Option<Vec<String>>,
+        default: Option<&Bound<'py, PyAny>>,
+        mask: bool,
+    ) -> PyResult<PyObject> {
+        self.lock(py)?.get_many(py, keys, columns, default, mask)
+    }
+
+    /// The values of column `name` as a numpy array, in the order of `keys()`.
+    fn column<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+        self.lock(py)?.column(py, name)
+    }
+
+    /// Export the contents as a `(keys, columns)` pair: a numpy array of the
+    /// keys and a dict mapping every column name to a numpy array of its values.
+    fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyDict>)> {
+        self.lock(py)?.to_numpy(py)
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        Ok(self.lock(py)?.delete(key))
+    }
+
+    /// Remove many keys in a single call. Returns how many were present.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        self.lock(py)?.delete_many(py, keys)
+    }
+
+    /// Check membership for many keys in a single call.
+    /// Large batches are probed in parallel.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        self.lock(py)?.contains_many(py, keys)
+    }
+
+    /// A live, set-like view of the keys, in slot order. See ElasticTable.keys.
+    fn keys(slf: &Bound<'_, Self>) -> KeysView {
+        KeysView::new(slf.clone().unbind())
+    }
+
+    /// A live view of the rows as dicts, in the order of `keys()`.
+    fn values(slf: &Bound<'_, Self>) -> ValuesView {
+        ValuesView::new(slf.clone().unbind())
+    }
+
+    /// A live, set-like view of the `(key, row)` pairs, in the order of `keys()`.
+    fn items(slf: &Bound<'_, Self>) -> ItemsView {
+        ItemsView::new(slf.clone().unbind())
+    }
+
+    fn clear(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.clear();
+        Ok(())
+    }
+
+    /// Number of slots the table was created with.
+    fn capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.capacity())
+    }
+
+    /// See `ElasticTable.remaining_capacity`.
+    fn remaining_capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.remaining_capacity())
+    }
+
+    /// See `ElasticTable.is_full`.
+    fn is_full(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(self.lock(py)?.is_full())
+    }
+
+    fn stats(&self, py: Python<'_>) -> PyResult<Vec<(usize, usize, f64)>> {
+        Ok(self.lock(py)?.stats())
+    }
+
+    /// Bytes of memory the table takes. See ElasticTable.memory_usage; a slot
+    /// holds the index of its row, and "column_bytes", also counted in
+    /// "total_bytes", maps every column to the bytes of its values, including
+    /// rows freed by deletes and not reused yet.
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.memory_usage(py)
+    }
+
+    /// A structured version of `stats()`. See ElasticTable.detailed_stats.
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.detailed_stats(py)
+    }
+
+    /// Check the table's internal invariants. See ElasticTable.validate.
+    fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        Ok(self.lock(py)?.validate(py))
+    }
+}
@@ -0,0 +1,247 @@
+//! A harness running one workload through elastic hashing, plain and placing
+//! keys by two choices or Robin Hood displacement, funnel hashing and `std::collections::HashMap`,
+//! so claims about their behavior at high load factors can be checked on any
+//! machine.
+//!
+//! The workload inserts `n` distinct pseudo-random `u64` keys, derived from a
+//! seed, into tables sized for them at the requested load factor, looks every
+//! key up again, and then looks up `n` keys that were never inserted. Every
+//! table stores the same keys and values in the same order. The benchmark
+//! target `cargo bench --bench compare` and `ElasticTable.compare()` both run
+//! it; the latter adds a `dict`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::funnel::FunnelTable;
+use crate::hash::hash_key;
+use crate::map::ElasticMap;
+
+/// The parameters of a run. The same workload always uses the same keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Workload {
+    pub n: usize,
+    /// Share of the slots of the open addressing tables that the keys fill,
+    /// strictly between 0 and 1.
+    pub load_factor: f64,
+    pub seed: u64,
+}
+
+impl Workload {
+    /// The keys to insert and the keys to look up as misses.
+    pub fn keys(&self) -> (Vec<u64>, Vec<u64>) {
+        // `hash_key` is a bijection and so is xoring with a constant, so the
+        // keys are distinct and look random.
+        let mask = hash_key(self.seed);
+        let n = self.n as u64;
+        let keys = (0..n).map(|i| hash_key(i) ^ mask).collect();
+        let missing = (n..2 * n).map(|i| hash_key(i) ^ mask).collect();
+        (keys, missing)
+    }
+
+    /// Number of slots the open addressing tables get.
+    pub fn capacity(&self) -> usize {
+        (self.n as f64 / self.load_factor).ceil() as usize
+    }
+}
+
+/// Mean and longest probe sequences of one phase of the workload.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Probes {
+    pub mean: f64,
+    pub max: usize,
+}
+
+impl Probes {
+    fn of(probes: impl IntoIterator<Item = usize>) -> Self {
+        let (mut total, mut count, mut max) = (0, 0, 0);
+        for probes in probes {
+            total += probes;
+            count += 1;
+            max = max.max(probes);
+        }
+        Probes { mean: total as f64 / count.max(1) as f64, max }
+    }
+}
+
+/// The results of one table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub name: String,
+    /// Nanoseconds per operation of each phase.
+    pub insert_ns: f64,
+    pub hit_ns: f64,
+    pub miss_ns: f64,
+    /// Keys the table could not place. The lookups still run over all keys.
+    pub failed_inserts: usize,
+    /// Probes of each phase, for the open addressing tables. Elastic hashing
+    /// counts 16-slot groups and funnel hashing buckets and single slots of
+    /// its special array, so both count roughly the cache lines touched.
+    pub insert_probes: Option<Probes>,
+    pub hit_probes: Option<Probes>,
+    pub miss_probes: Option<Probes>,
+}
+
+impl Measurement {
+    /// A measurement from the seconds each phase of a workload of `n` keys took.
+    pub fn from_seconds(name: &str, n: usize, insert: f64, hit: f64, miss: f64) -> Self {
+        let ns = |seconds: f64| seconds * 1e9 / n.max(1) as f64;
+        Measurement {
+            name: name.to_string(),
+            insert_ns: ns(insert),
+            hit_ns: ns(hit),
+            miss_ns: ns(miss),
+            failed_inserts: 0,
+            insert_probes: None,
+            hit_probes: None,
+            miss_probes: None,
+        }
+    }
+}
+
+/// The results of every table on one workload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub workload: Workload,
+    pub capacity: usize,
+    pub results: Vec<Measurement>,
+}
+
+/// Runs `f` and returns its result with the seconds it took.
+fn timed<R>(f: impl FnOnce() -> R) -> (R, f64) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed().as_secs_f64())
+}
+
+/// How the elastic hashing tables of a comparison place their keys.
+#[derive(Clone, Copy)]
+enum Placement {
+    Plain,
+    TwoChoices,
+    RobinHood,
+}
+
+fn elastic(workload: &Workload, keys: &[u64], missing: &[u64], placement: Placement) -> Measurement {
+    let capacity = workload.capacity();
+    let mut map = ElasticMap::new(capacity, 1.0 - workload.load_factor);
+    map.set_two_choices(matches!(placement, Placement::TwoChoices));
+    map.set_robin_hood(matches!(placement, Placement::RobinHood));
+    let (probes, insert) = timed(|| {
+        keys.iter().zip(0u64..).map(|(&key, value)| map.insert(key, value).ok()).collect::<Vec<_>>()
+    });
+    let lookups = |lookups: &[u64]| timed(|| lookups.iter().filter_map(|&key| map.get(key)).sum::<u64>());
+    let (sum, hit) = lookups(keys);
+    black_box(sum);
+    let (sum, miss) = lookups(missing);
+    black_box(sum);
+
+    let name = match placement {
+        Placement::Plain => "elastic",
+        Placement::TwoChoices => "two choices",
+        Placement::RobinHood => "robin hood",
+    };
+    let mut measurement = Measurement::from_seconds(name, keys.len(), insert, hit, miss);
+    measurement.failed_inserts = probes.iter().filter(|probes| probes.is_none()).count();
+    measurement.insert_probes = Some(Probes::of(probes.into_iter().flatten()));
+    // Lookup probes are counted in an untimed pass, so recording them does not
+    // slow the timed one down.
+    map.track_probes(true);
+    for (lookups, probes) in [(keys, &mut measurement.hit_probes), (missing, &mut measurement.miss_probes)] {
+        map.reset_counts();
+        for &key in lookups {
+            map.get(key);
+        }
+        let gets = &map.histograms().expect("probes are tracked").gets;
+        *probes = Some(Probes { mean: gets.mean(), max: gets.longest() as usize });
+    }
+    measurement
+}
+
+fn funnel(workload: &Workload, keys: &[u64], missing: &[u64]) -> Measurement {
+    let mut table = FunnelTable::new(workload.capacity(), 1.0 - workload.load_factor);
+    let hashes: Vec<u64> = keys.iter().map(|&key| hash_key(key)).collect();
+    let missing_hashes: Vec<u64> = missing.iter().map(|&key| hash_key(key)).collect();
+    let (probes, insert) = timed(|| {
+        keys.iter().zip(&hashes).zip(0u64..)
+            .map(|((&key, &hash), value)| table.insert(key, hash, value))
+            .collect::<Vec<_>>()
+    });
+    let lookups = |lookups: &[u64], hashes: &[u64]| timed(|| {
+        lookups.iter().zip(hashes).map(|(&key, &hash)| table.get(key, hash).0.unwrap_or(0)).sum::<u64>()
+    });
+    let (sum, hit) = lookups(keys, &hashes);
+    black_box(sum);
+    let (sum, miss) = lookups(missing, &missing_hashes);
+    black_box(sum);
+
+    let mut measurement = Measurement::from_seconds("funnel", keys.len(), insert, hit, miss);
+    measurement.failed_inserts = keys.len() - table.len();
+    measurement.insert_probes = Some(Probes::of(probes.into_iter().flatten()));
+    let probes = |lookups: &[u64], hashes: &[u64]| {
+        Probes::of(lookups.iter().zip(hashes).map(|(&key, &hash)| table.get(key, hash).1))
+    };
+    measurement.hit_probes = Some(probes(keys, &hashes));
+    measurement.miss_probes = Some(probes(missing, &missing_hashes));
+    measurement
+}
+
+fn std_map(keys: &[u64], missing: &[u64]) -> Measurement {
+    let mut map = HashMap::with_capacity(keys.len());
+    let ((), insert) = timed(|| {
+        for (&key, value) in keys.iter().zip(0u64..) {
+            map.insert(key, value);
+        }
+    });
+    let lookups = |lookups: &[u64]| timed(|| lookups.iter().filter_map(|key| map.get(key)).sum::<u64>());
+    let (sum, hit) = lookups(keys);
+    black_box(sum);
+    let (sum, miss) = lookups(missing);
+    black_box(sum);
+    Measurement::from_seconds("std HashMap", keys.len(), insert, hit, miss)
+}
+
+/// Runs `workload` through every table.
+///
+/// # Panics
+///
+/// If the load factor does not lie strictly between 0 and 1.
+pub fn compare(workload: &Workload) -> Comparison {
+    assert!(workload.load_factor > 0.0 && workload.load_factor < 1.0, "load_factor must be between 0 and 1");
+    let (keys, missing) = workload.keys();
+    let results = vec![
+        elastic(workload, &keys, &missing, Placement::Plain),
+        elastic(workload, &keys, &missing, Placement::TwoChoices),
+        elastic(workload, &keys, &missing, Placement::RobinHood),
+        funnel(workload, &keys, &missing),
+        std_map(&keys, &missing),
+    ];
+    Comparison { workload: *workload, capacity: workload.capacity(), results }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Workload { n, load_factor, seed } = self.workload;
+        writeln!(f, "n = {n}, load factor = {load_factor}, capacity = {}, seed = {seed}", self.capacity)?;
+        writeln!(
+            f,
+            "{:<12} {:>10} {:>10} {:>10} {:>8} {:>14} {:>14} {:>14}",
+            "table", "insert ns", "hit ns", "miss ns", "failed", "insert probes", "hit probes", "miss probes",
+        )?;
+        let probes = |probes: Option<Probes>| match probes {
+            Some(Probes { mean, max }) => format!("{mean:.2} / {max}"),
+            None => "-".to_string(),
+        };
+        for m in &self.results {
+            writeln!(
+                f,
+                "{:<12} {:>10.1} {:>10.1} {:>10.1} {:>8} {:>14} {:>14} {:>14}",
+                m.name, m.insert_ns, m.hit_ns, m.miss_ns, m.failed_inserts,
+                probes(m.insert_probes), probes(m.hit_probes), probes(m.miss_probes),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! `ConcurrentElasticTable`: an `ElasticTable` that many Python threads can share.
+//!
+//! The map sits behind a reader-writer lock. The lock is never waited on while the
+//! GIL is held: writers take it with the GIL released, and readers that need the GIL
+//! to build their results poll it, releasing the GIL between attempts. A thread
+//! that holds the lock may then wait for the GIL without deadlocking.
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+
+use crate::batch::{lookup_result, map_keys, KeyBatch, ObjectFills};
+use crate::hash::hash_keys;
+use crate::map::{ElasticMap, InsertError};
+use crate::table::{check_delta, sizing_for_items};
+use crate::value::PyValue;
+
+/// Acquires a read guard while holding the GIL, releasing the GIL between attempts.
+pub(crate) fn read_with_gil<'a, T>(py: Python<'_>, lock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+    loop {
+        match lock.try_read() {
+            Ok(guard) => return guard,
+            Err(TryLockError::Poisoned(err)) => return err.into_inner(),
+            Err(TryLockError::WouldBlock) => py.allow_threads(std::thread::yield_now),
+        }
+    }
+}
+
+/// Acquires a write guard. Must be called with the GIL released.
+pub(crate) fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquires a read guard. Must be called with the GIL released.
+pub(crate) fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reports the values of `map` to the cyclic GC.
+pub(crate) fn visit_values(map: &ElasticMap<PyValue>, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+    map.iter().try_for_each(|(_, value)| value.traverse(visit))
+}
+
+/// Clears the map behind `lock` for the cyclic GC, unless another thread
+/// holds the lock; waiting for it with the GIL held could deadlock.
+pub(crate) fn clear_unless_locked(lock: &RwLock<ElasticMap<PyValue>>) {
+    match lock.try_write() {
+        Ok(mut map) => map.clear(),
+        Err(TryLockError::Poisoned(err)) => err.into_inner().clear(),
+        Err(TryLockError::WouldBlock) => {}
+    }
+}
+
+/// A thread-safe `ElasticTable`.
+///
+/// Lookups from different threads run concurrently; inserts and deletes are
+/// exclusive. Batch methods release the GIL, so other threads keep running
+/// (and reading) while a large batch is probed.
+#[pyclass(frozen)]
+pub(crate) struct ConcurrentElasticTable {
+    map: RwLock<ElasticMap<PyValue>>,
+}
+
+impl ConcurrentElasticTable {
+    fn lookup(&self, py: Python<'_>, key: u64) -> Option<PyObject> {
+        read_with_gil(py, &self.map).get(key).map(|value| value.to_object(py, &[]))
+    }
+}
+
+#[pymethods]
+impl ConcurrentElasticTable {
+    /// Create a table with the given slot capacity and delta parameter.
+    /// See ElasticTable for the meaning of the arguments.
+    #[new]
+    #[pyo3(signature = (capacity, delta=0.05))]
+    fn new(capacity: usize, delta: f64) -> PyResult<Self> {
+        check_delta(delta)?;
+        Ok(ConcurrentElasticTable {
+            map: RwLock::new(ElasticMap::new(capacity, delta)),
+        })
+    }
+
+    /// Create a table sized for `expected_items` at the target load factor.
+    #[staticmethod]
+    #[pyo3(signature = (expected_items, load_factor=0.90))]
+    fn for_items(expected_items: usize, load_factor: f64) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(capacity, delta)
+    }
+
+    fn insert(&self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let value = PyValue::new(value);
+        Ok(py.allow_threads(|| write(&self.map).insert(key, value))?)
+    }
+
+    /// Insert many key/value pairs in a single call. Returns the total probes used.
+    /// Pairs are inserted in order; if one fails, the pairs before it stay inserted.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        if keys.len() != values.len() {
+            return Err(PyValueError::new_err("keys and values must have the same length"));
+        }
+        let values: Vec<PyValue> = values.iter().map(PyValue::new).collect();
+
+        let result = py.allow_threads(|| {
+            // Hash before locking so writers hold the lock only while probing.
+            let hashes = hash_keys(&keys);
+            let mut map = write(&self.map);
+            let mut total_probes = 0;
+            for ((&key, &hash), value) in keys.iter().zip(&hashes).zip(values) {
+                total_probes += map.insert_hashed(key, hash, value)?;
+            }
+            Ok::<_, InsertError>(total_probes)
+        });
+        Ok(result?)
+    }
+
+    /// The value stored under `key`, or `default` if the table does not hold
+    /// the key. See ElasticTable.get.
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python<'_>, key: u64, default: Option<PyObject>) -> PyObject {
+        self.lookup(py, key).or(default).unwrap_or_else(|| py.None())
+    }
+
+    /// `table[key]`: the value stored under `key`. Raises KeyError if the
+    /// table does not hold the key.
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let found = match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key),
+            Err(_) => None,
+        };
+        found.ok_or_else(|| PyKeyError::new_err(key.clone().unbind()))
+    }
+
+    /// `key in table`, which is True for keys holding None too.
+    fn __contains__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key).is_some(),
+            Err(_) => false,
+        })
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> bool {
+        // The removed value is dropped after the lock is released.
+        py.allow_threads(|| write(&self.map).remove(key)).is_some()
+    }
+
+    /// Remove many keys in a single call. Returns the number of keys that were present.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        let removed: Vec<PyValue> = py.allow_threads(|| {
+            let hashes = hash_keys(&keys);
+            let mut map = write(&self.map);
+            keys.iter().zip(&hashes).filter_map(|(&key, &hash)| map.remove_hashed(key, hash)).collect()
+        });
+        Ok(removed.len())
+    }
+
+    /// Look up many keys in a single call, using `default` (or the matching
+    /// item of `defaults`) for missing keys. With `mask`, returns a
+    /// `(values, found)` pair; see ElasticTable.get_many.
+    #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+    fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<PyObject>, mask: bool, defaults: Option<Vec<PyObject>>) -> PyResult<PyObject> {
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let fills = ObjectFills::new(py, default, defaults, keys.len())?;
+        let map = read_with_gil(py, &self.map);
+        let found: Vec<Option<&PyValue>> = py.allow_threads(|| {
+            map_keys(&map, &keys, |key, hash| map.get_hashed(key, hash))
+        });
+        let hits = mask.then(|| found.iter().map(Option::is_some).collect());
+        let values: Vec<PyObject> = found.into_iter()
+            .enumerate().map(|(index, value)| match value {
+                Some(value) => value.to_object(py, &[]),
+                None => fills.at(py, index),
+            })
+            .collect();
+        lookup_result(py, values, hits, keys_buffer)
+    }
+
+    /// Check membership for many keys in a single call.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        let keys = keys.as_slice(py)?;
+        Ok(py.allow_threads(|| {
+            let map = read(&self.map);
+            map_keys(&map, &keys, |key, hash| map.contains_hashed(key, hash))
+        }))
+    }
+
+    /// Remove every entry, keeping the allocated capacity.
+    fn clear(&self, py: Python<'_>) {
+        py.allow_threads(|| write(&self.map).clear());
+    }
+
+    fn stats(&self, py: Python<'_>) -> Vec<(usize, usize, f64)> {
+        py.allow_threads(|| read(&self.map).stats())
+    }
+
+    /// Reports the stored values to the cyclic GC. While a writer holds the
+    /// lock the values are not reported, which only delays collecting a cycle.
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        match self.map.try_read() {
+            Ok(map) => visit_values(&map, &visit),
+            Err(TryLockError::Poisoned(err)) => visit_values(&err.into_inner(), &visit),
+            Err(TryLockError::WouldBlock) => Ok(()),
+        }
+    }
+
+    fn __clear__(&self) {
+        clear_unless_locked(&self.map);
+    }
+}
@@ -0,0 +1,48 @@
+//! Exceptions raised by the tables.
+//!
+//! Failures specific to this crate raise subclasses of `ElasticHashError`, so
+//! callers can tell a full table or a damaged file apart from a bad argument,
+//! which still raises a plain `ValueError`. `ElasticHashError` itself derives
+//! from `ValueError`, the exception these failures raised before, so existing
+//! `except ValueError` handlers keep catching them. `LoadFactorWarning` is
+//! the warning a load alert without a callback issues.
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyUserWarning, PyValueError};
+
+create_exception!(
+    rb_elastic_hash,
+    ElasticHashError,
+    PyValueError,
+    "Base class of the failures specific to elastic hash tables."
+);
+create_exception!(
+    rb_elastic_hash,
+    TableFullError,
+    ElasticHashError,
+    "Raised when an insert scanned the last subarray completely without finding a free slot."
+);
+create_exception!(
+    rb_elastic_hash,
+    InsertProbeLimitError,
+    ElasticHashError,
+    "Raised when every subarray was skipped or exhausted its probe budget during an insert."
+);
+create_exception!(
+    rb_elastic_hash,
+    CorruptedDataError,
+    ElasticHashError,
+    "Raised when a saved table, checkpoint or mapped file fails validation."
+);
+create_exception!(
+    rb_elastic_hash,
+    TableClosedError,
+    ElasticHashError,
+    "Raised when a table is used after `close()`."
+);
+create_exception!(
+    rb_elastic_hash,
+    LoadFactorWarning,
+    PyUserWarning,
+    "Issued when a table's load factor crosses the threshold of `set_load_alert()`."
+);
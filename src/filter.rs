@@ -0,0 +1,74 @@
+//! Bloom filter that answers most lookups of missing keys without probing.
+//!
+//! A miss normally probes every subarray; a table with a filter first checks it
+//! and skips the probing for keys the filter has never seen. The filter is split
+//! into 32-byte blocks (the "split block" layout of Parquet): a key sets one bit
+//! in each of the eight 32-bit words of the block picked by its base hash, so a
+//! check reads a single cache line. Bits cannot be cleared, so removed keys keep
+//! answering "maybe" until the owner rebuilds the filter.
+
+use crate::hash::reduce;
+use crate::map::prefetch_read;
+
+/// Odd multipliers deriving one bit position per word from a key's hash.
+const SALTS: [u32; 8] = [
+    0x47b6_137b, 0x4497_4d91, 0x8824_ad5b, 0xa2b7_289d,
+    0x7054_95c7, 0x2df1_424b, 0x9efc_4947, 0x5c6b_fb31,
+];
+
+const BLOCK_BITS: usize = 256;
+
+pub(crate) struct BloomFilter {
+    blocks: Vec<[u32; 8]>,
+    bits_per_key: usize,
+}
+
+impl BloomFilter {
+    /// An empty filter for up to `keys` keys at `bits_per_key` bits each.
+    pub(crate) fn new(keys: usize, bits_per_key: usize) -> Self {
+        let blocks = (keys * bits_per_key).div_ceil(BLOCK_BITS).max(1);
+        BloomFilter { blocks: vec![[0; 8]; blocks], bits_per_key }
+    }
+
+    pub(crate) fn bits_per_key(&self) -> usize {
+        self.bits_per_key
+    }
+
+    /// The block of `hash` and the bit it sets in each word of it. The block
+    /// comes from the high half of the hash, the bits from the low half.
+    #[inline(always)]
+    fn locate(&self, hash: u64) -> (usize, [u32; 8]) {
+        let block = reduce(hash, self.blocks.len());
+        let low = hash as u32;
+        (block, SALTS.map(|salt| 1 << (low.wrapping_mul(salt) >> 27)))
+    }
+
+    pub(crate) fn insert(&mut self, hash: u64) {
+        let (block, mask) = self.locate(hash);
+        for (word, bit) in self.blocks[block].iter_mut().zip(mask) {
+            *word |= bit;
+        }
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        std::mem::size_of_val(self.blocks.as_slice())
+    }
+
+    /// Whether a key with base hash `hash` may have been inserted. `false` is exact.
+    #[inline(always)]
+    pub(crate) fn may_contain(&self, hash: u64) -> bool {
+        let (block, mask) = self.locate(hash);
+        self.blocks[block].iter().zip(mask).all(|(word, bit)| word & bit != 0)
+    }
+
+    /// Hints the CPU to start loading the block `may_contain(hash)` reads.
+    #[inline(always)]
+    pub(crate) fn prefetch(&self, hash: u64) {
+        prefetch_read(&self.blocks[reduce(hash, self.blocks.len())]);
+    }
+
+    /// Forgets every key.
+    pub(crate) fn clear(&mut self) {
+        self.blocks.fill([0; 8]);
+    }
+}
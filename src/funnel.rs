@@ -0,0 +1,172 @@
+//! Funnel hashing, the paper's other open addressing scheme without
+//! reordering, kept as a baseline for the comparison harness.
+//!
+//! The slots are split into a special array of about `delta / 2` of them and
+//! levels `A_1, A_2, ...` that each hold about three quarters of the slots of
+//! the one before, divided into buckets of `beta` slots. An insert tries one
+//! bucket per level and takes the first free slot of the first bucket that
+//! has one; keys that find every bucket full go to the special array, whose
+//! first half is probed at `O(log log n)` uniformly random slots and whose
+//! second half is probed by two-choice hashing over small buckets. Entries
+//! are never moved or deleted, so a lookup stops at the first free slot on
+//! the key's path.
+
+use crate::hash::{mix, reduce};
+
+/// Hash of the `i`th probe of a key with base hash `hash`.
+fn probe_hash(hash: u64, i: usize) -> u64 {
+    mix(hash ^ (i as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+/// A contiguous run of slots divided into buckets.
+struct Level {
+    start: usize,
+    buckets: usize,
+    bucket_len: usize,
+}
+
+impl Level {
+    fn bucket(&self, hash: u64) -> std::ops::Range<usize> {
+        let first = self.start + reduce(hash, self.buckets) * self.bucket_len;
+        first..first + self.bucket_len
+    }
+}
+
+pub(crate) struct FunnelTable {
+    slots: Vec<Option<(u64, u64)>>,
+    levels: Vec<Level>,
+    /// The part of the special array probed at random slots.
+    uniform: std::ops::Range<usize>,
+    uniform_probes: usize,
+    /// The part of the special array probed by two-choice hashing.
+    two_choice: Level,
+    len: usize,
+}
+
+impl FunnelTable {
+    /// A table with `capacity` slots meant to be filled to `(1 - delta) * capacity`.
+    pub(crate) fn new(capacity: usize, delta: f64) -> Self {
+        let capacity = capacity.max(4);
+        let log_inv_delta = (1.0 / delta).log2();
+        let level_count = (4.0 * log_inv_delta + 10.0).ceil() as usize;
+        let beta = ((2.0 * log_inv_delta).ceil() as usize).max(1);
+        let log_log = ((capacity as f64).log2().max(2.0).log2().ceil() as usize).max(1);
+
+        let special = ((delta * capacity as f64 / 2.0) as usize).max(2);
+        let main = capacity - special;
+        // A_1 gets the share of a geometric series with ratio 3/4 over the levels.
+        let first = main as f64 * 0.25 / (1.0 - 0.75f64.powi(level_count as i32));
+        let mut levels = Vec::with_capacity(level_count);
+        let mut start = 0;
+        let mut size = first;
+        for _ in 0..level_count {
+            let buckets = ((size as usize) / beta).min((main - start) / beta);
+            if buckets == 0 {
+                break;
+            }
+            levels.push(Level { start, buckets, bucket_len: beta });
+            start += buckets * beta;
+            size *= 0.75;
+        }
+
+        // Slots the levels leave over go to the special array.
+        let special_start = start;
+        let uniform_len = (capacity - special_start) / 2;
+        let bucket_len = 2 * log_log;
+        let two_choice_start = special_start + uniform_len;
+        let two_choice = Level {
+            start: two_choice_start,
+            buckets: ((capacity - two_choice_start) / bucket_len).max(1),
+            bucket_len: bucket_len.min(capacity - two_choice_start),
+        };
+        FunnelTable {
+            slots: vec![None; capacity],
+            levels,
+            uniform: special_start..two_choice_start,
+            uniform_probes: log_log,
+            two_choice,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Inserts a key that is not stored yet, returning the number of
+    /// buckets and special array slots it probed, or `None` if it found no
+    /// free slot.
+    pub(crate) fn insert(&mut self, key: u64, hash: u64, value: u64) -> Option<usize> {
+        let mut probes = 0;
+        for (i, level) in self.levels.iter().enumerate() {
+            probes += 1;
+            let bucket = level.bucket(probe_hash(hash, i));
+            if let Some(slot) = bucket.into_iter().find(|&slot| self.slots[slot].is_none()) {
+                return Some(self.place(slot, key, value, probes));
+            }
+        }
+        if !self.uniform.is_empty() {
+            for j in 0..self.uniform_probes {
+                probes += 1;
+                let slot = self.uniform.start + reduce(probe_hash(hash, self.levels.len() + j), self.uniform.len());
+                if self.slots[slot].is_none() {
+                    return Some(self.place(slot, key, value, probes));
+                }
+            }
+        }
+        // Two-choice: the emptier of two buckets.
+        probes += 2;
+        let first = self.two_choice.bucket(probe_hash(hash, usize::MAX - 1));
+        let second = self.two_choice.bucket(probe_hash(hash, usize::MAX));
+        let free = |bucket: &std::ops::Range<usize>| bucket.clone().filter(|&slot| self.slots[slot].is_none()).count();
+        let bucket = if free(&second) > free(&first) { second } else { first };
+        let slot = bucket.into_iter().find(|&slot| self.slots[slot].is_none())?;
+        Some(self.place(slot, key, value, probes))
+    }
+
+    fn place(&mut self, slot: usize, key: u64, value: u64, probes: usize) -> usize {
+        self.slots[slot] = Some((key, value));
+        self.len += 1;
+        probes
+    }
+
+    /// The value of `key`, with the number of buckets and special array slots
+    /// probed to find it or rule it out.
+    pub(crate) fn get(&self, key: u64, hash: u64) -> (Option<u64>, usize) {
+        let mut probes = 0;
+        // Scans a bucket; `Err` ends the lookup, with the value if found.
+        let scan = |bucket: std::ops::Range<usize>, stop_at_free: bool| -> Result<(), Option<u64>> {
+            for slot in bucket {
+                match self.slots[slot] {
+                    Some((stored, value)) if stored == key => return Err(Some(value)),
+                    None if stop_at_free => return Err(None),
+                    _ => {}
+                }
+            }
+            Ok(())
+        };
+        for (i, level) in self.levels.iter().enumerate() {
+            probes += 1;
+            if let Err(found) = scan(level.bucket(probe_hash(hash, i)), true) {
+                return (found, probes);
+            }
+        }
+        if !self.uniform.is_empty() {
+            for j in 0..self.uniform_probes {
+                probes += 1;
+                let slot = self.uniform.start + reduce(probe_hash(hash, self.levels.len() + j), self.uniform.len());
+                if let Err(found) = scan(slot..slot + 1, true) {
+                    return (found, probes);
+                }
+            }
+        }
+        // Either bucket could hold the key, whatever their free slots.
+        for choice in [usize::MAX - 1, usize::MAX] {
+            probes += 1;
+            if let Err(found) = scan(self.two_choice.bucket(probe_hash(hash, choice)), false) {
+                return (found, probes);
+            }
+        }
+        (None, probes)
+    }
+}
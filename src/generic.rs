@@ -0,0 +1,517 @@
+//! Elastic hashing for Rust programs, over any hashable key type.
+//!
+//! The engine in `map` stores `u64` keys. `ElasticMap<K, V>` hashes each key
+//! to 64 bits with a `BuildHasher`, as `std::collections::HashMap` does, and
+//! stores the entries under that hash. Keys whose hashes collide share one
+//! engine entry, which then holds all of them, so lookups compare the keys
+//! themselves only after the engine has found their hash.
+//!
+//! The type is available without the `python` feature:
+//!
+//! ```toml
+//! rb_elastic_hash = { version = "1", default-features = false }
+//! ```
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Index;
+use std::vec;
+
+use crate::map;
+pub use crate::map::InsertError;
+
+/// The entries stored under one hash: almost always a single one.
+enum Bucket<K, V> {
+    One(K, V),
+    Many(Vec<(K, V)>),
+}
+
+impl<K, V> Bucket<K, V> {
+    fn entries(&self) -> impl Iterator<Item = (&K, &V)> {
+        let (one, many) = match self {
+            Bucket::One(key, value) => (Some((key, value)), &[][..]),
+            Bucket::Many(entries) => (None, &entries[..]),
+        };
+        one.into_iter().chain(many.iter().map(|(key, value)| (key, value)))
+    }
+
+    /// Adds an entry for a key the bucket does not hold.
+    fn push(&mut self, key: K, value: V) {
+        let entries = match std::mem::replace(self, Bucket::Many(Vec::new())) {
+            Bucket::One(first_key, first_value) => vec![(first_key, first_value), (key, value)],
+            Bucket::Many(mut entries) => {
+                entries.push((key, value));
+                entries
+            }
+        };
+        *self = Bucket::Many(entries);
+    }
+
+    fn position<Q: Eq + ?Sized>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        self.entries().position(|(stored, _)| stored.borrow() == key)
+    }
+
+    fn entry_at(&self, i: usize) -> (&K, &V) {
+        match self {
+            Bucket::One(key, value) => (key, value),
+            Bucket::Many(entries) => (&entries[i].0, &entries[i].1),
+        }
+    }
+
+    fn value_at_mut(&mut self, i: usize) -> &mut V {
+        match self {
+            Bucket::One(_, value) => value,
+            Bucket::Many(entries) => &mut entries[i].1,
+        }
+    }
+
+    fn get<Q: Eq + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.entries().find(|(stored, _)| (*stored).borrow() == key).map(|(_, value)| value)
+    }
+
+    fn get_mut<Q: Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        match self {
+            Bucket::One(stored, value) => ((*stored).borrow() == key).then_some(value),
+            Bucket::Many(entries) => entries.iter_mut()
+                .find(|(stored, _)| stored.borrow() == key)
+                .map(|(_, value)| value),
+        }
+    }
+}
+
+/// A hash map using elastic hashing, for keys of any type implementing
+/// `Hash` and `Eq`.
+///
+/// Like the Python tables, a map has a fixed capacity in slots: it places up
+/// to `(1 - delta) * capacity` entries with the guarantees of the paper, and
+/// `insert` returns an `InsertError` once it cannot place a key.
+///
+/// ```
+/// use rb_elastic_hash::ElasticMap;
+///
+/// let mut map = ElasticMap::new(1024);
+/// map.insert("answer", 42).unwrap();
+/// assert_eq!(map.get("answer"), Some(&42));
+/// assert_eq!(map.remove("answer"), Some(42));
+/// assert!(map.is_empty());
+/// ```
+pub struct ElasticMap<K, V, S = RandomState> {
+    map: map::ElasticMap<Bucket<K, V>>,
+    hasher: S,
+    len: usize,
+}
+
+impl<K, V> ElasticMap<K, V> {
+    /// A map with `capacity` slots and the default `delta` of 0.05.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_delta(capacity, 0.05)
+    }
+
+    /// A map with `capacity` slots and elasticity `delta`, which places up to
+    /// `(1 - delta) * capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// If `delta` does not lie strictly between 0 and 1.
+    pub fn with_delta(capacity: usize, delta: f64) -> Self {
+        Self::with_hasher(capacity, delta, RandomState::new())
+    }
+
+    /// A map sized to hold `expected_items` at `load_factor`, as
+    /// `ElasticTable.for_items` sizes tables.
+    ///
+    /// # Panics
+    ///
+    /// If `load_factor` does not lie strictly between 0 and 1.
+    pub fn for_items(expected_items: usize, load_factor: f64) -> Self {
+        assert!(load_factor > 0.0 && load_factor < 1.0, "load_factor must be between 0 and 1");
+        let capacity = (expected_items as f64 / load_factor).ceil() as usize;
+        Self::with_delta(capacity, 1.0 - load_factor)
+    }
+}
+
+impl<K, V, S> ElasticMap<K, V, S> {
+    /// A map with `capacity` slots and elasticity `delta` that hashes keys
+    /// with `hasher`.
+    ///
+    /// # Panics
+    ///
+    /// If `delta` does not lie strictly between 0 and 1.
+    pub fn with_hasher(capacity: usize, delta: f64, hasher: S) -> Self {
+        assert!(delta > 0.0 && delta < 1.0, "delta must be between 0 and 1");
+        ElasticMap { map: map::ElasticMap::new(capacity, delta), hasher, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of slots the map was created with.
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.map.delta()
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Removes every entry, keeping the slots.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.len = 0;
+    }
+
+    /// The entries, in no particular order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { entries: Box::new(self.map.iter().flat_map(|(_, bucket)| bucket.entries())), len: self.len }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> ElasticMap<K, V, S> {
+    /// Inserts `value` under `key`, returning the value it replaces.
+    ///
+    /// Replacing the value of a stored key always succeeds. A new key the
+    /// map cannot place is dropped along with `value`, and the error says
+    /// why: the map is full, or every subarray turned the key away.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, InsertError> {
+        let hash = self.hasher.hash_one(&key);
+        if let Some(bucket) = self.map.get_mut(hash) {
+            if let Some(stored) = bucket.get_mut(&key) {
+                return Ok(Some(std::mem::replace(stored, value)));
+            }
+            bucket.push(key, value);
+            self.len += 1;
+            return Ok(None);
+        }
+        self.map.insert(hash, Bucket::One(key, value))?;
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// The entry of `key`, to read, update or fill in with a single lookup.
+    ///
+    /// ```
+    /// use rb_elastic_hash::ElasticMap;
+    ///
+    /// let mut counts = ElasticMap::new(1024);
+    /// for word in ["a", "b", "a"] {
+    ///     *counts.entry(word).or_insert(0).unwrap() += 1;
+    /// }
+    /// assert_eq!(counts["a"], 2);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hasher.hash_one(&key);
+        let Some(slot) = self.map.slot_of(hash) else {
+            return Entry::Vacant(VacantEntry { map: self, key, hash, slot: None });
+        };
+        match self.map.value_at_mut(slot).position(&key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { bucket: self.map.value_at_mut(slot), index }),
+            None => Entry::Vacant(VacantEntry { map: self, key, hash, slot: Some(slot) }),
+        }
+    }
+
+    pub fn get<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.get(self.hasher.hash_one(key))?.get(key)
+    }
+
+    pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.get_mut(self.hasher.hash_one(key))?.get_mut(key)
+    }
+
+    pub fn contains_key<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Removes `key`, returning its value. Its slot becomes a tombstone that
+    /// later inserts can reuse.
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let hash = self.hasher.hash_one(key);
+        let bucket = self.map.get_mut(hash)?;
+        let removed = match bucket {
+            Bucket::One(stored, _) if (*stored).borrow() == key => match self.map.remove(hash) {
+                Some(Bucket::One(_, value)) => Some(value),
+                _ => unreachable!("the bucket holds one entry"),
+            },
+            Bucket::One(..) => None,
+            Bucket::Many(entries) => {
+                let i = entries.iter().position(|(stored, _)| stored.borrow() == key)?;
+                let (_, value) = entries.swap_remove(i);
+                if let [_] = entries[..] {
+                    let (key, value) = entries.pop().expect("one entry is left");
+                    *bucket = Bucket::One(key, value);
+                }
+                Some(value)
+            }
+        };
+        self.len -= usize::from(removed.is_some());
+        removed
+    }
+}
+
+/// The entry of a key in a map, returned by `ElasticMap::entry`.
+pub enum Entry<'a, K, V, S = RandomState> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Calls `f` on the value if the key is stored.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+
+    /// The value of the key, after inserting `default` if it is not stored.
+    pub fn or_insert(self, default: V) -> Result<&'a mut V, InsertError> {
+        self.or_insert_with(|| default)
+    }
+
+    /// The value of the key, after inserting the result of `default` if it is
+    /// not stored. `default` is not called for a stored key.
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> Result<&'a mut V, InsertError> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+}
+
+/// The entry of a stored key.
+pub struct OccupiedEntry<'a, K, V> {
+    bucket: &'a mut Bucket<K, V>,
+    index: usize,
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        self.bucket.entry_at(self.index).0
+    }
+
+    pub fn get(&self) -> &V {
+        self.bucket.entry_at(self.index).1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.bucket.value_at_mut(self.index)
+    }
+
+    /// The value, borrowed for as long as the map was.
+    pub fn into_mut(self) -> &'a mut V {
+        self.bucket.value_at_mut(self.index)
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+}
+
+/// The entry of a key the map does not hold.
+pub struct VacantEntry<'a, K, V, S = RandomState> {
+    map: &'a mut ElasticMap<K, V, S>,
+    key: K,
+    hash: u64,
+    /// The slot of the entries whose keys share the hash, if any.
+    slot: Option<usize>,
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts `value` under the key, returning a reference to it. A key
+    /// sharing its hash with stored ones is always placed; otherwise, as with
+    /// `ElasticMap::insert`, the key and `value` are dropped if the map
+    /// cannot place them.
+    pub fn insert(self, value: V) -> Result<&'a mut V, InsertError> {
+        let VacantEntry { map, key, hash, slot } = self;
+        let slot = match slot {
+            Some(slot) => {
+                map.map.value_at_mut(slot).push(key, value);
+                slot
+            }
+            None => map.map.insert_slot(hash, Bucket::One(key, value))?,
+        };
+        map.len += 1;
+        let bucket = map.map.value_at_mut(slot);
+        let last = bucket.entries().count() - 1;
+        Ok(bucket.value_at_mut(last))
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for ElasticMap<K, V, S> {
+    /// Inserts every entry, replacing the values of stored keys.
+    ///
+    /// # Panics
+    ///
+    /// If the map cannot place a new key. Call `insert` to handle that case.
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        for (key, value) in entries {
+            if let Err(err) = self.insert(key, value) {
+                panic!("cannot extend the map: {err}");
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for ElasticMap<K, V, S> {
+    /// Collects the entries into a map sized to hold them at a load factor
+    /// of 0.90, the default of `ElasticTable.for_items`.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        let entries: Vec<(K, V)> = entries.into_iter().collect();
+        let capacity = (entries.len() as f64 / 0.90).ceil() as usize;
+        let mut map = Self::with_hasher(capacity, 0.10, S::default());
+        map.extend(entries);
+        map
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for ElasticMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// The value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// If `key` is not stored.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in the map")
+    }
+}
+
+/// An iterator over the entries of a map, returned by `ElasticMap::iter`.
+pub struct Iter<'a, K, V> {
+    entries: Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>,
+    len: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        self.len -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a ElasticMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator moving the entries out of a map, in no particular order.
+pub struct IntoIter<K, V> {
+    buckets: vec::IntoIter<Bucket<K, V>>,
+    /// The rest of a bucket holding several entries.
+    many: vec::IntoIter<(K, V)>,
+    len: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let entry = match self.many.next() {
+            Some(entry) => entry,
+            None => match self.buckets.next()? {
+                Bucket::One(key, value) => (key, value),
+                Bucket::Many(entries) => {
+                    self.many = entries.into_iter();
+                    self.many.next().expect("a bucket holds at least two entries")
+                }
+            },
+        };
+        self.len -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V, S> IntoIterator for ElasticMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { buckets: self.map.into_values().into_iter(), many: Vec::new().into_iter(), len: self.len }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for ElasticMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,166 @@
+//! SwissTable-style control bytes, matched 16 at a time.
+//!
+//! Every slot has one control byte: `EMPTY`, `DELETED`, or the 7-bit fingerprint
+//! of the key stored there. Subarrays probe whole groups of `GROUP_WIDTH` control
+//! bytes with a single SIMD comparison (SSE2 on x86_64, NEON on aarch64, a scalar
+//! loop elsewhere), so most non-matching slots are rejected without touching keys.
+
+/// Control byte of a slot that has never held an entry.
+pub(crate) const EMPTY: u8 = 0xFF;
+/// Control byte of a tombstone.
+pub(crate) const DELETED: u8 = 0x80;
+/// Control byte of the padding after the last real slot of a subarray. It never
+/// matches a fingerprint and is neither empty nor deleted.
+pub(crate) const SENTINEL: u8 = 0xFE;
+
+/// Whether a control byte marks an occupied slot (fingerprints never set the top bit).
+#[inline(always)]
+pub(crate) fn is_full(ctrl: u8) -> bool {
+    ctrl & 0x80 == 0
+}
+
+/// Number of control bytes inspected per probe.
+pub(crate) const GROUP_WIDTH: usize = 16;
+
+/// 7-bit fingerprint stored in the control byte of an occupied slot.
+#[inline(always)]
+pub(crate) fn fingerprint(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// Set of matching positions within a group, iterated lowest first.
+#[derive(Clone, Copy)]
+pub(crate) struct BitMask(u64);
+
+impl BitMask {
+    /// Bits used per group position (NEON produces one nibble per byte).
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    const STRIDE: u32 = 4;
+    #[cfg(not(all(target_arch = "aarch64", target_feature = "neon")))]
+    const STRIDE: u32 = 1;
+
+    #[inline(always)]
+    pub(crate) fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    #[inline(always)]
+    pub(crate) fn lowest(self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some((self.0.trailing_zeros() / Self::STRIDE) as usize)
+        }
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<usize> {
+        let bit = self.lowest()?;
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+/// One group of control bytes loaded for matching.
+#[derive(Clone, Copy)]
+pub(crate) struct Group(imp::Raw);
+
+impl Group {
+    /// Loads the group starting at `ctrl[0]`. `ctrl` must hold at least `GROUP_WIDTH` bytes.
+    #[inline(always)]
+    pub(crate) fn load(ctrl: &[u8]) -> Group {
+        assert!(ctrl.len() >= GROUP_WIDTH);
+        // SAFETY: the assertion above guarantees GROUP_WIDTH readable bytes.
+        Group(unsafe { imp::load(ctrl.as_ptr()) })
+    }
+
+    /// Positions whose control byte equals `byte`.
+    #[inline(always)]
+    pub(crate) fn match_byte(self, byte: u8) -> BitMask {
+        BitMask(imp::match_byte(self.0, byte))
+    }
+
+    #[inline(always)]
+    pub(crate) fn match_empty(self) -> BitMask {
+        self.match_byte(EMPTY)
+    }
+
+    #[inline(always)]
+    pub(crate) fn match_deleted(self) -> BitMask {
+        self.match_byte(DELETED)
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+mod imp {
+    use core::arch::x86_64::*;
+
+    pub(super) type Raw = __m128i;
+
+    #[inline(always)]
+    pub(super) unsafe fn load(ptr: *const u8) -> Raw {
+        _mm_loadu_si128(ptr as *const __m128i)
+    }
+
+    #[inline(always)]
+    pub(super) fn match_byte(group: Raw, byte: u8) -> u64 {
+        // SAFETY: SSE2 is statically enabled for this module.
+        unsafe {
+            let cmp = _mm_cmpeq_epi8(group, _mm_set1_epi8(byte as i8));
+            _mm_movemask_epi8(cmp) as u16 as u64
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod imp {
+    use core::arch::aarch64::*;
+
+    pub(super) type Raw = uint8x16_t;
+
+    #[inline(always)]
+    pub(super) unsafe fn load(ptr: *const u8) -> Raw {
+        vld1q_u8(ptr)
+    }
+
+    #[inline(always)]
+    pub(super) fn match_byte(group: Raw, byte: u8) -> u64 {
+        // SAFETY: NEON is statically enabled for this module.
+        unsafe {
+            let cmp = vceqq_u8(group, vdupq_n_u8(byte));
+            // Narrow each 0x00/0xFF byte to a nibble, then keep one bit per nibble.
+            let nibbles = vshrn_n_u16(vreinterpretq_u16_u8(cmp), 4);
+            vget_lane_u64(vreinterpret_u64_u8(nibbles), 0) & 0x8888_8888_8888_8888
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_feature = "sse2"),
+    all(target_arch = "aarch64", target_feature = "neon"),
+)))]
+mod imp {
+    use super::GROUP_WIDTH;
+
+    pub(super) type Raw = [u8; GROUP_WIDTH];
+
+    #[inline(always)]
+    pub(super) unsafe fn load(ptr: *const u8) -> Raw {
+        core::ptr::read_unaligned(ptr as *const Raw)
+    }
+
+    #[inline(always)]
+    pub(super) fn match_byte(group: Raw, byte: u8) -> u64 {
+        let mut mask = 0;
+        for (i, &ctrl) in group.iter().enumerate() {
+            if ctrl == byte {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+}
@@ -0,0 +1,76 @@
+//! Key hashing.
+//!
+//! Keys are hashed once into a 64-bit base hash; each subarray then derives its
+//! own double hashing parameters from that value and a seed of its own. The mixer is plain integer
+//! arithmetic, so hashing a whole batch of keys vectorizes well.
+
+const PRIME_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME_3: u64 = 0x1656_67B1_9E37_79F9;
+
+/// Number of keys hashed per iteration in `hash_keys`; wide enough to fill
+/// AVX-512 registers, and a plain unrolled loop on targets without SIMD.
+const LANES: usize = 8;
+
+/// Full-avalanche 64-bit finalizer (the xxh3/murmur3 `fmix` family).
+#[inline(always)]
+pub(crate) fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME_3);
+    h ^= h >> 32;
+    h
+}
+
+/// Where the seeds of `subarray_seed` start.
+const SEED_ORIGIN: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// Seed of the probe sequences of the `index`-th subarray of every map: the
+/// `index`-th output of a SplitMix64 generator, so the sequences a key gets in
+/// successive subarrays are decorrelated from each other and from their
+/// capacities. The seeds are fixed, so a map's layout depends only on its
+/// parameters and the keys inserted, as saved tables and recordings require.
+pub(crate) fn subarray_seed(index: usize) -> u64 {
+    let mut z = SEED_ORIGIN.wrapping_add((index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Base hash of a single key.
+#[inline(always)]
+pub(crate) fn hash_key(key: u64) -> u64 {
+    mix(key.wrapping_mul(PRIME_1))
+}
+
+/// Maps `hash` uniformly onto `0..n`, using its high bits.
+#[inline(always)]
+pub(crate) fn reduce(hash: u64, n: usize) -> usize {
+    ((hash as u128 * n as u128) >> 64) as usize
+}
+
+/// Shard, out of `shards`, of a key with base hash `hash`. Shared by
+/// `ShardedElasticTable` and `split()`, so their partitions agree.
+#[inline(always)]
+pub(crate) fn shard_of(hash: u64, shards: usize) -> usize {
+    (hash % shards as u64) as usize
+}
+
+/// Base hashes for a batch of keys, `LANES` at a time.
+pub(crate) fn hash_keys(keys: &[u64]) -> Vec<u64> {
+    let mut hashes = vec![0u64; keys.len()];
+
+    let mut out = hashes.chunks_exact_mut(LANES);
+    let mut src = keys.chunks_exact(LANES);
+    for (dst, lane) in out.by_ref().zip(src.by_ref()) {
+        for i in 0..LANES {
+            dst[i] = hash_key(lane[i]);
+        }
+    }
+    for (dst, &key) in out.into_remainder().iter_mut().zip(src.remainder()) {
+        *dst = hash_key(key);
+    }
+
+    hashes
+}
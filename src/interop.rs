@@ -0,0 +1,89 @@
+//! Conversions between typed tables and other columnar libraries.
+//!
+//! Columns travel as numpy arrays: pyarrow and pandas expose null-free numeric
+//! columns as numpy views and wrap numpy arrays without copying, so the only
+//! copy made is the one into or out of the table itself.
+//!
+//! JSON goes through Python's `json` module and is meant for small tables.
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyDict, PyString};
+
+/// Views an Arrow array or chunked array as a numpy array. Single-chunk and
+/// plain arrays are not copied.
+pub(crate) fn arrow_to_numpy<'py>(array: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    let py = array.py();
+    let array = if array.hasattr("combine_chunks")? {
+        array.call_method0("combine_chunks")?
+    } else {
+        array.clone()
+    };
+    if array.getattr("null_count")?.extract::<usize>()? > 0 {
+        return Err(PyValueError::new_err("Arrow arrays must not contain nulls"));
+    }
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("zero_copy_only", true)?;
+    array.call_method("to_numpy", (), Some(&kwargs))
+}
+
+/// Wraps a numpy array in a pyarrow array sharing its memory.
+pub(crate) fn numpy_to_arrow<'py>(array: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    array.py().import("pyarrow")?.call_method1("array", (array,))
+}
+
+/// Views column `name` of a pandas DataFrame as a numpy array. Numeric columns
+/// without missing values are not copied.
+pub(crate) fn pandas_column<'py>(df: &Bound<'py, PyAny>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+    let column = df.get_item(name)?;
+    if column.getattr("hasnans")?.is_truthy()? {
+        return Err(PyValueError::new_err(format!("column {name:?} must not contain missing values")));
+    }
+    column.call_method0("to_numpy")
+}
+
+/// Builds a two-column pandas DataFrame around numpy arrays without copying them.
+pub(crate) fn pandas_frame<'py>(columns: [(&str, &Bound<'py, PyAny>); 2]) -> PyResult<Bound<'py, PyAny>> {
+    let py = columns[0].1.py();
+    let data = PyDict::new(py);
+    for (name, array) in columns {
+        data.set_item(name, array)?;
+    }
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("copy", false)?;
+    py.import("pandas")?.getattr("DataFrame")?.call((data,), Some(&kwargs))
+}
+
+/// Serializes entries as a JSON object keyed by the decimal keys, in key order,
+/// so equal tables always produce the same text.
+pub(crate) fn entries_to_json(
+    py: Python<'_>,
+    mut entries: Vec<(u64, PyObject)>,
+    indent: Option<usize>,
+) -> PyResult<String> {
+    entries.sort_unstable_by_key(|&(key, _)| key);
+    let object = PyDict::new(py);
+    for (key, value) in entries {
+        object.set_item(key.to_string(), value)?;
+    }
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("indent", indent)?;
+    py.import("json")?.getattr("dumps")?.call((object,), Some(&kwargs))?.extract()
+}
+
+/// Parses a JSON object written by `entries_to_json` into its entries.
+pub(crate) fn entries_from_json<'py>(py: Python<'py>, data: &str) -> PyResult<Vec<(u64, Bound<'py, PyAny>)>> {
+    let object = py.import("json")?.call_method1("loads", (data,))?;
+    let object = object
+        .downcast::<PyDict>()
+        .map_err(|_| PyValueError::new_err("JSON data must be an object"))?;
+    object.iter()
+        .map(|(key, value)| {
+            let key = key.downcast_into::<PyString>()?;
+            let key = key.to_str()?.parse().map_err(|_| {
+                PyValueError::new_err(format!("JSON key {key} is not a non-negative integer"))
+            })?;
+            Ok((key, value))
+        })
+        .collect()
+}
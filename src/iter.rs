@@ -0,0 +1,221 @@
+//! Iteration over a table's keys, values or items that notices the table
+//! changing meanwhile.
+//!
+//! Iterators walk the slots of the table in place instead of copying its keys
+//! up front; ordered tables copy only their insertion order when iteration
+//! starts. Values are looked up by key as the iterator reaches them. As with a `dict`, entries must not be inserted or removed while an
+//! iterator is in use: the map's generation then moves on, and the next step
+//! raises `RuntimeError` instead of skipping or repeating keys.
+
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::PyRuntimeError;
+
+use crate::bytes::BytesTable;
+use crate::columns::ColumnTable;
+use crate::map::{ElasticMap, Key};
+use crate::narrow::{FloatTable32, IntTable32};
+use crate::record::RecordTable;
+use crate::table::ElasticTable;
+use crate::typed::{FloatTable, IntTable};
+
+/// An owned reference to a table whose entries views and iterators walk after
+/// the call that made them.
+pub(crate) enum ViewedTable {
+    Elastic(Py<ElasticTable>),
+    Int(Py<IntTable>),
+    Float(Py<FloatTable>),
+    Int32(Py<IntTable32>),
+    Float32(Py<FloatTable32>),
+    Bytes(Py<BytesTable>),
+    Record(Py<RecordTable>),
+    Columns(Py<ColumnTable>),
+}
+
+/// Evaluates `$body` with `$table` bound to the locked state of the table of `$handle`.
+/// The state of every table class has a `map()` and a `value_of(py, key)`.
+macro_rules! with_table {
+    ($handle:expr, $py:expr, $table:ident => $body:expr) => {
+        match $handle {
+            ViewedTable::Elastic(table) => {
+                let $table = table.get().lock($py)?;
+                $table.check_open()?;
+                $body
+            }
+            ViewedTable::Int(table) => { let $table = table.get().lock($py)?; $body }
+            ViewedTable::Float(table) => { let $table = table.get().lock($py)?; $body }
+            ViewedTable::Int32(table) => { let $table = table.get().lock($py)?; $body }
+            ViewedTable::Float32(table) => { let $table = table.get().lock($py)?; $body }
+            ViewedTable::Bytes(table) => { let $table = table.get().lock($py)?; $body }
+            ViewedTable::Record(table) => { let $table = table.get().lock($py)?; $body }
+            ViewedTable::Columns(table) => { let $table = table.get().lock($py)?; $body }
+        }
+    };
+}
+
+impl ViewedTable {
+    /// Reports the table to the cyclic GC.
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        match self {
+            ViewedTable::Elastic(table) => visit.call(table),
+            ViewedTable::Int(table) => visit.call(table),
+            ViewedTable::Float(table) => visit.call(table),
+            ViewedTable::Int32(table) => visit.call(table),
+            ViewedTable::Float32(table) => visit.call(table),
+            ViewedTable::Bytes(table) => visit.call(table),
+            ViewedTable::Record(table) => visit.call(table),
+            ViewedTable::Columns(table) => visit.call(table),
+        }
+    }
+
+    /// The number of entries in the table, leaving out those of a weak
+    /// `ElasticTable` whose value has been collected.
+    pub(crate) fn len(&self, py: Python<'_>) -> PyResult<usize> {
+        if let ViewedTable::Elastic(table) = self {
+            let table = table.get().lock(py)?;
+            table.check_open()?;
+            return table.live_len(py);
+        }
+        Ok(with_table!(self, py, table => table.map().len()))
+    }
+
+    /// Whether the entry of `key` counts as present; see `ElasticTable::is_live`.
+    fn is_live(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        match self {
+            ViewedTable::Elastic(table) => table.get().lock(py)?.is_live(py, key),
+            _ => Ok(true),
+        }
+    }
+
+    /// The value stored under `key`, without counting a lookup.
+    pub(crate) fn value(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+        with_table!(self, py, table => table.value_of(py, key))
+    }
+
+    /// Starts iterating over the table.
+    pub(crate) fn iter(&self, py: Python<'_>, yields: Yield) -> PyResult<TableIterator> {
+        let handle = self.clone_ref(py);
+        Ok(with_table!(self, py, table => TableIterator::new(handle, table.map(), yields)))
+    }
+
+    fn clone_ref(&self, py: Python<'_>) -> Self {
+        match self {
+            ViewedTable::Elastic(table) => ViewedTable::Elastic(table.clone_ref(py)),
+            ViewedTable::Int(table) => ViewedTable::Int(table.clone_ref(py)),
+            ViewedTable::Float(table) => ViewedTable::Float(table.clone_ref(py)),
+            ViewedTable::Int32(table) => ViewedTable::Int32(table.clone_ref(py)),
+            ViewedTable::Float32(table) => ViewedTable::Float32(table.clone_ref(py)),
+            ViewedTable::Bytes(table) => ViewedTable::Bytes(table.clone_ref(py)),
+            ViewedTable::Record(table) => ViewedTable::Record(table.clone_ref(py)),
+            ViewedTable::Columns(table) => ViewedTable::Columns(table.clone_ref(py)),
+        }
+    }
+}
+
+macro_rules! viewed_table_from {
+    ($($variant:ident($class:ty)),* $(,)?) => {
+        $(
+            impl From<Py<$class>> for ViewedTable {
+                fn from(table: Py<$class>) -> Self {
+                    ViewedTable::$variant(table)
+                }
+            }
+        )*
+    };
+}
+
+viewed_table_from!(
+    Elastic(ElasticTable),
+    Int(IntTable),
+    Float(FloatTable),
+    Int32(IntTable32),
+    Float32(FloatTable32),
+    Bytes(BytesTable),
+    Record(RecordTable),
+    Columns(ColumnTable),
+);
+
+/// Where an iterator resumes.
+enum Position {
+    /// The arena index to look for the next occupied slot from.
+    Slot(usize),
+    /// The keys of an ordered table not visited yet.
+    Ordered(std::vec::IntoIter<u64>),
+}
+
+/// What an iterator produces for each entry.
+#[derive(Clone, Copy)]
+pub(crate) enum Yield {
+    Keys,
+    Values,
+    /// `(key, value)` tuples.
+    Items,
+}
+
+#[pyclass]
+pub(crate) struct TableIterator {
+    /// Holding the table keeps it alive.
+    table: ViewedTable,
+    /// The map's generation when iteration started.
+    generation: u64,
+    position: Position,
+    yields: Yield,
+}
+
+impl TableIterator {
+    /// Starts iterating over `table`, whose map is `map`.
+    pub(crate) fn new<V, K: Key>(table: impl Into<ViewedTable>, map: &ElasticMap<V, K>, yields: Yield) -> Self {
+        let position = if map.keeps_order() {
+            let keys: Vec<u64> = map.entries_in_order().into_iter().map(|(key, _)| key.to_u64()).collect();
+            Position::Ordered(keys.into_iter())
+        } else {
+            Position::Slot(0)
+        };
+        TableIterator { table: table.into(), generation: map.generation(), position, yields }
+    }
+
+    fn advance<V, K: Key>(generation: u64, position: &mut Position, map: &ElasticMap<V, K>) -> PyResult<Option<u64>> {
+        if map.generation() != generation {
+            return Err(PyRuntimeError::new_err("table changed during iteration"));
+        }
+        Ok(match position {
+            Position::Slot(next) => map.next_key(*next).map(|(idx, key)| {
+                *next = idx + 1;
+                key.to_u64()
+            }),
+            Position::Ordered(keys) => keys.next(),
+        })
+    }
+}
+
+#[pymethods]
+impl TableIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        self.table.traverse(&visit)
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let TableIterator { table, generation, position, yields } = self;
+        // Entries of a weak table whose value has been collected are skipped.
+        loop {
+            let key = with_table!(table, py, stored => Self::advance(*generation, position, stored.map())?);
+            let Some(key) = key else { return Ok(None) };
+            if let Yield::Keys = yields {
+                if table.is_live(py, key)? {
+                    return Ok(Some(key.into_py_any(py)?));
+                }
+                continue;
+            }
+            let Some(value) = table.value(py, key)? else { continue };
+            return Ok(Some(match yields {
+                Yield::Items => (key, value).into_py_any(py)?,
+                _ => value,
+            }));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! `TableLock`: the lock every call on a plain table holds.
+//!
+//! The plain tables keep their state behind a mutex, so calls from several
+//! threads take turns instead of failing: on free-threaded CPython, and while
+//! a batch method has released the GIL. A waiting thread never holds the GIL,
+//! so the holder can take it back without deadlocking. A call reaching the
+//! table again from the thread that holds it, through an observer, a tracer
+//! or a finalizer, cannot wait for itself and raises `RuntimeError` instead.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyRuntimeError;
+
+/// A number identifying the current thread, never 0.
+fn thread_token() -> usize {
+    thread_local!(static TOKEN: u8 = const { 0 });
+    TOKEN.with(|token| token as *const u8 as usize)
+}
+
+/// The state of a table and the lock guarding it.
+pub(crate) struct TableLock<T> {
+    state: Mutex<T>,
+    /// `thread_token` of the thread holding the lock, or 0.
+    owner: AtomicUsize,
+}
+
+/// Access to the state of a table, holding its lock.
+pub(crate) struct TableGuard<'a, T> {
+    guard: MutexGuard<'a, T>,
+    owner: &'a AtomicUsize,
+}
+
+impl<T: Send> TableLock<T> {
+    pub(crate) fn new(state: T) -> Self {
+        TableLock { state: Mutex::new(state), owner: AtomicUsize::new(0) }
+    }
+
+    /// Waits for the lock with the GIL released. Raises RuntimeError if this
+    /// thread already holds it.
+    pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, T>> {
+        let token = thread_token();
+        loop {
+            match self.state.try_lock() {
+                Ok(guard) => return Ok(self.hold(guard, token)),
+                Err(TryLockError::Poisoned(err)) => return Ok(self.hold(err.into_inner(), token)),
+                Err(TryLockError::WouldBlock) if self.owner.load(Ordering::Acquire) == token => {
+                    return Err(PyRuntimeError::new_err("the table is already in use by a call on this thread"));
+                }
+                // The guard cannot leave `allow_threads`, so the lock is taken
+                // again once the GIL is back.
+                Err(TryLockError::WouldBlock) => py.allow_threads(|| {
+                    drop(self.state.lock().unwrap_or_else(PoisonError::into_inner));
+                }),
+            }
+        }
+    }
+
+    /// The lock if no call holds it, for the cyclic GC, which must not wait.
+    pub(crate) fn try_lock(&self) -> Option<TableGuard<'_, T>> {
+        match self.state.try_lock() {
+            Ok(guard) => Some(self.hold(guard, thread_token())),
+            Err(TryLockError::Poisoned(err)) => Some(self.hold(err.into_inner(), thread_token())),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    fn hold<'a>(&'a self, guard: MutexGuard<'a, T>, token: usize) -> TableGuard<'a, T> {
+        self.owner.store(token, Ordering::Release);
+        TableGuard { guard, owner: &self.owner }
+    }
+}
+
+impl<T> Deref for TableGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TableGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for TableGuard<'_, T> {
+    /// Runs before the mutex is unlocked, when `guard` is dropped.
+    fn drop(&mut self) {
+        self.owner.store(0, Ordering::Release);
+    }
+}
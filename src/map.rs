@@ -0,0 +1,2208 @@
+//! The elastic hashing engine, independent of how values are exposed to Python.
+
+use std::collections::{HashMap, HashSet};
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::filter::BloomFilter;
+use crate::group::{fingerprint, is_full, BitMask, Group, DELETED, EMPTY, GROUP_WIDTH, SENTINEL};
+use crate::hash::{hash_key, mix, subarray_seed};
+use crate::region::{Layout, Region, Slots};
+use crate::tuning::{Tuner, Tuning, DEFAULT_BUDGET};
+
+/// A type of key the engine stores: `u64` for most tables, and `u32` for the
+/// narrow tables, whose slots take half the key bytes.
+pub(crate) trait Key: Copy + Eq + std::hash::Hash + std::fmt::Display + Default + Send + Sync + 'static {
+    fn to_u64(self) -> u64;
+}
+
+impl Key for u64 {
+    fn to_u64(self) -> u64 {
+        self
+    }
+}
+
+impl Key for u32 {
+    fn to_u64(self) -> u64 {
+        u64::from(self)
+    }
+}
+
+/// Slot storage shared by all subarrays of a table.
+///
+/// Slots are stored as parallel arrays so probe loops only touch `ctrl` and
+/// `keys` until a match is found. Each array is a single allocation for the whole
+/// table, with subarrays occupying consecutive group-aligned ranges, so building,
+/// clearing and dropping a table touch three buffers rather than three per subarray.
+///
+/// Occupancy lives solely in `ctrl` (see `group`): `values[i]` is initialized, and
+/// `keys[i]` meaningful, exactly when `ctrl[i]` holds a fingerprint, so a slot
+/// costs its key and value bytes plus one control byte.
+///
+/// The arrays are normally heap vectors; typed tables can instead place them in
+/// a `Region` such as a shared-memory segment (see `storage`).
+struct Arena<V, K> {
+    ctrl: Slots<u8>,
+    keys: Slots<K>,
+    values: Slots<MaybeUninit<V>>,
+    /// Keeps mapped slot arrays alive; declared last so it is dropped after them.
+    region: Option<Region>,
+}
+
+impl<V, K: Key> Arena<V, K> {
+    fn new(slots: usize) -> Self {
+        let mut values = Vec::with_capacity(slots);
+        values.resize_with(slots, MaybeUninit::uninit);
+        Arena {
+            ctrl: Slots::Owned(vec![SENTINEL; slots]),
+            keys: Slots::Owned(vec![K::default(); slots]),
+            values: Slots::Owned(values),
+            region: None,
+        }
+    }
+}
+
+impl<V> Arena<V, u64> {
+    /// Views the slot arrays stored in `region` at the offsets given by `layout`.
+    fn in_region(mut region: Region, layout: Layout) -> Self {
+        assert!(std::mem::size_of::<V>() == 8 && std::mem::align_of::<V>() <= 8);
+        assert!(region.len() >= layout.len);
+        // SAFETY: the three ranges are disjoint, inside the region and 8-byte
+        // aligned; any byte pattern is a valid control byte, key or `MaybeUninit`.
+        unsafe {
+            Arena {
+                ctrl: Slots::mapped(&mut region, layout.ctrl, layout.slots),
+                keys: Slots::mapped(&mut region, layout.keys, layout.slots),
+                values: Slots::mapped(&mut region, layout.values, layout.slots),
+                region: Some(region),
+            }
+        }
+    }
+}
+
+impl<V, K> Arena<V, K> {
+    /// Drops every stored value. Control bytes are left untouched.
+    fn drop_values(&mut self) {
+        if !std::mem::needs_drop::<V>() { return; }
+
+        for (ctrl, value) in self.ctrl.iter().zip(self.values.iter_mut()) {
+            if is_full(*ctrl) {
+                // SAFETY: the slot is occupied; callers reset or discard `ctrl` afterwards.
+                unsafe { value.assume_init_drop() };
+            }
+        }
+    }
+}
+
+impl<V, K> Drop for Arena<V, K> {
+    fn drop(&mut self) {
+        self.drop_values();
+    }
+}
+
+/// Represents one of the A_i arrays described in the paper: the slot range
+/// `base..base + capacity` of the table's `Arena`.
+///
+/// Probing visits whole groups of `GROUP_WIDTH` slots, so one probe inspects
+/// up to 16 candidate slots. The range is padded to a whole number of groups
+/// with `SENTINEL` control bytes.
+struct SubArray {
+    /// Mixed into the hash of every key probed here (see `hash::subarray_seed`).
+    seed: u64,
+    base: usize,
+    count: usize,
+    tombstones: usize,
+    capacity: usize,
+    groups: usize,
+    /// Most probes a lookup needs: every stored key is found within this many.
+    /// Equal to `groups` unless the map was sealed (see `ElasticMap::seal`).
+    probe_limit: usize,
+    /// Inserts that moved on to the next subarray from this one, by the case
+    /// of the paper's analysis they were in here.
+    fallthroughs: [u64; 3],
+}
+
+/// Salt of the second probe sequence of each subarray, for maps placing keys
+/// by two choices.
+const SECOND_CHOICE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Most entries one insert displaces under Robin Hood placement; the last one
+/// displaced takes the first free slot along its probe sequence.
+const MAX_DISPLACEMENTS: usize = 64;
+
+/// Simple GCD helper to ensure probe sequence covers the whole array
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+    a
+}
+
+impl SubArray {
+    /// Claims `capacity` slots starting at arena index `base`, for the
+    /// `index`-th subarray of a map.
+    fn new<V, K: Key>(arena: &mut Arena<V, K>, index: usize, base: usize, capacity: usize) -> Self {
+        let groups = capacity.div_ceil(GROUP_WIDTH);
+        let sub = SubArray {
+            seed: subarray_seed(index),
+            base,
+            count: 0,
+            tombstones: 0,
+            capacity,
+            groups,
+            probe_limit: groups,
+            fallthroughs: [0; 3],
+        };
+        sub.reset(arena);
+        sub
+    }
+
+    /// Adopts slots `base..base + capacity` of an arena that already holds
+    /// entries. Without known `(count, tombstones)`, they are recounted from the
+    /// control bytes.
+    fn attach<V, K: Key>(
+        arena: &Arena<V, K>,
+        index: usize,
+        base: usize,
+        capacity: usize,
+        counts: Option<(usize, usize)>,
+    ) -> Self {
+        let (count, tombstones) = counts.unwrap_or_else(|| {
+            let ctrl = &arena.ctrl[base..base + capacity];
+            (
+                ctrl.iter().filter(|&&byte| is_full(byte)).count(),
+                ctrl.iter().filter(|&&byte| byte == DELETED).count(),
+            )
+        });
+        let groups = capacity.div_ceil(GROUP_WIDTH);
+        SubArray {
+            seed: subarray_seed(index),
+            base,
+            count,
+            tombstones,
+            capacity,
+            groups,
+            probe_limit: groups,
+            fallthroughs: [0; 3],
+        }
+    }
+
+    /// Number of arena slots taken, including the padding of the last group.
+    fn padded_len(capacity: usize) -> usize {
+        capacity.div_ceil(GROUP_WIDTH) * GROUP_WIDTH
+    }
+
+    /// Marks every slot empty. Values must already have been dropped.
+    fn reset<V, K: Key>(&self, arena: &mut Arena<V, K>) {
+        let end = self.base + self.groups * GROUP_WIDTH;
+        arena.ctrl[self.base..self.base + self.capacity].fill(EMPTY);
+        arena.ctrl[self.base + self.capacity..end].fill(SENTINEL);
+    }
+
+    fn load_factor(&self) -> f64 {
+        if self.capacity == 0 { return 1.0; }
+        self.count as f64 / self.capacity as f64
+    }
+
+    fn epsilon(&self) -> f64 {
+        1.0 - self.load_factor()
+    }
+
+    /// Helper to generate Double Hashing parameters (h1, h2) over groups from a
+    /// key's base hash. Ensures h2 is coprime to the group count so we visit all groups.
+    /// `second` picks the key's second probe sequence, for two-choice maps.
+    fn probe_params(&self, hash: u64, second: bool) -> (usize, usize) {
+        // Seed each subarray separately so a key's sequences in different
+        // subarrays are unrelated.
+        let salt = if second { SECOND_CHOICE } else { 0 };
+        let full_hash = mix(hash ^ self.seed ^ salt);
+        
+        let h1 = full_hash as usize;
+        
+        // Initial guess for step size (odd number)
+        let mut h2 = ((full_hash >> 32) as usize) | 1;
+
+        // CRITICAL FIX: Ensure gcd(h2, groups) == 1
+        // If they share a factor, the probe sequence will cycle early 
+        // and we won't find empty slots.
+        while gcd(h2, self.groups) != 1 {
+            h2 = h2.wrapping_add(2); // Keep it odd, try next
+            // If h2 wraps around to 1, we are fine (linear probing)
+            if h2 == 1 { break; } 
+        }
+        
+        (h1, h2)
+    }
+
+    /// Hints the CPU to start loading the first group a lookup for `hash` will probe.
+    #[inline(always)]
+    fn prefetch<V, K: Key>(&self, arena: &Arena<V, K>, hash: u64) {
+        if self.capacity == 0 { return; }
+
+        // The first probe is at h1, which does not depend on the step size.
+        let g = (mix(hash ^ self.seed) as usize) % self.groups;
+        let start = self.base + g * GROUP_WIDTH;
+        prefetch_read(arena.ctrl[start..].as_ptr());
+        prefetch_read(arena.keys[start..].as_ptr());
+    }
+
+    /// Free slots in the first group of the probe sequence `second` picks.
+    fn free_at_start<V, K: Key>(&self, arena: &Arena<V, K>, hash: u64, second: bool) -> usize {
+        let (h1, h2) = self.probe_params(hash, second);
+        let group = Group::load(&arena.ctrl[self.probe_group(h1, h2, 0)..]);
+        group.match_empty().count() + group.match_deleted().count()
+    }
+
+    /// Arena index of the first slot of the group visited by the `i`-th probe.
+    #[inline(always)]
+    fn probe_group(&self, h1: usize, h2: usize, i: usize) -> usize {
+        // Safe Double Hashing
+        self.base + (h1.wrapping_add(i.wrapping_mul(h2))) % self.groups * GROUP_WIDTH
+    }
+
+    /// Position along the probe sequence of `hash` of the group at `start`,
+    /// if it is among the first `bound` probes.
+    fn probe_position(&self, hash: u64, start: usize, bound: usize) -> Option<usize> {
+        let (h1, h2) = self.probe_params(hash, false);
+        (0..bound.min(self.groups)).find(|&i| self.probe_group(h1, h2, i) == start)
+    }
+
+    /// Returns the first slot among the fingerprint `matches` of the group at
+    /// `start` that holds `key`.
+    #[inline(always)]
+    fn matching_slot<V, K: Key>(arena: &Arena<V, K>, start: usize, matches: BitMask, key: K) -> Option<usize> {
+        matches
+            .map(|bit| start + bit)
+            .find(|&idx| arena.keys[idx] == key)
+    }
+
+    /// Walks the probe sequence looking for either the key itself or a free slot.
+    /// Tombstones are remembered but probing continues past them, so an existing
+    /// copy of the key further along the sequence is updated rather than duplicated.
+    /// Returns the arena index (if one was found) and the number of probes used.
+    fn find_slot<V, K: Key>(
+        &self,
+        arena: &Arena<V, K>,
+        key: K,
+        hash: u64,
+        limit: usize,
+        force: bool,
+        second: bool,
+    ) -> (Option<usize>, usize) {
+        if self.capacity == 0 { return (None, 0); }
+
+        let (h1, h2) = self.probe_params(hash, second);
+        let tag = fingerprint(hash);
+        let loop_limit = if force { self.groups } else { limit.min(self.groups) };
+        let mut first_deleted = None;
+
+        for i in 0..loop_limit {
+            let start = self.probe_group(h1, h2, i);
+            let group = Group::load(&arena.ctrl[start..]);
+
+            if let Some(idx) = Self::matching_slot(arena, start, group.match_byte(tag), key) {
+                return (Some(idx), i + 1);
+            }
+            if first_deleted.is_none() {
+                first_deleted = group.match_deleted().lowest().map(|bit| start + bit);
+            }
+            if let Some(bit) = group.match_empty().lowest() {
+                return (Some(first_deleted.unwrap_or(start + bit)), i + 1);
+            }
+        }
+        (first_deleted, loop_limit)
+    }
+
+    /// The slot a stored entry with base hash `hash` can move to within the
+    /// first `limit` groups of its probe sequence: the free slot `find_slot`
+    /// would pick for a new key there, so lookups keep finding the entry.
+    /// Returns the arena index (if one was found) and the number of probes used.
+    fn free_slot<V, K: Key>(&self, arena: &Arena<V, K>, hash: u64, limit: usize) -> (Option<usize>, usize) {
+        let (h1, h2) = self.probe_params(hash, false);
+        let loop_limit = limit.min(self.groups);
+        let mut first_deleted = None;
+
+        for i in 0..loop_limit {
+            let start = self.probe_group(h1, h2, i);
+            let group = Group::load(&arena.ctrl[start..]);
+            if first_deleted.is_none() {
+                first_deleted = group.match_deleted().lowest().map(|bit| start + bit);
+            }
+            if let Some(bit) = group.match_empty().lowest() {
+                return (Some(first_deleted.unwrap_or(start + bit)), i + 1);
+            }
+        }
+        (first_deleted, loop_limit)
+    }
+
+    /// Moves the entry at arena index `from` to the free slot `to`, for a
+    /// cuckoo kick, leaving a tombstone behind.
+    fn relocate<V, K: Key>(&mut self, arena: &mut Arena<V, K>, from: usize, to: usize) {
+        if arena.ctrl[to] == DELETED {
+            self.tombstones -= 1;
+        }
+        arena.ctrl[to] = arena.ctrl[from];
+        arena.keys[to] = arena.keys[from];
+        // SAFETY: `from` is occupied, and marking it deleted below keeps its
+        // value from being read or dropped again.
+        let value = unsafe { arena.values[from].assume_init_read() };
+        arena.values[to].write(value);
+        arena.ctrl[from] = DELETED;
+        self.tombstones += 1;
+        // The entry may now sit past the sealed bound.
+        self.probe_limit = self.groups;
+    }
+
+    /// Stores the entry at a slot previously returned by `find_slot`. Returns
+    /// false if it replaced the value of a stored key.
+    fn place<V, K: Key>(&mut self, arena: &mut Arena<V, K>, idx: usize, key: K, hash: u64, value: V) -> bool {
+        let added = match arena.ctrl[idx] {
+            EMPTY => {
+                self.count += 1;
+                true
+            }
+            DELETED => {
+                self.tombstones -= 1;
+                self.count += 1;
+                true
+            }
+            _ => {
+                // SAFETY: a fingerprint in `ctrl` means the value is initialized.
+                unsafe { arena.values[idx].assume_init_drop() };
+                false
+            }
+        };
+        // The new entry may sit past the sealed bound.
+        self.probe_limit = self.groups;
+        arena.ctrl[idx] = fingerprint(hash);
+        arena.keys[idx] = key;
+        arena.values[idx].write(value);
+        added
+    }
+
+    /// Locates the slot holding `key` along the probe sequence `second` picks,
+    /// returning its arena index and the probes used.
+    fn find<V, K: Key>(&self, arena: &Arena<V, K>, key: K, hash: u64, second: bool) -> (Option<usize>, usize) {
+        if self.capacity == 0 { return (None, 0); }
+
+        let (h1, h2) = self.probe_params(hash, second);
+        let tag = fingerprint(hash);
+        
+        for i in 0..self.probe_limit {
+            let start = self.probe_group(h1, h2, i);
+            let group = Group::load(&arena.ctrl[start..]);
+
+            if let Some(idx) = Self::matching_slot(arena, start, group.match_byte(tag), key) {
+                return (Some(idx), i + 1);
+            }
+            if group.match_empty().any() {
+                return (None, i + 1);
+            }
+        }
+        (None, self.probe_limit)
+    }
+
+    /// Turns the slot holding `key` along the probe sequence `second` picks
+    /// into a tombstone, returning the removed value.
+    fn remove<V, K: Key>(&mut self, arena: &mut Arena<V, K>, key: K, hash: u64, second: bool) -> Option<V> {
+        match self.find(arena, key, hash, second) {
+            (Some(idx), _) => {
+                self.count -= 1;
+                self.tombstones += 1;
+                arena.ctrl[idx] = DELETED;
+                // SAFETY: `find` only returns occupied slots, and the slot is now
+                // marked deleted so the value is not read or dropped again.
+                Some(unsafe { arena.values[idx].assume_init_read() })
+            }
+            (None, _) => None,
+        }
+    }
+
+    /// Iterates over the occupied slots as `(key, value)` pairs.
+    fn entries<'a, V, K: Key>(&self, arena: &'a Arena<V, K>) -> impl Iterator<Item = (K, &'a V)> {
+        (self.base..self.base + self.capacity)
+            .filter(move |&idx| is_full(arena.ctrl[idx]))
+            .map(move |idx| {
+                // SAFETY: the slot is occupied.
+                (arena.keys[idx], unsafe { arena.values[idx].assume_init_ref() })
+            })
+    }
+}
+
+/// Issues a read prefetch for the cache line holding `ptr`. A no-op on targets
+/// without a stable prefetch instruction.
+#[inline(always)]
+pub(crate) fn prefetch_read<T>(ptr: *const T) {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse"))]
+    // SAFETY: prefetching is a hint and never faults, even for invalid addresses.
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch(ptr as *const i8, _MM_HINT_T0);
+    }
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: as above; `prfm` only hints the memory system.
+    unsafe {
+        std::arch::asm!("prfm pldl1keep, [{0}]", in(reg) ptr, options(nostack, readonly, preserves_flags));
+    }
+    #[cfg(not(any(all(target_arch = "x86_64", target_feature = "sse"), target_arch = "aarch64")))]
+    let _ = ptr;
+}
+
+/// Number of bins of a probe-length histogram; the last bin also counts
+/// operations that took more probes.
+pub(crate) const HISTOGRAM_BINS: usize = 32;
+
+/// Operations counted by the number of probes they used.
+pub(crate) struct Histogram {
+    bins: [AtomicU64; HISTOGRAM_BINS],
+    /// Probes used by all operations together, and by the longest one.
+    total: AtomicU64,
+    longest: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            bins: std::array::from_fn(|_| AtomicU64::new(0)),
+            total: AtomicU64::new(0),
+            longest: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, probes: usize) {
+        self.bins[probes.min(HISTOGRAM_BINS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(probes as u64, Ordering::Relaxed);
+        self.longest.fetch_max(probes as u64, Ordering::Relaxed);
+    }
+
+    /// Bin `i` holds the number of operations that used `i` probes.
+    pub(crate) fn counts(&self) -> Vec<u64> {
+        self.bins.iter().map(|bin| bin.load(Ordering::Relaxed)).collect()
+    }
+
+    /// Number of operations recorded.
+    pub(crate) fn len(&self) -> u64 {
+        self.bins.iter().map(|bin| bin.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Mean probes per operation, or 0 if none were recorded.
+    pub(crate) fn mean(&self) -> f64 {
+        match self.len() {
+            0 => 0.0,
+            len => self.total() as f64 / len as f64,
+        }
+    }
+
+    /// Probes used by all operations together.
+    pub(crate) fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Probes used by the longest operation.
+    pub(crate) fn longest(&self) -> u64 {
+        self.longest.load(Ordering::Relaxed)
+    }
+}
+
+/// Probe-length histograms of a map's inserts and lookups.
+///
+/// The overall histograms count every probe an operation made, across all
+/// subarrays; a lookup the Bloom filter rejected counts as 0 probes. The
+/// per-subarray histograms count the inserts placed in that subarray and the
+/// lookups that found their key there, by the probes spent in that subarray.
+/// Only successful inserts are recorded.
+pub(crate) struct ProbeHistograms {
+    pub(crate) inserts: Histogram,
+    pub(crate) gets: Histogram,
+    /// `(inserts, gets)` for each subarray.
+    pub(crate) subarrays: Vec<(Histogram, Histogram)>,
+}
+
+impl ProbeHistograms {
+    fn new(subarrays: usize) -> Self {
+        ProbeHistograms {
+            inserts: Histogram::new(),
+            gets: Histogram::new(),
+            subarrays: (0..subarrays).map(|_| (Histogram::new(), Histogram::new())).collect(),
+        }
+    }
+}
+
+/// Counts of the operations applied to a map.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct OpCounts {
+    pub(crate) gets: u64,
+    pub(crate) hits: u64,
+    /// Successful inserts, including overwrites.
+    pub(crate) inserts: u64,
+    /// Inserts that replaced the value of a stored key in its slot.
+    pub(crate) overwrites: u64,
+    pub(crate) failed_inserts: u64,
+    /// Entries moved to another slot to make room for an insert, by cuckoo
+    /// kicks or Robin Hood displacement.
+    pub(crate) relocations: u64,
+}
+
+/// What an insert does in a subarray, per the paper's case analysis.
+#[derive(Clone, Copy)]
+pub(crate) enum InsertCase {
+    /// Case 1: the subarray has room and so does the next one; probe at most
+    /// this many groups before moving on.
+    Probe(usize),
+    /// Case 2: the subarray is nearly full; skip it.
+    Skip,
+    /// Case 3: the next subarray is nearly full, or there is none; scan this
+    /// one completely.
+    Scan,
+}
+
+impl InsertCase {
+    /// Number of the case in the paper.
+    pub(crate) fn number(self) -> u8 {
+        match self {
+            InsertCase::Probe(_) => 1,
+            InsertCase::Skip => 2,
+            InsertCase::Scan => 3,
+        }
+    }
+
+    /// Groups an insert in this case may probe in a subarray of `groups` groups.
+    pub(crate) fn probe_limit(self, groups: usize) -> usize {
+        match self {
+            InsertCase::Probe(limit) => limit.min(groups),
+            InsertCase::Skip => 0,
+            InsertCase::Scan => groups,
+        }
+    }
+}
+
+/// One subarray an insert visited, as recorded while tracing.
+pub(crate) struct TraceStep {
+    pub(crate) subarray: usize,
+    pub(crate) case: InsertCase,
+    /// Groups the insert could probe there.
+    pub(crate) limit: usize,
+    pub(crate) probes: usize,
+}
+
+/// The path one insert took through the subarrays. The key was placed in the
+/// subarray of the last step if `inserted` is set.
+pub(crate) struct InsertTrace {
+    pub(crate) key: u64,
+    pub(crate) steps: Vec<TraceStep>,
+    pub(crate) inserted: bool,
+}
+
+/// A detailed description of one subarray.
+pub(crate) struct SubArrayStats {
+    pub(crate) capacity: usize,
+    pub(crate) count: usize,
+    pub(crate) tombstones: usize,
+    pub(crate) load_factor: f64,
+    pub(crate) epsilon: f64,
+    /// Groups the next insert reaching this subarray may probe.
+    pub(crate) insert_probe_limit: usize,
+    /// Groups a lookup probes at most.
+    pub(crate) lookup_probe_limit: usize,
+    pub(crate) insert_case: InsertCase,
+    /// Inserts that fell through to the next subarray, by case: those that
+    /// exhausted a nonzero probe budget, skipped the subarray, or scanned it
+    /// without finding a free slot.
+    pub(crate) fallthroughs: [u64; 3],
+}
+
+/// Bytes of slot storage taken by one subarray.
+pub(crate) struct SubArrayMemory {
+    /// Slots, including the padding of the last group.
+    pub(crate) slots: usize,
+    pub(crate) ctrl: usize,
+    pub(crate) keys: usize,
+    pub(crate) values: usize,
+}
+
+/// Where `ElasticMap::choose_slot` puts a key.
+struct Placement {
+    subarray: usize,
+    /// Arena index of the slot.
+    slot: usize,
+    /// Probes used in `subarray`, and in all subarrays visited.
+    probes: usize,
+    total_probes: usize,
+    /// Subarrays whose probe budget ran out before the key was placed.
+    fallthroughs: usize,
+    /// Set if the entry in `slot` must first be kicked to this arena index.
+    kick: Option<usize>,
+}
+
+/// Why an insertion could not be completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertError {
+    /// The last subarray was scanned completely without finding a free slot.
+    Full,
+    /// Every subarray was skipped or exhausted its probe budget.
+    NoSlot,
+}
+
+impl std::fmt::Display for InsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InsertError::Full => "hash table is completely full",
+            InsertError::NoSlot => "every subarray turned the key away",
+        })
+    }
+}
+
+impl std::error::Error for InsertError {}
+
+/// What `ElasticMap::in_region` finds in the slots of its region.
+#[derive(Clone, Copy)]
+pub(crate) enum Contents<'a> {
+    /// Arbitrary bytes: the map starts out empty.
+    Empty,
+    /// The entries of an earlier map; occupancy is recounted from the control bytes.
+    Recount,
+    /// As `Recount`, with every subarray's `(count, tombstones)` already known.
+    Counted(&'a [(usize, usize)]),
+}
+
+/// When a map gives up capacity after entries are removed: once they fill
+/// less than `threshold` of it, it shrinks until they fill twice that, but to
+/// no less than `min_capacity` slots.
+#[derive(Clone, Copy)]
+pub(crate) struct ShrinkPolicy {
+    pub(crate) threshold: f64,
+    pub(crate) min_capacity: usize,
+}
+
+impl ShrinkPolicy {
+    /// The capacity a map holding `len` entries shrinks to.
+    fn capacity_for(self, len: usize) -> usize {
+        let capacity = (len as f64 / (2.0 * self.threshold)).ceil() as usize;
+        capacity.max(self.min_capacity).max(GROUP_WIDTH)
+    }
+}
+
+/// When a map takes on capacity ahead of an insert: once the insert would
+/// leave its entries filling more than `trigger` of it, or the insertion
+/// algorithm turns a key away, it grows by `factor`, but never past
+/// `max_capacity` slots.
+#[derive(Clone, Copy)]
+pub(crate) struct GrowthPolicy {
+    pub(crate) factor: f64,
+    pub(crate) trigger: f64,
+    pub(crate) max_capacity: Option<usize>,
+}
+
+/// An entry Robin Hood placement took out of its slot, on its way to another.
+struct Displaced<V, K> {
+    key: K,
+    hash: u64,
+    value: V,
+    /// Its number in the insertion order, for ordered maps.
+    seq: u64,
+    /// Group it was taken from, as a position along its probe sequence.
+    position: usize,
+}
+
+/// Insertion order of a map's entries: `seq[i]` numbers the entry in arena
+/// slot `i` by when its key was first inserted. Updating a stored key's value
+/// keeps its number.
+struct Order {
+    seq: Vec<u64>,
+    next: u64,
+}
+
+/// Elastic hashing over keys of type `K`, `u64` unless given, with values
+/// of type `V`.
+///
+/// The table is split into geometrically shrinking subarrays; `insert` implements
+/// the paper's three-case analysis to decide which subarray receives a key.
+pub(crate) struct ElasticMap<V, K = u64> {
+    subarrays: Vec<SubArray>,
+    arena: Arena<V, K>,
+    total_capacity: usize,
+    delta: f64,
+    c_param: f64,
+    /// Optional filter over the stored keys, consulted before probing.
+    filter: Option<BloomFilter>,
+    /// Keys removed since the filter was last rebuilt; their bits are still set.
+    stale: usize,
+    /// Set while probe lengths are being recorded.
+    histograms: Option<Box<ProbeHistograms>>,
+    /// Insert counts; lookups are counted in `gets` and `hits`, which shared
+    /// references update.
+    counts: OpCounts,
+    gets: AtomicU64,
+    hits: AtomicU64,
+    /// Set while inserts are traced.
+    traces: Option<Vec<InsertTrace>>,
+    /// Set for maps that keep insertion order.
+    order: Option<Order>,
+    /// Bumped whenever entries are added, removed or moved, so iterators can
+    /// tell that the map changed under them.
+    generation: u64,
+    /// Set while the probe budget `c_param` adapts to the workload.
+    tuner: Option<Tuner>,
+    /// Set for maps that shrink once most of their entries are removed.
+    shrink: Option<ShrinkPolicy>,
+    /// Set for maps that grow as entries are inserted.
+    growth: Option<GrowthPolicy>,
+    /// Set for maps that place keys by two choices; see `set_two_choices`.
+    two_choices: bool,
+    /// Entries an insert may try to move aside in a subarray whose probe
+    /// budget it exhausted; see `set_cuckoo_kicks`.
+    cuckoo_kicks: usize,
+    /// Set for maps that place keys by Robin Hood displacement; see `set_robin_hood`.
+    robin_hood: bool,
+}
+
+impl<V, K: Key> ElasticMap<V, K> {
+    /// Capacities of the subarrays `capacity` slots are split into.
+    fn subarray_sizes(capacity: usize) -> Vec<usize> {
+        let mut sizes = Vec::new();
+        let mut remaining = capacity;
+        
+        while remaining > 0 {
+            let size = if remaining < 16 { 
+                remaining 
+            } else { 
+                (remaining as f64 / 2.0).ceil() as usize 
+            };
+            
+            sizes.push(size);
+            remaining = remaining.saturating_sub(size);
+        }
+        sizes
+    }
+
+    /// Number of arena slots, padding included, of a map with `capacity` slots.
+    pub(crate) fn arena_slots(capacity: usize) -> usize {
+        Self::subarray_sizes(capacity).into_iter().map(SubArray::padded_len).sum()
+    }
+
+    /// Lays out `capacity` slots across the subarrays. `delta` must lie in (0, 1).
+    pub(crate) fn new(capacity: usize, delta: f64) -> Self {
+        let arena = Arena::new(Self::arena_slots(capacity));
+        Self::with_arena(arena, capacity, delta, Contents::Empty)
+    }
+
+    fn with_arena(mut arena: Arena<V, K>, capacity: usize, delta: f64, contents: Contents<'_>) -> Self {
+        let sizes = Self::subarray_sizes(capacity);
+        let mut subarrays = Vec::with_capacity(sizes.len());
+        let mut base = 0;
+        for (i, size) in sizes.into_iter().enumerate() {
+            subarrays.push(match contents {
+                Contents::Empty => SubArray::new(&mut arena, i, base, size),
+                Contents::Recount => SubArray::attach(&arena, i, base, size, None),
+                Contents::Counted(counts) => SubArray::attach(&arena, i, base, size, Some(counts[i])),
+            });
+            base += SubArray::padded_len(size);
+        }
+
+        ElasticMap {
+            subarrays,
+            arena,
+            total_capacity: capacity,
+            delta,
+            c_param: DEFAULT_BUDGET,
+            filter: None,
+            stale: 0,
+            histograms: None,
+            counts: OpCounts::default(),
+            gets: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            traces: None,
+            order: None,
+            generation: 0,
+            tuner: None,
+            shrink: None,
+            growth: None,
+            two_choices: false,
+            cuckoo_kicks: 0,
+            robin_hood: false,
+        }
+    }
+
+    /// Inserts or updates `key`, returning the number of probes used.
+    pub(crate) fn insert(&mut self, key: K, value: V) -> Result<usize, InsertError> {
+        self.insert_hashed(key, hash_key(key.to_u64()), value)
+    }
+
+    /// Which case of the paper's analysis an insert reaching subarray `i` is in.
+    fn insert_case(&self, i: usize) -> InsertCase {
+        let has_next = i < self.subarrays.len() - 1;
+
+        let eps1 = self.subarrays[i].epsilon();
+        let eps2 = if has_next { self.subarrays[i+1].epsilon() } else { 0.0 };
+
+        let safe_eps = if eps1 < 1e-9 { 1e-9 } else { eps1 };
+        let log_term = (1.0 / safe_eps).log2();
+        let limit = (self.c_param * log_term.powi(2)).ceil() as usize;
+
+        let is_case_1 = eps1 > (self.delta / 2.0) && eps2 > 0.25;
+        let is_case_2 = eps1 <= (self.delta / 2.0);
+        let is_case_3 = eps2 <= 0.25; 
+
+        if is_case_1 {
+            InsertCase::Probe(limit)
+        } else if is_case_2 {
+            InsertCase::Skip
+        } else if is_case_3 || !has_next {
+            InsertCase::Scan
+        } else {
+            InsertCase::Probe(limit)
+        }
+    }
+
+    /// `insert` for a key whose base hash was already computed with `hash::hash_key`.
+    pub(crate) fn insert_hashed(&mut self, key: K, hash: u64, value: V) -> Result<usize, InsertError> {
+        self.insert_placed(key, hash, value).map(|(probes, _)| probes)
+    }
+
+    /// `insert` returning the slot the entry went into, for `value_at_mut`.
+    pub(crate) fn insert_slot(&mut self, key: K, value: V) -> Result<usize, InsertError> {
+        self.insert_placed(key, hash_key(key.to_u64()), value).map(|(_, slot)| slot)
+    }
+
+    /// `insert_hashed` returning both the number of probes and the slot used.
+    fn insert_placed(&mut self, key: K, hash: u64, value: V) -> Result<(usize, usize), InsertError> {
+        if self.traces.is_none() {
+            return self.insert_traced(key, hash, value, None);
+        }
+        let mut steps = Vec::new();
+        let result = self.insert_traced(key, hash, value, Some(&mut steps));
+        if let Some(traces) = &mut self.traces {
+            traces.push(InsertTrace { key: key.to_u64(), steps, inserted: result.is_ok() });
+        }
+        result
+    }
+
+    /// `insert_hashed` that appends the subarrays it visits to `steps`, if given.
+    fn insert_traced(
+        &mut self,
+        key: K,
+        hash: u64,
+        value: V,
+        mut steps: Option<&mut Vec<TraceStep>>,
+    ) -> Result<(usize, usize), InsertError> {
+        if self.growth.is_some() && (self.len() + 1) as f64 > self.growth_trigger() * self.total_capacity as f64 {
+            self.grow(self.len() + 1);
+        }
+        let placement = match self.choose_slot(key, hash, steps.as_deref_mut()) {
+            Ok(placement) => placement,
+            // A key stored where the insertion algorithm no longer finds room,
+            // such as a subarray it now skips, is updated there instead.
+            Err(err) => match self.may_contain(hash).then(|| self.locate(key, hash)).flatten() {
+                Some((subarray, slot)) => Placement { subarray, slot, probes: 0, total_probes: 0, fallthroughs: 0, kick: None },
+                None if self.grow(self.len() + 1) => return self.insert_traced(key, hash, value, steps),
+                None => {
+                    self.count_fallthroughs(self.subarrays.len());
+                    self.counts.failed_inserts += 1;
+                    let load = self.load_factor();
+                    if let Some(tuner) = &mut self.tuner {
+                        self.c_param = tuner.failed(self.c_param, load);
+                    }
+                    return Err(err);
+                }
+            },
+        };
+        let Placement { subarray, slot, probes, total_probes, fallthroughs, kick } = placement;
+        self.count_fallthroughs(subarray);
+        if let Some(to) = kick {
+            self.kick(subarray, slot, to);
+        }
+        let (subarray, slot, added) = self.store(subarray, slot, key, hash, value);
+        self.counts.inserts += 1;
+        self.counts.overwrites += u64::from(!added);
+        if let Some(histograms) = &self.histograms {
+            histograms.inserts.record(total_probes);
+            histograms.subarrays[subarray].0.record(probes);
+        }
+        if let Some(tuner) = &mut self.tuner {
+            let (subarrays, capacity) = (&self.subarrays, self.total_capacity);
+            let load = || subarrays.iter().map(|sub| sub.count).sum::<usize>() as f64 / capacity as f64;
+            if let Some(budget) = tuner.inserted(fallthroughs, self.c_param, load) {
+                self.c_param = budget;
+            }
+        }
+        Ok((total_probes, slot))
+    }
+
+    /// Counts an insert that `choose_slot` moved through the first `passed`
+    /// subarrays without placing it, by the case it was in in each. The map
+    /// has not changed since, so the cases are the ones the insert saw. An
+    /// empty subarray has a budget of 0, and passing it over is not counted.
+    fn count_fallthroughs(&mut self, passed: usize) {
+        for i in 0..passed {
+            let slot = match self.insert_case(i) {
+                InsertCase::Probe(0) => continue,
+                InsertCase::Probe(_) => 0,
+                InsertCase::Skip => 1,
+                InsertCase::Scan => 2,
+            };
+            self.subarrays[i].fallthroughs[slot] += 1;
+        }
+    }
+
+    /// Stores an entry at `slot` of `subarray`, as chosen for it by the
+    /// insertion algorithm or `forced_slot`, and keeps the order, filter and
+    /// generation up to date. Returns the subarray and slot the entry went
+    /// into and whether it took a slot of its own rather than replacing a value.
+    fn store(&mut self, mut subarray: usize, mut slot: usize, key: K, hash: u64, value: V) -> (usize, usize, bool) {
+        // The insertion algorithm can pick a free slot while the key is stored
+        // in a subarray it did not fully probe, or along the probe sequence of
+        // a two-choice map it did not take. That copy is updated instead, so
+        // keys stay unique and an ordered map's keep their position.
+        if !is_full(self.arena.ctrl[slot]) && self.may_contain(hash) {
+            if let Some(found) = self.locate(key, hash) {
+                (subarray, slot) = found;
+            }
+        }
+        let added = if self.robin_hood && !is_full(self.arena.ctrl[slot]) {
+            slot = self.place_robin_hood(subarray, slot, key, hash, value);
+            true
+        } else {
+            self.subarrays[subarray].place(&mut self.arena, slot, key, hash, value)
+        };
+        self.generation += u64::from(added);
+        if let (true, Some(order)) = (added, &mut self.order) {
+            order.seq[slot] = order.next;
+            order.next += 1;
+        }
+        if let Some(filter) = &mut self.filter {
+            filter.insert(hash);
+        }
+        (subarray, slot, added)
+    }
+
+    /// The subarray and slot the insertion algorithm picks for `key`, or, if
+    /// it turns the key away, the first free slot of any subarray, carrying
+    /// out any cuckoo kick that makes room for it. Panics if the map has no
+    /// free slot at all.
+    fn forced_slot(&mut self, key: K, hash: u64) -> (usize, usize) {
+        match self.choose_slot(key, hash, None) {
+            Ok(Placement { subarray, slot, kick, .. }) => {
+                if let Some(to) = kick {
+                    self.kick(subarray, slot, to);
+                }
+                (subarray, slot)
+            }
+            Err(_) => self.subarrays.iter().enumerate()
+                .find_map(|(i, sub)| sub.find_slot(&self.arena, key, hash, 0, true, false).0.map(|slot| (i, slot)))
+                .expect("the map has a free slot"),
+        }
+    }
+
+    /// Puts back an entry the map held before, such as one a rolled back
+    /// transaction removed: like `insert`, but taking any free slot if the
+    /// insertion algorithm turns the key away, and not counted as an insert.
+    /// The map must have room for the entry.
+    pub(crate) fn restore(&mut self, key: K, value: V) {
+        let hash = hash_key(key.to_u64());
+        let (subarray, slot) = self.forced_slot(key, hash);
+        self.store(subarray, slot, key, hash, value);
+    }
+
+    /// Runs the insertion algorithm for `key` up to the point of storing it:
+    /// finds the slot it goes to, without changing the map.
+    fn choose_slot(
+        &self,
+        key: K,
+        hash: u64,
+        mut steps: Option<&mut Vec<TraceStep>>,
+    ) -> Result<Placement, InsertError> {
+        let n_arrays = self.subarrays.len();
+        let mut total_probes = 0;
+        let mut fallthroughs = 0;
+
+        for i in 0..n_arrays {
+            let has_next = i < n_arrays - 1;
+            let sub = &self.subarrays[i];
+            let case = self.insert_case(i);
+            let (mut slot, mut probes) = match case {
+                InsertCase::Probe(limit) => self.find_free(sub, key, hash, limit, false),
+                InsertCase::Skip => (None, 0),
+                InsertCase::Scan => self.find_free(sub, key, hash, 0, true),
+            };
+            let mut kick = None;
+            if let (None, InsertCase::Probe(limit)) = (slot, case) {
+                if self.cuckoo_kicks > 0 {
+                    let (found, more) = self.find_kick(sub, hash, limit);
+                    probes += more;
+                    if let Some((from, to)) = found {
+                        (slot, kick) = (Some(from), Some(to));
+                    }
+                }
+            }
+            if let Some(steps) = steps.as_deref_mut() {
+                steps.push(TraceStep { subarray: i, case, limit: case.probe_limit(sub.groups), probes });
+            }
+            if slot.is_none() && !has_next && matches!(case, InsertCase::Scan) {
+                return Err(InsertError::Full);
+            }
+
+            total_probes += probes;
+
+            if let Some(slot) = slot {
+                return Ok(Placement { subarray: i, slot, probes, total_probes, fallthroughs, kick });
+            }
+            // An empty subarray has a budget of 0 whatever the constant, so
+            // passing it over says nothing about the budget.
+            fallthroughs += usize::from(matches!(case, InsertCase::Probe(limit) if limit > 0));
+        }
+
+        Err(InsertError::NoSlot)
+    }
+
+    /// `SubArray::find_slot` for `sub`. Two-choice maps probe the sequence
+    /// whose first group has more free slots, and the other one if that fails.
+    fn find_free(&self, sub: &SubArray, key: K, hash: u64, limit: usize, force: bool) -> (Option<usize>, usize) {
+        if !self.two_choices {
+            return sub.find_slot(&self.arena, key, hash, limit, force, false);
+        }
+        let second = sub.capacity > 0
+            && sub.free_at_start(&self.arena, hash, true) > sub.free_at_start(&self.arena, hash, false);
+        let (slot, probes) = sub.find_slot(&self.arena, key, hash, limit, force, second);
+        if slot.is_some() {
+            return (slot, probes);
+        }
+        let (slot, more) = sub.find_slot(&self.arena, key, hash, limit, force, !second);
+        (slot, probes + more)
+    }
+
+    /// Looks for a cuckoo kick that frees a slot for a key with base hash
+    /// `hash` in `sub`, whose first `limit` probe groups are full: an entry
+    /// there, among the first `cuckoo_kicks` tried, that can move to a free
+    /// slot within the first `limit` groups of its own probe sequence.
+    /// Returns the slot freed and the one the entry moves to, if a kick was
+    /// found, and the number of probes used.
+    fn find_kick(&self, sub: &SubArray, hash: u64, limit: usize) -> (Option<(usize, usize)>, usize) {
+        let (h1, h2) = sub.probe_params(hash, false);
+        let mut probes = 0;
+        let mut tried = 0;
+        for i in 0..limit.min(sub.groups) {
+            let start = sub.probe_group(h1, h2, i);
+            for from in start..start + GROUP_WIDTH {
+                if !is_full(self.arena.ctrl[from]) {
+                    continue;
+                }
+                if tried == self.cuckoo_kicks {
+                    return (None, probes);
+                }
+                tried += 1;
+                let (to, used) = sub.free_slot(&self.arena, hash_key(self.arena.keys[from].to_u64()), limit);
+                probes += used;
+                if let Some(to) = to {
+                    return (Some((from, to)), probes);
+                }
+            }
+        }
+        (None, probes)
+    }
+
+    /// Carries out a cuckoo kick found by `find_kick` in `subarray`, moving
+    /// the entry at arena index `from` to the free slot `to`.
+    fn kick(&mut self, subarray: usize, from: usize, to: usize) {
+        self.subarrays[subarray].relocate(&mut self.arena, from, to);
+        if let Some(order) = &mut self.order {
+            order.seq[to] = order.seq[from];
+        }
+        self.counts.relocations += 1;
+        self.generation += 1;
+    }
+
+    /// Stores a new key headed for the free slot `free` of `subarray` by
+    /// Robin Hood displacement: walking the key's probe sequence, the key
+    /// takes the slot of the first entry it meets that sits closer to the
+    /// start of its own sequence than the key is, picking the closest one of
+    /// a group, and the displaced entry moves on along its sequence the same
+    /// way until one lands in a free slot. Evening out how far entries sit
+    /// from the start of their sequences bounds the spread of lookup probes.
+    /// Returns the slot the key went into; the caller numbers it in the order.
+    fn place_robin_hood(&mut self, subarray: usize, free: usize, key: K, hash: u64, value: V) -> usize {
+        let sub = &self.subarrays[subarray];
+        let target = sub.base + (free - sub.base) / GROUP_WIDTH * GROUP_WIDTH;
+        let (h1, h2) = sub.probe_params(hash, false);
+        let found = (0..sub.groups)
+            .map(|i| (sub.probe_group(h1, h2, i), i))
+            .take_while(|&(start, _)| start != target)
+            .find_map(|(start, i)| self.richer(sub, start, i, None));
+        let Some((home, position)) = found else {
+            self.subarrays[subarray].place(&mut self.arena, free, key, hash, value);
+            return free;
+        };
+        let mut carried = self.swap_out(home, key, hash, value, position);
+        for _ in 1..MAX_DISPLACEMENTS {
+            let Some((slot, position)) = self.next_slot(subarray, &carried, Some(home)) else { break };
+            if !is_full(self.arena.ctrl[slot]) {
+                return self.settle(subarray, slot, carried, home);
+            }
+            let Displaced { key, hash, value, seq, .. } = carried;
+            carried = self.swap_out(slot, key, hash, value, position);
+            if let Some(order) = &mut self.order {
+                order.seq[slot] = seq;
+            }
+        }
+        let (last, _) = self.next_slot(subarray, &carried, None).expect("the subarray has a free slot");
+        self.settle(subarray, last, carried, home)
+    }
+
+    /// The entry of the group at `start` sitting closest to the start of its
+    /// own probe sequence, if that is before `position`, with its position.
+    /// The entry in slot `keep`, if given, is passed over.
+    fn richer(&self, sub: &SubArray, start: usize, position: usize, keep: Option<usize>) -> Option<(usize, usize)> {
+        (start..start + GROUP_WIDTH)
+            .filter(|&idx| is_full(self.arena.ctrl[idx]) && Some(idx) != keep)
+            .filter_map(|idx| {
+                let hash = hash_key(self.arena.keys[idx].to_u64());
+                sub.probe_position(hash, start, position).map(|found| (idx, found))
+            })
+            .min_by_key(|&(_, found)| found)
+    }
+
+    /// Where displaced entry `carried` goes next along its probe sequence in
+    /// `subarray`: the first free slot, or, if that comes later, the slot of
+    /// an entry it displaces in turn, with that entry's position. Entries are
+    /// only displaced if `home`, the slot of the key being inserted, which
+    /// stays put, is given. The sequence wraps around, so every group is visited.
+    fn next_slot(&self, subarray: usize, carried: &Displaced<V, K>, home: Option<usize>) -> Option<(usize, usize)> {
+        let sub = &self.subarrays[subarray];
+        let (h1, h2) = sub.probe_params(carried.hash, false);
+        (carried.position + 1..carried.position + 1 + sub.groups).find_map(|i| {
+            let start = sub.probe_group(h1, h2, i % sub.groups);
+            let group = Group::load(&self.arena.ctrl[start..]);
+            if let Some(bit) = group.match_deleted().lowest().or_else(|| group.match_empty().lowest()) {
+                return Some((start + bit, i));
+            }
+            home.and_then(|home| self.richer(sub, start, i, Some(home)))
+        })
+    }
+
+    /// Puts an entry into the occupied `slot`, returning the entry it held,
+    /// which sat `position` probes along its sequence.
+    fn swap_out(&mut self, slot: usize, key: K, hash: u64, value: V, position: usize) -> Displaced<V, K> {
+        let old_key = std::mem::replace(&mut self.arena.keys[slot], key);
+        // SAFETY: `slot` is occupied, and its value is replaced right away.
+        let old_value = unsafe { self.arena.values[slot].assume_init_read() };
+        self.arena.values[slot].write(value);
+        self.arena.ctrl[slot] = fingerprint(hash);
+        self.counts.relocations += 1;
+        Displaced {
+            key: old_key,
+            hash: hash_key(old_key.to_u64()),
+            value: old_value,
+            seq: self.order.as_ref().map_or(0, |order| order.seq[slot]),
+            position,
+        }
+    }
+
+    /// Stores displaced entry `carried` in the free slot `free` of
+    /// `subarray`, ending a Robin Hood insert that put its new key in `home`,
+    /// which it returns.
+    fn settle(&mut self, subarray: usize, free: usize, carried: Displaced<V, K>, home: usize) -> usize {
+        let Displaced { key, hash, value, seq, .. } = carried;
+        self.subarrays[subarray].place(&mut self.arena, free, key, hash, value);
+        if let Some(order) = &mut self.order {
+            order.seq[free] = seq;
+        }
+        home
+    }
+
+    /// The probe sequences keys can be stored along in each subarray.
+    fn choices(&self) -> &'static [bool] {
+        if self.two_choices { &[false, true] } else { &[false] }
+    }
+
+    /// `SubArray::find` for `sub`, along every probe sequence of the map.
+    #[inline(always)]
+    fn find_in(&self, sub: &SubArray, key: K, hash: u64) -> (Option<usize>, usize) {
+        let (slot, probes) = sub.find(&self.arena, key, hash, false);
+        if slot.is_some() || !self.two_choices {
+            return (slot, probes);
+        }
+        let (slot, more) = sub.find(&self.arena, key, hash, true);
+        (slot, probes + more)
+    }
+
+    /// The subarray and arena index of the copy of `key` lookups find.
+    fn locate(&self, key: K, hash: u64) -> Option<(usize, usize)> {
+        self.subarrays.iter().enumerate()
+            .find_map(|(i, sub)| self.find_in(sub, key, hash).0.map(|idx| (i, idx)))
+    }
+
+    pub(crate) fn get(&self, key: K) -> Option<&V> {
+        self.get_hashed(key, hash_key(key.to_u64()))
+    }
+
+    pub(crate) fn get_hashed(&self, key: K, hash: u64) -> Option<&V> {
+        if !self.may_contain(hash) {
+            if let Some(histograms) = &self.histograms {
+                histograms.gets.record(0);
+            }
+            return None;
+        }
+        match &self.histograms {
+            None => self.subarrays.iter().find_map(|sub| {
+                let idx = self.find_in(sub, key, hash).0?;
+                // SAFETY: `find` only returns occupied slots.
+                Some(unsafe { self.arena.values[idx].assume_init_ref() })
+            }),
+            Some(histograms) => self.get_recorded(key, hash, histograms),
+        }
+    }
+
+    /// `get_hashed` that records the probes it used.
+    fn get_recorded(&self, key: K, hash: u64, histograms: &ProbeHistograms) -> Option<&V> {
+        let mut total_probes = 0;
+        for (sub, (_, gets)) in self.subarrays.iter().zip(&histograms.subarrays) {
+            let (slot, probes) = self.find_in(sub, key, hash);
+            total_probes += probes;
+            if let Some(idx) = slot {
+                gets.record(probes);
+                histograms.gets.record(total_probes);
+                // SAFETY: `find` only returns occupied slots.
+                return Some(unsafe { self.arena.values[idx].assume_init_ref() });
+            }
+        }
+        histograms.gets.record(total_probes);
+        None
+    }
+
+    /// The value lookups of `key` find, for updating in place.
+    pub(crate) fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let hash = hash_key(key.to_u64());
+        if !self.may_contain(hash) {
+            return None;
+        }
+        let (_, idx) = self.locate(key, hash)?;
+        // SAFETY: `locate` only returns occupied slots.
+        Some(unsafe { self.arena.values[idx].assume_init_mut() })
+    }
+
+    /// The slot of the copy of `key` lookups find, for `value_at_mut`.
+    pub(crate) fn slot_of(&self, key: K) -> Option<usize> {
+        let hash = hash_key(key.to_u64());
+        if !self.may_contain(hash) {
+            return None;
+        }
+        self.locate(key, hash).map(|(_, idx)| idx)
+    }
+
+    /// The value at `slot`, which `slot_of` or `insert_slot` returned and no
+    /// insert or remove has emptied or moved since.
+    pub(crate) fn value_at_mut(&mut self, slot: usize) -> &mut V {
+        assert!(is_full(self.arena.ctrl[slot]), "slot {slot} is not occupied");
+        // SAFETY: the slot is occupied.
+        unsafe { self.arena.values[slot].assume_init_mut() }
+    }
+
+    /// Prefetches the first probe of `hash` in the two largest subarrays, which
+    /// together hold most entries.
+    pub(crate) fn prefetch(&self, hash: u64) {
+        if let Some(filter) = &self.filter {
+            filter.prefetch(hash);
+        }
+        for sub in self.subarrays.iter().take(2) {
+            sub.prefetch(&self.arena, hash);
+        }
+    }
+
+    pub(crate) fn contains_hashed(&self, key: K, hash: u64) -> bool {
+        self.get_hashed(key, hash).is_some()
+    }
+
+    /// Removes `key`, returning its value if it was present.
+    pub(crate) fn remove(&mut self, key: K) -> Option<V> {
+        self.remove_hashed(key, hash_key(key.to_u64()))
+    }
+
+    pub(crate) fn remove_hashed(&mut self, key: K, hash: u64) -> Option<V> {
+        if !self.may_contain(hash) {
+            return None;
+        }
+        // Every subarray is checked so that no stale copy of the key survives.
+        let mut removed = None;
+        let choices = self.choices();
+        for sub in self.subarrays.iter_mut() {
+            for &second in choices {
+                while let Some(value) = sub.remove(&mut self.arena, key, hash, second) {
+                    removed.get_or_insert(value);
+                }
+            }
+        }
+        self.generation += u64::from(removed.is_some());
+        if removed.is_some() && self.filter.is_some() {
+            // Rebuilding costs a pass over the slots, so it waits until enough
+            // removed keys have accumulated to pay for it.
+            self.stale += 1;
+            if self.stale > self.total_capacity / 2 {
+                self.rebuild_filter();
+            }
+        }
+        removed
+    }
+
+    /// Whether `hash` passes the filter; always true without one.
+    #[inline(always)]
+    fn may_contain(&self, hash: u64) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.may_contain(hash))
+    }
+
+    /// Keeps a Bloom filter over the stored keys, sized for the map's capacity
+    /// at `bits_per_key` bits per key, so most lookups of missing keys skip
+    /// probing. Replaces any existing filter.
+    pub(crate) fn enable_filter(&mut self, bits_per_key: usize) {
+        self.filter = Some(BloomFilter::new(self.total_capacity, bits_per_key));
+        self.rebuild_filter();
+    }
+
+    pub(crate) fn disable_filter(&mut self) {
+        self.filter = None;
+    }
+
+    /// Bits per key of the filter, if the map keeps one.
+    pub(crate) fn filter_bits_per_key(&self) -> Option<usize> {
+        self.filter.as_ref().map(BloomFilter::bits_per_key)
+    }
+
+    /// Starts recording probe lengths, into fresh histograms unless they are
+    /// already being recorded, or stops and discards them.
+    pub(crate) fn track_probes(&mut self, enabled: bool) {
+        if !enabled {
+            self.histograms = None;
+        } else if self.histograms.is_none() {
+            self.histograms = Some(Box::new(ProbeHistograms::new(self.subarrays.len())));
+        }
+    }
+
+    /// The histograms being recorded, if any.
+    pub(crate) fn histograms(&self) -> Option<&ProbeHistograms> {
+        self.histograms.as_deref()
+    }
+
+    /// Adds `lookups` lookups, `hits` of which found their key, to the counts.
+    /// Lookups are counted by their callers, once per call.
+    pub(crate) fn count_lookups(&self, lookups: usize, hits: usize) {
+        self.gets.fetch_add(lookups as u64, Ordering::Relaxed);
+        self.hits.fetch_add(hits as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn op_counts(&self) -> OpCounts {
+        OpCounts {
+            gets: self.gets.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            ..self.counts
+        }
+    }
+
+    /// Zeroes the operation counts and any probe histograms being recorded.
+    pub(crate) fn reset_counts(&mut self) {
+        self.counts = OpCounts::default();
+        *self.gets.get_mut() = 0;
+        *self.hits.get_mut() = 0;
+        for sub in &mut self.subarrays {
+            sub.fallthroughs = [0; 3];
+        }
+        if self.histograms.is_some() {
+            self.histograms = Some(Box::new(ProbeHistograms::new(self.subarrays.len())));
+        }
+    }
+
+    /// Starts adapting the insert probe budget to the workload, or stops and
+    /// returns to the default budget.
+    pub(crate) fn tune_probes(&mut self, enabled: bool) {
+        match (enabled, &self.tuner) {
+            (true, None) => self.tuner = Some(Tuner::new()),
+            (false, _) => {
+                self.tuner = None;
+                self.c_param = DEFAULT_BUDGET;
+            }
+            (true, Some(_)) => {}
+        }
+    }
+
+    pub(crate) fn tunes_probes(&self) -> bool {
+        self.tuner.is_some()
+    }
+
+    /// Starts or stops placing keys by two choices: each key gets a second
+    /// probe sequence in every subarray, and inserts follow whichever of the
+    /// two starts at the group with more free slots. Lookups then probe both
+    /// sequences, so misses cost about twice as much, and `store` looks up
+    /// every new key so it is not stored twice. Stopping rebalances the
+    /// map, since lookups no longer reach the keys along second sequences.
+    pub(crate) fn set_two_choices(&mut self, enabled: bool) {
+        let was = std::mem::replace(&mut self.two_choices, enabled);
+        if was && !enabled && self.len() > 0 {
+            self.rebalance();
+        }
+    }
+
+    pub(crate) fn two_choices(&self) -> bool {
+        self.two_choices
+    }
+
+    /// Sets how many entries an insert may try to move aside, cuckoo style,
+    /// when it exhausts its probe budget in a subarray, before spilling to
+    /// the next one; 0 turns kicks off. A kicked entry moves to a free slot
+    /// within the same budget along its own probe sequence, so lookups keep
+    /// finding it, and a kick never cascades.
+    pub(crate) fn set_cuckoo_kicks(&mut self, kicks: usize) {
+        self.cuckoo_kicks = kicks;
+    }
+
+    pub(crate) fn cuckoo_kicks(&self) -> usize {
+        self.cuckoo_kicks
+    }
+
+    /// Starts or stops placing new keys by Robin Hood displacement within
+    /// their subarray (see `place_robin_hood`). Keys already stored stay put
+    /// until they are displaced or the map is rebalanced. Only one probe
+    /// sequence per key is measured, so two-choice maps must not use it.
+    pub(crate) fn set_robin_hood(&mut self, enabled: bool) {
+        self.robin_hood = enabled;
+    }
+
+    pub(crate) fn robin_hood(&self) -> bool {
+        self.robin_hood
+    }
+
+    /// The constant `c` of the insert probe budget `c * log2(1 / epsilon)^2`.
+    pub(crate) fn probe_budget(&self) -> f64 {
+        self.c_param
+    }
+
+    /// What the tuner has observed, if the budget is being tuned.
+    pub(crate) fn tuning(&self) -> Option<Tuning> {
+        let tuner = self.tuner.as_ref()?;
+        let full_load = self.entry_limit() as f64 / self.total_capacity.max(1) as f64;
+        Some(tuner.report(self.delta, full_load, self.c_param, self.load_factor()))
+    }
+
+    /// Entries over slot capacity.
+    pub(crate) fn load_factor(&self) -> f64 {
+        if self.total_capacity == 0 { return 1.0; }
+        self.len() as f64 / self.total_capacity as f64
+    }
+
+    /// Every subarray's entries over its slot capacity.
+    pub(crate) fn subarray_loads(&self) -> impl Iterator<Item = f64> + '_ {
+        self.subarrays.iter().map(SubArray::load_factor)
+    }
+
+    /// Starts recording the path of every insert, or stops and discards the
+    /// recorded traces.
+    pub(crate) fn trace_inserts(&mut self, enabled: bool) {
+        self.traces = enabled.then(Vec::new);
+    }
+
+    /// Takes the inserts traced since the last call.
+    pub(crate) fn take_traces(&mut self) -> Vec<InsertTrace> {
+        self.traces.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Refills the filter from the stored keys, dropping the bits of removed ones.
+    fn rebuild_filter(&mut self) {
+        self.stale = 0;
+        let Some(filter) = &mut self.filter else { return };
+        filter.clear();
+        for sub in &self.subarrays {
+            for (key, _) in sub.entries(&self.arena) {
+                filter.insert(hash_key(key.to_u64()));
+            }
+        }
+    }
+
+    /// Number of live entries.
+    pub(crate) fn len(&self) -> usize {
+        self.subarrays.iter().map(|sub| sub.count).sum()
+    }
+
+    /// Iterates over all live entries, subarray by subarray.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.subarrays.iter().flat_map(|sub| sub.entries(&self.arena))
+    }
+
+    /// Changes whenever entries are added, removed or moved.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The first occupied slot at or after arena index `from` and its key.
+    /// Following these from 0 visits the entries in `iter` order.
+    pub(crate) fn next_key(&self, from: usize) -> Option<(usize, K)> {
+        let ctrl = self.arena.ctrl.get(from..)?;
+        let offset = ctrl.iter().position(|&ctrl| is_full(ctrl))?;
+        Some((from + offset, self.arena.keys[from + offset]))
+    }
+
+    /// Starts keeping insertion order, at the cost of a sequence number per
+    /// slot. Entries already stored are ordered as `iter` visits them.
+    pub(crate) fn keep_order(&mut self) {
+        if self.order.is_some() {
+            return;
+        }
+        let mut order = Order { seq: vec![0; self.arena.ctrl.len()], next: 0 };
+        for sub in &self.subarrays {
+            for idx in sub.base..sub.base + sub.capacity {
+                if is_full(self.arena.ctrl[idx]) {
+                    order.seq[idx] = order.next;
+                    order.next += 1;
+                }
+            }
+        }
+        self.order = Some(order);
+    }
+
+    pub(crate) fn keeps_order(&self) -> bool {
+        self.order.is_some()
+    }
+
+    /// All live entries, in insertion order if the map keeps it and as `iter`
+    /// visits them otherwise.
+    pub(crate) fn entries_in_order(&self) -> Vec<(K, &V)> {
+        let Some(order) = &self.order else {
+            return self.iter().collect();
+        };
+        let mut entries: Vec<(u64, K, &V)> = self.subarrays.iter()
+            .flat_map(|sub| sub.base..sub.base + sub.capacity)
+            .filter(|&idx| is_full(self.arena.ctrl[idx]))
+            // SAFETY: the slot is occupied.
+            .map(|idx| (order.seq[idx], self.arena.keys[idx], unsafe { self.arena.values[idx].assume_init_ref() }))
+            .collect();
+        entries.sort_unstable_by_key(|&(seq, _, _)| seq);
+        entries.into_iter().map(|(_, key, value)| (key, value)).collect()
+    }
+
+    /// Up to `n` distinct entries, chosen uniformly at random. While most slots
+    /// are occupied, random slots are drawn until enough occupied ones turn up,
+    /// so the cost grows with `n` rather than with the map. Sparse maps, and
+    /// samples of most of the entries, take one pass over the slots instead.
+    pub(crate) fn sample(&self, n: usize, rng: &mut impl Rng) -> Vec<(K, &V)> {
+        let len = self.len();
+        let slots = self.arena.ctrl.len();
+        let n = n.min(len);
+        // SAFETY: only called for occupied slots.
+        let entry = |idx: usize| (self.arena.keys[idx], unsafe { self.arena.values[idx].assume_init_ref() });
+
+        if n * 2 > len || len * 4 < slots {
+            let mut picks = rand::seq::index::sample(rng, len, n).into_vec();
+            picks.sort_unstable();
+            let mut picks = picks.into_iter().peekable();
+            let mut sample: Vec<_> = self.iter().enumerate()
+                .filter(|&(i, _)| picks.next_if_eq(&i).is_some())
+                .map(|(_, entry)| entry)
+                .collect();
+            sample.shuffle(rng);
+            return sample;
+        }
+        let mut taken = HashSet::with_capacity(n);
+        let mut sample = Vec::with_capacity(n);
+        while sample.len() < n {
+            let idx = rng.gen_range(0..slots);
+            if is_full(self.arena.ctrl[idx]) && taken.insert(idx) {
+                sample.push(entry(idx));
+            }
+        }
+        sample
+    }
+
+    /// Bytes taken by the insertion order, if the map keeps it.
+    pub(crate) fn order_bytes(&self) -> usize {
+        self.order.as_ref().map_or(0, |order| order.seq.len() * std::mem::size_of::<u64>())
+    }
+
+    /// Removes every entry, keeping the allocated capacity.
+    pub(crate) fn clear(&mut self) {
+        self.arena.drop_values();
+        for sub in self.subarrays.iter_mut() {
+            sub.reset(&mut self.arena);
+            sub.count = 0;
+            sub.tombstones = 0;
+        }
+        if let Some(filter) = &mut self.filter {
+            filter.clear();
+        }
+        self.stale = 0;
+        if let Some(order) = &mut self.order {
+            order.next = 0;
+        }
+        self.generation += 1;
+    }
+
+    /// Moves every value out of the map, in slot order, and discards the rest.
+    pub(crate) fn into_values(mut self) -> Vec<V> {
+        let mut values = Vec::with_capacity(self.len());
+        let arena = &mut self.arena;
+        for (ctrl, value) in arena.ctrl.iter_mut().zip(arena.values.iter_mut()) {
+            if is_full(*ctrl) {
+                // Marking the slot empty keeps the arena from dropping the value again.
+                *ctrl = EMPTY;
+                // SAFETY: the slot was occupied.
+                values.push(unsafe { value.assume_init_read() });
+            }
+        }
+        values
+    }
+
+    /// Bounds the lookups of every subarray by the longest probe sequence among
+    /// the keys it holds, so misses stop as soon as no stored key could follow,
+    /// and empty subarrays are skipped outright. Meant for maps that are done
+    /// changing: the next insert into a subarray lifts its bound again.
+    pub(crate) fn seal(&mut self) {
+        let choices = self.choices();
+        for sub in self.subarrays.iter_mut() {
+            sub.probe_limit = sub.groups;
+            let longest = sub.entries(&self.arena)
+                .filter_map(|(key, _)| {
+                    let hash = hash_key(key.to_u64());
+                    choices.iter()
+                        .map(|&second| sub.find(&self.arena, key, hash, second))
+                        .find_map(|(slot, probes)| slot.map(|_| probes))
+                })
+                .max();
+            sub.probe_limit = longest.unwrap_or(0);
+        }
+    }
+
+    /// Takes every entry out and inserts it again into the emptied subarrays,
+    /// in subarray order, restoring the occupancy profile the insertion
+    /// algorithm aims for after skewed inserts and removals. Tombstones go away,
+    /// and of a key stored more than once only the copy lookups find is kept.
+    /// Capacity, insertion order, counts, histograms and traces are unchanged.
+    pub(crate) fn rebalance(&mut self) {
+        let entries = self.take_entries();
+        self.put_entries(entries);
+    }
+
+    /// Moves the entries into a new arena of `capacity` slots, placing them
+    /// as `rebalance` does. `capacity` must leave room for every entry. The
+    /// Bloom filter is resized along, and probe histograms start over since
+    /// the subarrays change. The new arena is on the heap, so a map in a
+    /// region leaves it: a table whose slots others share must not resize.
+    pub(crate) fn resize(&mut self, capacity: usize) {
+        let entries = self.take_entries();
+        let mut arena = Arena::new(Self::arena_slots(capacity));
+        let mut base = 0;
+        self.subarrays = Self::subarray_sizes(capacity).into_iter().enumerate()
+            .map(|(i, size)| {
+                let sub = SubArray::new(&mut arena, i, base, size);
+                base += SubArray::padded_len(size);
+                sub
+            })
+            .collect();
+        if let Some(order) = &mut self.order {
+            order.seq = vec![0; arena.ctrl.len()];
+        }
+        self.arena = arena;
+        self.total_capacity = capacity;
+        if let Some(bits_per_key) = self.filter_bits_per_key() {
+            self.filter = Some(BloomFilter::new(capacity, bits_per_key));
+        }
+        if self.histograms.is_some() {
+            self.histograms = Some(Box::new(ProbeHistograms::new(self.subarrays.len())));
+        }
+        self.put_entries(entries);
+    }
+
+    /// Empties every subarray, returning the entries lookups find with their
+    /// insertion sequence numbers.
+    fn take_entries(&mut self) -> Vec<(K, V, u64)> {
+        let mut seen = HashSet::with_capacity(self.len());
+        let mut entries = Vec::with_capacity(self.len());
+        for sub in self.subarrays.iter_mut() {
+            for idx in sub.base..sub.base + sub.capacity {
+                if !is_full(self.arena.ctrl[idx]) {
+                    continue;
+                }
+                // SAFETY: the slot is occupied, and `reset` below marks it empty
+                // so the value is not read or dropped again.
+                let value = unsafe { self.arena.values[idx].assume_init_read() };
+                let key = self.arena.keys[idx];
+                let seq = self.order.as_ref().map_or(0, |order| order.seq[idx]);
+                // Lookups stop at the first subarray holding the key.
+                if seen.insert(key) {
+                    entries.push((key, value, seq));
+                }
+            }
+            sub.reset(&mut self.arena);
+            sub.count = 0;
+            sub.tombstones = 0;
+        }
+        if let Some(filter) = &mut self.filter {
+            filter.clear();
+        }
+        self.stale = 0;
+        self.generation += 1;
+        entries
+    }
+
+    /// Places entries taken out by `take_entries` into the emptied subarrays.
+    fn put_entries(&mut self, entries: Vec<(K, V, u64)>) {
+        for (key, value, seq) in entries {
+            let hash = hash_key(key.to_u64());
+            // The map has room for every entry, so some subarray takes it even
+            // if the algorithm turns it away.
+            let (subarray, mut slot) = self.forced_slot(key, hash);
+            if self.robin_hood {
+                slot = self.place_robin_hood(subarray, slot, key, hash, value);
+            } else {
+                self.subarrays[subarray].place(&mut self.arena, slot, key, hash, value);
+            }
+            if let Some(order) = &mut self.order {
+                order.seq[slot] = seq;
+            }
+            if let Some(filter) = &mut self.filter {
+                filter.insert(hash);
+            }
+        }
+    }
+
+    /// Shrinks the map under `policy` if its entries fill less than
+    /// `policy.threshold` of the capacity. Returns whether it shrank.
+    pub(crate) fn shrink_if_sparse(&mut self) -> bool {
+        let Some(policy) = self.shrink else { return false };
+        let len = self.len();
+        if len as f64 >= policy.threshold * self.total_capacity as f64 {
+            return false;
+        }
+        // Entries must still fit within `1 - delta` of the new capacity.
+        let capacity = policy.capacity_for(len).max((len as f64 / (1.0 - self.delta)).ceil() as usize);
+        if capacity >= self.total_capacity {
+            return false;
+        }
+        self.resize(capacity);
+        true
+    }
+
+    /// Load factor past which the growth policy grows the map.
+    fn growth_trigger(&self) -> f64 {
+        self.growth.map_or(1.0, |policy| policy.trigger)
+    }
+
+    /// Grows the map under its growth policy so it can hold `needed` entries,
+    /// by the policy's factor or more if `needed` calls for it. Returns false,
+    /// leaving the map as it was, for maps without a policy and maps already
+    /// at the policy's ceiling.
+    fn grow(&mut self, needed: usize) -> bool {
+        let Some(policy) = self.growth else { return false };
+        let ceiling = policy.max_capacity.unwrap_or(usize::MAX);
+        let wanted = (self.total_capacity as f64 * policy.factor).ceil() as usize;
+        let capacity = wanted.max((needed as f64 / policy.trigger).ceil() as usize).min(ceiling);
+        if capacity <= self.total_capacity {
+            return false;
+        }
+        self.resize(capacity);
+        true
+    }
+
+    pub(crate) fn set_growth(&mut self, policy: Option<GrowthPolicy>) {
+        self.growth = policy;
+    }
+
+    pub(crate) fn growth(&self) -> Option<GrowthPolicy> {
+        self.growth
+    }
+
+    pub(crate) fn set_auto_shrink(&mut self, policy: Option<ShrinkPolicy>) {
+        self.shrink = policy;
+    }
+
+    pub(crate) fn auto_shrink(&self) -> Option<ShrinkPolicy> {
+        self.shrink
+    }
+
+    /// Slot capacity the map was created with.
+    pub(crate) fn capacity(&self) -> usize {
+        self.total_capacity
+    }
+
+    pub(crate) fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// Entries that can still be inserted before the map holds the
+    /// `(1 - delta) * capacity` entries elastic hashing guarantees to place.
+    pub(crate) fn remaining_capacity(&self) -> usize {
+        self.entry_limit().saturating_sub(self.len())
+    }
+
+    /// `(1 - delta) * capacity`, rounded down.
+    fn entry_limit(&self) -> usize {
+        // The tolerance absorbs the rounding of a `delta` computed as `1 - load_factor`.
+        ((1.0 - self.delta) * self.total_capacity as f64 + 1e-6).floor() as usize
+    }
+
+    /// `(count, tombstones)` for every subarray, as accepted by `Contents::Counted`.
+    pub(crate) fn subarray_counts(&self) -> Vec<(usize, usize)> {
+        self.subarrays.iter().map(|sub| (sub.count, sub.tombstones)).collect()
+    }
+
+    /// Number of arena slots, padding included, of the first `n` subarrays
+    /// of a map with `capacity` slots.
+    pub(crate) fn leading_slots(capacity: usize, n: usize) -> usize {
+        Self::subarray_sizes(capacity).into_iter().take(n).map(SubArray::padded_len).sum()
+    }
+
+    /// Number of subarrays of a map with `capacity` slots.
+    pub(crate) fn subarray_count(capacity: usize) -> usize {
+        Self::subarray_sizes(capacity).len()
+    }
+
+    /// The region holding the slots, for maps built with `in_region`.
+    pub(crate) fn region_mut(&mut self) -> Option<&mut Region> {
+        self.arena.region.as_mut()
+    }
+
+    /// Every subarray's occupancy, probe limits and current insertion case.
+    pub(crate) fn subarray_stats(&self) -> Vec<SubArrayStats> {
+        self.subarrays.iter().enumerate().map(|(i, sub)| {
+            let insert_case = self.insert_case(i);
+            SubArrayStats {
+                capacity: sub.capacity,
+                count: sub.count,
+                tombstones: sub.tombstones,
+                load_factor: sub.load_factor(),
+                epsilon: sub.epsilon(),
+                insert_probe_limit: insert_case.probe_limit(sub.groups),
+                lookup_probe_limit: sub.probe_limit,
+                insert_case,
+                fallthroughs: sub.fallthroughs,
+            }
+        }).collect()
+    }
+
+    /// Slot storage taken by every subarray. Values are counted at their size
+    /// in the slot; whatever they refer to is not included.
+    pub(crate) fn memory(&self) -> Vec<SubArrayMemory> {
+        self.subarrays.iter().map(|sub| {
+            let slots = sub.groups * GROUP_WIDTH;
+            SubArrayMemory {
+                slots,
+                ctrl: slots,
+                keys: slots * std::mem::size_of::<K>(),
+                values: slots * std::mem::size_of::<V>(),
+            }
+        }).collect()
+    }
+
+    /// Bytes taken by the Bloom filter, if any.
+    pub(crate) fn filter_bytes(&self) -> usize {
+        self.filter.as_ref().map_or(0, BloomFilter::bytes)
+    }
+
+    /// Whether each slot of every subarray is occupied, padding excluded.
+    pub(crate) fn occupancy(&self) -> Vec<Vec<bool>> {
+        self.subarrays.iter()
+            .map(|sub| self.arena.ctrl[sub.base..sub.base + sub.capacity].iter().map(|&ctrl| is_full(ctrl)).collect())
+            .collect()
+    }
+
+    /// Iterates over the live entries of subarray `i`.
+    pub(crate) fn subarray_entries(&self, i: usize) -> impl Iterator<Item = (K, &V)> {
+        self.subarrays[i].entries(&self.arena)
+    }
+
+    /// Arena index of the entry at position `index` of `iter` order. The
+    /// subarray counts locate its subarray, and the control bytes are then
+    /// counted a group at a time, so no entry before it is visited.
+    fn entry_slot(&self, index: usize) -> Option<usize> {
+        let mut rest = index;
+        for sub in &self.subarrays {
+            if rest >= sub.count {
+                rest -= sub.count;
+                continue;
+            }
+            let mut start = sub.base;
+            for chunk in self.arena.ctrl[sub.base..sub.base + sub.capacity].chunks(GROUP_WIDTH) {
+                let full = chunk.iter().filter(|&&ctrl| is_full(ctrl)).count();
+                if rest < full {
+                    let offset = chunk.iter().enumerate().filter(|&(_, &ctrl)| is_full(ctrl)).nth(rest)?.0;
+                    return Some(start + offset);
+                }
+                rest -= full;
+                start += chunk.len();
+            }
+            return None;
+        }
+        None
+    }
+
+    /// The entry at position `index` of `iter` order. Positions only change
+    /// when entries are inserted or removed.
+    pub(crate) fn entry_at(&self, index: usize) -> Option<(K, &V)> {
+        // SAFETY: `entry_slot` only returns occupied slots.
+        self.entry_slot(index).map(|idx| (self.arena.keys[idx], unsafe { self.arena.values[idx].assume_init_ref() }))
+    }
+
+    /// The entries at positions `start..end` of `iter` order, found without
+    /// visiting the entries before `start`.
+    pub(crate) fn entry_range(&self, start: usize, end: usize) -> impl Iterator<Item = (K, &V)> {
+        let first = self.entry_slot(start).unwrap_or(self.arena.ctrl.len());
+        // Subarrays lie in the arena in `iter` order and their padding is never
+        // full, so the entries that follow are the occupied slots after `first`.
+        (first..self.arena.ctrl.len())
+            .filter(move |&idx| is_full(self.arena.ctrl[idx]))
+            .take(end.saturating_sub(start))
+            // SAFETY: the slot is occupied.
+            .map(move |idx| (self.arena.keys[idx], unsafe { self.arena.values[idx].assume_init_ref() }))
+    }
+
+    /// Number of live entries in every subarray.
+    pub(crate) fn subarray_lens(&self) -> Vec<usize> {
+        self.subarrays.iter().map(|sub| sub.count).collect()
+    }
+
+    /// Checks the map's invariants and describes every violation found: counts
+    /// that disagree with the control bytes, damaged group padding, stored keys
+    /// that a lookup would not reach, keys stored more than once, and keys the
+    /// Bloom filter would reject. A consistent map yields no violations.
+    pub(crate) fn validate(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        let mut seen = HashMap::new();
+        for (i, sub) in self.subarrays.iter().enumerate() {
+            let end = sub.base + sub.groups * GROUP_WIDTH;
+            let (slots, padding) = self.arena.ctrl[sub.base..end].split_at(sub.capacity);
+            let full = slots.iter().filter(|&&byte| is_full(byte)).count();
+            let deleted = slots.iter().filter(|&&byte| byte == DELETED).count();
+            if full != sub.count {
+                violations.push(format!("subarray {i}: count is {} but {full} slots are occupied", sub.count));
+            }
+            if deleted != sub.tombstones {
+                violations.push(format!(
+                    "subarray {i}: tombstone count is {} but {deleted} slots are deleted",
+                    sub.tombstones,
+                ));
+            }
+            if slots.contains(&SENTINEL) || padding.iter().any(|&byte| byte != SENTINEL) {
+                violations.push(format!("subarray {i}: group padding is damaged"));
+            }
+
+            for idx in sub.base..sub.base + sub.capacity {
+                if !is_full(self.arena.ctrl[idx]) {
+                    continue;
+                }
+                let key = self.arena.keys[idx];
+                let hash = hash_key(key.to_u64());
+                let slot = idx - sub.base;
+                if self.arena.ctrl[idx] != fingerprint(hash) {
+                    violations.push(format!("subarray {i}, slot {slot}: fingerprint does not match key {key}"));
+                } else if !self.choices().iter().any(|&second| sub.find(&self.arena, key, hash, second).0 == Some(idx)) {
+                    violations.push(format!("subarray {i}, slot {slot}: key {key} is not reachable by a lookup"));
+                }
+                if let Some(&(first, first_slot)) = seen.get(&key) {
+                    violations.push(format!(
+                        "key {key} is stored twice: subarray {first}, slot {first_slot} and subarray {i}, slot {slot}",
+                    ));
+                } else {
+                    seen.insert(key, (i, slot));
+                }
+                if !self.may_contain(hash) {
+                    violations.push(format!("key {key} is missing from the Bloom filter"));
+                }
+            }
+        }
+        violations
+    }
+
+    /// `(index, count, load_factor)` for every subarray.
+    pub(crate) fn stats(&self) -> Vec<(usize, usize, f64)> {
+        self.subarrays.iter().enumerate().map(|(i, sub)| {
+            (i, sub.count, sub.load_factor())
+        }).collect()
+    }
+}
+
+/// Maps whose slots can live in a `Region`, which stores `u64` keys.
+impl<V> ElasticMap<V> {
+    /// Builds a map whose slots live in `region`, laid out as `region::Layout`
+    /// describes. Unless `contents` is `Empty`, the region must already hold a
+    /// map created with the same parameters.
+    pub(crate) fn in_region(region: Region, capacity: usize, delta: f64, contents: Contents<'_>) -> Self {
+        Self::in_layout(region, Layout::new(Self::arena_slots(capacity)), capacity, delta, contents)
+    }
+
+    /// `in_region` for a region whose slot arrays are at the offsets of
+    /// `layout`, which must hold `arena_slots(capacity)` slots.
+    pub(crate) fn in_layout(region: Region, layout: Layout, capacity: usize, delta: f64, contents: Contents<'_>) -> Self {
+        assert_eq!(layout.slots, Self::arena_slots(capacity));
+        Self::with_arena(Arena::in_region(region, layout), capacity, delta, contents)
+    }
+
+    /// The arena's control bytes and keys, plus every slot's value (`None` for
+    /// slots without an entry), in the order `region::Layout` stores them.
+    pub(crate) fn raw_slots(&self) -> (&[u8], &[u64], impl Iterator<Item = Option<&V>>) {
+        let arena = &self.arena;
+        let values = arena.ctrl.iter().zip(arena.values.iter()).map(|(&ctrl, value)| {
+            // SAFETY: occupied slots hold initialized values.
+            is_full(ctrl).then(|| unsafe { value.assume_init_ref() })
+        });
+        (&arena.ctrl, &arena.keys, values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::ElasticMap;
+
+    /// Spreads consecutive numbers over the key space, like real ids.
+    fn spread(n: u64) -> u64 {
+        n.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (n >> 7)
+    }
+
+    /// Fills `map` to the load its inserts stop at, deletes every third key,
+    /// refills it with new keys and updates every fifth, then checks that
+    /// every key is found with its latest value, in insertion order if the
+    /// map keeps order, before and after sealing it and after inserting into
+    /// the sealed map.
+    fn churn_and_check(mut map: ElasticMap<i64>) {
+        let mut stored: HashMap<u64, i64> = HashMap::new();
+        let mut order: Vec<u64> = Vec::new();
+        let capacity = map.capacity();
+        let mut next = 0;
+        let mut fill = |map: &mut ElasticMap<i64>, stored: &mut HashMap<u64, i64>, order: &mut Vec<u64>, count: usize| {
+            for _ in 0..count {
+                let key = spread(next);
+                next += 1;
+                if map.insert(key, key as i64).is_ok() {
+                    stored.insert(key, key as i64);
+                    order.push(key);
+                }
+            }
+        };
+        fill(&mut map, &mut stored, &mut order, capacity);
+        let deleted: Vec<u64> = order.iter().copied().step_by(3).collect();
+        for &key in &deleted {
+            assert_eq!(map.remove(key), stored.remove(&key));
+        }
+        order.retain(|key| stored.contains_key(key));
+        fill(&mut map, &mut stored, &mut order, capacity * 2 / 5);
+        for &key in order.iter().step_by(5) {
+            map.insert(key, -(key as i64)).unwrap();
+            stored.insert(key, -(key as i64));
+        }
+
+        let check = |map: &ElasticMap<i64>, stored: &HashMap<u64, i64>, order: &[u64]| {
+            assert_eq!(map.len(), stored.len());
+            for (&key, value) in stored {
+                assert_eq!(map.get(key), Some(value), "key {key}");
+            }
+            for &key in &deleted {
+                assert_eq!(map.get(key), None, "deleted key {key}");
+            }
+            if map.keeps_order() {
+                let keys: Vec<u64> = map.entries_in_order().into_iter().map(|(key, _)| key).collect();
+                assert_eq!(keys, order);
+            }
+            assert_eq!(map.validate(), Vec::<String>::new());
+        };
+        check(&map, &stored, &order);
+        map.seal();
+        check(&map, &stored, &order);
+        fill(&mut map, &mut stored, &mut order, capacity / 20);
+        check(&map, &stored, &order);
+        assert!(map.op_counts().relocations > 0, "no entry was moved");
+    }
+
+    // Big enough that the first subarrays have many more probe groups than
+    // the tight budget below lets an insert probe.
+    const CAPACITY: usize = 20_000;
+
+    /// A map whose inserts probe only one or two groups of a subarray, so
+    /// they often find them full and have to kick or displace entries.
+    fn tight() -> ElasticMap<i64> {
+        let mut map = ElasticMap::new(CAPACITY, 0.05);
+        map.c_param = 0.05;
+        map
+    }
+
+    fn kicking(kicks: usize, ordered: bool) -> ElasticMap<i64> {
+        let mut map = tight();
+        map.set_cuckoo_kicks(kicks);
+        if ordered {
+            map.keep_order();
+        }
+        map
+    }
+
+    fn robin_hood(ordered: bool) -> ElasticMap<i64> {
+        let mut map = tight();
+        map.set_robin_hood(true);
+        if ordered {
+            map.keep_order();
+        }
+        map
+    }
+
+    #[test]
+    fn cuckoo_kicks_keep_every_key_reachable() {
+        // Most inserts that have to kick run out of kicks at 8, fewer at 64.
+        for kicks in [8, 64] {
+            churn_and_check(kicking(kicks, false));
+        }
+    }
+
+    #[test]
+    fn cuckoo_kicks_keep_insertion_order() {
+        churn_and_check(kicking(64, true));
+    }
+
+    #[test]
+    fn robin_hood_keeps_every_key_reachable() {
+        churn_and_check(robin_hood(false));
+    }
+
+    #[test]
+    fn robin_hood_keeps_insertion_order() {
+        churn_and_check(robin_hood(true));
+    }
+
+    /// Updating a key whose insert probes pick a free slot in an earlier
+    /// subarray than the one holding it must not store a second copy.
+    #[test]
+    fn update_after_deletes_keeps_keys_unique() {
+        let mut map: ElasticMap<i64> = ElasticMap::new(1000, 0.1);
+        for key in 0..880 {
+            map.insert(key, key as i64).unwrap();
+        }
+        let freed: Vec<u64> = map.subarray_entries(0).map(|(key, _)| key).take(200).collect();
+        for &key in &freed {
+            map.remove(key);
+        }
+        let updated: Vec<u64> = map.subarray_entries(1).map(|(key, _)| key).take(50).collect();
+        for &key in &updated {
+            map.insert(key, -(key as i64)).unwrap();
+        }
+
+        assert_eq!(map.len(), 680);
+        assert_eq!(map.iter().count(), 680);
+        for &key in &updated {
+            assert_eq!(map.get(key), Some(&-(key as i64)));
+        }
+        assert_eq!(map.validate(), Vec::<String>::new());
+    }
+
+    /// Updating a key must not fail once inserts no longer find room for it.
+    #[test]
+    fn update_in_full_map_finds_stored_key() {
+        let mut map: ElasticMap<i64> = ElasticMap::new(1000, 0.1);
+        let stored: Vec<u64> = (0..2000).filter(|&key| map.insert(key, key as i64).is_ok()).collect();
+        assert!(map.insert(5000, 0).is_err());
+        for &key in &stored {
+            map.insert(key, -(key as i64)).unwrap();
+        }
+
+        assert_eq!(map.len(), stored.len());
+        for &key in &stored {
+            assert_eq!(map.get(key), Some(&-(key as i64)));
+        }
+        assert_eq!(map.validate(), Vec::<String>::new());
+    }
+}
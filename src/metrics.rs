@@ -0,0 +1,125 @@
+//! Table metrics in the Prometheus text exposition format.
+//!
+//! `metrics_text()` renders a table's operation counters, occupancy gauges and,
+//! while `track_probes` is on, its probe-length histograms, so a service
+//! embedding a table can serve them from its `/metrics` endpoint as they are.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+use crate::map::{ElasticMap, Histogram, HISTOGRAM_BINS};
+
+fn check_name(name: &str, what: &str) -> PyResult<()> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(PyValueError::new_err(format!("{what} {name:?} is not a valid Prometheus name")));
+    }
+    Ok(())
+}
+
+/// Writes metric families into a string, adding the constant labels to every sample.
+struct Exposition {
+    out: String,
+    prefix: String,
+    /// Rendered constant labels, without braces, e.g. `table="users"`.
+    labels: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {}_{name} {help}", self.prefix);
+        let _ = writeln!(self.out, "# TYPE {}_{name} {kind}", self.prefix);
+    }
+
+    fn sample(&mut self, name: &str, extra: Option<(&str, &str)>, value: impl std::fmt::Display) {
+        let mut labels = self.labels.clone();
+        if let Some((label, label_value)) = extra {
+            if !labels.is_empty() {
+                labels.push(',');
+            }
+            let _ = write!(labels, "{label}=\"{label_value}\"");
+        }
+        let _ = if labels.is_empty() {
+            writeln!(self.out, "{}_{name} {value}", self.prefix)
+        } else {
+            writeln!(self.out, "{}_{name}{{{labels}}} {value}", self.prefix)
+        };
+    }
+
+    fn metric(&mut self, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+        self.family(name, kind, help);
+        self.sample(name, None, value);
+    }
+
+    fn histogram(&mut self, name: &str, help: &str, histogram: &Histogram) {
+        self.family(name, "histogram", help);
+        let mut cumulative = 0;
+        for (probes, count) in histogram.counts().into_iter().enumerate().take(HISTOGRAM_BINS - 1) {
+            cumulative += count;
+            self.sample(&format!("{name}_bucket"), Some(("le", &probes.to_string())), cumulative);
+        }
+        self.sample(&format!("{name}_bucket"), Some(("le", "+Inf")), histogram.len());
+        self.sample(&format!("{name}_sum"), None, histogram.total());
+        self.sample(&format!("{name}_count"), None, histogram.len());
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+/// Renders the metrics of `map` with every name starting with `prefix` and
+/// every sample carrying `labels`.
+pub(crate) fn metrics_text<V>(map: &ElasticMap<V>, prefix: &str, labels: BTreeMap<String, String>) -> PyResult<String> {
+    check_name(prefix, "prefix")?;
+    let mut rendered = Vec::with_capacity(labels.len());
+    for (label, value) in &labels {
+        check_name(label, "label")?;
+        if label == "subarray" || label == "le" {
+            return Err(PyValueError::new_err(format!("label {label:?} is reserved")));
+        }
+        rendered.push(format!("{label}=\"{}\"", escape(value)));
+    }
+    let mut out = Exposition { out: String::new(), prefix: prefix.to_owned(), labels: rendered.join(",") };
+
+    let counts = map.op_counts();
+    out.metric("gets_total", "counter", "Keys looked up.", counts.gets);
+    out.metric("hits_total", "counter", "Keys looked up and found.", counts.hits);
+    out.metric("misses_total", "counter", "Keys looked up and not found.", counts.gets - counts.hits);
+    out.metric("inserts_total", "counter", "Successful inserts, including overwrites.", counts.inserts);
+    out.metric("overwrites_total", "counter", "Inserts that replaced a stored value in its slot.", counts.overwrites);
+    out.metric("failed_inserts_total", "counter", "Inserts that found no free slot.", counts.failed_inserts);
+    out.metric("relocations_total", "counter", "Entries moved to make room for an insert.", counts.relocations);
+
+    let stats = map.subarray_stats();
+    let capacity = map.capacity();
+    let entries = map.len();
+    out.metric("capacity_slots", "gauge", "Slot capacity.", capacity);
+    out.metric("entries", "gauge", "Stored entries.", entries);
+    let load_factor = if capacity == 0 { 1.0 } else { entries as f64 / capacity as f64 };
+    out.metric("load_factor", "gauge", "Fraction of the capacity in use.", load_factor);
+    out.metric("tombstones", "gauge", "Slots left behind by deletes.", stats.iter().map(|sub| sub.tombstones).sum::<usize>());
+    let slot_bytes: usize = map.memory().iter().map(|sub| sub.ctrl + sub.keys + sub.values).sum();
+    let memory = slot_bytes + map.filter_bytes() + map.order_bytes();
+    out.metric("memory_bytes", "gauge", "Bytes of slot storage, filter and insertion order.", memory);
+
+    out.family("subarray_entries", "gauge", "Stored entries per subarray.");
+    for (i, sub) in stats.iter().enumerate() {
+        out.sample("subarray_entries", Some(("subarray", &i.to_string())), sub.count);
+    }
+    out.family("subarray_load_factor", "gauge", "Fraction of each subarray in use.");
+    for (i, sub) in stats.iter().enumerate() {
+        out.sample("subarray_load_factor", Some(("subarray", &i.to_string())), sub.load_factor);
+    }
+
+    if let Some(histograms) = map.histograms() {
+        out.histogram("insert_probes", "Probes used per successful insert.", &histograms.inserts);
+        out.histogram("get_probes", "Probes used per lookup.", &histograms.gets);
+    }
+    Ok(out.out)
+}
@@ -0,0 +1,54 @@
+//! Key namespaces, for hosting several logical keyspaces in one table.
+//!
+//! A namespaced key carries a small tag in its top `tag_bits` bits and the
+//! key within the namespace in the rest. Keys are hashed before they are
+//! placed, so tags do not cluster keys in the slots; picking out a namespace,
+//! as `namespace_keys` and `delete_namespace` do, takes a pass over the table.
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+/// The keys of one tag, for `tag_bits` bits of it.
+pub(crate) struct Namespace {
+    tag: u64,
+    /// Bits below the tag, `64 - tag_bits`.
+    shift: u32,
+}
+
+impl Namespace {
+    pub(crate) fn new(tag: u64, tag_bits: u32) -> PyResult<Self> {
+        let shift = check_tag_bits(tag_bits)?;
+        if tag >> tag_bits != 0 {
+            return Err(PyValueError::new_err(format!("tag must be less than 2**{tag_bits}")));
+        }
+        Ok(Namespace { tag, shift })
+    }
+
+    /// The namespaced key of `key` in this namespace.
+    pub(crate) fn key(&self, key: u64) -> PyResult<u64> {
+        if key >> self.shift != 0 {
+            return Err(PyValueError::new_err(format!("key must be less than 2**{} to fit beside the tag", self.shift)));
+        }
+        Ok(self.tag << self.shift | key)
+    }
+
+    /// The key within this namespace of namespaced key `key`, if it is in it.
+    #[inline]
+    pub(crate) fn local(&self, key: u64) -> Option<u64> {
+        (key >> self.shift == self.tag).then(|| key & (u64::MAX >> (64 - self.shift)))
+    }
+}
+
+/// Validates `tag_bits`, returning the bits left for keys.
+fn check_tag_bits(tag_bits: u32) -> PyResult<u32> {
+    if !(1..=32).contains(&tag_bits) {
+        return Err(PyValueError::new_err("tag_bits must be between 1 and 32"));
+    }
+    Ok(64 - tag_bits)
+}
+
+/// The tag and the key within its namespace of namespaced key `key`.
+pub(crate) fn split_key(key: u64, tag_bits: u32) -> PyResult<(u64, u64)> {
+    let shift = check_tag_bits(tag_bits)?;
+    Ok((key >> shift, key & (u64::MAX >> tag_bits)))
+}
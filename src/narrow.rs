@@ -0,0 +1,299 @@
+//! Typed tables with 32-bit keys.
+//!
+//! `IntTable32` and `FloatTable32` store `u32` keys where `IntTable` and
+//! `FloatTable` store `u64` ones, so a slot takes 13 bytes instead of 17: at
+//! hundreds of millions of entries that saves gigabytes for datasets whose
+//! IDs fit in 32 bits. They offer the core of the typed table API; keys out
+//! of the 32-bit range raise OverflowError.
+
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+
+use crate::batch::{lookup_result, Batch, Fills, PARALLEL_THRESHOLD};
+use crate::lock::{TableGuard, TableLock};
+use crate::map::{ElasticMap, InsertError};
+use crate::table::{check_delta, detailed_stats, memory_usage, sizing_for_items};
+use crate::views::{ItemsView, KeysView, ValuesView};
+
+/// Maps every key of a lookup batch through `f`, in parallel for large batches.
+fn map_keys<R: Send>(keys: &[u32], f: impl Fn(u32) -> R + Sync + Send) -> Vec<R> {
+    if keys.len() < PARALLEL_THRESHOLD {
+        keys.iter().map(|&key| f(key)).collect()
+    } else {
+        keys.par_iter().map(|&key| f(key)).collect()
+    }
+}
+
+macro_rules! narrow_table {
+    ($name:ident, $state:ident, $value:ty, $doc:literal) => {
+        #[doc = $doc]
+        #[pyclass(frozen)]
+        pub(crate) struct $name {
+            state: TableLock<$state>,
+        }
+
+        /// The state of a table, behind its lock.
+        pub(crate) struct $state {
+            map: ElasticMap<$value, u32>,
+        }
+
+        impl From<$state> for $name {
+            fn from(state: $state) -> Self {
+                $name { state: TableLock::new(state) }
+            }
+        }
+
+        impl $name {
+            /// The table's state, waiting for any call on another thread to finish.
+            pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, $state>> {
+                self.state.lock(py)
+            }
+        }
+
+impl $state {
+            fn new(capacity: usize, delta: f64) -> PyResult<Self> {
+                check_delta(delta)?;
+                Ok($state { map: ElasticMap::new(capacity, delta) })
+            }
+
+            fn for_items(expected_items: usize, load_factor: f64) -> PyResult<Self> {
+                let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+                Self::new(capacity, delta)
+            }
+
+            fn insert(&mut self, key: u32, value: $value) -> PyResult<usize> {
+                Ok(self.map.insert(key, value)?)
+            }
+
+            fn insert_many(&mut self, py: Python<'_>, keys: Batch<u32>, values: Batch<$value>) -> PyResult<usize> {
+                let (keys, values) = (keys.as_slice(py)?, values.as_slice(py)?);
+                if keys.len() != values.len() {
+                    return Err(PyValueError::new_err("keys and values must have the same length"));
+                }
+                let map = &mut self.map;
+                let probes = py.allow_threads(|| {
+                    keys.iter().zip(values.iter())
+                        .map(|(&key, &value)| map.insert(key, value))
+                        .sum::<Result<usize, InsertError>>()
+                });
+                Ok(probes?)
+            }
+
+            fn get(&self, key: u32) -> Option<$value> {
+                let found = self.map.get(key).copied();
+                self.map.count_lookups(1, found.is_some() as usize);
+                found
+            }
+
+            fn delete(&mut self, key: u32) -> bool {
+                self.map.remove(key).is_some()
+            }
+
+            fn delete_many(&mut self, py: Python<'_>, keys: Batch<u32>) -> PyResult<usize> {
+                let keys = keys.as_slice(py)?;
+                Ok(keys.iter().filter(|&&key| self.map.remove(key).is_some()).count())
+            }
+
+            fn get_many(&self, py: Python<'_>, keys: Batch<u32>, default: Option<$value>, mask: bool, defaults: Option<Batch<$value>>) -> PyResult<PyObject> {
+                let keys_buffer = keys.is_buffer();
+                let keys = keys.as_slice(py)?;
+                let fills = Fills::new(py, default, defaults.as_ref(), keys.len())?;
+                let map = &self.map;
+                let (values, hits) = py.allow_threads(|| {
+                    let found = map_keys(&keys, |key| map.get(key).copied());
+                    map.count_lookups(found.len(), found.iter().flatten().count());
+                    let hits: Option<Vec<bool>> = mask.then(|| found.iter().map(Option::is_some).collect());
+                    (found.into_iter().enumerate().map(|(index, value)| value.or_else(|| fills.as_ref().map(|fills| fills.at(index)))).collect::<Vec<_>>(), hits)
+                });
+                lookup_result(py, values, hits, keys_buffer)
+            }
+
+            fn contains_many(&self, py: Python<'_>, keys: Batch<u32>) -> PyResult<Vec<bool>> {
+                let keys = keys.as_slice(py)?;
+                let map = &self.map;
+                let found = py.allow_threads(|| map_keys(&keys, |key| map.get(key).is_some()));
+                map.count_lookups(found.len(), found.iter().filter(|&&hit| hit).count());
+                Ok(found)
+            }
+
+            fn clear(&mut self) {
+                self.map.clear();
+            }
+
+            fn capacity(&self) -> usize {
+                self.map.capacity()
+            }
+
+            fn remaining_capacity(&self) -> usize {
+                self.map.remaining_capacity()
+            }
+
+            fn is_full(&self) -> bool {
+                self.map.remaining_capacity() == 0
+            }
+
+            fn stats(&self) -> Vec<(usize, usize, f64)> {
+                self.map.stats()
+            }
+
+            fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                memory_usage(py, &self.map, |_| None)
+            }
+
+            fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                detailed_stats(py, &self.map)
+            }
+
+            fn validate(&self, py: Python<'_>) -> Vec<String> {
+                py.allow_threads(|| self.map.validate())
+            }
+}
+
+#[pymethods]
+impl $name {
+            /// Create a table with the given slot capacity and delta parameter.
+            /// See ElasticTable for the meaning of the arguments.
+            #[new]
+            #[pyo3(signature = (capacity, delta=0.05))]
+            fn new(capacity: usize, delta: f64) -> PyResult<Self> {
+                $state::new(capacity, delta).map(Self::from)
+            }
+
+            /// Create a table sized for `expected_items` at the target load factor.
+            #[staticmethod]
+            #[pyo3(signature = (expected_items, load_factor=0.90))]
+            fn for_items(expected_items: usize, load_factor: f64) -> PyResult<Self> {
+                $state::for_items(expected_items, load_factor).map(Self::from)
+            }
+
+            fn insert(&self, py: Python<'_>, key: u32, value: $value) -> PyResult<usize> {
+                self.lock(py)?.insert(key, value)
+            }
+
+            /// Insert many key/value pairs in a single call. Returns the total probes used.
+            fn insert_many(&self, py: Python<'_>, keys: Batch<u32>, values: Batch<$value>) -> PyResult<usize> {
+                self.lock(py)?.insert_many(py, keys, values)
+            }
+
+            fn get(&self, py: Python<'_>, key: u32) -> PyResult<Option<$value>> {
+                Ok(self.lock(py)?.get(key))
+            }
+
+            /// Remove a key from the table. Returns True if the key was present.
+            fn delete(&self, py: Python<'_>, key: u32) -> PyResult<bool> {
+                Ok(self.lock(py)?.delete(key))
+            }
+
+            /// Remove many keys in a single call. Returns how many were present.
+            fn delete_many(&self, py: Python<'_>, keys: Batch<u32>) -> PyResult<usize> {
+                self.lock(py)?.delete_many(py, keys)
+            }
+
+            /// Look up many keys in a single call, using `default` for missing keys,
+            /// or the matching item of `defaults`, a sequence or array holding one
+            /// value for each key. Large batches are probed in parallel. With
+            /// `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+            #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+            fn get_many(
+                &self,
+                py: Python<'_>,
+                keys: Batch<u32>,
+                default: Option<$value>,
+                mask: bool,
+                defaults: Option<Batch<$value>>,
+            ) -> PyResult<PyObject> {
+                self.lock(py)?.get_many(py, keys, default, mask, defaults)
+            }
+
+            /// Check membership for many keys in a single call.
+            /// Large batches are probed in parallel.
+            fn contains_many(&self, py: Python<'_>, keys: Batch<u32>) -> PyResult<Vec<bool>> {
+                self.lock(py)?.contains_many(py, keys)
+            }
+
+            /// A live, set-like view of the keys, in slot order. See ElasticTable.keys.
+            fn keys(slf: &Bound<'_, Self>) -> KeysView {
+                KeysView::new(slf.clone().unbind())
+            }
+
+            /// A live view of the values, in the order of `keys()`.
+            fn values(slf: &Bound<'_, Self>) -> ValuesView {
+                ValuesView::new(slf.clone().unbind())
+            }
+
+            /// A live, set-like view of the `(key, value)` pairs, in the order of `keys()`.
+            fn items(slf: &Bound<'_, Self>) -> ItemsView {
+                ItemsView::new(slf.clone().unbind())
+            }
+
+            fn clear(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.clear();
+                Ok(())
+            }
+
+            /// Number of slots the table was created with.
+            fn capacity(&self, py: Python<'_>) -> PyResult<usize> {
+                Ok(self.lock(py)?.capacity())
+            }
+
+            /// See `ElasticTable.remaining_capacity`.
+            fn remaining_capacity(&self, py: Python<'_>) -> PyResult<usize> {
+                Ok(self.lock(py)?.remaining_capacity())
+            }
+
+            /// See `ElasticTable.is_full`.
+            fn is_full(&self, py: Python<'_>) -> PyResult<bool> {
+                Ok(self.lock(py)?.is_full())
+            }
+
+            fn stats(&self, py: Python<'_>) -> PyResult<Vec<(usize, usize, f64)>> {
+                Ok(self.lock(py)?.stats())
+            }
+
+            /// Bytes of memory the table takes. See ElasticTable.memory_usage;
+            /// keys take 4 bytes per slot and values are stored inline.
+            fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                self.lock(py)?.memory_usage(py)
+            }
+
+            /// A structured version of `stats()`. See ElasticTable.detailed_stats.
+            fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                self.lock(py)?.detailed_stats(py)
+            }
+
+            /// Check the table's internal invariants. See ElasticTable.validate.
+            fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+                Ok(self.lock(py)?.validate(py))
+            }
+}
+
+        impl $state {
+            pub(crate) fn map(&self) -> &ElasticMap<$value, u32> {
+                &self.map
+            }
+
+            /// The value stored under `key`, without counting a lookup. Keys
+            /// outside the 32-bit range are never stored.
+            pub(crate) fn value_of(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+                let Ok(key) = u32::try_from(key) else { return Ok(None) };
+                self.map.get(key).map(|&value| value.into_py_any(py)).transpose()
+            }
+        }
+    };
+}
+
+narrow_table!(
+    IntTable32,
+    IntTable32State,
+    i64,
+    "An IntTable with 32-bit keys: 64-bit signed integer values stored inline, in 13 bytes per slot."
+);
+narrow_table!(
+    FloatTable32,
+    FloatTable32State,
+    f64,
+    "A FloatTable with 32-bit keys: 64-bit float values stored inline, in 13 bytes per slot."
+);
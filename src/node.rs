@@ -0,0 +1,161 @@
+//! Node.js bindings for the typed tables, behind the `node` feature.
+//!
+//! Built with napi-rs, the addon exports `IntTable` and `FloatTable` classes
+//! over the same maps as the Python classes of those names, so Python and
+//! Node services can share one implementation. Keys are `BigInt`s, as are
+//! the values of an `IntTable`; methods follow `Map` where they overlap
+//! (`get`, `has`, `delete`, `size`, `clear`).
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::map::ElasticMap;
+
+/// Converts a `BigInt` key, which must fit in 64 unsigned bits.
+fn key(key: BigInt) -> Result<u64> {
+    let (negative, value, lossless) = key.get_u64();
+    if negative || !lossless {
+        return Err(Error::new(Status::InvalidArg, "key must fit in 64 unsigned bits"));
+    }
+    Ok(value)
+}
+
+/// Validates `delta`, which defaults to 0.05.
+fn delta_or_default(delta: Option<f64>) -> Result<f64> {
+    let delta = delta.unwrap_or(0.05);
+    if !(delta > 0.0 && delta < 1.0) {
+        return Err(Error::new(Status::InvalidArg, "delta must be between 0 and 1"));
+    }
+    Ok(delta)
+}
+
+/// Turns `forItems` arguments into a `(capacity, delta)` pair.
+fn sizing_for_items(expected_items: u32, load_factor: Option<f64>) -> Result<(usize, f64)> {
+    let load_factor = load_factor.unwrap_or(0.90);
+    if load_factor <= 0.5 || load_factor >= 0.99 {
+        return Err(Error::new(Status::InvalidArg, "load_factor must be between 0.5 and 0.99"));
+    }
+    let capacity = (f64::from(expected_items) / load_factor).ceil() as usize;
+    Ok((capacity, 1.0 - load_factor))
+}
+
+/// Converts between the JavaScript and Rust forms of a table's values.
+trait NodeValue: Copy + 'static {
+    type Js;
+
+    fn from_js(value: Self::Js) -> Result<Self>;
+    fn to_js(self) -> Self::Js;
+}
+
+impl NodeValue for i64 {
+    type Js = BigInt;
+
+    fn from_js(value: BigInt) -> Result<i64> {
+        let (value, lossless) = value.get_i64();
+        if !lossless {
+            return Err(Error::new(Status::InvalidArg, "value must fit in 64 signed bits"));
+        }
+        Ok(value)
+    }
+
+    fn to_js(self) -> BigInt {
+        BigInt::from(self)
+    }
+}
+
+impl NodeValue for f64 {
+    type Js = f64;
+
+    fn from_js(value: f64) -> Result<f64> {
+        Ok(value)
+    }
+
+    fn to_js(self) -> f64 {
+        self
+    }
+}
+
+/// Inserts or updates `key`, throwing if a new key cannot be placed.
+fn insert<V: NodeValue>(map: &mut ElasticMap<V>, key: BigInt, value: V::Js) -> Result<()> {
+    map.insert(self::key(key)?, V::from_js(value)?)
+        .map(|_| ())
+        .map_err(|err| Error::new(Status::GenericFailure, err.to_string()))
+}
+
+macro_rules! node_table {
+    ($name:ident, $js_name:literal, $value:ty, $js_value:ty) => {
+        #[napi(js_name = $js_name)]
+        pub struct $name {
+            map: ElasticMap<$value>,
+        }
+
+        #[napi]
+        impl $name {
+            /// Creates a table with `capacity` slots and elasticity `delta`
+            /// (default 0.05), which holds up to `(1 - delta) * capacity` entries.
+            #[napi(constructor)]
+            pub fn new(capacity: u32, delta: Option<f64>) -> Result<Self> {
+                Ok($name { map: ElasticMap::new(capacity as usize, delta_or_default(delta)?) })
+            }
+
+            /// Creates a table sized for `expectedItems` at `loadFactor`
+            /// (default 0.90).
+            #[napi(factory)]
+            pub fn for_items(expected_items: u32, load_factor: Option<f64>) -> Result<Self> {
+                let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+                Ok($name { map: ElasticMap::new(capacity, delta) })
+            }
+
+            /// Inserts or updates `key`. Throws if a new key cannot be placed.
+            #[napi]
+            pub fn insert(&mut self, key: BigInt, value: $js_value) -> Result<()> {
+                insert(&mut self.map, key, value)
+            }
+
+            /// Inserts `keys[i]` with `values[i]` for every `i`. Throws at the
+            /// first key that cannot be placed, keeping the ones before it.
+            #[napi]
+            pub fn insert_many(&mut self, keys: Vec<BigInt>, values: Vec<$js_value>) -> Result<()> {
+                if keys.len() != values.len() {
+                    return Err(Error::new(Status::InvalidArg, "keys and values must have the same length"));
+                }
+                keys.into_iter().zip(values).try_for_each(|(key, value)| insert(&mut self.map, key, value))
+            }
+
+            #[napi]
+            pub fn get(&self, key: BigInt) -> Result<Option<$js_value>> {
+                Ok(self.map.get(self::key(key)?).map(|&value| value.to_js()))
+            }
+
+            #[napi]
+            pub fn has(&self, key: BigInt) -> Result<bool> {
+                Ok(self.map.get(self::key(key)?).is_some())
+            }
+
+            /// Removes `key`, returning whether it was stored.
+            #[napi]
+            pub fn delete(&mut self, key: BigInt) -> Result<bool> {
+                Ok(self.map.remove(self::key(key)?).is_some())
+            }
+
+            #[napi]
+            pub fn clear(&mut self) {
+                self.map.clear();
+            }
+
+            #[napi(getter)]
+            pub fn size(&self) -> u32 {
+                self.map.len() as u32
+            }
+
+            /// Number of slots the table was created with.
+            #[napi(getter)]
+            pub fn capacity(&self) -> u32 {
+                self.map.capacity() as u32
+            }
+        }
+    };
+}
+
+node_table!(NodeIntTable, "IntTable", i64, BigInt);
+node_table!(NodeFloatTable, "FloatTable", f64, f64);
@@ -0,0 +1,129 @@
+//! Change observers, for `add_observer`.
+//!
+//! Every observer of a table is called with each change the table goes
+//! through, as `callback(event, key, old, new)`, once the call that made it
+//! has taken effect. The event is "insert", "update" or "delete", and `old`
+//! and `new` are the key's values before and after, None where it had none.
+//! Finding the replaced values costs a lookup per key, which tables only pay
+//! while they have observers.
+
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyTraverseError, PyVisit};
+
+/// A change to one key: its value before and after.
+pub(crate) struct Change<V> {
+    pub(crate) key: u64,
+    pub(crate) old: Option<V>,
+    pub(crate) new: Option<V>,
+}
+
+impl<V> Change<V> {
+    fn event(&self) -> Option<&'static str> {
+        match (&self.old, &self.new) {
+            (None, Some(_)) => Some("insert"),
+            (Some(_), Some(_)) => Some("update"),
+            (Some(_), None) => Some("delete"),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The changes a batch insert of `values` under `keys` makes, given the values
+/// the keys hold beforehand. A key given twice replaces the value of its
+/// first occurrence the second time.
+pub(crate) fn batch_changes<V>(
+    keys: &[u64],
+    values: impl IntoIterator<Item = V>,
+    current: impl Fn(u64) -> Option<V>,
+    copy: impl Fn(&V) -> V,
+) -> Vec<Change<V>> {
+    let mut latest = HashMap::new();
+    let mut changes: Vec<Change<V>> = Vec::with_capacity(keys.len());
+    for (i, (&key, value)) in keys.iter().zip(values).enumerate() {
+        let old = match latest.insert(key, i) {
+            Some(previous) => changes[previous].new.as_ref().map(&copy),
+            None => current(key),
+        };
+        changes.push(Change { key, old, new: Some(value) });
+    }
+    changes
+}
+
+/// A value observers can be passed.
+pub(crate) trait Observed {
+    fn to_object(&self, py: Python<'_>) -> PyObject;
+}
+
+impl Observed for PyObject {
+    fn to_object(&self, py: Python<'_>) -> PyObject {
+        self.clone_ref(py)
+    }
+}
+
+macro_rules! observed_number {
+    ($($value:ty),*) => {$(
+        impl Observed for $value {
+            fn to_object(&self, py: Python<'_>) -> PyObject {
+                self.into_pyobject(py).unwrap().into_any().unbind()
+            }
+        }
+    )*};
+}
+
+observed_number!(i64, f64);
+
+/// The callbacks registered with `add_observer`, in registration order.
+#[derive(Default)]
+pub(crate) struct Observers {
+    callbacks: Vec<PyObject>,
+}
+
+impl Observers {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    pub(crate) fn add(&mut self, callback: PyObject) {
+        self.callbacks.push(callback);
+    }
+
+    /// Unregisters `callback`, the first registration of it if there are several.
+    pub(crate) fn remove(&mut self, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+        let Some(at) = self.callbacks.iter().position(|observer| callback.is(observer)) else {
+            return Err(PyValueError::new_err("callback is not an observer of the table"));
+        };
+        self.callbacks.remove(at);
+        Ok(())
+    }
+
+    /// Calls every observer with each of `changes`, in order. The first
+    /// exception an observer raises propagates and skips the calls left.
+    pub(crate) fn notify<V: Observed>(&self, py: Python<'_>, changes: impl IntoIterator<Item = Change<V>>) -> PyResult<()> {
+        if self.callbacks.is_empty() {
+            return Ok(());
+        }
+        for change in changes {
+            let Some(event) = change.event() else { continue };
+            let old = change.old.as_ref().map(|value| value.to_object(py));
+            let new = change.new.as_ref().map(|value| value.to_object(py));
+            for callback in &self.callbacks {
+                callback.call1(py, (event, change.key, old.as_ref(), new.as_ref()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        for callback in &self.callbacks {
+            visit.call(callback)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.callbacks.clear();
+    }
+}
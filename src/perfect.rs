@@ -0,0 +1,114 @@
+//! Minimal perfect hashing for frozen tables.
+//!
+//! `PerfectMap` stores `n` entries in exactly `n` slots, with a slot index that
+//! is collision-free for the stored keys, so every lookup inspects exactly one
+//! slot. The construction follows PTHash: keys are split into buckets of about
+//! `BUCKET_SIZE` keys by their base hash, and each bucket gets a "pilot",
+//! searched for largest bucket first, that sends its keys to slots no other key
+//! took. A lookup reads its bucket's pilot, derives the slot, and compares the
+//! one key stored there, which also rejects keys outside the set.
+
+use crate::hash::{hash_key, mix, reduce};
+use crate::map::prefetch_read;
+
+/// Average number of keys per bucket; each bucket costs one 4-byte pilot.
+const BUCKET_SIZE: usize = 4;
+
+/// Slot of a key with base hash `hash` in a map of `n` slots, given its
+/// bucket's pilot.
+#[inline(always)]
+fn slot_of(hash: u64, pilot: u32, n: usize) -> usize {
+    reduce(mix(hash ^ hash_key(pilot as u64)), n)
+}
+
+pub(crate) struct PerfectMap<V> {
+    pilots: Vec<u32>,
+    keys: Vec<u64>,
+    values: Vec<V>,
+}
+
+impl<V: Copy> PerfectMap<V> {
+    /// Builds the map over `entries`. Of entries sharing a key, the first one is
+    /// kept. Returns `None` in the unlikely case some bucket admits no pilot.
+    pub(crate) fn build(mut entries: Vec<(u64, V)>) -> Option<Self> {
+        // Equal keys would never land in distinct slots.
+        entries.sort_by_key(|&(key, _)| key);
+        entries.dedup_by_key(|&mut (key, _)| key);
+        let n = entries.len();
+        let buckets = n.div_ceil(BUCKET_SIZE).max(1);
+        let hashes: Vec<u64> = entries.iter().map(|&(key, _)| hash_key(key)).collect();
+
+        // Entries grouped by bucket; `starts[b]..starts[b + 1]` delimits bucket `b`.
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_unstable_by_key(|&i| reduce(hashes[i], buckets));
+        let mut starts = vec![0; buckets + 1];
+        for &i in &order {
+            starts[reduce(hashes[i], buckets) + 1] += 1;
+        }
+        for b in 0..buckets {
+            starts[b + 1] += starts[b];
+        }
+        let mut by_size: Vec<usize> = (0..buckets).collect();
+        by_size.sort_by_key(|&b| std::cmp::Reverse(starts[b + 1] - starts[b]));
+
+        let mut pilots = vec![0; buckets];
+        let mut owner = vec![usize::MAX; n];
+        let mut positions = Vec::with_capacity(BUCKET_SIZE * 4);
+        for b in by_size {
+            let members = &order[starts[b]..starts[b + 1]];
+            if members.is_empty() {
+                break;
+            }
+            let pilot = (0..=u32::MAX).find(|&pilot| {
+                positions.clear();
+                for &i in members {
+                    let slot = slot_of(hashes[i], pilot, n);
+                    if owner[slot] != usize::MAX || positions.contains(&slot) {
+                        return false;
+                    }
+                    positions.push(slot);
+                }
+                true
+            })?;
+            pilots[b] = pilot;
+            for (&i, &slot) in members.iter().zip(&positions) {
+                owner[slot] = i;
+            }
+        }
+
+        let keys = owner.iter().map(|&i| entries[i].0).collect();
+        let values = owner.iter().map(|&i| entries[i].1).collect();
+        Some(PerfectMap { pilots, keys, values })
+    }
+
+    #[inline(always)]
+    fn slot(&self, hash: u64) -> usize {
+        let pilot = self.pilots[reduce(hash, self.pilots.len())];
+        slot_of(hash, pilot, self.keys.len())
+    }
+
+    pub(crate) fn get_hashed(&self, key: u64, hash: u64) -> Option<&V> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let slot = self.slot(hash);
+        (self.keys[slot] == key).then(|| &self.values[slot])
+    }
+
+    pub(crate) fn get(&self, key: u64) -> Option<&V> {
+        self.get_hashed(key, hash_key(key))
+    }
+
+    /// Prefetches the pilot a lookup for `hash` reads first.
+    pub(crate) fn prefetch(&self, hash: u64) {
+        prefetch_read(&self.pilots[reduce(hash, self.pilots.len())]);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u64, &V)> {
+        self.keys.iter().copied().zip(&self.values)
+    }
+}
@@ -0,0 +1,331 @@
+//! `ReadMostlyElasticTable`: lookups that never wait for a lock.
+//!
+//! The table keeps two copies of the map (a "left-right" structure). Readers
+//! announce themselves on the copy currently marked active and read it without
+//! locking. A writer applies its change to the inactive copy, flips the active
+//! index, waits for the readers still on the old copy to drain, and then repeats
+//! the change there. Only writers coordinate, through a mutex, so an occasional
+//! insert never stalls lookups; the price is twice the memory and writes that do
+//! their work twice.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError, TryLockError};
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+
+use crate::batch::{lookup_result, map_keys, KeyBatch, ObjectFills};
+use crate::concurrent::visit_values;
+use crate::hash::hash_keys;
+use crate::map::ElasticMap;
+use crate::table::{check_delta, sizing_for_items};
+use crate::value::PyValue;
+
+/// Two copies of `M` that readers access without locking.
+struct LeftRight<M> {
+    maps: [UnsafeCell<M>; 2],
+    /// Index of the copy new readers use.
+    active: AtomicUsize,
+    /// Number of readers currently inside each copy.
+    readers: [AtomicUsize; 2],
+    writer: Mutex<()>,
+}
+
+// SAFETY: a copy is only mutated by the writer holding `writer`, and only after
+// its reader count has dropped to zero while it is not active.
+unsafe impl<M: Send + Sync> Sync for LeftRight<M> {}
+
+impl<M> LeftRight<M> {
+    fn new(left: M, right: M) -> Self {
+        LeftRight {
+            maps: [UnsafeCell::new(left), UnsafeCell::new(right)],
+            active: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Runs `f` on the active copy. Never blocks.
+    fn read<R>(&self, f: impl FnOnce(&M) -> R) -> R {
+        let side = loop {
+            let side = self.active.load(Ordering::SeqCst);
+            self.readers[side].fetch_add(1, Ordering::SeqCst);
+            // A writer may have flipped `active` before our count became visible;
+            // only a copy that is still active is safe to enter.
+            if self.active.load(Ordering::SeqCst) == side {
+                break side;
+            }
+            self.readers[side].fetch_sub(1, Ordering::SeqCst);
+        };
+        // SAFETY: the writer does not touch this copy while our count is non-zero.
+        let result = f(unsafe { &*self.maps[side].get() });
+        self.readers[side].fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+
+    fn wait_for_readers(&self, side: usize) {
+        while self.readers[side].load(Ordering::SeqCst) != 0 {
+            std::thread::yield_now();
+        }
+    }
+
+    /// Applies `op` to both copies, first to the inactive one. `op` receives
+    /// `true` on the first pass. If the first pass returns `None`, the change is
+    /// abandoned before any reader can see it. Must be called with the GIL released.
+    fn write<R>(&self, op: impl FnMut(&mut M, bool) -> Option<R>) -> Option<R> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        self.write_locked(op)
+    }
+
+    /// Like `write`, but gives up instead of waiting for another writer. Safe
+    /// to call with the GIL held, since the readers it waits for never take it.
+    fn try_write<R>(&self, op: impl FnMut(&mut M, bool) -> Option<R>) -> Option<R> {
+        let _writer = match self.writer.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        self.write_locked(op)
+    }
+
+    /// Runs `f` on both copies, unless a writer is changing them. Never blocks.
+    fn try_inspect<R>(&self, f: impl FnOnce(&M, &M) -> R) -> Option<R> {
+        let _writer = match self.writer.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        // SAFETY: copies are only mutated by the holder of `writer`.
+        Some(unsafe { f(&*self.maps[0].get(), &*self.maps[1].get()) })
+    }
+
+    fn write_locked<R>(&self, mut op: impl FnMut(&mut M, bool) -> Option<R>) -> Option<R> {
+        let old = self.active.load(Ordering::SeqCst);
+        let new = 1 - old;
+        self.wait_for_readers(new);
+        // SAFETY: `new` is inactive and drained, and we are the only writer.
+        let result = op(unsafe { &mut *self.maps[new].get() }, true)?;
+        self.active.store(new, Ordering::SeqCst);
+        self.wait_for_readers(old);
+        // SAFETY: as above, now for the copy that was active.
+        op(unsafe { &mut *self.maps[old].get() }, false);
+        Some(result)
+    }
+}
+
+/// An `ElasticTable` whose lookups never take a lock.
+///
+/// Meant for read-mostly serving: gets and batch lookups from any number of
+/// threads proceed without waiting on writers, so lookup latency is not
+/// affected by occasional inserts. Writers serialize among themselves and
+/// each change is applied to two internal copies, so the table uses twice
+/// the memory of an `ElasticTable`.
+#[pyclass(frozen)]
+pub(crate) struct ReadMostlyElasticTable {
+    maps: LeftRight<ElasticMap<PyValue>>,
+}
+
+impl ReadMostlyElasticTable {
+    fn lookup(&self, py: Python<'_>, key: u64) -> Option<PyObject> {
+        self.maps.read(|map| map.get(key).map(|value| value.to_object(py, &[])))
+    }
+}
+
+#[pymethods]
+impl ReadMostlyElasticTable {
+    /// Create a table with the given slot capacity and delta parameter.
+    /// See ElasticTable for the meaning of the arguments.
+    #[new]
+    #[pyo3(signature = (capacity, delta=0.05))]
+    fn new(capacity: usize, delta: f64) -> PyResult<Self> {
+        check_delta(delta)?;
+        Ok(ReadMostlyElasticTable {
+            maps: LeftRight::new(ElasticMap::new(capacity, delta), ElasticMap::new(capacity, delta)),
+        })
+    }
+
+    /// Create a table sized for `expected_items` at the target load factor.
+    #[staticmethod]
+    #[pyo3(signature = (expected_items, load_factor=0.90))]
+    fn for_items(expected_items: usize, load_factor: f64) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(capacity, delta)
+    }
+
+    fn insert(&self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let value = PyValue::new(value);
+        let mut copies = [Some(value.clone_ref(py)), Some(value)];
+        let mut error = None;
+        let probes = py.allow_threads(|| {
+            self.maps.write(|map, first| {
+                let value = copies[first as usize].take().unwrap();
+                // Both copies hold the same entries, so the second pass succeeds
+                // exactly when the first one did.
+                map.insert(key, value).map_err(|err| error = Some(err)).ok()
+            })
+        });
+        match error {
+            Some(err) => Err(err.into()),
+            None => Ok(probes.unwrap_or_default()),
+        }
+    }
+
+    /// Insert many key/value pairs in a single call. Returns the total probes used.
+    /// Pairs are inserted in order; if one fails, the pairs before it stay inserted.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        if keys.len() != values.len() {
+            return Err(PyValueError::new_err("keys and values must have the same length"));
+        }
+        let values: Vec<PyValue> = values.iter().map(PyValue::new).collect();
+        let mirrors: Vec<PyValue> = values.iter().map(|value| value.clone_ref(py)).collect();
+        let mut copies = [Some(mirrors), Some(values)];
+
+        let mut error = None;
+        let mut inserted = 0;
+        let probes = py.allow_threads(|| {
+            let hashes = hash_keys(&keys);
+            self.maps.write(|map, first| {
+                let values = copies[first as usize].take().unwrap();
+                let mut total_probes = 0;
+                // The second pass replays exactly the pairs the first one inserted.
+                let count = if first { keys.len() } else { inserted };
+                for ((&key, &hash), value) in keys.iter().zip(&hashes).zip(values).take(count) {
+                    match map.insert_hashed(key, hash, value) {
+                        Ok(probes) => total_probes += probes,
+                        Err(err) => {
+                            error = Some(err);
+                            break;
+                        }
+                    }
+                    if first {
+                        inserted += 1;
+                    }
+                }
+                Some(total_probes)
+            })
+        });
+        match error {
+            Some(err) => Err(err.into()),
+            None => Ok(probes.unwrap_or_default()),
+        }
+    }
+
+    /// The value stored under `key`, or `default` if the table does not hold
+    /// the key. See ElasticTable.get.
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python<'_>, key: u64, default: Option<PyObject>) -> PyObject {
+        self.lookup(py, key).or(default).unwrap_or_else(|| py.None())
+    }
+
+    /// `table[key]`: the value stored under `key`. Raises KeyError if the
+    /// table does not hold the key.
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let found = match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key),
+            Err(_) => None,
+        };
+        found.ok_or_else(|| PyKeyError::new_err(key.clone().unbind()))
+    }
+
+    /// `key in table`, which is True for keys holding None too.
+    fn __contains__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key).is_some(),
+            Err(_) => false,
+        })
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> bool {
+        let mut removed = Vec::new();
+        let found = py.allow_threads(|| {
+            self.maps.write(|map, _| {
+                let value = map.remove(key)?;
+                removed.push(value);
+                Some(())
+            })
+        });
+        found.is_some()
+    }
+
+    /// Remove many keys in a single call. Returns the number of keys that were present.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        let mut removed: Vec<PyValue> = Vec::new();
+        let count = py.allow_threads(|| {
+            let hashes = hash_keys(&keys);
+            self.maps.write(|map, _| {
+                let before = removed.len();
+                removed.extend(keys.iter().zip(&hashes).filter_map(|(&key, &hash)| map.remove_hashed(key, hash)));
+                Some(removed.len() - before)
+            })
+        });
+        // Both copies' values are dropped here, with the GIL held.
+        Ok(count.unwrap_or_default())
+    }
+
+    /// Look up many keys in a single call, using `default` (or the matching
+    /// item of `defaults`) for missing keys. With `mask`, returns a
+    /// `(values, found)` pair; see ElasticTable.get_many.
+    #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+    fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<PyObject>, mask: bool, defaults: Option<Vec<PyObject>>) -> PyResult<PyObject> {
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let fills = ObjectFills::new(py, default, defaults, keys.len())?;
+        // The probing runs without the GIL but inside the read, so the values it
+        // finds stay alive until they are converted.
+        let (values, hits) = self.maps.read(|map| {
+            let found: Vec<Option<&PyValue>> = py.allow_threads(|| {
+                map_keys(map, &keys, |key, hash| map.get_hashed(key, hash))
+            });
+            let hits: Option<Vec<bool>> = mask.then(|| found.iter().map(Option::is_some).collect());
+            let values: Vec<PyObject> = found.into_iter()
+                .enumerate().map(|(index, value)| match value {
+                    Some(value) => value.to_object(py, &[]),
+                    None => fills.at(py, index),
+                })
+                .collect();
+            (values, hits)
+        });
+        lookup_result(py, values, hits, keys_buffer)
+    }
+
+    /// Check membership for many keys in a single call.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        let keys = keys.as_slice(py)?;
+        Ok(py.allow_threads(|| {
+            self.maps.read(|map| map_keys(map, &keys, |key, hash| map.contains_hashed(key, hash)))
+        }))
+    }
+
+    /// Remove every entry, keeping the allocated capacity.
+    fn clear(&self, py: Python<'_>) {
+        py.allow_threads(|| {
+            self.maps.write(|map, _| {
+                map.clear();
+                Some(())
+            })
+        });
+    }
+
+    fn stats(&self) -> Vec<(usize, usize, f64)> {
+        self.maps.read(ElasticMap::stats)
+    }
+
+    /// Reports the stored values to the cyclic GC. While a writer is busy the
+    /// values are not reported, which only delays collecting a cycle.
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        let visit_both = |left: &_, right: &_| visit_values(left, &visit).and_then(|()| visit_values(right, &visit));
+        self.maps.try_inspect(visit_both).unwrap_or(Ok(()))
+    }
+
+    fn __clear__(&self) {
+        self.maps.try_write(|map, _| {
+            map.clear();
+            Some(())
+        });
+    }
+}
@@ -0,0 +1,394 @@
+//! `RecordTable`: values packed as fixed-width records.
+//!
+//! The table is built with a `struct` format string such as `"<ffi"`. Each
+//! value is a tuple of fields packed with that format into a record of
+//! `struct.calcsize(format)` bytes, and lookups unpack it back into a tuple.
+//! Records live side by side in one buffer owned by Rust, and slots hold only
+//! their index, so a table of small records needs no Python object per entry
+//! and is about as dense as a structured array. Slots of deleted records are
+//! reused by later inserts.
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyBytes, PyDict, PyTuple};
+
+use crate::batch::{lookup_result, map_keys, KeyBatch, ObjectFills};
+use crate::hash::{hash_key, hash_keys};
+use crate::lock::{TableGuard, TableLock};
+use crate::map::{ElasticMap, InsertError};
+use crate::table::{check_delta, detailed_stats, memory_usage, sizing_for_items};
+use crate::views::{ItemsView, KeysView, ValuesView};
+
+/// The records of a table, `size` bytes each, and the indices free for reuse.
+struct Records {
+    size: usize,
+    bytes: Vec<u8>,
+    free: Vec<u32>,
+}
+
+impl Records {
+    fn get(&self, index: u32) -> &[u8] {
+        let start = index as usize * self.size;
+        &self.bytes[start..start + self.size]
+    }
+
+    fn set(&mut self, index: u32, record: &[u8]) {
+        let start = index as usize * self.size;
+        self.bytes[start..start + self.size].copy_from_slice(record);
+    }
+
+    /// Stores `record` in a free index, or a new one at the end.
+    fn add(&mut self, record: &[u8]) -> Result<u32, InsertError> {
+        if let Some(index) = self.free.pop() {
+            self.set(index, record);
+            return Ok(index);
+        }
+        let index = u32::try_from(self.bytes.len() / self.size).map_err(|_| InsertError::Full)?;
+        self.bytes.extend_from_slice(record);
+        Ok(index)
+    }
+
+    fn release(&mut self, index: u32) {
+        self.free.push(index);
+    }
+
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.free.clear();
+    }
+}
+
+/// A table of fixed-width records described by a `struct` format string.
+#[pyclass(frozen)]
+pub(crate) struct RecordTable {
+    state: TableLock<RecordTableState>,
+}
+
+/// The state of a `RecordTable`, behind its lock.
+pub(crate) struct RecordTableState {
+    map: ElasticMap<u32>,
+    records: Records,
+    /// The `struct.Struct` packing and unpacking records.
+    layout: PyObject,
+}
+
+impl From<RecordTableState> for RecordTable {
+    fn from(state: RecordTableState) -> Self {
+        RecordTable { state: TableLock::new(state) }
+    }
+}
+
+impl RecordTable {
+    /// The table's state, waiting for any call on another thread to finish.
+    pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, RecordTableState>> {
+        self.state.lock(py)
+    }
+}
+
+impl RecordTableState {
+    pub(crate) fn map(&self) -> &ElasticMap<u32> {
+        &self.map
+    }
+
+    /// The record stored under `key` as a tuple, without counting a lookup.
+    pub(crate) fn value_of(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+        self.map.get(key).map(|&index| Ok(self.unpack(py, self.records.get(index))?.unbind())).transpose()
+    }
+
+    /// Packs `record`, a sequence of fields, into its bytes.
+    fn pack(&self, record: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+        let fields = PyTuple::new(record.py(), record.try_iter()?.collect::<PyResult<Vec<_>>>()?)?;
+        let packed = self.layout.bind(record.py()).call_method1("pack", fields)?;
+        Ok(packed.downcast::<PyBytes>()?.as_bytes().to_vec())
+    }
+
+    fn unpack<'py>(&self, py: Python<'py>, record: &[u8]) -> PyResult<Bound<'py, PyAny>> {
+        self.layout.bind(py).call_method1("unpack", (PyBytes::new(py, record),))
+    }
+
+    /// Unpacks records laid end to end in `bytes`, all at once.
+    fn unpack_all<'py>(&self, py: Python<'py>, bytes: &[u8]) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        if bytes.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.layout.bind(py).call_method1("iter_unpack", (PyBytes::new(py, bytes),))?.try_iter()?.collect()
+    }
+
+    /// Stores `record` under `key`, overwriting a stored record in place.
+    fn store(map: &mut ElasticMap<u32>, records: &mut Records, key: u64, hash: u64, record: &[u8]) -> Result<usize, InsertError> {
+        if let Some(&index) = map.get_hashed(key, hash) {
+            records.set(index, record);
+            return Ok(0);
+        }
+        let index = records.add(record)?;
+        map.insert_hashed(key, hash, index).inspect_err(|_| records.release(index))
+    }
+}
+
+impl RecordTableState {
+    fn new(py: Python<'_>, format: &str, capacity: usize, delta: f64) -> PyResult<Self> {
+        check_delta(delta)?;
+        let layout = py.import("struct")?.getattr("Struct")?.call1((format,))?;
+        let size: usize = layout.getattr("size")?.extract()?;
+        if size == 0 {
+            return Err(PyValueError::new_err("format must describe at least one field"));
+        }
+        Ok(RecordTableState {
+            map: ElasticMap::new(capacity, delta),
+            records: Records { size, bytes: Vec::new(), free: Vec::new() },
+            layout: layout.unbind(),
+        })
+    }
+
+    fn for_items(py: Python<'_>, format: &str, expected_items: usize, load_factor: f64) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(py, format, capacity, delta)
+    }
+
+    fn format(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.layout.getattr(py, "format")
+    }
+
+    fn record_size(&self) -> usize {
+        self.records.size
+    }
+
+    fn insert(&mut self, key: u64, record: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let record = self.pack(record)?;
+        Ok(Self::store(&mut self.map, &mut self.records, key, hash_key(key), &record)?)
+    }
+
+    fn insert_many(&mut self, py: Python<'_>, keys: KeyBatch, records: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        if keys.len() != records.len() {
+            return Err(PyValueError::new_err("keys and records must have the same length"));
+        }
+        let packed = records.iter().map(|record| self.pack(record)).collect::<PyResult<Vec<_>>>()?;
+        let (map, records) = (&mut self.map, &mut self.records);
+        let probes = py.allow_threads(|| {
+            let hashes = hash_keys(&keys);
+            keys.iter().zip(&hashes).zip(&packed)
+                .map(|((&key, &hash), record)| Self::store(map, records, key, hash, record))
+                .sum::<Result<usize, InsertError>>()
+        });
+        Ok(probes?)
+    }
+
+    fn get<'py>(&self, py: Python<'py>, key: u64) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let found = self.map.get(key);
+        self.map.count_lookups(1, found.is_some() as usize);
+        found.map(|&index| self.unpack(py, self.records.get(index))).transpose()
+    }
+
+    fn delete(&mut self, key: u64) -> bool {
+        let removed = self.map.remove(key);
+        removed.inspect(|&index| self.records.release(index)).is_some()
+    }
+
+    fn delete_many(&mut self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        Ok(keys.iter().filter(|&&key| self.delete(key)).count())
+    }
+
+    fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<PyObject>, mask: bool, defaults: Option<Vec<PyObject>>) -> PyResult<PyObject> {
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let map = &self.map;
+        let found = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.get_hashed(key, hash).copied()));
+        let hits: Vec<u8> = found.iter().flatten().flat_map(|&index| self.records.get(index)).copied().collect();
+        map.count_lookups(found.len(), hits.len() / self.records.size);
+        let mut unpacked = self.unpack_all(py, &hits)?.into_iter();
+        let fills = ObjectFills::new(py, default, defaults, keys.len())?;
+        let found_mask = mask.then(|| found.iter().map(Option::is_some).collect());
+        let values: Vec<PyObject> = found.into_iter()
+            .enumerate().map(|(position, index)| match index {
+                Some(_) => unpacked.next().expect("one tuple per record").unbind(),
+                None => fills.at(py, position),
+            })
+            .collect();
+        lookup_result(py, values, found_mask, keys_buffer)
+    }
+
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        let keys = keys.as_slice(py)?;
+        let map = &self.map;
+        let found = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.contains_hashed(key, hash)));
+        map.count_lookups(found.len(), found.iter().filter(|&&hit| hit).count());
+        Ok(found)
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.records.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    fn remaining_capacity(&self) -> usize {
+        self.map.remaining_capacity()
+    }
+
+    fn is_full(&self) -> bool {
+        self.map.remaining_capacity() == 0
+    }
+
+    fn stats(&self) -> Vec<(usize, usize, f64)> {
+        self.map.stats()
+    }
+
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = memory_usage(py, &self.map, |_| None)?;
+        let records = self.records.bytes.len() + 4 * self.records.free.len();
+        let total: usize = dict.get_item("total_bytes")?.map_or(Ok(0), |total| total.extract())?;
+        dict.set_item("record_bytes", records)?;
+        dict.set_item("total_bytes", total + records)?;
+        Ok(dict)
+    }
+
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        detailed_stats(py, &self.map)
+    }
+
+    fn validate(&self, py: Python<'_>) -> Vec<String> {
+        py.allow_threads(|| self.map.validate())
+    }
+}
+
+#[pymethods]
+impl RecordTable {
+    /// Create a table with the given slot capacity and delta parameter for
+    /// records of the `struct` format `format`. See ElasticTable for the
+    /// meaning of the other arguments.
+    #[new]
+    #[pyo3(signature = (format, capacity, delta=0.05))]
+    fn new(py: Python<'_>, format: &str, capacity: usize, delta: f64) -> PyResult<Self> {
+        RecordTableState::new(py, format, capacity, delta).map(Self::from)
+    }
+
+    /// Create a table sized for `expected_items` at the target load factor.
+    #[staticmethod]
+    #[pyo3(signature = (format, expected_items, load_factor=0.90))]
+    fn for_items(py: Python<'_>, format: &str, expected_items: usize, load_factor: f64) -> PyResult<Self> {
+        RecordTableState::for_items(py, format, expected_items, load_factor).map(Self::from)
+    }
+
+    /// The `struct` format string of the records.
+    #[getter]
+    fn format(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.lock(py)?.format(py)
+    }
+
+    /// Bytes per record.
+    #[getter]
+    fn record_size(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.record_size())
+    }
+
+    /// Pack `record`, a tuple of fields matching the format, and store it.
+    /// Returns the probes used.
+    fn insert(&self, py: Python<'_>, key: u64, record: &Bound<'_, PyAny>) -> PyResult<usize> {
+        self.lock(py)?.insert(key, record)
+    }
+
+    /// Insert many key/record pairs in a single call. Returns the total probes used.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, records: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.lock(py)?.insert_many(py, keys, records)
+    }
+
+    /// The record stored under `key` as a tuple, or None.
+    fn get<'py>(&self, py: Python<'py>, key: u64) -> PyResult<Option<Bound<'py, PyAny>>> {
+        self.lock(py)?.get(py, key)
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        Ok(self.lock(py)?.delete(key))
+    }
+
+    /// Remove many keys in a single call. Returns how many were present.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        self.lock(py)?.delete_many(py, keys)
+    }
+
+    /// Look up many keys in a single call, using `default` (or the matching
+    /// item of `defaults`) for missing keys.
+    /// Large batches are probed in parallel, and the records found are
+    /// unpacked in one pass. With `mask`, returns a `(values, found)` pair;
+    /// see ElasticTable.get_many.
+    #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+    fn get_many(
+        &self,
+        py: Python<'_>,
+        keys: KeyBatch,
+        default: Option<PyObject>,
+        mask: bool,
+        defaults: Option<Vec<PyObject>>,
+    ) -> PyResult<PyObject> {
+        self.lock(py)?.get_many(py, keys, default, mask, defaults)
+    }
+
+    /// Check membership for many keys in a single call.
+    /// Large batches are probed in parallel.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        self.lock(py)?.contains_many(py, keys)
+    }
+
+    /// A live, set-like view of the keys, in slot order. See ElasticTable.keys.
+    fn keys(slf: &Bound<'_, Self>) -> KeysView {
+        KeysView::new(slf.clone().unbind())
+    }
+
+    /// A live view of the records as tuples, in the order of `keys()`.
+    fn values(slf: &Bound<'_, Self>) -> ValuesView {
+        ValuesView::new(slf.clone().unbind())
+    }
+
+    /// A live, set-like view of the `(key, record)` pairs, in the order of `keys()`.
+    fn items(slf: &Bound<'_, Self>) -> ItemsView {
+        ItemsView::new(slf.clone().unbind())
+    }
+
+    fn clear(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.clear();
+        Ok(())
+    }
+
+    /// Number of slots the table was created with.
+    fn capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.capacity())
+    }
+
+    /// See `ElasticTable.remaining_capacity`.
+    fn remaining_capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.remaining_capacity())
+    }
+
+    /// See `ElasticTable.is_full`.
+    fn is_full(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(self.lock(py)?.is_full())
+    }
+
+    fn stats(&self, py: Python<'_>) -> PyResult<Vec<(usize, usize, f64)>> {
+        Ok(self.lock(py)?.stats())
+    }
+
+    /// Bytes of memory the table takes. See ElasticTable.memory_usage; a slot
+    /// holds the index of its record, and "record_bytes", also counted in
+    /// "total_bytes", gives the bytes of the record buffer, including
+    /// records freed by deletes and not reused yet.
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.memory_usage(py)
+    }
+
+    /// A structured version of `stats()`. See ElasticTable.detailed_stats.
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.detailed_stats(py)
+    }
+
+    /// Check the table's internal invariants. See ElasticTable.validate.
+    fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        Ok(self.lock(py)?.validate(py))
+    }
+}
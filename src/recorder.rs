@@ -0,0 +1,142 @@
+//! Operation recordings of typed tables, for `start_recording` and `replay`.
+//!
+//! Keys are hashed without a seed, so where a table puts its keys depends only
+//! on the parameters it was created with and the changes made to it. A
+//! recording holds both: a header with the parameters, then every change as a
+//! change log record (see `wal`), inserts that failed included, so replaying
+//! it into a fresh table repeats the original's probes exactly.
+//!
+//! ```text
+//! | magic (8) | kind (4) | adaptive (4) | capacity (8) | delta (8) |
+//! | growth factor (8) | grow at (8) | max capacity (8) | crc32 of the above (4) | 0 (4) | records |
+//! ```
+//!
+//! A growth factor of zero means the table does not grow, and a max capacity
+//! of zero that its growth is unbounded. Records are handed to the OS at the
+//! end of every call; replay stops at the first incomplete or corrupted
+//! record, which is where a crash cut the recording off.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+use crate::errors::CorruptedDataError;
+use crate::map::{ElasticMap, GrowthPolicy};
+use crate::storage::crc32;
+use crate::wal::Record;
+
+const MAGIC: [u8; 8] = *b"ELHRECRD";
+const PARAMS_LEN: usize = 56;
+const HEADER_LEN: usize = PARAMS_LEN + 8;
+
+/// The parameters of the table a recording was taken on.
+pub(crate) struct Params {
+    pub(crate) capacity: usize,
+    pub(crate) delta: f64,
+    pub(crate) adaptive: bool,
+    pub(crate) growth: Option<GrowthPolicy>,
+}
+
+impl Params {
+    fn encode(&self, kind: u32) -> [u8; HEADER_LEN] {
+        let growth = self.growth.as_ref();
+        let mut header = [0; HEADER_LEN];
+        header[0..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&kind.to_le_bytes());
+        header[12..16].copy_from_slice(&(self.adaptive as u32).to_le_bytes());
+        header[16..24].copy_from_slice(&(self.capacity as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&self.delta.to_le_bytes());
+        header[32..40].copy_from_slice(&growth.map_or(0.0, |growth| growth.factor).to_le_bytes());
+        header[40..48].copy_from_slice(&growth.map_or(0.0, |growth| growth.trigger).to_le_bytes());
+        header[48..56].copy_from_slice(&(growth.and_then(|growth| growth.max_capacity).unwrap_or(0) as u64).to_le_bytes());
+        let crc = crc32(0, &header[..PARAMS_LEN]);
+        header[PARAMS_LEN..PARAMS_LEN + 4].copy_from_slice(&crc.to_le_bytes());
+        header
+    }
+
+    fn decode(header: &[u8; HEADER_LEN], kind: u32) -> PyResult<Self> {
+        if header[0..8] != MAGIC {
+            return Err(PyValueError::new_err("not an elastic hash table recording"));
+        }
+        let word = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+        let half = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        if crc32(0, &header[..PARAMS_LEN]) != half(PARAMS_LEN) {
+            return Err(CorruptedDataError::new_err("recording header is corrupted"));
+        }
+        if half(8) != kind {
+            return Err(PyValueError::new_err("recording was taken on a table of a different value type"));
+        }
+        let factor = f64::from_bits(word(32));
+        let growth = (factor != 0.0).then(|| GrowthPolicy {
+            factor,
+            trigger: f64::from_bits(word(40)),
+            max_capacity: Some(word(48) as usize).filter(|&max| max != 0),
+        });
+        Ok(Params {
+            capacity: word(16) as usize,
+            delta: f64::from_bits(word(24)),
+            adaptive: half(12) != 0,
+            growth,
+        })
+    }
+}
+
+/// An open recording that records are appended to.
+pub(crate) struct Recording {
+    out: BufWriter<File>,
+    path: PathBuf,
+}
+
+impl Recording {
+    /// Starts a recording at `path`, replacing any file there, of a table of
+    /// value kind `kind` with the parameters of `map`. `entries`, the records
+    /// inserting what the table holds, come first.
+    pub(crate) fn create<V>(
+        path: PathBuf,
+        kind: u32,
+        map: &ElasticMap<V>,
+        entries: impl IntoIterator<Item = Record>,
+    ) -> io::Result<Recording> {
+        let params = Params {
+            capacity: map.capacity(),
+            delta: map.delta(),
+            adaptive: map.tunes_probes(),
+            growth: map.growth(),
+        };
+        let mut out = BufWriter::new(File::create(&path)?);
+        out.write_all(&params.encode(kind))?;
+        for record in entries {
+            out.write_all(&record.encode())?;
+        }
+        out.flush()?;
+        Ok(Recording { out, path })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn append(&mut self, record: Record) -> io::Result<()> {
+        self.out.write_all(&record.encode())
+    }
+
+    /// Hands buffered records to the OS, so they survive the process crashing.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Opens the recording at `path` of a table of value kind `kind`, returning
+/// its parameters and a reader positioned at its first record.
+pub(crate) fn open_recording(path: &Path, kind: u32) -> PyResult<(Params, impl Read)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => PyValueError::new_err("not an elastic hash table recording"),
+        _ => err.into(),
+    })?;
+    Ok((Params::decode(&header, kind)?, reader))
+}
@@ -0,0 +1,126 @@
+//! Memory the slot arrays of a map can live in.
+//!
+//! An `Arena` normally owns its slot arrays as vectors. It can instead view
+//! them in a `Region`, memory kept alive by some other owner, such as a file
+//! loaded or mapped by `storage`. Regions start with the header and counts
+//! `storage` writes, followed by the slot arrays at the offsets `Layout`
+//! assigns.
+
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+pub(crate) const HEADER_LEN: usize = 128;
+/// Subarrays halve in size, so no map has more than this many.
+pub(crate) const MAX_SUBARRAYS: usize = 64;
+pub(crate) const COUNTS_LEN: usize = MAX_SUBARRAYS * 16;
+
+/// Memory owned by a foreign object that stays alive as long as the region.
+pub(crate) struct Region {
+    ptr: NonNull<u8>,
+    len: usize,
+    _owner: Box<dyn Send + Sync>,
+}
+
+// SAFETY: the region is plain memory; access to it is synchronized by the
+// table that owns it, exactly as for heap-allocated slots.
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads and writes of `len` bytes, aligned to 8,
+    /// for as long as `owner` is alive.
+    pub(crate) unsafe fn new(ptr: *mut u8, len: usize, owner: Box<dyn Send + Sync>) -> Result<Self, &'static str> {
+        let ptr = NonNull::new(ptr).ok_or("region is empty")?;
+        if !(ptr.as_ptr() as usize).is_multiple_of(8) {
+            return Err("region is not 8-byte aligned");
+        }
+        Ok(Region { ptr, len, _owner: owner })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        // SAFETY: guaranteed by `Region::new`.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub(crate) fn bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: guaranteed by `Region::new`.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// The header and counts, which no slot array overlaps.
+    pub(crate) fn prefix_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes_mut()[..HEADER_LEN + COUNTS_LEN]
+    }
+}
+
+/// One slot array: either a heap vector or a typed view into a `Region`.
+pub(crate) enum Slots<T> {
+    Owned(Vec<T>),
+    Mapped { ptr: NonNull<T>, len: usize },
+}
+
+// SAFETY: a mapped view behaves like a `&mut [T]` into memory kept alive by the
+// arena's `Region`.
+unsafe impl<T: Send> Send for Slots<T> {}
+unsafe impl<T: Sync> Sync for Slots<T> {}
+
+impl<T> Slots<T> {
+    /// A view of `len` items of `T` at byte `offset` of `region`.
+    ///
+    /// # Safety
+    ///
+    /// The range must lie inside the region, be aligned for `T`, hold valid bit
+    /// patterns for `T`, and not overlap any other view.
+    pub(crate) unsafe fn mapped(region: &mut Region, offset: usize, len: usize) -> Self {
+        let ptr = region.bytes_mut()[offset..].as_mut_ptr() as *mut T;
+        Slots::Mapped { ptr: NonNull::new_unchecked(ptr), len }
+    }
+}
+
+impl<T> Deref for Slots<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Slots::Owned(items) => items,
+            // SAFETY: see `Slots::mapped`.
+            Slots::Mapped { ptr, len } => unsafe { std::slice::from_raw_parts(ptr.as_ptr(), *len) },
+        }
+    }
+}
+
+impl<T> DerefMut for Slots<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Slots::Owned(items) => items,
+            // SAFETY: see `Slots::mapped`.
+            Slots::Mapped { ptr, len } => unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), *len) },
+        }
+    }
+}
+
+/// Byte offsets of the slot arrays of a table with `slots` arena slots.
+#[derive(Clone, Copy)]
+pub(crate) struct Layout {
+    pub(crate) slots: usize,
+    pub(crate) ctrl: usize,
+    pub(crate) keys: usize,
+    pub(crate) values: usize,
+    pub(crate) len: usize,
+}
+
+impl Layout {
+    /// Layout for 8-byte keys and 8-byte values.
+    pub(crate) fn new(slots: usize) -> Self {
+        let ctrl = HEADER_LEN + COUNTS_LEN;
+        let keys = ctrl + slots.div_ceil(8) * 8;
+        let values = keys + slots * 8;
+        Layout { slots, ctrl, keys, values, len: values + slots * 8 }
+    }
+}
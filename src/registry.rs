@@ -0,0 +1,274 @@
+//! `TableRegistry`: named `ElasticTable`s managed behind one object.
+//!
+//! Services keeping one table per tenant or per dataset create, look up and
+//! drop their tables by name, and get a single report over all of them.
+//! Dropping a table only removes it from the registry; references to it held
+//! elsewhere keep working.
+
+use std::collections::BTreeMap;
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::types::{PyDict, PyTuple};
+
+use crate::lock::{TableGuard, TableLock};
+use crate::table::ElasticTable;
+
+/// A set of ElasticTables addressed by name.
+#[pyclass(frozen)]
+pub(crate) struct TableRegistry {
+    state: TableLock<TableRegistryState>,
+}
+
+/// The state of a `TableRegistry`, behind its lock.
+pub(crate) struct TableRegistryState {
+    tables: BTreeMap<String, Py<ElasticTable>>,
+}
+
+impl From<TableRegistryState> for TableRegistry {
+    fn from(state: TableRegistryState) -> Self {
+        TableRegistry { state: TableLock::new(state) }
+    }
+}
+
+impl TableRegistry {
+    /// The registry's state, waiting for any call on another thread to finish.
+    pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, TableRegistryState>> {
+        self.state.lock(py)
+    }
+}
+
+impl TableRegistryState {
+    fn check_free(&self, name: &str) -> PyResult<()> {
+        if self.tables.contains_key(name) {
+            return Err(PyValueError::new_err(format!("a table named {name:?} already exists")));
+        }
+        Ok(())
+    }
+
+    fn new() -> Self {
+        TableRegistryState { tables: BTreeMap::new() }
+    }
+
+    fn create(
+        &mut self,
+        py: Python<'_>,
+        name: String,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<ElasticTable>> {
+        self.check_free(&name)?;
+        let table: Py<ElasticTable> = py.get_type::<ElasticTable>().call(args, kwargs)?.extract()?;
+        self.tables.insert(name, table.clone_ref(py));
+        Ok(table)
+    }
+
+    fn create_for_items(
+        &mut self,
+        py: Python<'_>,
+        name: String,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<ElasticTable>> {
+        self.check_free(&name)?;
+        let table: Py<ElasticTable> = py.get_type::<ElasticTable>().call_method("for_items", args, kwargs)?.extract()?;
+        self.tables.insert(name, table.clone_ref(py));
+        Ok(table)
+    }
+
+    fn add(&mut self, name: String, table: Py<ElasticTable>) -> PyResult<()> {
+        self.check_free(&name)?;
+        self.tables.insert(name, table);
+        Ok(())
+    }
+
+    fn get(&self, py: Python<'_>, name: &str) -> Option<Py<ElasticTable>> {
+        self.tables.get(name).map(|table| table.clone_ref(py))
+    }
+
+    fn remove(&mut self, name: &str) -> bool {
+        self.tables.remove(name).is_some()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    fn clear(&mut self) {
+        self.tables.clear();
+    }
+
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let per_table = PyDict::new(py);
+        let (mut count, mut capacity, mut bytes) = (0, 0, 0);
+        for (name, table) in &self.tables {
+            let table = table.get().lock(py)?;
+            if table.check_open().is_err() {
+                continue;
+            }
+            let map = table.map();
+            let table_bytes = map.memory().iter().map(|sub| sub.ctrl + sub.keys + sub.values).sum::<usize>()
+                + map.filter_bytes()
+                + map.order_bytes();
+            let entry = PyDict::new(py);
+            entry.set_item("count", map.len())?;
+            entry.set_item("capacity", map.capacity())?;
+            entry.set_item("load_factor", map.load_factor())?;
+            entry.set_item("total_bytes", table_bytes)?;
+            per_table.set_item(name, entry)?;
+            count += map.len();
+            capacity += map.capacity();
+            bytes += table_bytes;
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("tables", per_table.len())?;
+        dict.set_item("count", count)?;
+        dict.set_item("capacity", capacity)?;
+        dict.set_item("load_factor", if capacity == 0 { 0.0 } else { count as f64 / capacity as f64 })?;
+        dict.set_item("total_bytes", bytes)?;
+        dict.set_item("per_table", per_table)?;
+        Ok(dict)
+    }
+
+    fn __len__(&self) -> usize {
+        self.tables.len()
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.tables.contains_key(name)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<Py<ElasticTable>> {
+        self.get(py, name).ok_or_else(|| PyKeyError::new_err(name.to_owned()))
+    }
+
+    fn __delitem__(&mut self, name: &str) -> PyResult<()> {
+        match self.tables.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(PyKeyError::new_err(name.to_owned())),
+        }
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(self.names().into_pyobject(py)?.try_iter()?.into_any().unbind())
+    }
+
+    fn traverse(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        for table in self.tables.values() {
+            visit.call(table)?;
+        }
+        Ok(())
+    }
+
+    fn clear_references(&mut self) {
+        self.tables.clear();
+    }
+}
+
+#[pymethods]
+impl TableRegistry {
+    #[new]
+    fn new() -> Self {
+        Self::from(TableRegistryState::new())
+    }
+
+    /// Create a table named `name` and return it. The other arguments are
+    /// those of the ElasticTable constructor. Raises ValueError if the name is
+    /// taken.
+    #[pyo3(signature = (name, *args, **kwargs))]
+    fn create(
+        &self,
+        py: Python<'_>,
+        name: String,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<ElasticTable>> {
+        self.lock(py)?.create(py, name, args, kwargs)
+    }
+
+    /// Like `create`, with the arguments of ElasticTable.for_items.
+    #[pyo3(signature = (name, *args, **kwargs))]
+    fn create_for_items(
+        &self,
+        py: Python<'_>,
+        name: String,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<ElasticTable>> {
+        self.lock(py)?.create_for_items(py, name, args, kwargs)
+    }
+
+    /// Register an existing table under `name`.
+    fn add(&self, py: Python<'_>, name: String, table: Py<ElasticTable>) -> PyResult<()> {
+        self.lock(py)?.add(name, table)
+    }
+
+    /// The table named `name`, or None.
+    fn get(&self, py: Python<'_>, name: &str) -> PyResult<Option<Py<ElasticTable>>> {
+        Ok(self.lock(py)?.get(py, name))
+    }
+
+    /// Remove the table named `name` from the registry. Returns True if there
+    /// was one. The table itself stays usable through other references.
+    fn drop(&self, py: Python<'_>, name: &str) -> PyResult<bool> {
+        Ok(self.lock(py)?.remove(name))
+    }
+
+    /// The names of the tables, sorted.
+    fn names(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        Ok(self.lock(py)?.names())
+    }
+
+    /// Remove every table from the registry.
+    fn clear(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.clear();
+        Ok(())
+    }
+
+    /// Totals over all tables.
+    ///
+    /// Returns a dict with the number of "tables", their combined "count",
+    /// "capacity" and "load_factor", the "total_bytes" of `memory_usage()`
+    /// summed over them, and "per_table", a dict from each name to its
+    /// "count", "capacity", "load_factor" and "total_bytes". Closed tables are
+    /// left out.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.stats(py)
+    }
+
+    fn __len__(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.lock(py)?.__len__())
+    }
+
+    fn __contains__(&self, py: Python<'_>, name: &str) -> PyResult<bool> {
+        Ok(self.lock(py)?.__contains__(name))
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<Py<ElasticTable>> {
+        self.lock(py)?.__getitem__(py, name)
+    }
+
+    fn __delitem__(&self, py: Python<'_>, name: &str) -> PyResult<()> {
+        self.lock(py)?.__delitem__(name)
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.lock(py)?.__iter__(py)
+    }
+
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        // A table in use by a call is not reported, which only delays
+        // collecting a cycle through it.
+        match self.state.try_lock() {
+            Some(state) => state.traverse(visit),
+            None => Ok(()),
+        }
+    }
+
+    fn __clear__(&self) {
+        if let Some(mut state) = self.state.try_lock() {
+            state.clear_references();
+        }
+    }
+}
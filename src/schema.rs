@@ -0,0 +1,63 @@
+//! Value checks of tables built with `value_type`.
+//!
+//! A table with a schema checks every value before storing it, so a value of
+//! the wrong type fails the insert that brought it instead of the code that
+//! reads it back later.
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::PyTypeError;
+use pyo3::types::{PyTuple, PyType};
+
+pub(crate) enum Schema {
+    /// A type or tuple of types, checked with `isinstance`.
+    Type(PyObject),
+    /// A callable that returns a false value, or raises, for values it rejects.
+    Check(PyObject),
+}
+
+impl Schema {
+    pub(crate) fn new(value_type: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let is_types = value_type.is_instance_of::<PyType>()
+            || value_type.downcast::<PyTuple>().is_ok_and(|types| types.iter().all(|t| t.is_instance_of::<PyType>()));
+        if is_types {
+            Ok(Schema::Type(value_type.clone().unbind()))
+        } else if value_type.is_callable() {
+            Ok(Schema::Check(value_type.clone().unbind()))
+        } else {
+            Err(PyTypeError::new_err("value_type must be a type, a tuple of types or a callable"))
+        }
+    }
+
+    /// The type, tuple or callable the schema was built from.
+    pub(crate) fn object(&self) -> &PyObject {
+        match self {
+            Schema::Type(types) | Schema::Check(types) => types,
+        }
+    }
+
+    /// Raises TypeError unless `value` conforms.
+    pub(crate) fn check(&self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        let py = value.py();
+        let accepted = match self {
+            Schema::Type(types) => value.is_instance(types.bind(py))?,
+            Schema::Check(check) => check.bind(py).call1((value,))?.is_truthy()?,
+        };
+        if accepted {
+            return Ok(());
+        }
+        let expected = self.object().bind(py);
+        let expected = match self {
+            Schema::Type(_) if expected.is_instance_of::<PyType>() => expected.getattr("__name__")?.str()?.to_string(),
+            _ => expected.repr()?.to_string(),
+        };
+        Err(PyTypeError::new_err(match self {
+            Schema::Type(_) => format!("value {} is not an instance of {expected}", value.repr()?),
+            Schema::Check(_) => format!("value {} was rejected by {expected}", value.repr()?),
+        }))
+    }
+
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        visit.call(self.object())
+    }
+}
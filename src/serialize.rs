@@ -0,0 +1,142 @@
+//! serde support for `ElasticMap`, behind the `serde` feature.
+//!
+//! A map serializes as a struct of its `capacity`, its `delta` and its
+//! `entries`, a sequence of `(key, value)` pairs, so it comes back with the
+//! capacity it was saved with and keys need not serialize as strings. Entries
+//! are inserted as they are read, without collecting them first, which is
+//! why `entries` must come after the other two fields, where `Serialize` puts
+//! it.
+
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::generic::ElasticMap;
+
+const FIELDS: &[&str] = &["capacity", "delta", "entries"];
+
+/// Serializes the entries of a map as a sequence of pairs.
+struct Entries<'a, K, V, S>(&'a ElasticMap<K, V, S>);
+
+impl<K: Serialize, V: Serialize, S> Serialize for Entries<'_, K, V, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for entry in self.0.iter() {
+            seq.serialize_element(&entry)?;
+        }
+        seq.end()
+    }
+}
+
+impl<K: Serialize, V: Serialize, S> Serialize for ElasticMap<K, V, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let mut state = serializer.serialize_struct("ElasticMap", FIELDS.len())?;
+        state.serialize_field("capacity", &self.capacity())?;
+        state.serialize_field("delta", &self.delta())?;
+        state.serialize_field("entries", &Entries(self))?;
+        state.end()
+    }
+}
+
+/// Inserts a serialized sequence of pairs into a map.
+struct EntriesSeed<'a, K, V, S>(&'a mut ElasticMap<K, V, S>);
+
+impl<'de, K, V, S> DeserializeSeed<'de> for EntriesSeed<'_, K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, K, V, S> Visitor<'de> for EntriesSeed<'_, K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence of (key, value) pairs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some((key, value)) = seq.next_element::<(K, V)>()? {
+            self.0.insert(key, value)
+                .map_err(|err| de::Error::custom(format_args!("cannot restore an entry: {err}")))?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the map once `capacity` and `delta` are known.
+fn empty_map<K, V, S: Default, E: de::Error>(capacity: usize, delta: f64) -> Result<ElasticMap<K, V, S>, E> {
+    if !(delta > 0.0 && delta < 1.0) {
+        return Err(E::custom("delta must be between 0 and 1"));
+    }
+    Ok(ElasticMap::with_hasher(capacity, delta, S::default()))
+}
+
+struct MapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    type Value = ElasticMap<K, V, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an ElasticMap")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let capacity = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let delta = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let mut map = empty_map(capacity, delta)?;
+        seq.next_element_seed(EntriesSeed(&mut map))?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        Ok(map)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut fields: A) -> Result<Self::Value, A::Error> {
+        let (mut capacity, mut delta, mut map) = (None, None, None);
+        while let Some(field) = fields.next_key::<String>()? {
+            match field.as_str() {
+                "capacity" => capacity = Some(fields.next_value()?),
+                "delta" => delta = Some(fields.next_value()?),
+                "entries" => {
+                    let (Some(capacity), Some(delta)) = (capacity, delta) else {
+                        return Err(de::Error::custom("entries must come after capacity and delta"));
+                    };
+                    let mut empty = empty_map(capacity, delta)?;
+                    fields.next_value_seed(EntriesSeed(&mut empty))?;
+                    map = Some(empty);
+                }
+                other => return Err(de::Error::unknown_field(other, FIELDS)),
+            }
+        }
+        map.ok_or_else(|| de::Error::missing_field("entries"))
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for ElasticMap<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("ElasticMap", FIELDS, MapVisitor(PhantomData))
+    }
+}
@@ -0,0 +1,257 @@
+//! `ShardedElasticTable`: keys partitioned across independently locked maps.
+//!
+//! Each key belongs to the shard picked by the low bits of its base hash (the
+//! subarrays re-mix the hash, and fingerprints use the top bits, so shards see
+//! well-spread keys). Writers lock only the shards they touch, so threads
+//! inserting into different shards do not wait for each other, and large
+//! batches are applied to their shards in parallel. Locking follows the rules
+//! in `concurrent`: no thread blocks on a shard lock while holding the GIL, and
+//! no thread ever holds more than one write lock.
+
+use std::sync::{RwLock, TryLockError};
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use rayon::prelude::*;
+
+use crate::batch::{lookup_result, map_keys_with, KeyBatch, ObjectFills, PARALLEL_THRESHOLD};
+use crate::concurrent::{clear_unless_locked, read, read_with_gil, visit_values, write};
+use crate::hash::{hash_key, hash_keys, shard_of};
+use crate::map::{ElasticMap, InsertError};
+use crate::table::{check_delta, sizing_for_items};
+use crate::value::PyValue;
+
+/// A thread-safe `ElasticTable` split into independently locked shards.
+///
+/// Unlike `ConcurrentElasticTable`, inserts into different shards proceed in
+/// parallel, which suits insert-heavy workloads from many threads.
+#[pyclass(frozen)]
+pub(crate) struct ShardedElasticTable {
+    shards: Box<[RwLock<ElasticMap<PyValue>>]>,
+}
+
+impl ShardedElasticTable {
+    fn shard_of(&self, hash: u64) -> usize {
+        shard_of(hash, self.shards.len())
+    }
+
+    fn lookup(&self, py: Python<'_>, key: u64) -> Option<PyObject> {
+        let hash = hash_key(key);
+        let shard = read_with_gil(py, &self.shards[self.shard_of(hash)]);
+        shard.get_hashed(key, hash).map(|value| value.to_object(py, &[]))
+    }
+
+    /// Splits `items` by shard, keeping their relative order within each shard.
+    fn partition<T>(&self, items: impl Iterator<Item = (u64, u64, T)>) -> Vec<Vec<(u64, u64, T)>> {
+        let mut buckets: Vec<Vec<_>> = (0..self.shards.len()).map(|_| Vec::new()).collect();
+        for (key, hash, item) in items {
+            buckets[self.shard_of(hash)].push((key, hash, item));
+        }
+        buckets
+    }
+
+    /// Runs `f` on every shard with its bucket of items, in parallel for large batches.
+    fn apply<T, R, F>(&self, buckets: Vec<Vec<(u64, u64, T)>>, total: usize, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(&mut ElasticMap<PyValue>, Vec<(u64, u64, T)>) -> R + Sync + Send,
+    {
+        let run = |(shard, bucket): (&RwLock<ElasticMap<PyValue>>, Vec<_>)| {
+            if bucket.is_empty() {
+                return None;
+            }
+            Some(f(&mut write(shard), bucket))
+        };
+        if total < PARALLEL_THRESHOLD {
+            self.shards.iter().zip(buckets).filter_map(run).collect()
+        } else {
+            self.shards.par_iter().zip(buckets).filter_map(run).collect()
+        }
+    }
+}
+
+#[pymethods]
+impl ShardedElasticTable {
+    /// Create a table with `capacity` slots split evenly across `shards` shards.
+    /// See ElasticTable for the meaning of `delta`.
+    #[new]
+    #[pyo3(signature = (capacity, delta=0.05, shards=16))]
+    fn new(capacity: usize, delta: f64, shards: usize) -> PyResult<Self> {
+        check_delta(delta)?;
+        if shards == 0 {
+            return Err(PyValueError::new_err("shards must be at least 1"));
+        }
+        let per_shard = capacity.div_ceil(shards);
+        Ok(ShardedElasticTable {
+            shards: (0..shards).map(|_| RwLock::new(ElasticMap::new(per_shard, delta))).collect(),
+        })
+    }
+
+    /// Create a table sized for `expected_items` at the target load factor.
+    #[staticmethod]
+    #[pyo3(signature = (expected_items, load_factor=0.90, shards=16))]
+    fn for_items(expected_items: usize, load_factor: f64, shards: usize) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(capacity, delta, shards)
+    }
+
+    /// Number of shards.
+    #[getter]
+    fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn insert(&self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let value = PyValue::new(value);
+        let hash = hash_key(key);
+        let shard = &self.shards[self.shard_of(hash)];
+        Ok(py.allow_threads(|| write(shard).insert_hashed(key, hash, value))?)
+    }
+
+    /// Insert many key/value pairs in a single call. Returns the total probes used.
+    ///
+    /// Pairs are grouped by shard and each shard is locked once. Within a shard
+    /// pairs are inserted in order; if an insertion fails, other shards may
+    /// already hold their part of the batch.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        if keys.len() != values.len() {
+            return Err(PyValueError::new_err("keys and values must have the same length"));
+        }
+        let values: Vec<PyValue> = values.iter().map(PyValue::new).collect();
+
+        let result = py.allow_threads(|| {
+            let hashes = hash_keys(&keys);
+            let items = keys.iter().zip(&hashes).zip(values).map(|((&key, &hash), value)| (key, hash, value));
+            let buckets = self.partition(items);
+            let probes = self.apply(buckets, keys.len(), |map, bucket| {
+                let mut total_probes = 0;
+                for (key, hash, value) in bucket {
+                    total_probes += map.insert_hashed(key, hash, value)?;
+                }
+                Ok::<_, InsertError>(total_probes)
+            });
+            probes.into_iter().sum::<Result<usize, InsertError>>()
+        });
+        Ok(result?)
+    }
+
+    /// The value stored under `key`, or `default` if the table does not hold
+    /// the key. See ElasticTable.get.
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python<'_>, key: u64, default: Option<PyObject>) -> PyObject {
+        self.lookup(py, key).or(default).unwrap_or_else(|| py.None())
+    }
+
+    /// `table[key]`: the value stored under `key`. Raises KeyError if the
+    /// table does not hold the key.
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let found = match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key),
+            Err(_) => None,
+        };
+        found.ok_or_else(|| PyKeyError::new_err(key.clone().unbind()))
+    }
+
+    /// `key in table`, which is True for keys holding None too.
+    fn __contains__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key).is_some(),
+            Err(_) => false,
+        })
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> bool {
+        let hash = hash_key(key);
+        let shard = &self.shards[self.shard_of(hash)];
+        py.allow_threads(|| write(shard).remove_hashed(key, hash)).is_some()
+    }
+
+    /// Remove many keys in a single call. Returns the number of keys that were present.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        let keys = keys.as_slice(py)?;
+        let removed: Vec<Vec<PyValue>> = py.allow_threads(|| {
+            let hashes = hash_keys(&keys);
+            let buckets = self.partition(keys.iter().zip(&hashes).map(|(&key, &hash)| (key, hash, ())));
+            self.apply(buckets, keys.len(), |map, bucket| {
+                bucket.into_iter().filter_map(|(key, hash, ())| map.remove_hashed(key, hash)).collect()
+            })
+        });
+        Ok(removed.iter().map(Vec::len).sum())
+    }
+
+    /// Look up many keys in a single call, using `default` (or the matching
+    /// item of `defaults`) for missing keys. With `mask`, returns a
+    /// `(values, found)` pair; see ElasticTable.get_many.
+    #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+    fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<PyObject>, mask: bool, defaults: Option<Vec<PyObject>>) -> PyResult<PyObject> {
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let fills = ObjectFills::new(py, default, defaults, keys.len())?;
+        let shards: Vec<_> = self.shards.iter().map(|shard| read_with_gil(py, shard)).collect();
+        let found: Vec<Option<&PyValue>> = py.allow_threads(|| {
+            map_keys_with(
+                &keys,
+                |hash| shards[self.shard_of(hash)].prefetch(hash),
+                |key, hash| shards[self.shard_of(hash)].get_hashed(key, hash),
+            )
+        });
+        let hits = mask.then(|| found.iter().map(Option::is_some).collect());
+        let values: Vec<PyObject> = found.into_iter()
+            .enumerate().map(|(index, value)| match value {
+                Some(value) => value.to_object(py, &[]),
+                None => fills.at(py, index),
+            })
+            .collect();
+        lookup_result(py, values, hits, keys_buffer)
+    }
+
+    /// Check membership for many keys in a single call.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        let keys = keys.as_slice(py)?;
+        Ok(py.allow_threads(|| {
+            let shards: Vec<_> = self.shards.iter().map(read).collect();
+            map_keys_with(
+                &keys,
+                |hash| shards[self.shard_of(hash)].prefetch(hash),
+                |key, hash| shards[self.shard_of(hash)].contains_hashed(key, hash),
+            )
+        }))
+    }
+
+    /// Remove every entry, keeping the allocated capacity.
+    fn clear(&self, py: Python<'_>) {
+        py.allow_threads(|| {
+            for shard in self.shards.iter() {
+                write(shard).clear();
+            }
+        });
+    }
+
+    /// Per-shard subarray stats, in the format of `ElasticTable.stats()`.
+    fn stats(&self, py: Python<'_>) -> Vec<Vec<(usize, usize, f64)>> {
+        py.allow_threads(|| self.shards.iter().map(|shard| read(shard).stats()).collect())
+    }
+
+    /// Reports the stored values to the cyclic GC, skipping shards a writer
+    /// holds, which only delays collecting a cycle.
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        for shard in self.shards.iter() {
+            match shard.try_read() {
+                Ok(map) => visit_values(&map, &visit)?,
+                Err(TryLockError::Poisoned(err)) => visit_values(&err.into_inner(), &visit)?,
+                Err(TryLockError::WouldBlock) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn __clear__(&self) {
+        for shard in self.shards.iter() {
+            clear_unless_locked(shard);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! The value log `BytesTable` spills large values to.
+//!
+//! The log is an append-only scratch file: a spilled value is written at its
+//! end, and the table keeps only its offset and length. Space of values that
+//! are later overwritten or deleted is not reclaimed until `clear()`. The file
+//! is truncated when the log is created and removed when it is dropped, since
+//! nothing but the table that wrote it knows where its values are.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) struct ValueLog {
+    file: File,
+    path: PathBuf,
+    /// Bytes written so far, where the next value goes.
+    len: u64,
+}
+
+impl ValueLog {
+    pub(crate) fn create(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        Ok(ValueLog { file, path, len: 0 })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes of the file, including values no longer stored.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Writes `bytes` at the end of the log, returning their offset.
+    pub(crate) fn append(&mut self, bytes: &[u8]) -> io::Result<u64> {
+        let offset = self.len;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(bytes)?;
+        self.len += bytes.len() as u64;
+        Ok(offset)
+    }
+
+    /// Reads the `len` bytes at `offset`. Takes `&self` so lookups can read
+    /// in parallel.
+    pub(crate) fn read(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; len];
+        read_exact_at(&self.file, &mut bytes, offset)?;
+        Ok(bytes)
+    }
+
+    /// Drops every value.
+    pub(crate) fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl Drop for ValueLog {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, bytes: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, bytes, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut bytes: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !bytes.is_empty() {
+        match file.seek_read(bytes, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                bytes = &mut bytes[read..];
+                offset += read as u64;
+            }
+        }
+    }
+    Ok(())
+}
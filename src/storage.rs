@@ -0,0 +1,448 @@
+//! Slot arrays that live outside the Rust heap.
+//!
+//! Typed tables can keep their `Arena` in a memory region owned by something
+//! else: a `multiprocessing.shared_memory` segment or a memory-mapped file
+//! (see `Backing`). The region starts
+//! with a `Header` describing the table, followed by the control bytes, keys
+//! and values of every slot:
+//!
+//! ```text
+//! | header (128) | counts (1024) | ctrl (slots, padded to 8) | keys (8 * slots) | values (8 * slots) |
+//! ```
+//!
+//! The header starts with a magic number, the format version and a parameter
+//! block (value kind, capacity, delta, slot count and snapshot id) protected by
+//! its own CRC-32, so a damaged or incompatible file is refused on open. It then
+//! holds flags and a CRC-32 of each of the four sections that follow, which
+//! snapshots written by `save_map` fill in and `verify_sections` checks, and
+//! last the growth policy of a saved table, under a CRC-32 of its own. A growth
+//! factor of zero means the table does not grow, and a max capacity of zero
+//! that its growth is unbounded; snapshots written before the policy was
+//! stored leave the whole block zeroed.
+//!
+//! `counts` caches every subarray's entry and tombstone counts, so a table can be
+//! reopened without scanning its control bytes. It is only trusted while the
+//! header marks it valid: writable mappings clear the mark on open and set it
+//! again on `flush()`, so a table modified and then not flushed is recounted.
+//! They clear the section checksums' mark for good, since nothing recomputes them.
+//!
+//! The header is little-endian; slots are stored in native byte order so a region
+//! can be used in place. Opening a region rebuilds the subarray layout from the header, so any process
+//! that maps the same bytes sees the same table.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use pyo3::prelude::*;
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+
+use crate::errors::CorruptedDataError;
+use crate::map::{Contents, ElasticMap, GrowthPolicy};
+use crate::region::{Layout, Region, COUNTS_LEN, HEADER_LEN, MAX_SUBARRAYS};
+
+/// Identifies a region holding an elastic hash table.
+const MAGIC: [u8; 8] = *b"ELHASH\0\0";
+/// Bumped whenever the layout below, or where keys are placed in it, changes.
+const VERSION: u32 = 3;
+/// Length of the parameter block at the start of the header, which is followed
+/// by its CRC-32.
+const PARAMS_LEN: usize = 48;
+/// Header offset of the flag marking the counts section valid.
+const COUNTS_VALID: usize = 64;
+/// Header offset of the flag marking the section checksums valid.
+const SECTIONS_VALID: usize = 72;
+/// Header offset of the CRC-32 of each section, in `SECTIONS` order.
+const SECTION_CRCS: usize = 80;
+const SECTIONS: [&str; 4] = ["counts", "control bytes", "keys", "values"];
+/// Header offset of the growth policy (factor, trigger and max capacity),
+/// which is followed by its CRC-32.
+const GROWTH: usize = 96;
+const GROWTH_LEN: usize = 24;
+
+/// Parameters a region was created with.
+#[derive(Clone, Copy)]
+pub(crate) struct Header {
+    /// Which typed table the values belong to (see `typed_table!`).
+    pub(crate) kind: u32,
+    pub(crate) capacity: usize,
+    pub(crate) delta: f64,
+    pub(crate) slots: usize,
+    /// Random identifier of this snapshot, which its checkpoints refer to (see `wal`).
+    pub(crate) id: u64,
+    /// How the table grew when it was saved.
+    pub(crate) growth: Option<GrowthPolicy>,
+}
+
+impl Header {
+    pub(crate) fn write(&self, bytes: &mut [u8]) {
+        let header = &mut bytes[..HEADER_LEN];
+        header.fill(0);
+        header[0..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        header[12..16].copy_from_slice(&self.kind.to_le_bytes());
+        header[16..24].copy_from_slice(&(self.capacity as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&self.delta.to_le_bytes());
+        header[32..40].copy_from_slice(&(self.slots as u64).to_le_bytes());
+        header[40..48].copy_from_slice(&self.id.to_le_bytes());
+        let crc = crc32(0, &header[..PARAMS_LEN]);
+        header[PARAMS_LEN..PARAMS_LEN + 4].copy_from_slice(&crc.to_le_bytes());
+        if let Some(growth) = &self.growth {
+            header[GROWTH..GROWTH + 8].copy_from_slice(&growth.factor.to_le_bytes());
+            header[GROWTH + 8..GROWTH + 16].copy_from_slice(&growth.trigger.to_le_bytes());
+            header[GROWTH + 16..GROWTH + 24].copy_from_slice(&(growth.max_capacity.unwrap_or(0) as u64).to_le_bytes());
+            let crc = crc32(0, &header[GROWTH..GROWTH + GROWTH_LEN]);
+            header[GROWTH + GROWTH_LEN..GROWTH + GROWTH_LEN + 4].copy_from_slice(&crc.to_le_bytes());
+        }
+    }
+
+    /// Parses and validates the header at the start of `bytes`, which must hold
+    /// a table of value kind `kind`.
+    pub(crate) fn read(bytes: &[u8], kind: u32) -> PyResult<Self> {
+        let header = Self::parse(bytes, kind)?;
+        if Layout::new(header.slots).len > bytes.len() {
+            return Err(CorruptedDataError::new_err("table data is truncated"));
+        }
+        Ok(header)
+    }
+
+    /// Like `read`, but only looks at the header itself.
+    fn parse(bytes: &[u8], kind: u32) -> PyResult<Self> {
+        if bytes.len() < HEADER_LEN || bytes[0..8] != MAGIC {
+            return Err(PyValueError::new_err("not an elastic hash table"));
+        }
+        let word = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let half = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        if half(8) != VERSION {
+            return Err(PyValueError::new_err(format!(
+                "unsupported table format version {} (expected {VERSION})",
+                half(8)
+            )));
+        }
+        if crc32(0, &bytes[..PARAMS_LEN]) != half(PARAMS_LEN) {
+            return Err(CorruptedDataError::new_err("table header is corrupted"));
+        }
+        if half(12) != kind {
+            return Err(PyValueError::new_err("table holds a different value type"));
+        }
+        let block = &bytes[GROWTH..GROWTH + GROWTH_LEN];
+        if bytes[GROWTH..GROWTH + GROWTH_LEN + 4].iter().any(|&byte| byte != 0)
+            && crc32(0, block) != half(GROWTH + GROWTH_LEN)
+        {
+            return Err(CorruptedDataError::new_err("table header is corrupted"));
+        }
+        let factor = f64::from_bits(word(GROWTH));
+        let growth = (factor != 0.0).then(|| GrowthPolicy {
+            factor,
+            trigger: f64::from_bits(word(GROWTH + 8)),
+            max_capacity: Some(word(GROWTH + 16) as usize).filter(|&max| max != 0),
+        });
+        let header = Header {
+            kind,
+            capacity: word(16) as usize,
+            delta: f64::from_bits(word(24)),
+            slots: word(32) as usize,
+            id: word(40),
+            growth,
+        };
+        Ok(header)
+    }
+}
+
+/// Reads the header of the table file at `path`.
+pub(crate) fn read_header_file(path: &Path, kind: u32) -> PyResult<Header> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?.take(HEADER_LEN as u64).read_to_end(&mut bytes)?;
+    Header::parse(&bytes, kind)
+}
+
+/// The four sections of a region laid out as `layout`, in `SECTIONS` order.
+fn sections(bytes: &[u8], layout: Layout) -> [&[u8]; 4] {
+    [
+        &bytes[HEADER_LEN..layout.ctrl],
+        &bytes[layout.ctrl..layout.keys],
+        &bytes[layout.keys..layout.values],
+        &bytes[layout.values..layout.len],
+    ]
+}
+
+/// Checks the sections of the region in `bytes` against the checksums recorded
+/// when it was saved. Regions without recorded checksums pass unchecked.
+pub(crate) fn verify_sections(bytes: &[u8], kind: u32) -> PyResult<()> {
+    let header = Header::read(bytes, kind)?;
+    let half = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    if bytes[SECTIONS_VALID..SECTIONS_VALID + 8] != 1u64.to_le_bytes() {
+        return Ok(());
+    }
+    let sections = sections(bytes, Layout::new(header.slots));
+    for (i, (section, name)) in sections.iter().zip(SECTIONS).enumerate() {
+        if crc32(0, section) != half(SECTION_CRCS + 4 * i) {
+            return Err(CorruptedDataError::new_err(format!("table data is corrupted: {name} checksum mismatch")));
+        }
+    }
+    Ok(())
+}
+
+/// Writes `counts` to the counts section of `prefix` and marks it valid.
+fn write_counts(prefix: &mut [u8], counts: &[(usize, usize)]) {
+    assert!(counts.len() <= MAX_SUBARRAYS);
+    let section = &mut prefix[HEADER_LEN..HEADER_LEN + COUNTS_LEN];
+    section.fill(0);
+    for (entry, &(count, tombstones)) in section.chunks_exact_mut(16).zip(counts) {
+        entry[..8].copy_from_slice(&(count as u64).to_le_bytes());
+        entry[8..].copy_from_slice(&(tombstones as u64).to_le_bytes());
+    }
+    prefix[COUNTS_VALID..COUNTS_VALID + 8].copy_from_slice(&1u64.to_le_bytes());
+}
+
+/// The counts of the first `n` subarrays, if the section is marked valid.
+fn read_counts(prefix: &[u8], n: usize) -> Option<Vec<(usize, usize)>> {
+    let word = |at: usize| u64::from_le_bytes(prefix[at..at + 8].try_into().unwrap()) as usize;
+    if word(COUNTS_VALID) != 1 || n > MAX_SUBARRAYS {
+        return None;
+    }
+    Some((0..n).map(|i| (word(HEADER_LEN + 16 * i), word(HEADER_LEN + 16 * i + 8))).collect())
+}
+
+/// Initializes `region` as an empty map of value kind `kind`.
+pub(crate) fn create_map<V>(mut region: Region, kind: u32, capacity: usize, delta: f64) -> ElasticMap<V> {
+    let slots = ElasticMap::<V>::arena_slots(capacity);
+    Header { kind, capacity, delta, slots, id: rand::random(), growth: None }.write(region.bytes_mut());
+    ElasticMap::in_region(region, capacity, delta, Contents::Empty)
+}
+
+/// Reopens a map previously written to `region` by `create_map` or `save_map`.
+/// Read-only regions are never written to; writable ones have their cached
+/// counts invalidated until the next `sync_counts`.
+pub(crate) fn open_map<V>(mut region: Region, kind: u32, writable: bool) -> PyResult<ElasticMap<V>> {
+    let header = Header::read(region.bytes(), kind)?;
+    if !(header.delta > 0.0 && header.delta < 1.0)
+        || header.slots != ElasticMap::<V>::arena_slots(header.capacity)
+    {
+        return Err(CorruptedDataError::new_err("table header is inconsistent"));
+    }
+    let counts = read_counts(region.bytes(), ElasticMap::<V>::subarray_count(header.capacity));
+    if writable {
+        region.prefix_mut()[COUNTS_VALID..SECTION_CRCS].fill(0);
+    }
+    let contents = match &counts {
+        Some(counts) => Contents::Counted(counts),
+        None => Contents::Recount,
+    };
+    Ok(ElasticMap::in_region(region, header.capacity, header.delta, contents))
+}
+
+/// Records the current subarray counts in the region of a map built with
+/// `create_map` or `open_map`, so the next open can skip recounting.
+pub(crate) fn sync_counts<V>(map: &mut ElasticMap<V>) {
+    let counts = map.subarray_counts();
+    if let Some(region) = map.region_mut() {
+        write_counts(region.prefix_mut(), &counts);
+    }
+}
+
+/// Size in bytes of the region holding a map with `capacity` slots.
+pub(crate) fn region_len<V>(capacity: usize) -> usize {
+    Layout::new(ElasticMap::<V>::arena_slots(capacity)).len
+}
+
+/// What holds the slots of a typed table.
+pub(crate) enum Backing {
+    Heap,
+    /// A `multiprocessing.shared_memory.SharedMemory` segment.
+    SharedMemory(PyObject),
+    /// An `mmap.mmap` of a table file.
+    File(PyObject),
+    /// A tiered region (see `tier`) keeping the first this many subarrays in
+    /// memory. The region owns its mappings.
+    Tiered(usize),
+}
+
+/// Views the memory of an object exporting a byte buffer. Unless `writable` is
+/// false, the buffer must be writable; otherwise the caller must never write.
+fn buffer_region(obj: &Bound<'_, PyAny>, writable: bool) -> PyResult<Region> {
+    let buf = PyBuffer::<u8>::get(obj)?;
+    if writable && buf.readonly() {
+        return Err(PyValueError::new_err("table memory is read-only"));
+    }
+    let (ptr, len) = (buf.buf_ptr() as *mut u8, buf.len_bytes());
+    // SAFETY: the exported buffer pins the mapping until `buf` is released.
+    unsafe { Region::new(ptr, len, Box::new(buf)) }.map_err(PyValueError::new_err)
+}
+
+/// Creates a shared memory segment of at least `len` bytes, returning the
+/// `SharedMemory` object and its memory.
+pub(crate) fn create_segment(py: Python<'_>, name: Option<&str>, len: usize) -> PyResult<(PyObject, Region)> {
+    let shared_memory = py.import("multiprocessing.shared_memory")?.getattr("SharedMemory")?;
+    let shm = shared_memory.call((name, true, len), None)?;
+    let region = buffer_region(&shm.getattr("buf")?, true)?;
+    Ok((shm.unbind(), region))
+}
+
+/// Attaches to the existing shared memory segment `name`.
+pub(crate) fn open_segment(py: Python<'_>, name: &str) -> PyResult<(PyObject, Region)> {
+    let shared_memory = py.import("multiprocessing.shared_memory")?.getattr("SharedMemory")?;
+    let shm = shared_memory.call1((name,))?;
+    let region = buffer_region(&shm.getattr("buf")?, true)?;
+    Ok((shm.unbind(), region))
+}
+
+/// Maps `file`, an open binary file object, returning the `mmap` object and its
+/// memory. Read-only maps must never be written to.
+fn map_file(py: Python<'_>, file: &Bound<'_, PyAny>, writable: bool) -> PyResult<(PyObject, Region)> {
+    let mmap = py.import("mmap")?;
+    let access = mmap.getattr(if writable { "ACCESS_WRITE" } else { "ACCESS_READ" })?;
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("access", access)?;
+    let mapped = mmap.getattr("mmap")?.call((file.call_method0("fileno")?, 0), Some(&kwargs))?;
+    let region = buffer_region(&mapped, writable)?;
+    Ok((mapped.unbind(), region))
+}
+
+/// Creates (or truncates) the file at `path` with room for `len` bytes and maps it.
+/// The file is sparse, so untouched slots take no disk space.
+pub(crate) fn create_file(py: Python<'_>, path: &Path, len: usize) -> PyResult<(PyObject, Region)> {
+    let file = py.import("builtins")?.call_method1("open", (path, "w+b"))?;
+    file.call_method1("truncate", (len,))?;
+    let mapped = map_file(py, &file, true);
+    // The mapping stays valid after the file object is closed.
+    file.call_method0("close")?;
+    mapped
+}
+
+/// Maps the existing table file at `path`, read-only unless `writable`.
+pub(crate) fn open_file(py: Python<'_>, path: &Path, writable: bool) -> PyResult<(PyObject, Region)> {
+    let mode = if writable { "r+b" } else { "rb" };
+    let file = py.import("builtins")?.call_method1("open", (path, mode))?;
+    let mapped = map_file(py, &file, writable);
+    file.call_method0("close")?;
+    mapped
+}
+
+/// Writes `map` to `path` in the region format, so the file can be reopened
+/// with `load_file` or mapped with `open_file`. `encode` gives a value's bytes.
+/// Returns the new snapshot's id.
+pub(crate) fn save_map<V>(map: &ElasticMap<V>, kind: u32, path: &Path, encode: impl Fn(&V) -> [u8; 8]) -> io::Result<u64> {
+    let (ctrl, keys, values) = map.raw_slots();
+    let layout = Layout::new(ctrl.len());
+    let mut prefix = [0; HEADER_LEN + COUNTS_LEN];
+    let id = rand::random();
+    let header = Header { kind, capacity: map.capacity(), delta: map.delta(), slots: layout.slots, id, growth: map.growth() };
+    header.write(&mut prefix);
+    write_counts(&mut prefix, &map.subarray_counts());
+
+    // Write next to the target and rename, so a crash never leaves a torn snapshot.
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut out = Checksummed { inner: BufWriter::new(File::create(&tmp)?), crc: 0 };
+    // The prefix is written again below, once the section checksums are known.
+    out.write_all(&prefix)?;
+    let mut crcs = [crc32(0, &prefix[HEADER_LEN..]), 0, 0, 0];
+    out.crc = 0;
+    out.write_all(ctrl)?;
+    out.write_all(&vec![0; layout.keys - layout.ctrl - ctrl.len()])?;
+    crcs[1] = std::mem::take(&mut out.crc);
+    for key in keys {
+        out.write_all(&key.to_ne_bytes())?;
+    }
+    crcs[2] = std::mem::take(&mut out.crc);
+    for value in values {
+        out.write_all(&value.map_or([0; 8], &encode))?;
+    }
+    crcs[3] = out.crc;
+
+    prefix[SECTIONS_VALID..SECTIONS_VALID + 8].copy_from_slice(&1u64.to_le_bytes());
+    for (i, crc) in crcs.iter().enumerate() {
+        prefix[SECTION_CRCS + 4 * i..SECTION_CRCS + 4 * i + 4].copy_from_slice(&crc.to_le_bytes());
+    }
+    let mut file = out.inner.into_inner()?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&prefix[..HEADER_LEN])?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(id)
+}
+
+/// A writer that keeps a CRC-32 of everything written through it.
+struct Checksummed<W> {
+    inner: W,
+    crc: u32,
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc = crc32(self.crc, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads the whole file at `path` into an 8-byte aligned heap region.
+pub(crate) fn load_file(path: &Path) -> PyResult<Region> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+    let mut words = vec![0u64; len.div_ceil(8)];
+    // SAFETY: the byte view covers exactly the vector's initialized words.
+    let bytes = unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, len) };
+    file.read_exact(bytes)?;
+    let ptr = words.as_mut_ptr() as *mut u8;
+    // SAFETY: the vector's buffer does not move when the vector itself is boxed.
+    unsafe { Region::new(ptr, len, Box::new(words)) }.map_err(PyValueError::new_err)
+}
+
+/// Lookup tables for slicing-by-8: `CRC_TABLES[k][b]` is the CRC contribution of
+/// byte `b` followed by `k` zero bytes.
+static CRC_TABLES: [[u32; 256]; 8] = {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        tables[0][i] = c;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 256 {
+        let mut k = 1;
+        while k < 8 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev >> 8) ^ tables[0][(prev & 0xFF) as usize];
+            k += 1;
+        }
+        i += 1;
+    }
+    tables
+};
+
+/// CRC-32 (IEEE) of `bytes`, continuing from `crc` (0 for a fresh checksum).
+pub(crate) fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let t = &CRC_TABLES;
+    let mut crc = !crc;
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let lo = crc ^ u32::from_le_bytes(chunk[..4].try_into().unwrap());
+        let hi = u32::from_le_bytes(chunk[4..].try_into().unwrap());
+        crc = t[7][(lo & 0xFF) as usize]
+            ^ t[6][(lo >> 8 & 0xFF) as usize]
+            ^ t[5][(lo >> 16 & 0xFF) as usize]
+            ^ t[4][(lo >> 24) as usize]
+            ^ t[3][(hi & 0xFF) as usize]
+            ^ t[2][(hi >> 8 & 0xFF) as usize]
+            ^ t[1][(hi >> 16 & 0xFF) as usize]
+            ^ t[0][(hi >> 24) as usize];
+    }
+    for &byte in chunks.remainder() {
+        crc = t[0][((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
@@ -0,0 +1,2450 @@
+//! `ElasticTable`: the Python-facing table storing arbitrary Python objects.
+
+use std::collections::BTreeMap;
+
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::types::{PyAny, PyBytes, PyDict, PyTuple, PyType, PyWeakrefMethods, PyWeakrefReference};
+use pyo3::{PyClass, PyTraverseError, PyVisit};
+use numpy::IntoPyArray;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::batch::{lookup_result, map_keys, KeyBatch, ObjectFills};
+use crate::bench::{benchmark, compare_tables};
+use crate::alert::LoadAlert;
+use crate::bulk::{Bulk, BulkBlock};
+use crate::errors::{InsertProbeLimitError, TableClosedError, TableFullError};
+use crate::hash::{hash_key, hash_keys, shard_of};
+use crate::interop::{entries_from_json, entries_to_json};
+use crate::iter::{TableIterator, Yield};
+use crate::lock::{TableGuard, TableLock};
+use crate::map::{ElasticMap, GrowthPolicy, InsertError, Key, OpCounts, ProbeHistograms, ShrinkPolicy};
+use crate::metrics::metrics_text;
+use crate::namespace::{split_key, Namespace};
+use crate::observers::{batch_changes, Change, Observers};
+use crate::schema::Schema;
+use crate::transaction::{Journal, Transaction};
+use crate::typed::{FloatTable, IntTable};
+use crate::value::{Interner, PyValue};
+use crate::views::{ItemsView, KeysView, ValuesView};
+
+impl From<InsertError> for PyErr {
+    fn from(err: InsertError) -> PyErr {
+        match err {
+            InsertError::Full => TableFullError::new_err("Hash table is completely full"),
+            InsertError::NoSlot => InsertProbeLimitError::new_err("Could not insert key"),
+        }
+    }
+}
+
+/// Validates the `delta` constructor argument shared by all table classes.
+pub(crate) fn check_delta(delta: f64) -> PyResult<()> {
+    if delta <= 0.0 || delta >= 1.0 {
+        return Err(PyValueError::new_err("delta must be between 0 and 1"));
+    }
+    Ok(())
+}
+
+/// Builds the growth policy of a table with `capacity` slots and `delta` from
+/// the constructor arguments, or None if `growth_factor` is not given.
+pub(crate) fn growth_policy(
+    capacity: usize,
+    delta: f64,
+    growth_factor: Option<f64>,
+    grow_at: Option<f64>,
+    max_capacity: Option<usize>,
+) -> PyResult<Option<GrowthPolicy>> {
+    let Some(factor) = growth_factor else {
+        if grow_at.is_some() || max_capacity.is_some() {
+            return Err(PyValueError::new_err("grow_at and max_capacity need a growth_factor"));
+        }
+        return Ok(None);
+    };
+    if !(factor > 1.0 && factor.is_finite()) {
+        return Err(PyValueError::new_err("growth_factor must be greater than 1"));
+    }
+    let trigger = grow_at.unwrap_or(1.0 - delta);
+    if trigger <= 0.0 || trigger > 1.0 - delta + 1e-9 {
+        return Err(PyValueError::new_err("grow_at must be between 0 and 1 - delta"));
+    }
+    if max_capacity.is_some_and(|max| max < capacity) {
+        return Err(PyValueError::new_err("max_capacity must be at least capacity"));
+    }
+    Ok(Some(GrowthPolicy { factor, trigger, max_capacity }))
+}
+
+/// Builds the policy of `enable_auto_shrink` from its arguments.
+pub(crate) fn shrink_policy(threshold: f64, min_capacity: usize) -> PyResult<ShrinkPolicy> {
+    if threshold <= 0.0 || threshold >= 0.5 {
+        return Err(PyValueError::new_err("threshold must be between 0 and 0.5"));
+    }
+    Ok(ShrinkPolicy { threshold, min_capacity })
+}
+
+/// Validates the `bits_per_key` argument of `enable_bloom_filter`.
+pub(crate) fn check_bits_per_key(bits_per_key: usize) -> PyResult<()> {
+    if bits_per_key == 0 || bits_per_key > 64 {
+        return Err(PyValueError::new_err("bits_per_key must be between 1 and 64"));
+    }
+    Ok(())
+}
+
+/// Converts probe histograms into the dict returned by `probe_histograms()`.
+pub(crate) fn histograms_to_dict<'py>(py: Python<'py>, histograms: &ProbeHistograms) -> PyResult<Bound<'py, PyDict>> {
+    let subarrays = histograms.subarrays.iter()
+        .map(|(inserts, gets)| {
+            let sub = PyDict::new(py);
+            sub.set_item("insert", inserts.counts())?;
+            sub.set_item("get", gets.counts())?;
+            Ok(sub)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let dict = PyDict::new(py);
+    dict.set_item("insert", histograms.inserts.counts())?;
+    dict.set_item("get", histograms.gets.counts())?;
+    dict.set_item("subarrays", subarrays)?;
+    Ok(dict)
+}
+
+/// Converts operation counts into the dict returned by `op_stats()`.
+pub(crate) fn op_counts_to_dict(py: Python<'_>, counts: OpCounts) -> PyResult<Bound<'_, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("gets", counts.gets)?;
+    dict.set_item("hits", counts.hits)?;
+    dict.set_item("misses", counts.gets - counts.hits)?;
+    dict.set_item("inserts", counts.inserts)?;
+    dict.set_item("overwrites", counts.overwrites)?;
+    dict.set_item("failed_inserts", counts.failed_inserts)?;
+    dict.set_item("relocations", counts.relocations)?;
+    Ok(dict)
+}
+
+/// Builds the dict returned by `detailed_stats()`.
+pub(crate) fn detailed_stats<'py, V, K: Key>(py: Python<'py>, map: &ElasticMap<V, K>) -> PyResult<Bound<'py, PyDict>> {
+    let subarrays = map.subarray_stats().into_iter().enumerate()
+        .map(|(i, sub)| {
+            let dict = PyDict::new(py);
+            dict.set_item("index", i)?;
+            dict.set_item("capacity", sub.capacity)?;
+            dict.set_item("count", sub.count)?;
+            dict.set_item("tombstones", sub.tombstones)?;
+            dict.set_item("load_factor", sub.load_factor)?;
+            dict.set_item("epsilon", sub.epsilon)?;
+            dict.set_item("insert_case", sub.insert_case.number())?;
+            dict.set_item("insert_probe_limit", sub.insert_probe_limit)?;
+            dict.set_item("lookup_probe_limit", sub.lookup_probe_limit)?;
+            let [exhausted, skipped, full] = sub.fallthroughs;
+            let fallthroughs = PyDict::new(py);
+            fallthroughs.set_item("exhausted", exhausted)?;
+            fallthroughs.set_item("skipped", skipped)?;
+            fallthroughs.set_item("full", full)?;
+            dict.set_item("fallthroughs", fallthroughs)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let (capacity, count) = (map.capacity(), map.len());
+    let dict = PyDict::new(py);
+    dict.set_item("capacity", capacity)?;
+    dict.set_item("count", count)?;
+    dict.set_item("load_factor", if capacity == 0 { 1.0 } else { count as f64 / capacity as f64 })?;
+    dict.set_item("delta", map.delta())?;
+    dict.set_item("probe_budget", map.probe_budget())?;
+    let tuning = map.tuning()
+        .map(|tuning| {
+            let dict = PyDict::new(py);
+            dict.set_item("inserts", tuning.inserts)?;
+            dict.set_item("fallthroughs", tuning.fallthroughs)?;
+            dict.set_item("adjustments", tuning.adjustments)?;
+            dict.set_item("peak_load", tuning.peak_load)?;
+            dict.set_item("failure_load", tuning.failure_load)?;
+            dict.set_item("recommended_delta", tuning.recommended_delta)?;
+            Ok::<_, PyErr>(dict)
+        })
+        .transpose()?;
+    dict.set_item("tuning", tuning)?;
+    dict.set_item("subarrays", subarrays)?;
+    Ok(dict)
+}
+
+/// The entries of subarray `i`, in slot order, for `items_in_subarray`.
+/// Raises IndexError for a subarray the map does not have.
+pub(crate) fn subarray_items<V, K: Key>(map: &ElasticMap<V, K>, i: usize) -> PyResult<impl Iterator<Item = (K, &V)>> {
+    if i >= map.subarray_lens().len() {
+        return Err(PyIndexError::new_err("subarray index out of range"));
+    }
+    Ok(map.subarray_entries(i))
+}
+
+/// Every subarray's occupancy for `occupancy()`: a numpy bool array per
+/// subarray, or, if `packed`, a bitmap with slot `j` in bit `j % 8` of byte
+/// `j / 8`.
+pub(crate) fn occupancy<V, K: Key>(py: Python<'_>, map: &ElasticMap<V, K>, packed: bool) -> PyResult<Vec<PyObject>> {
+    if !packed {
+        py.import("numpy")?;
+    }
+    map.occupancy().into_iter()
+        .map(|slots| {
+            if !packed {
+                return Ok(slots.into_pyarray(py).into_any().unbind());
+            }
+            let bytes: Vec<u8> = slots.chunks(8)
+                .map(|bits| bits.iter().enumerate().fold(0, |byte, (j, &full)| byte | (full as u8) << j))
+                .collect();
+            Ok(PyBytes::new(py, &bytes).into_any().unbind())
+        })
+        .collect()
+}
+
+/// Builds the dict returned by `memory_usage()`. `references(i)` gives the
+/// number of values subarray `i` holds as Python object references, for tables
+/// that store them.
+pub(crate) fn memory_usage<'py, V, K: Key>(
+    py: Python<'py>,
+    map: &ElasticMap<V, K>,
+    references: impl Fn(usize) -> Option<usize>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let mut total = 0;
+    let subarrays = map.memory().into_iter().enumerate()
+        .map(|(i, sub)| {
+            let bytes = sub.ctrl + sub.keys + sub.values;
+            total += bytes;
+            let dict = PyDict::new(py);
+            dict.set_item("index", i)?;
+            dict.set_item("slots", sub.slots)?;
+            dict.set_item("control_bytes", sub.ctrl)?;
+            dict.set_item("key_bytes", sub.keys)?;
+            dict.set_item("value_bytes", sub.values)?;
+            dict.set_item("total_bytes", bytes)?;
+            if let Some(references) = references(i) {
+                dict.set_item("references", references)?;
+            }
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let (filter, order) = (map.filter_bytes(), map.order_bytes());
+    let dict = PyDict::new(py);
+    dict.set_item("subarrays", subarrays)?;
+    dict.set_item("filter_bytes", filter)?;
+    dict.set_item("order_bytes", order)?;
+    dict.set_item("total_bytes", total + filter + order)?;
+    Ok(dict)
+}
+
+/// Builds the report returned by `probe_bounds()`: the recorded probe counts
+/// next to the paper's bounds, scaled by `tolerance`.
+pub(crate) fn probe_bounds<'py, V>(py: Python<'py>, map: &ElasticMap<V>, tolerance: f64) -> PyResult<Bound<'py, PyDict>> {
+    let Some(histograms) = map.histograms() else {
+        return Err(PyValueError::new_err("probe_bounds() requires track_probes = True"));
+    };
+    if tolerance <= 0.0 {
+        return Err(PyValueError::new_err("tolerance must be positive"));
+    }
+    let log_inv_delta = (1.0 / map.delta()).log2();
+    let amortized_bound = tolerance;
+    let worst_case_bound = tolerance * log_inv_delta.max(1.0);
+
+    let mut deviations = Vec::new();
+    let dict = PyDict::new(py);
+    dict.set_item("log_inv_delta", log_inv_delta)?;
+    dict.set_item("amortized_bound", amortized_bound)?;
+    dict.set_item("worst_case_bound", worst_case_bound)?;
+    for (name, histogram) in [("insert", &histograms.inserts), ("get", &histograms.gets)] {
+        let (count, mean, longest) = (histogram.len(), histogram.mean(), histogram.longest());
+        dict.set_item(format!("{name}_count"), count)?;
+        dict.set_item(format!("{name}_amortized_probes"), mean)?;
+        dict.set_item(format!("{name}_worst_case_probes"), longest)?;
+        if mean > amortized_bound {
+            deviations.push(format!(
+                "{name}: amortized {mean:.2} probes exceeds the O(1) bound of {amortized_bound:.2}"
+            ));
+        }
+        if longest as f64 > worst_case_bound {
+            deviations.push(format!(
+                "{name}: worst case {longest} probes exceeds the O(log 1/delta) bound of {worst_case_bound:.2}"
+            ));
+        }
+    }
+    dict.set_item("deviations", deviations)?;
+    Ok(dict)
+}
+
+/// Passes the inserts `map` traced since the last call to `tracer`, if set.
+pub(crate) fn report_traces<V>(py: Python<'_>, map: &mut ElasticMap<V>, tracer: &Option<PyObject>) -> PyResult<()> {
+    let Some(tracer) = tracer else { return Ok(()) };
+    for trace in map.take_traces() {
+        let steps: Vec<_> = trace.steps.iter()
+            .map(|step| (step.subarray, step.case.number(), step.limit, step.probes))
+            .collect();
+        tracer.call1(py, (trace.key, steps, trace.inserted))?;
+    }
+    Ok(())
+}
+
+/// Checks `alert`, if set, against the load of `map` after inserts.
+pub(crate) fn check_load<V>(py: Python<'_>, map: &ElasticMap<V>, alert: &mut Option<LoadAlert>) -> PyResult<()> {
+    match alert {
+        Some(alert) => alert.check(py, map),
+        None => Ok(()),
+    }
+}
+
+/// How `merge` treats keys that both tables hold.
+#[derive(Clone, Copy)]
+pub(crate) enum OnConflict {
+    /// The merged-in value replaces the stored one.
+    Replace,
+    /// The stored value stays.
+    Keep,
+    /// Nothing is merged and `KeyError` is raised.
+    Error,
+}
+
+impl OnConflict {
+    pub(crate) fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "replace" => Ok(OnConflict::Replace),
+            "keep" => Ok(OnConflict::Keep),
+            "error" => Ok(OnConflict::Error),
+            _ => Err(PyValueError::new_err("on_conflict must be 'replace', 'keep' or 'error'")),
+        }
+    }
+
+    /// Drops the entries about to be merged into `map` that the policy skips,
+    /// or fails on the first key `map` already holds.
+    pub(crate) fn apply<V, W>(self, map: &ElasticMap<V>, keys: &mut Vec<u64>, values: &mut Vec<W>) -> PyResult<()> {
+        match self {
+            OnConflict::Replace => {}
+            OnConflict::Keep => {
+                let absent: Vec<bool> = keys.iter().map(|&key| map.get(key).is_none()).collect();
+                let (mut keys_absent, mut values_absent) = (absent.iter(), absent.iter());
+                keys.retain(|_| *keys_absent.next().unwrap());
+                values.retain(|_| *values_absent.next().unwrap());
+            }
+            OnConflict::Error => {
+                if let Some(&key) = keys.iter().find(|&&key| map.get(key).is_some()) {
+                    return Err(PyKeyError::new_err(key));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the `|` operator of table class `T` accepts: a table of the same
+/// class or a dict. Anything else makes it return NotImplemented.
+pub(crate) enum Mergeable<'py, T> {
+    Table(Bound<'py, T>),
+    Dict(Bound<'py, PyDict>),
+}
+
+impl<'py, T: PyClass> FromPyObject<'py> for Mergeable<'py, T> {
+    fn extract_bound(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(table) = obj.downcast::<T>() {
+            return Ok(Mergeable::Table(table.clone()));
+        }
+        Ok(Mergeable::Dict(obj.downcast::<PyDict>()?.clone()))
+    }
+}
+
+impl<'py, T> Mergeable<'py, T> {
+    pub(crate) fn as_any(&self) -> &Bound<'py, PyAny> {
+        match self {
+            Mergeable::Table(table) => table.as_any(),
+            Mergeable::Dict(dict) => dict.as_any(),
+        }
+    }
+}
+
+/// The entries of `other` the way `dict.update` reads them: the items of a
+/// dict, the keys of any other object with a `keys()` method along with
+/// `other[key]`, or else the pairs of an iterable of key/value pairs.
+pub(crate) fn update_entries<'py>(other: &Bound<'py, PyAny>) -> PyResult<Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>)>> {
+    if let Ok(dict) = other.downcast::<PyDict>() {
+        return Ok(dict.iter().collect());
+    }
+    if other.hasattr("keys")? {
+        return other.call_method0("keys")?
+            .try_iter()?
+            .map(|key| {
+                let key = key?;
+                let value = other.get_item(&key)?;
+                Ok((key, value))
+            })
+            .collect();
+    }
+    other.try_iter()?
+        .enumerate()
+        .map(|(index, pair)| {
+            let pair = pair?.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+            <[_; 2]>::try_from(pair).map(|[key, value]| (key, value)).map_err(|pair| {
+                PyValueError::new_err(format!("update sequence element #{index} has length {}; 2 is required", pair.len()))
+            })
+        })
+        .collect()
+}
+
+/// Validates the `n` argument of `split` and `shard_of`.
+pub(crate) fn check_parts(n: usize) -> PyResult<()> {
+    if n == 0 {
+        return Err(PyValueError::new_err("n must be at least 1"));
+    }
+    Ok(())
+}
+
+/// Splits the entries of `map` by `hash::shard_of` into `n` parts, each with
+/// the capacity for a table of its own: an `n`-th of the capacity of `map`, or
+/// more if its entries need it at the same delta.
+pub(crate) fn split_entries<V>(map: &ElasticMap<V>, n: usize) -> Vec<(usize, Vec<(u64, &V)>)> {
+    let mut parts: Vec<Vec<(u64, &V)>> = (0..n).map(|_| Vec::new()).collect();
+    for (key, value) in map.entries_in_order() {
+        parts[shard_of(hash_key(key), n)].push((key, value));
+    }
+    let share = map.capacity().div_ceil(n);
+    parts.into_iter()
+        .map(|entries| {
+            let needed = (entries.len() as f64 / (1.0 - map.delta())).ceil() as usize;
+            (share.max(needed), entries)
+        })
+        .collect()
+}
+
+/// The generator `sample` draws from: seeded with `seed` for a repeatable
+/// sample, or from the OS otherwise.
+pub(crate) fn sample_rng(seed: Option<u64>) -> StdRng {
+    seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+}
+
+/// Resolves an `entry_at` index, which counts from the end if negative,
+/// against a table of `len` entries.
+pub(crate) fn entry_index(index: isize, len: usize) -> PyResult<usize> {
+    let resolved = if index < 0 { len.checked_sub(index.unsigned_abs()) } else { Some(index as usize) };
+    resolved.filter(|&index| index < len).ok_or_else(|| PyIndexError::new_err("entry index out of range"))
+}
+
+/// A table of any class, for operations that only look at keys.
+pub(crate) enum AnyTable<'py> {
+    Elastic(Bound<'py, ElasticTable>),
+    Int(Bound<'py, IntTable>),
+    Float(Bound<'py, FloatTable>),
+}
+
+impl<'py> FromPyObject<'py> for AnyTable<'py> {
+    fn extract_bound(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(table) = obj.downcast::<ElasticTable>() {
+            Ok(AnyTable::Elastic(table.clone()))
+        } else if let Ok(table) = obj.downcast::<IntTable>() {
+            Ok(AnyTable::Int(table.clone()))
+        } else if let Ok(table) = obj.downcast::<FloatTable>() {
+            Ok(AnyTable::Float(table.clone()))
+        } else {
+            Err(PyTypeError::new_err("expected an ElasticTable, IntTable or FloatTable"))
+        }
+    }
+}
+
+/// Values released by `close()` between two releases of the GIL.
+const RELEASE_CHUNK: usize = 1 << 16;
+
+/// Drops `items` with the GIL held, so their objects are freed right away
+/// rather than queued for the next time it is taken, and lets other threads
+/// run after every chunk.
+fn release<T>(py: Python<'_>, items: Vec<T>) {
+    let mut items = items.into_iter();
+    while items.len() > 0 {
+        items.by_ref().take(RELEASE_CHUNK).for_each(drop);
+        py.allow_threads(|| ());
+    }
+}
+
+/// An owned reference to a table of one of the plain classes, for objects
+/// such as transactions that act on a table after the call that made them.
+pub(crate) enum TableHandle {
+    Elastic(Py<ElasticTable>),
+    Int(Py<IntTable>),
+    Float(Py<FloatTable>),
+}
+
+impl TableHandle {
+    pub(crate) fn object(&self, py: Python<'_>) -> PyObject {
+        match self {
+            TableHandle::Elastic(table) => table.clone_ref(py).into_any(),
+            TableHandle::Int(table) => table.clone_ref(py).into_any(),
+            TableHandle::Float(table) => table.clone_ref(py).into_any(),
+        }
+    }
+
+    /// Reports the table to the cyclic GC.
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        match self {
+            TableHandle::Elastic(table) => visit.call(table),
+            TableHandle::Int(table) => visit.call(table),
+            TableHandle::Float(table) => visit.call(table),
+        }
+    }
+}
+
+impl From<Py<ElasticTable>> for TableHandle {
+    fn from(table: Py<ElasticTable>) -> Self {
+        TableHandle::Elastic(table)
+    }
+}
+
+impl From<Py<IntTable>> for TableHandle {
+    fn from(table: Py<IntTable>) -> Self {
+        TableHandle::Int(table)
+    }
+}
+
+impl From<Py<FloatTable>> for TableHandle {
+    fn from(table: Py<FloatTable>) -> Self {
+        TableHandle::Float(table)
+    }
+}
+
+/// The keys of `map` in ascending order, for `compare_keys`. Sorts without
+/// the GIL.
+pub(crate) fn sorted_keys<V: Sync>(py: Python<'_>, map: &ElasticMap<V>) -> Vec<u64> {
+    py.allow_threads(|| {
+        let mut keys: Vec<u64> = map.iter().map(|(key, _)| key).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    })
+}
+
+/// The `keys` that `other` holds, if `present`, or lacks otherwise. The
+/// caller releases the lock of its own table first, so that two tables
+/// compared with each other from two threads never wait for each other.
+/// Probes without the GIL.
+pub(crate) fn compare_keys(py: Python<'_>, keys: Vec<u64>, other: &AnyTable<'_>, present: bool) -> PyResult<Vec<u64>> {
+    Ok(match other {
+        AnyTable::Elastic(table) => {
+            let table = table.get().lock(py)?;
+            table.check_open()?;
+            filter_keys(py, keys, &table.map, present)
+        }
+        AnyTable::Int(table) => filter_keys(py, keys, table.get().lock(py)?.map(), present),
+        AnyTable::Float(table) => filter_keys(py, keys, table.get().lock(py)?.map(), present),
+    })
+}
+
+fn filter_keys<V: Sync>(py: Python<'_>, keys: Vec<u64>, other: &ElasticMap<V>, present: bool) -> Vec<u64> {
+    py.allow_threads(|| {
+        let found = map_keys(other, &keys, |key, hash| other.contains_hashed(key, hash));
+        keys.into_iter().zip(found).filter(|&(_, found)| found == present).map(|(key, _)| key).collect()
+    })
+}
+
+/// Capacity of the table `a | b` returns, with `entries` entries in total
+/// between `a` and `b`: that of `a`, or more if it would be fuller than its
+/// `delta` intends.
+pub(crate) fn union_capacity<V>(map: &ElasticMap<V>, entries: usize) -> usize {
+    let needed = (entries as f64 / (1.0 - map.delta())).ceil() as usize;
+    map.capacity().max(needed)
+}
+
+/// Turns `for_items` arguments into a `(capacity, delta)` pair.
+pub(crate) fn sizing_for_items(expected_items: usize, load_factor: f64) -> PyResult<(usize, f64)> {
+    if load_factor <= 0.5 || load_factor >= 0.99 {
+        return Err(PyValueError::new_err(
+            "load_factor must be between 0.5 and 0.99"
+        ));
+    }
+    
+    // Calculate capacity needed for the expected items at the target load factor
+    let capacity = ((expected_items as f64) / load_factor).ceil() as usize;
+    
+    // Delta is the "empty space" parameter: 1 - load_factor
+    let delta = 1.0 - load_factor;
+    
+    Ok((capacity, delta))
+}
+
+/// The object `value` stands for in a table that interns into `pool` and,
+/// if `weak`, holds its values through weak references. `None` once a weakly
+/// held value has been collected, when the entry counts as missing.
+fn decode(py: Python<'_>, weak: bool, pool: &[PyObject], value: &PyValue) -> PyResult<Option<PyObject>> {
+    if weak {
+        if let Some(obj) = value.as_object() {
+            // Every reference stored by a weak table is a `weakref.ref`.
+            let weakref = obj.downcast_bound::<PyWeakrefReference>(py)?;
+            return Ok(weakref.upgrade().map(Bound::unbind));
+        }
+    }
+    Ok(Some(value.to_object(py, pool)))
+}
+
+/// `value` as observers see it: like `decode`, but None once a weakly held
+/// value has been collected.
+fn observed(py: Python<'_>, weak: bool, pool: &[PyObject], value: &PyValue) -> PyObject {
+    decode(py, weak, pool, value).ok().flatten().unwrap_or_else(|| py.None())
+}
+
+#[pyclass(frozen, subclass)]
+pub(crate) struct ElasticTable {
+    state: TableLock<ElasticTableState>,
+}
+
+/// The state of a `ElasticTable`, behind its lock.
+pub(crate) struct ElasticTableState {
+    map: ElasticMap<PyValue>,
+    interner: Option<Interner>,
+    /// Values that are not stored inline are held through `weakref.ref`.
+    weak: bool,
+    /// Called with the path of every insert; see `set_tracer`.
+    tracer: Option<PyObject>,
+    /// Called with every change; see `add_observer`.
+    observers: Observers,
+    /// Set by `set_load_alert`.
+    alert: Option<LoadAlert>,
+    /// Set inside a `bulk()` block.
+    bulk: Option<Bulk<PyValue>>,
+    /// Set while a transaction is open.
+    journal: Option<Journal<PyValue>>,
+    /// Set by `close()`; every later use raises.
+    closed: bool,
+    /// Checks every value before it is stored; see `value_type`.
+    schema: Option<Schema>,
+}
+
+impl From<ElasticTableState> for ElasticTable {
+    fn from(state: ElasticTableState) -> Self {
+        ElasticTable { state: TableLock::new(state) }
+    }
+}
+
+impl ElasticTable {
+    /// The table's state, waiting for any call on another thread to finish.
+    pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, ElasticTableState>> {
+        self.state.lock(py)
+    }
+}
+
+impl ElasticTableState {
+    pub(crate) fn map(&self) -> &ElasticMap<PyValue> {
+        &self.map
+    }
+
+    pub(crate) fn check_open(&self) -> PyResult<()> {
+        if self.closed {
+            return Err(TableClosedError::new_err("table is closed"));
+        }
+        Ok(())
+    }
+
+    fn encode(&mut self, value: &Bound<'_, PyAny>) -> PyResult<PyValue> {
+        if let Some(schema) = &self.schema {
+            schema.check(value)?;
+        }
+        match &mut self.interner {
+            Some(interner) => PyValue::new_interned(value, interner),
+            None if self.weak => {
+                let encoded = PyValue::new(value);
+                if encoded.as_object().is_none() {
+                    return Ok(encoded);
+                }
+                Ok(PyValue::new(PyWeakrefReference::new(value)?.as_any()))
+            }
+            None => Ok(PyValue::new(value)),
+        }
+    }
+
+    fn decode(&self, py: Python<'_>, value: &PyValue) -> PyResult<Option<PyObject>> {
+        decode(py, self.weak, self.pool(), value)
+    }
+
+    fn observed(&self, py: Python<'_>, value: &PyValue) -> PyObject {
+        observed(py, self.weak, self.pool(), value)
+    }
+
+    /// Decodes `entries`, leaving out those whose weakly held value has been
+    /// collected.
+    fn live_entries<'a>(&self, py: Python<'_>, entries: impl IntoIterator<Item = (u64, &'a PyValue)>) -> PyResult<Vec<(u64, PyObject)>> {
+        let mut live = Vec::new();
+        for (key, value) in entries {
+            if let Some(value) = self.decode(py, value)? {
+                live.push((key, value));
+            }
+        }
+        Ok(live)
+    }
+
+    /// Number of entries, leaving out those whose weakly held value has been
+    /// collected. Takes a pass over the entries of a weak table.
+    pub(crate) fn live_len(&self, py: Python<'_>) -> PyResult<usize> {
+        if !self.weak {
+            return Ok(self.map.len());
+        }
+        let mut live = 0;
+        for (_, value) in self.map.iter() {
+            live += self.decode(py, value)?.is_some() as usize;
+        }
+        Ok(live)
+    }
+
+    /// Whether the entry of `key`, which the table holds, still has its
+    /// value: False once a weakly held value has been collected.
+    pub(crate) fn is_live(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        Ok(!self.weak || self.value_of(py, key)?.is_some())
+    }
+
+    /// The value stored under `key`, without counting a lookup.
+    pub(crate) fn value_of(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+        match self.map.get(key) {
+            Some(value) => self.decode(py, value),
+            None => Ok(None),
+        }
+    }
+
+    /// The value stored under `key`, counted as a lookup.
+    fn lookup(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+        self.check_open()?;
+        let found = self.value_of(py, key)?;
+        self.map.count_lookups(1, found.is_some() as usize);
+        Ok(found)
+    }
+
+    fn pool(&self) -> &[PyObject] {
+        self.interner.as_ref().map_or(&[], Interner::values)
+    }
+
+    /// Bulk insert shared by `insert_many` and `merge`; probes without the GIL.
+    fn insert_values(&mut self, py: Python<'_>, keys: &[u64], values: Vec<PyValue>) -> PyResult<usize> {
+        for &key in keys {
+            self.journal_key(py, key);
+        }
+        let mut changes = Vec::new();
+        if !self.observers.is_empty() {
+            let new = values.iter().map(|value| self.observed(py, value));
+            changes = batch_changes(keys, new, |key| self.current(py, key), |value| value.clone_ref(py));
+        }
+        let map = &mut self.map;
+        let mut inserted = 0;
+        // Values not inserted after a failure are dropped without the GIL;
+        // PyO3 defers their decref until it is next acquired.
+        let result = py.allow_threads(|| {
+            let hashes = hash_keys(keys);
+            let mut total_probes = 0;
+            for ((&key, &hash), value) in keys.iter().zip(&hashes).zip(values) {
+                total_probes += map.insert_hashed(key, hash, value)?;
+                inserted += 1;
+            }
+            Ok::<_, InsertError>(total_probes)
+        });
+        self.report_inserts(py)?;
+        changes.truncate(inserted);
+        self.observers.notify(py, changes)?;
+        Ok(result?)
+    }
+
+    /// Removes `keys`, for `delete_many` and `delete_namespace`, returning how
+    /// many were present. Probes without the GIL.
+    fn remove_keys(&mut self, py: Python<'_>, keys: &[u64]) -> PyResult<usize> {
+        let map = &mut self.map;
+        let removed: Vec<(u64, PyValue)> = py.allow_threads(|| {
+            let hashes = hash_keys(keys);
+            keys.iter().zip(&hashes)
+                .filter_map(|(&key, &hash)| Some((key, map.remove_hashed(key, hash)?)))
+                .collect()
+        });
+        // Dropping the removed values touches refcounts, so it happens with the GIL held.
+        let count = removed.len();
+        let mut changes = Vec::new();
+        for (key, value) in removed {
+            if !self.observers.is_empty() {
+                changes.push(Change { key, old: Some(self.observed(py, &value)), new: None });
+            }
+            self.journal_removed(key, value);
+        }
+        self.shrink_if_sparse(py);
+        self.observers.notify(py, changes)?;
+        Ok(count)
+    }
+
+    /// The value `key` holds, if any, as observers see it.
+    fn current(&self, py: Python<'_>, key: u64) -> Option<PyObject> {
+        self.map.get(key).map(|value| self.observed(py, value))
+    }
+
+    /// Returns the keys `journal` recorded to their recorded state and tells
+    /// the observers.
+    fn roll_back(&mut self, py: Python<'_>, journal: Journal<PyValue>) -> PyResult<()> {
+        let mut changes = Vec::new();
+        let observing = !self.observers.is_empty();
+        let (weak, pool) = (self.weak, self.interner.as_ref().map_or(&[][..], Interner::values));
+        journal.roll_back(&mut self.map, |key, removed, restored| {
+            if observing {
+                let observed = |value: &PyValue| observed(py, weak, pool, value);
+                changes.push(Change { key, old: removed.as_ref().map(observed), new: restored.map(observed) });
+            }
+        });
+        self.observers.notify(py, changes)
+    }
+
+    /// Records the current state of `key` in the journal of the open
+    /// transaction, if any, ahead of a change to it.
+    fn journal_key(&mut self, py: Python<'_>, key: u64) {
+        if let Some(journal) = &mut self.journal {
+            journal.record(key, || self.map.get(key).map(|value| value.clone_ref(py)));
+        }
+    }
+
+    /// Records a value just removed from the map in the journal of the open
+    /// transaction, if any.
+    fn journal_removed(&mut self, key: u64, value: PyValue) {
+        if let Some(journal) = &mut self.journal {
+            journal.record(key, || Some(value));
+        }
+    }
+
+    /// Gives up capacity after removals if auto-shrink calls for it, except
+    /// while a transaction is open: rolling it back needs the room.
+    fn shrink_if_sparse(&mut self, py: Python<'_>) {
+        if self.journal.is_none() {
+            let map = &mut self.map;
+            py.allow_threads(|| map.shrink_if_sparse());
+        }
+    }
+
+    pub(crate) fn begin_transaction(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.check_open()?;
+        if self.journal.is_some() {
+            return Err(PyRuntimeError::new_err("the table already has an open transaction"));
+        }
+        // Inserts buffered before the transaction are not part of it.
+        self.apply_buffered(py)?;
+        self.journal = Some(Journal::new());
+        Ok(())
+    }
+
+    /// Closes the open transaction, undoing its changes unless `commit`.
+    pub(crate) fn end_transaction(&mut self, py: Python<'_>, commit: bool) -> PyResult<()> {
+        self.check_open()?;
+        let Some(journal) = self.journal.take() else { return Ok(()) };
+        if !commit {
+            if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+                buffer.clear();
+            }
+            self.roll_back(py, journal)?;
+        }
+        Ok(())
+    }
+
+    /// Calls the tracer with the inserts traced since the last call and checks
+    /// the load alert, unless a `bulk()` block defers that to its end.
+    fn report_inserts(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.bulk.is_some() {
+            return Ok(());
+        }
+        report_traces(py, &mut self.map, &self.tracer)?;
+        check_load(py, &self.map, &mut self.alert)
+    }
+
+    pub(crate) fn begin_bulk(&mut self, buffer: bool) -> PyResult<()> {
+        self.check_open()?;
+        if self.bulk.is_some() {
+            return Err(PyRuntimeError::new_err("the table is already in a bulk() block"));
+        }
+        self.bulk = Some(Bulk::new(buffer));
+        Ok(())
+    }
+
+    /// Inserts what the current `bulk()` block has buffered so far.
+    fn apply_buffered(&mut self, py: Python<'_>) -> PyResult<()> {
+        let Some(bulk) = &mut self.bulk else { return Ok(()) };
+        let (keys, values) = bulk.take();
+        if !keys.is_empty() {
+            self.insert_values(py, &keys, values)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the `bulk()` block: inserts what it buffered and makes the tracer
+    /// calls it deferred.
+    pub(crate) fn end_bulk(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.check_open()?;
+        let applied = self.apply_buffered(py);
+        self.bulk = None;
+        applied?;
+        self.report_inserts(py)
+    }
+
+    /// The entries of `other`, an ElasticTable or a dict with int keys.
+    fn merge_source<'py>(other: &Bound<'py, PyAny>) -> PyResult<(Vec<u64>, Vec<Bound<'py, PyAny>>)> {
+        let py = other.py();
+        if let Ok(table) = other.downcast::<ElasticTable>() {
+            let table = table.get().lock(py)?;
+            table.check_open()?;
+            let entries = table.live_entries(py, table.map.entries_in_order())?;
+            Ok(entries.into_iter().map(|(key, value)| (key, value.into_bound(py))).unzip())
+        } else {
+            let entries = update_entries(other)?
+                .into_iter()
+                .map(|(key, value)| Ok((key.extract::<u64>()?, value)))
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(entries.into_iter().unzip())
+        }
+    }
+
+    fn merge_entries(
+        &mut self,
+        py: Python<'_>,
+        mut keys: Vec<u64>,
+        mut values: Vec<Bound<'_, PyAny>>,
+        on_conflict: OnConflict,
+    ) -> PyResult<()> {
+        on_conflict.apply(&self.map, &mut keys, &mut values)?;
+        let values = values.iter()
+            .map(|value| self.encode(value))
+            .collect::<PyResult<Vec<_>>>()?;
+        self.insert_values(py, &keys, values)?;
+        Ok(())
+    }
+}
+
+impl ElasticTableState {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        capacity: usize,
+        delta: f64,
+        intern: bool,
+        weak: bool,
+        ordered: bool,
+        growth_factor: Option<f64>,
+        grow_at: Option<f64>,
+        max_capacity: Option<usize>,
+        value_type: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        check_delta(delta)?;
+        let schema = value_type.map(Schema::new).transpose()?;
+        if intern && weak {
+            return Err(PyValueError::new_err("intern and weak cannot be combined"));
+        }
+        let mut map = ElasticMap::new(capacity, delta);
+        if ordered {
+            map.keep_order();
+        }
+        map.set_growth(growth_policy(capacity, delta, growth_factor, grow_at, max_capacity)?);
+        Ok(ElasticTableState {
+            map,
+            interner: intern.then(|| Interner::new(py)),
+            weak,
+            tracer: None,
+            observers: Observers::default(),
+            alert: None,
+            bulk: None,
+            journal: None,
+            closed: false,
+            schema,
+        })
+    }
+
+    fn for_items(
+        py: Python<'_>,
+        expected_items: usize,
+        load_factor: f64,
+        intern: bool,
+        weak: bool,
+        ordered: bool,
+        value_type: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+        Self::new(py, capacity, delta, intern, weak, ordered, None, None, None, value_type)
+    }
+
+    fn from_json(py: Python<'_>, data: &str, load_factor: f64, intern: bool, weak: bool, ordered: bool) -> PyResult<Self> {
+        let entries = entries_from_json(py, data)?;
+        let mut table = Self::for_items(py, entries.len(), load_factor, intern, weak, ordered, None)?;
+        for (key, value) in entries {
+            table.insert(py, key, &value)?;
+        }
+        Ok(table)
+    }
+
+    fn to_json(&self, py: Python<'_>, indent: Option<usize>) -> PyResult<String> {
+        self.check_open()?;
+        let entries = self.live_entries(py, self.map.iter())?;
+        entries_to_json(py, entries, indent)
+    }
+
+    fn ordered(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(self.map.keeps_order())
+    }
+
+    fn value_type(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.check_open()?;
+        Ok(self.schema.as_ref().map(|schema| schema.object().clone_ref(py)))
+    }
+
+    fn closed(&self) -> bool {
+        self.closed
+    }
+
+    fn traverse(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        for (_, value) in self.map.iter() {
+            value.traverse(&visit)?;
+        }
+        if let Some(interner) = &self.interner {
+            interner.traverse(&visit)?;
+        }
+        if let Some(tracer) = &self.tracer {
+            visit.call(tracer)?;
+        }
+        self.observers.traverse(&visit)?;
+        if let Some(alert) = &self.alert {
+            alert.traverse(&visit)?;
+        }
+        if let Some(schema) = &self.schema {
+            schema.traverse(&visit)?;
+        }
+        for value in self.bulk.iter().flat_map(Bulk::values) {
+            value.traverse(&visit)?;
+        }
+        for value in self.journal.iter().flat_map(Journal::values) {
+            value.traverse(&visit)?;
+        }
+        Ok(())
+    }
+
+    fn clear_references(&mut self) {
+        self.map.clear();
+        self.tracer = None;
+        self.observers.clear();
+        self.alert = None;
+        self.schema = None;
+        self.bulk = None;
+        self.journal = None;
+        if self.interner.is_some() {
+            self.interner = Some(Python::with_gil(Interner::new));
+        }
+    }
+
+    fn sample(&self, py: Python<'_>, n: usize, items: bool, seed: Option<u64>) -> PyResult<PyObject> {
+        self.check_open()?;
+        let sample = self.map.sample(n, &mut sample_rng(seed));
+        if items {
+            let items = self.live_entries(py, sample)?;
+            Ok(items.into_pyobject(py)?.into_any().unbind())
+        } else {
+            let keys: Vec<u64> = sample.into_iter().map(|(key, _)| key).collect();
+            Ok(keys.into_pyobject(py)?.into_any().unbind())
+        }
+    }
+
+    fn entry_at(&self, py: Python<'_>, index: isize) -> PyResult<(u64, PyObject)> {
+        self.check_open()?;
+        let index = entry_index(index, self.map.len())?;
+        let (key, value) = self.map.entry_at(index).expect("index is below the entry count");
+        Ok((key, self.observed(py, value)))
+    }
+
+    fn entry_range(&self, py: Python<'_>, start: usize, stop: Option<usize>) -> PyResult<Vec<(u64, PyObject)>> {
+        self.check_open()?;
+        let stop = stop.unwrap_or(usize::MAX).min(self.map.len());
+        self.live_entries(py, self.map.entry_range(start, stop))
+    }
+
+    fn subarray_lens(&self) -> PyResult<Vec<usize>> {
+        self.check_open()?;
+        Ok(self.map.subarray_lens())
+    }
+
+    fn insert(&mut self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        self.check_open()?;
+        let value = self.encode(value)?;
+        if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+            buffer.push((key, value));
+            return Ok(0);
+        }
+        self.journal_key(py, key);
+        let change = (!self.observers.is_empty()).then(|| (self.current(py, key), self.observed(py, &value)));
+        let result = self.map.insert(key, value);
+        self.report_inserts(py)?;
+        let probes = result?;
+        if let Some((old, new)) = change {
+            self.observers.notify(py, [Change { key, old, new: Some(new) }])?;
+        }
+        Ok(probes)
+    }
+
+    fn insert_many(&mut self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.check_open()?;
+        let keys = keys.as_slice(py)?;
+        if keys.len() != values.len() {
+            return Err(PyValueError::new_err("keys and values must have the same length"));
+        }
+        let values = values.iter()
+            .map(|value| self.encode(value))
+            .collect::<PyResult<Vec<_>>>()?;
+        if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+            buffer.extend(keys.iter().copied().zip(values));
+            return Ok(0);
+        }
+        self.insert_values(py, &keys, values)
+    }
+
+    fn insert_all(&mut self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.check_open()?;
+        let keys = keys.as_slice(py)?;
+        if keys.len() != values.len() {
+            return Err(PyValueError::new_err("keys and values must have the same length"));
+        }
+        let values = values.iter()
+            .map(|value| self.encode(value))
+            .collect::<PyResult<Vec<_>>>()?;
+        // Buffered inserts come first, so they cannot overwrite these later.
+        self.apply_buffered(py)?;
+        let mut undo = Journal::new();
+        for &key in keys.iter() {
+            undo.record(key, || self.map.get(key).map(|value| value.clone_ref(py)));
+        }
+        match self.insert_values(py, &keys, values) {
+            Ok(probes) => Ok(probes),
+            Err(err) => {
+                self.roll_back(py, undo)?;
+                Err(err)
+            }
+        }
+    }
+
+    fn get(&self, py: Python<'_>, key: u64, default: Option<PyObject>) -> PyResult<PyObject> {
+        Ok(self.lookup(py, key)?.or(default).unwrap_or_else(|| py.None()))
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let found = match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key)?,
+            Err(_) => None,
+        };
+        found.ok_or_else(|| PyKeyError::new_err(key.clone().unbind()))
+    }
+
+    fn __contains__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(match key.extract::<u64>() {
+            Ok(key) => self.lookup(py, key)?.is_some(),
+            Err(_) => false,
+        })
+    }
+
+    fn delete(&mut self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        self.check_open()?;
+        self.apply_buffered(py)?;
+        let Some(value) = self.map.remove(key) else { return Ok(false) };
+        let old = (!self.observers.is_empty()).then(|| self.observed(py, &value));
+        self.journal_removed(key, value);
+        self.shrink_if_sparse(py);
+        if let Some(old) = old {
+            self.observers.notify(py, [Change { key, old: Some(old), new: None }])?;
+        }
+        Ok(true)
+    }
+
+    fn delete_many(&mut self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        self.check_open()?;
+        let keys = keys.as_slice(py)?;
+        self.apply_buffered(py)?;
+        self.remove_keys(py, &keys)
+    }
+
+    fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<PyObject>, mask: bool, defaults: Option<Vec<PyObject>>) -> PyResult<PyObject> {
+        self.check_open()?;
+        let keys_buffer = keys.is_buffer();
+        let keys = keys.as_slice(py)?;
+        let fills = ObjectFills::new(py, default, defaults, keys.len())?;
+        let map = &self.map;
+        let found: Vec<Option<&PyValue>> = py.allow_threads(|| {
+            map_keys(map, &keys, |key, hash| map.get_hashed(key, hash))
+        });
+        // A weakly held value that has been collected counts as missing.
+        let found = found.into_iter()
+            .map(|value| Ok(match value {
+                Some(value) => self.decode(py, value)?,
+                None => None,
+            }))
+            .collect::<PyResult<Vec<_>>>()?;
+        map.count_lookups(found.len(), found.iter().flatten().count());
+        let hits = mask.then(|| found.iter().map(Option::is_some).collect());
+        let values: Vec<PyObject> = found.into_iter()
+            .enumerate().map(|(index, value)| value.unwrap_or_else(|| fills.at(py, index)))
+            .collect();
+        lookup_result(py, values, hits, keys_buffer)
+    }
+
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        self.check_open()?;
+        let keys = keys.as_slice(py)?;
+        let map = &self.map;
+        let mut found = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.contains_hashed(key, hash)));
+        if self.weak {
+            for (hit, &key) in found.iter_mut().zip(keys.iter()) {
+                if *hit {
+                    *hit = self.value_of(py, key)?.is_some();
+                }
+            }
+        }
+        map.count_lookups(found.len(), found.iter().filter(|&&hit| hit).count());
+        Ok(found)
+    }
+
+    /// The keys in ascending order, for `intersection_keys` and `difference_keys`.
+    fn sorted_keys(&self, py: Python<'_>) -> PyResult<Vec<u64>> {
+        self.check_open()?;
+        Ok(sorted_keys(py, &self.map))
+    }
+
+    fn split(&self, py: Python<'_>, n: usize) -> PyResult<Vec<Self>> {
+        self.check_open()?;
+        check_parts(n)?;
+        split_entries(&self.map, n).into_iter()
+            .map(|(capacity, entries)| {
+                let (delta, intern, ordered) = (self.map.delta(), self.interner.is_some(), self.map.keeps_order());
+                let value_type = self.schema.as_ref().map(|schema| schema.object().bind(py));
+                let mut part = ElasticTableState::new(py, capacity, delta, intern, self.weak, ordered, None, None, None, value_type)?;
+                let (keys, values): (Vec<u64>, Vec<_>) = self.live_entries(py, entries)?.into_iter()
+                    .map(|(key, value)| (key, value.into_bound(py)))
+                    .unzip();
+                part.merge_entries(py, keys, values, OnConflict::Replace)?;
+                Ok(part)
+            })
+            .collect()
+    }
+
+    fn namespace_keys(&self, tag: u64, tag_bits: u32) -> PyResult<Vec<u64>> {
+        self.check_open()?;
+        let namespace = Namespace::new(tag, tag_bits)?;
+        Ok(self.map.entries_in_order().into_iter().filter_map(|(key, _)| namespace.local(key)).collect())
+    }
+
+    fn namespace_items(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<Vec<(u64, PyObject)>> {
+        self.check_open()?;
+        let namespace = Namespace::new(tag, tag_bits)?;
+        let local = self.map.entries_in_order().into_iter()
+            .filter_map(|(key, value)| Some((namespace.local(key)?, value)));
+        self.live_entries(py, local)
+    }
+
+    fn delete_namespace(&mut self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<usize> {
+        self.check_open()?;
+        let namespace = Namespace::new(tag, tag_bits)?;
+        self.apply_buffered(py)?;
+        let keys: Vec<u64> = self.map.iter().map(|(key, _)| key).filter(|&key| namespace.local(key).is_some()).collect();
+        self.remove_keys(py, &keys)
+    }
+
+    fn rebalance(&mut self) -> PyResult<()> {
+        self.check_open()?;
+        self.map.rebalance();
+        Ok(())
+    }
+
+    fn clear(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.check_open()?;
+        if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+            buffer.clear();
+        }
+        if let Some(journal) = &mut self.journal {
+            for (key, value) in self.map.iter() {
+                journal.record(key, || Some(value.clone_ref(py)));
+            }
+        }
+        let mut changes = Vec::new();
+        if !self.observers.is_empty() {
+            changes = self.map.iter()
+                .map(|(key, value)| Change { key, old: Some(self.observed(py, value)), new: None })
+                .collect();
+        }
+        self.map.clear();
+        if self.interner.is_some() && self.journal.is_none() {
+            self.interner = Some(Interner::new(py));
+        }
+        self.observers.notify(py, changes)
+    }
+
+    fn purge(&mut self, py: Python<'_>) -> PyResult<usize> {
+        self.check_open()?;
+        if !self.weak {
+            return Ok(0);
+        }
+        let dead: Vec<u64> = self.map.iter()
+            .filter(|(_, value)| matches!(self.decode(py, value), Ok(None)))
+            .map(|(key, _)| key)
+            .collect();
+        for &key in &dead {
+            if let Some(value) = self.map.remove(key) {
+                self.journal_removed(key, value);
+            }
+        }
+        self.shrink_if_sparse(py);
+        // The values are gone, so observers see each as None.
+        self.observers.notify(py, dead.iter().map(|&key| Change { key, old: Some(py.None()), new: None }))?;
+        Ok(dead.len())
+    }
+
+    fn enable_bloom_filter(&mut self, py: Python<'_>, bits_per_key: usize) -> PyResult<()> {
+        self.check_open()?;
+        check_bits_per_key(bits_per_key)?;
+        let map = &mut self.map;
+        py.allow_threads(|| map.enable_filter(bits_per_key));
+        Ok(())
+    }
+
+    fn disable_bloom_filter(&mut self) -> PyResult<()> {
+        self.check_open()?;
+        self.map.disable_filter();
+        Ok(())
+    }
+
+    fn bloom_filter(&self) -> PyResult<Option<usize>> {
+        self.check_open()?;
+        Ok(self.map.filter_bits_per_key())
+    }
+
+    fn enable_auto_shrink(&mut self, threshold: f64, min_capacity: usize) -> PyResult<()> {
+        self.check_open()?;
+        self.map.set_auto_shrink(Some(shrink_policy(threshold, min_capacity)?));
+        Ok(())
+    }
+
+    fn disable_auto_shrink(&mut self) -> PyResult<()> {
+        self.check_open()?;
+        self.map.set_auto_shrink(None);
+        Ok(())
+    }
+
+    fn auto_shrink(&self) -> PyResult<Option<f64>> {
+        self.check_open()?;
+        Ok(self.map.auto_shrink().map(|policy| policy.threshold))
+    }
+
+    fn track_probes(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(self.map.histograms().is_some())
+    }
+
+    fn set_track_probes(&mut self, enabled: bool) -> PyResult<()> {
+        self.check_open()?;
+        self.map.track_probes(enabled);
+        Ok(())
+    }
+
+    fn adaptive(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(self.map.tunes_probes())
+    }
+
+    fn set_adaptive(&mut self, enabled: bool) -> PyResult<()> {
+        self.check_open()?;
+        self.map.tune_probes(enabled);
+        Ok(())
+    }
+
+    fn two_choices(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(self.map.two_choices())
+    }
+
+    fn set_two_choices(&mut self, enabled: bool) -> PyResult<()> {
+        self.check_open()?;
+        if enabled && self.map.robin_hood() {
+            return Err(PyValueError::new_err("two_choices cannot be combined with robin_hood"));
+        }
+        self.map.set_two_choices(enabled);
+        Ok(())
+    }
+
+    fn robin_hood(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(self.map.robin_hood())
+    }
+
+    fn set_robin_hood(&mut self, enabled: bool) -> PyResult<()> {
+        self.check_open()?;
+        if enabled && self.map.two_choices() {
+            return Err(PyValueError::new_err("robin_hood cannot be combined with two_choices"));
+        }
+        self.map.set_robin_hood(enabled);
+        Ok(())
+    }
+
+    fn cuckoo_kicks(&self) -> PyResult<usize> {
+        self.check_open()?;
+        Ok(self.map.cuckoo_kicks())
+    }
+
+    fn set_cuckoo_kicks(&mut self, kicks: usize) -> PyResult<()> {
+        self.check_open()?;
+        self.map.set_cuckoo_kicks(kicks);
+        Ok(())
+    }
+
+    fn probe_histograms<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.check_open()?;
+        self.map.histograms().map(|histograms| histograms_to_dict(py, histograms)).transpose()
+    }
+
+    fn op_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.check_open()?;
+        op_counts_to_dict(py, self.map.op_counts())
+    }
+
+    fn reset_stats(&mut self) -> PyResult<()> {
+        self.check_open()?;
+        self.map.reset_counts();
+        Ok(())
+    }
+
+    fn interned_count(&self) -> PyResult<usize> {
+        self.check_open()?;
+        Ok(self.interner.as_ref().map_or(0, Interner::len))
+    }
+
+    fn capacity(&self) -> PyResult<usize> {
+        self.check_open()?;
+        Ok(self.map.capacity())
+    }
+
+    fn remaining_capacity(&self) -> PyResult<usize> {
+        self.check_open()?;
+        Ok(self.map.remaining_capacity())
+    }
+
+    fn is_full(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(self.map.remaining_capacity() == 0)
+    }
+
+    fn stats(&self) -> PyResult<Vec<(usize, usize, f64)>> {
+        self.check_open()?;
+        Ok(self.map.stats())
+    }
+
+    fn set_tracer(&mut self, callback: Option<PyObject>) -> PyResult<()> {
+        self.check_open()?;
+        self.map.trace_inserts(callback.is_some());
+        self.tracer = callback;
+        Ok(())
+    }
+
+    fn add_observer(&mut self, callback: PyObject) -> PyResult<()> {
+        self.check_open()?;
+        self.observers.add(callback);
+        Ok(())
+    }
+
+    fn remove_observer(&mut self, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.check_open()?;
+        self.observers.remove(callback)
+    }
+
+    fn set_load_alert(
+        &mut self,
+        threshold: Option<f64>,
+        callback: Option<PyObject>,
+        subarray_threshold: Option<f64>,
+    ) -> PyResult<()> {
+        self.check_open()?;
+        self.alert = threshold.map(|threshold| LoadAlert::new(threshold, subarray_threshold, callback)).transpose()?;
+        Ok(())
+    }
+
+    fn load_alert(&self) -> PyResult<Option<f64>> {
+        self.check_open()?;
+        Ok(self.alert.as_ref().map(LoadAlert::threshold))
+    }
+
+    fn metrics_text(&self, prefix: &str, labels: Option<BTreeMap<String, String>>) -> PyResult<String> {
+        self.check_open()?;
+        metrics_text(&self.map, prefix, labels.unwrap_or_default())
+    }
+
+    fn probe_bounds<'py>(&self, py: Python<'py>, tolerance: f64) -> PyResult<Bound<'py, PyDict>> {
+        self.check_open()?;
+        probe_bounds(py, &self.map, tolerance)
+    }
+
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.check_open()?;
+        memory_usage(py, &self.map, |i| {
+            Some(self.map.subarray_entries(i).filter(|(_, value)| value.as_object().is_some()).count())
+        })
+    }
+
+    fn occupancy(&self, py: Python<'_>, packed: bool) -> PyResult<Vec<PyObject>> {
+        self.check_open()?;
+        occupancy(py, &self.map, packed)
+    }
+
+    fn items_in_subarray(&self, py: Python<'_>, i: usize) -> PyResult<Vec<(u64, PyObject)>> {
+        self.check_open()?;
+        Ok(subarray_items(&self.map, i)?.map(|(key, value)| (key, self.observed(py, value))).collect())
+    }
+
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.check_open()?;
+        detailed_stats(py, &self.map)
+    }
+
+    fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        self.check_open()?;
+        Ok(py.allow_threads(|| self.map.validate()))
+    }
+}
+
+#[pymethods]
+impl ElasticTable {
+    /// Create a new ElasticTable with specified capacity and delta parameter.
+    /// 
+    /// Args:
+    ///     capacity: Total number of slots in the hash table
+    ///     delta: Elasticity parameter (default: 0.05). Target load factor = 1 - delta.
+    ///            Lower delta = higher load factor but may increase probe count.
+    ///            Recommended range: 0.05 to 0.20
+    ///     intern: Store each distinct value once and have slots refer to it
+    ///             (default: False). Worth it when many keys share few values.
+    ///     weak: Hold values through weak references (default: False), so the
+    ///           table does not keep them alive. Entries whose value has been
+    ///           collected count as missing.
+    ///     ordered: Remember insertion order, like dict (default: False), so
+    ///              `keys()`, `values()`, `items()` and iteration follow it.
+    ///              Costs 8 bytes per slot.
+    ///     growth_factor: Grow the table by this factor, instead of raising
+    ///                    TableFullError, when it fills up (default: None,
+    ///                    fixed capacity). Must be greater than 1.
+    ///     grow_at: Load factor an insert must not take the table past
+    ///              before it grows (default: 1 - delta).
+    ///     max_capacity: Capacity the table never grows past (default: None,
+    ///                   no ceiling). Inserts fail as usual once it is reached.
+    ///     value_type: A type or tuple of types every value must be an instance
+    ///                 of, or a callable returning whether a value is acceptable
+    ///                 (default: None, any value). Inserts of other values raise
+    ///                 TypeError, as does a callable that raises it.
+    #[new]
+    #[pyo3(signature = (
+        capacity, delta=0.05, intern=false, weak=false, ordered=false,
+        growth_factor=None, grow_at=None, max_capacity=None, value_type=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        capacity: usize,
+        delta: f64,
+        intern: bool,
+        weak: bool,
+        ordered: bool,
+        growth_factor: Option<f64>,
+        grow_at: Option<f64>,
+        max_capacity: Option<usize>,
+        value_type: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        ElasticTableState::new(py, capacity, delta, intern, weak, ordered, growth_factor, grow_at, max_capacity, value_type).map(Self::from)
+    }
+
+    /// Parametrize the class for type hints, as `ElasticTable[int, V]`.
+    ///
+    /// Like `dict[int, V]`, the result is a `types.GenericAlias`: its
+    /// `__origin__` is ElasticTable and its `__args__` the key and value
+    /// types, for `typing.get_origin` and `typing.get_args`. Calling it
+    /// creates a plain ElasticTable, and subclassing it, as in
+    /// `class Users(ElasticTable[int, User])`, records it in the subclass's
+    /// `__orig_bases__`. The types are not enforced; pass `value_type` for that.
+    #[classmethod]
+    fn __class_getitem__<'py>(cls: &Bound<'py, PyType>, params: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        if !params.downcast::<PyTuple>().is_ok_and(|params| params.len() == 2) {
+            return Err(PyTypeError::new_err("ElasticTable takes a key type and a value type, as in ElasticTable[int, str]"));
+        }
+        cls.py().import("types")?.getattr("GenericAlias")?.call1((cls, params))
+    }
+
+    /// Create an ElasticTable sized for a specific number of expected items.
+    /// 
+    /// Args:
+    ///     expected_items: The number of items you plan to store
+    ///     load_factor: Target load factor (default: 0.90). Must be between 0.5 and 0.98.
+    ///                  Higher values = more space-efficient but slightly more probes.
+    ///                  Recommended: 0.85-0.95
+    /// 
+    /// Example:
+    ///     table = ElasticTable.for_items(1000000)  # Stores ~1M items at 90% load
+    ///     table = ElasticTable.for_items(1000000, 0.95)  # More space-efficient
+    #[staticmethod]
+    #[pyo3(signature = (expected_items, load_factor=0.90, intern=false, weak=false, ordered=false, value_type=None))]
+    fn for_items(
+        py: Python<'_>,
+        expected_items: usize,
+        load_factor: f64,
+        intern: bool,
+        weak: bool,
+        ordered: bool,
+        value_type: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        ElasticTableState::for_items(py, expected_items, load_factor, intern, weak, ordered, value_type).map(Self::from)
+    }
+
+    /// Time a standard workload on this machine, against a dict.
+    ///
+    /// Inserts `n` distinct pseudo-random keys into a table sized for them at
+    /// `load_factor`, looks each one up again, then looks up `n` keys that are
+    /// absent, and repeats the same operations on a dict. Both are driven from
+    /// native code, so the cost of calling them from Python is left out.
+    ///
+    /// Returns a dict with "n", the reached "load_factor" and "capacity";
+    /// "elastic" and "dict" dicts giving the seconds each phase took
+    /// ("insert_seconds", "hit_seconds", "miss_seconds") and the same per
+    /// operation in nanoseconds ("insert_ns", ...); and "probes", with the mean
+    /// and max probes of the table's inserts, hits and misses.
+    #[staticmethod]
+    #[pyo3(signature = (n=1_000_000, load_factor=0.90))]
+    fn benchmark(py: Python<'_>, n: usize, load_factor: f64) -> PyResult<Bound<'_, PyDict>> {
+        benchmark(py, n, load_factor)
+    }
+
+    /// Run one workload through elastic hashing, plain, with `two_choices`
+    /// and with `robin_hood`, funnel hashing, Rust's `HashMap` and a dict,
+    /// for comparing them on the current machine.
+    ///
+    /// `n` distinct keys derived from `seed` are inserted into tables sized
+    /// for them at `load_factor`, looked up again, and then `n` absent keys
+    /// are looked up; the same arguments always give the same keys. Returns a
+    /// dict with "n", "load_factor", "capacity", "seed", "tables", mapping
+    /// each table's name to its "insert_ns", "hit_ns", "miss_ns",
+    /// "failed_inserts" and, for the open addressing tables,
+    /// "insert_probes", "hit_probes" and "miss_probes" (each a dict of "mean"
+    /// and "max", otherwise None), and "report", the same as printable text.
+    #[staticmethod]
+    #[pyo3(signature = (n=1_000_000, load_factor=0.95, seed=0))]
+    fn compare(py: Python<'_>, n: usize, load_factor: f64, seed: u64) -> PyResult<Bound<'_, PyDict>> {
+        compare_tables(py, n, load_factor, seed)
+    }
+
+    /// Build a table from a JSON object mapping decimal keys to values, such as
+    /// the output of `to_json()`. Takes the same options as `for_items`.
+    #[staticmethod]
+    #[pyo3(signature = (data, load_factor=0.90, intern=false, weak=false, ordered=false))]
+    fn from_json(py: Python<'_>, data: &str, load_factor: f64, intern: bool, weak: bool, ordered: bool) -> PyResult<Self> {
+        ElasticTableState::from_json(py, data, load_factor, intern, weak, ordered).map(Self::from)
+    }
+
+    /// Serialize the contents as a JSON object mapping each key, as a decimal
+    /// string, to its value. Keys appear in ascending order, so the output is
+    /// stable enough for golden-file tests. Values must be JSON-serializable.
+    #[pyo3(signature = (indent=None))]
+    fn to_json(&self, py: Python<'_>, indent: Option<usize>) -> PyResult<String> {
+        self.lock(py)?.to_json(py, indent)
+    }
+
+    /// Whether the table remembers insertion order.
+    #[getter]
+    fn ordered(&self, py: Python<'_>) -> PyResult<bool> {
+        self.lock(py)?.ordered()
+    }
+
+    /// The `value_type` values are checked against, or None.
+    #[getter]
+    fn value_type(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.lock(py)?.value_type(py)
+    }
+
+    /// A live view of the keys, in insertion order for ordered tables and in
+    /// slot order otherwise. Like a dict's, it reflects later changes and is
+    /// set-like: `&`, `|`, `-` and `^` take any iterable and return a set.
+    fn keys(slf: &Bound<'_, Self>) -> PyResult<KeysView> {
+        slf.get().lock(slf.py())?.check_open()?;
+        Ok(KeysView::new(slf.clone().unbind()))
+    }
+
+    /// A live view of the values, in the order of `keys()`.
+    fn values(slf: &Bound<'_, Self>) -> PyResult<ValuesView> {
+        slf.get().lock(slf.py())?.check_open()?;
+        Ok(ValuesView::new(slf.clone().unbind()))
+    }
+
+    /// A live, set-like view of the `(key, value)` pairs, in the order of `keys()`.
+    fn items(slf: &Bound<'_, Self>) -> PyResult<ItemsView> {
+        slf.get().lock(slf.py())?.check_open()?;
+        Ok(ItemsView::new(slf.clone().unbind()))
+    }
+
+    /// Start a block of inserts, for use as `with table.bulk():`.
+    ///
+    /// Until the block ends, the tracer set with `set_tracer` is called once,
+    /// at the end, instead of after every insert. With `buffer=True`, `insert`
+    /// and `insert_many` also hold their entries back and return 0, and the
+    /// end of the block inserts them in one batch with the GIL released;
+    /// lookups do not see them until then.
+    /// `delete` and `delete_many` first insert what was held back, and
+    /// `clear` discards it. The block ends, and applies its inserts, even if
+    /// it raises.
+    #[pyo3(signature = (buffer=false))]
+    fn bulk(slf: &Bound<'_, Self>, buffer: bool) -> PyResult<BulkBlock> {
+        slf.get().lock(slf.py())?.check_open()?;
+        Ok(BulkBlock::new(slf.clone().unbind(), buffer))
+    }
+
+    /// Open a transaction, so that a group of changes can be undone together.
+    ///
+    /// Until the returned transaction's `commit()` or `rollback()`, the table
+    /// remembers the value every key held before the transaction first
+    /// changed it. `rollback()` puts those values back and removes keys the
+    /// transaction added; `commit()` keeps the changes. Used as
+    /// `with table.transaction():`, it commits when the block completes and
+    /// rolls back when it raises. The journal costs one entry per distinct key
+    /// changed. Only one transaction can be open at a time; values restored
+    /// by a rollback move to the end of an ordered table.
+    fn transaction(slf: &Bound<'_, Self>) -> PyResult<Transaction> {
+        slf.get().lock(slf.py())?.begin_transaction(slf.py())?;
+        Ok(Transaction::new(slf.clone().unbind()))
+    }
+
+    /// Release every stored value now, and make later use of the table raise
+    /// `TableClosedError`.
+    ///
+    /// A table that is simply dropped frees its values whenever its last
+    /// reference goes away, which for tens of millions of objects is a long
+    /// pause at an unpredictable point. `close()` frees them where it is
+    /// called, in chunks, and lets other threads run between chunks. Values
+    /// whose finalizers look at the table find it closed already. An open
+    /// transaction or `bulk()` block is discarded. Closing a closed table does
+    /// nothing. Used as `with table:`, the table is closed when the block ends.
+    fn close(slf: &Bound<'_, Self>) -> PyResult<()> {
+        let mut table = slf.get().lock(slf.py())?;
+        if table.closed {
+            return Ok(());
+        }
+        table.closed = true;
+        let delta = table.map.delta();
+        let map = std::mem::replace(&mut table.map, ElasticMap::new(0, delta));
+        let rest = (
+            table.interner.take(),
+            table.tracer.take(),
+            std::mem::take(&mut table.observers),
+            table.alert.take(),
+            table.bulk.take(),
+            table.journal.take(),
+        );
+        // Values are freed without holding the table, so their finalizers can use it.
+        drop(table);
+        drop(rest);
+        release(slf.py(), map.into_values());
+        Ok(())
+    }
+
+    /// Whether `close()` was called.
+    #[getter]
+    fn closed(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(self.lock(py)?.closed())
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyResult<PyRef<'_, Self>> {
+        slf.lock(slf.py())?.check_open()?;
+        Ok(slf)
+    }
+
+    /// Closes the table, whether or not the block raised.
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        slf: &Bound<'_, Self>,
+        _exc_type: Option<&Bound<'_, PyType>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        Self::close(slf)?;
+        Ok(false)
+    }
+
+    /// Iterate over the keys, in the order of `keys()`. Like a `dict`, the
+    /// table must not gain or lose entries meanwhile: the iterator then
+    /// raises `RuntimeError`. Unordered tables are walked in place, without
+    /// copying their keys.
+    fn __iter__(slf: &Bound<'_, Self>) -> PyResult<TableIterator> {
+        let table = slf.get().lock(slf.py())?;
+        table.check_open()?;
+        Ok(TableIterator::new(slf.clone().unbind(), table.map(), Yield::Keys))
+    }
+
+    /// Reports every object the table holds to the cyclic GC, so values that
+    /// refer back to the table can be collected.
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        // A table in use by a call is not reported, which only delays
+        // collecting a cycle through it.
+        match self.state.try_lock() {
+            Some(state) => state.traverse(visit),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops every object the table holds, to break a reference cycle.
+    fn __clear__(&self) {
+        if let Some(mut state) = self.state.try_lock() {
+            state.clear_references();
+        }
+    }
+
+    /// `n` distinct keys chosen uniformly at random, or every key if the table
+    /// holds fewer, as a list in random order. With `items=True` the list holds
+    /// `(key, value)` pairs instead. Passing a `seed` makes the sample
+    /// repeatable for the same contents.
+    ///
+    /// Well-filled tables are sampled by drawing random slots, so the cost
+    /// grows with `n` rather than with the table; only tables that are mostly
+    /// empty, or samples of most of the entries, take a pass over every slot.
+    #[pyo3(signature = (n, items=false, seed=None))]
+    fn sample(&self, py: Python<'_>, n: usize, items: bool, seed: Option<u64>) -> PyResult<PyObject> {
+        self.lock(py)?.sample(py, n, items, seed)
+    }
+
+    /// The entry at position `index` of slot order, as a `(key, value)` pair.
+    /// Negative indices count from the end.
+    ///
+    /// Positions number the entries subarray by subarray, in the order an
+    /// unordered table's `keys()` lists them, and only shift when entries are
+    /// inserted or removed. Finding one skips whole subarrays by their counts
+    /// and then counts control bytes, without visiting the entries before it.
+    fn entry_at(&self, py: Python<'_>, index: isize) -> PyResult<(u64, PyObject)> {
+        self.lock(py)?.entry_at(py, index)
+    }
+
+    /// The entries at positions `start` up to `stop` of slot order (see
+    /// `entry_at`), as `(key, value)` pairs, so workers can each export one
+    /// chunk of a table. `stop` defaults to, and is capped at, the number of entries.
+    #[pyo3(signature = (start, stop=None))]
+    fn entry_range(&self, py: Python<'_>, start: usize, stop: Option<usize>) -> PyResult<Vec<(u64, PyObject)>> {
+        self.lock(py)?.entry_range(py, start, stop)
+    }
+
+    /// Number of entries in each subarray, in slot order, so that
+    /// `entry_range` chunks can follow subarray boundaries.
+    fn subarray_lens(&self, py: Python<'_>) -> PyResult<Vec<usize>> {
+        self.lock(py)?.subarray_lens()
+    }
+
+    fn insert(&self, py: Python<'_>, key: u64, value: &Bound<'_, PyAny>) -> PyResult<usize> {
+        self.lock(py)?.insert(py, key, value)
+    }
+
+    /// Insert many key/value pairs in a single call.
+    ///
+    /// Args:
+    ///     keys: Sequence of integer keys, or a uint64 numpy array
+    ///     values: Sequence of values, same length as `keys`
+    ///
+    /// Returns the total number of probes used across all insertions.
+    /// Pairs are inserted in order; if one fails, the pairs before it stay inserted.
+    /// The GIL is released while probing.
+    fn insert_many(&self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.lock(py)?.insert_many(py, keys, values)
+    }
+
+    /// Insert many key/value pairs, all or none.
+    ///
+    /// Takes the same arguments as `insert_many`. If some key cannot be
+    /// placed, the pairs already inserted are taken out again, the values they
+    /// replaced are put back, and the error is raised with the table as it was
+    /// before the call. Remembering the replaced values costs one lookup per
+    /// key. Returns the total probes used.
+    fn insert_all(&self, py: Python<'_>, keys: KeyBatch, values: Vec<Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.lock(py)?.insert_all(py, keys, values)
+    }
+
+    /// The value stored under `key`, or `default` if the table does not hold
+    /// the key. A stored None comes back as None too; `table[key]` and
+    /// `key in table` tell it apart from a missing key.
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python<'_>, key: u64, default: Option<PyObject>) -> PyResult<PyObject> {
+        self.lock(py)?.get(py, key, default)
+    }
+
+    /// `table[key]`: the value stored under `key`. Raises KeyError if the
+    /// table does not hold the key.
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.lock(py)?.__getitem__(py, key)
+    }
+
+    /// `key in table`, which is True for keys holding None too.
+    fn __contains__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        self.lock(py)?.__contains__(py, key)
+    }
+
+    /// Remove a key from the table. Returns True if the key was present.
+    fn delete(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+        self.lock(py)?.delete(py, key)
+    }
+
+    /// Remove many keys in a single call.
+    ///
+    /// Accepts a sequence of integer keys or a uint64 numpy array.
+    /// Returns the number of keys that were actually present.
+    /// The GIL is released while probing.
+    fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+        self.lock(py)?.delete_many(py, keys)
+    }
+
+    /// Look up many keys in a single call.
+    ///
+    /// Args:
+    ///     keys: Sequence of integer keys, or a uint64 numpy array
+    ///     default: Value returned for keys that are not present (default: None).
+    ///         Always a single value: a list is returned as it is.
+    ///     mask: Also return which keys were found (default: False)
+    ///     defaults: Sequence holding a value for each key, returned in place
+    ///         of that key's value when it is not present; exclusive with `default`.
+    ///         Every table class takes per-key values this way.
+    ///
+    /// Returns a list of values in the same order as `keys`. With `mask`,
+    /// returns a `(values, found)` pair, where `found` flags the keys present:
+    /// a numpy bool array if `keys` is a numpy array, a list otherwise.
+    /// The GIL is released while probing; large batches are probed in parallel.
+    #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+    fn get_many(
+        &self,
+        py: Python<'_>,
+        keys: KeyBatch,
+        default: Option<PyObject>,
+        mask: bool,
+        defaults: Option<Vec<PyObject>>,
+    ) -> PyResult<PyObject> {
+        self.lock(py)?.get_many(py, keys, default, mask, defaults)
+    }
+
+    /// Check membership for many keys in a single call.
+    ///
+    /// Accepts a sequence of integer keys or a uint64 numpy array.
+    /// Returns a list of booleans in the same order as `keys`.
+    /// The GIL is released while probing; large batches are probed in parallel.
+    fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+        self.lock(py)?.contains_many(py, keys)
+    }
+
+    /// Insert every entry of `other`: an ElasticTable, a dict or other mapping
+    /// with int keys, or an iterable of `(key, value)` pairs, read as
+    /// `dict.update` reads them.
+    ///
+    /// `on_conflict` decides what happens to keys both hold: "replace" (the
+    /// default) stores the value from `other`, "keep" leaves the stored value,
+    /// and "error" raises KeyError for the first such key before anything is
+    /// inserted. Values are inserted in bulk with the GIL released. As with
+    /// `insert_many`, entries inserted before a failed insert stay in the table.
+    #[pyo3(signature = (other, on_conflict="replace"))]
+    fn merge(slf: &Bound<'_, Self>, other: &Bound<'_, PyAny>, on_conflict: &str) -> PyResult<()> {
+        slf.get().lock(slf.py())?.check_open()?;
+        let on_conflict = OnConflict::parse(on_conflict)?;
+        // Every key conflicts with itself, and replacing or keeping it changes nothing.
+        if other.is(slf) && !matches!(on_conflict, OnConflict::Error) {
+            return Ok(());
+        }
+        let (keys, values) = ElasticTableState::merge_source(other)?;
+        slf.get().lock(slf.py())?.merge_entries(slf.py(), keys, values, on_conflict)
+    }
+
+    /// `table | other`: a new table holding the entries of both, with the
+    /// values of `other` for keys both hold. The new table has the same `delta`,
+    /// `intern`, `weak` and `ordered` settings, and enough capacity for both
+    /// tables.
+    fn __or__(slf: &Bound<'_, Self>, other: Mergeable<'_, Self>) -> PyResult<Py<Self>> {
+        let py = slf.py();
+        let (keys, values) = ElasticTableState::merge_source(other.as_any())?;
+        let (own_keys, own_values) = ElasticTableState::merge_source(slf.as_any())?;
+        let this = slf.get().lock(py)?;
+        let capacity = union_capacity(&this.map, own_keys.len() + keys.len());
+        let (delta, intern, ordered) = (this.map.delta(), this.interner.is_some(), this.map.keeps_order());
+        let value_type = this.schema.as_ref().map(|schema| schema.object().bind(py));
+        let mut table = ElasticTableState::new(py, capacity, delta, intern, this.weak, ordered, None, None, None, value_type)?;
+        drop(this);
+        table.merge_entries(py, own_keys, own_values, OnConflict::Replace)?;
+        table.merge_entries(py, keys, values, OnConflict::Replace)?;
+        Py::new(py, Self::from(table))
+    }
+
+    /// `table |= other`: `merge(other)` in place. Like `dict |=`, and unlike
+    /// `|`, it takes anything `merge` does, not only dicts and ElasticTables.
+    fn __ior__(slf: &Bound<'_, Self>, other: &Bound<'_, PyAny>) -> PyResult<()> {
+        Self::merge(slf, other, "replace")
+    }
+
+    /// Keys of this table that `other` also holds, in ascending order.
+    ///
+    /// `other` can be a table of any class, as only keys are compared. The
+    /// comparison runs in Rust with the GIL released, so two large key sets
+    /// are compared without building Python sets.
+    fn intersection_keys(&self, py: Python<'_>, other: AnyTable<'_>) -> PyResult<Vec<u64>> {
+        let keys = self.lock(py)?.sorted_keys(py)?;
+        compare_keys(py, keys, &other, true)
+    }
+
+    /// Keys of this table that `other` does not hold, in ascending order.
+    /// See `intersection_keys`.
+    fn difference_keys(&self, py: Python<'_>, other: AnyTable<'_>) -> PyResult<Vec<u64>> {
+        let keys = self.lock(py)?.sorted_keys(py)?;
+        compare_keys(py, keys, &other, false)
+    }
+
+    /// Partition the entries into `n` new tables by key hash.
+    ///
+    /// Key `k` goes to part `ElasticTable.shard_of(k, n)`, the shard a
+    /// `ShardedElasticTable` with `n` shards stores it in, so a table built
+    /// once can be split across worker processes that each serve one part.
+    /// The parts keep this table's `delta`, `intern`, `weak` and `ordered`
+    /// settings, and each gets an `n`-th of its capacity, or more if its
+    /// entries need it.
+    fn split(&self, py: Python<'_>, n: usize) -> PyResult<Vec<Py<Self>>> {
+        let parts = self.lock(py)?.split(py, n)?;
+        parts.into_iter().map(|part| Py::new(py, Self::from(part))).collect()
+    }
+
+    /// The part of `split(n)`, or shard of a `ShardedElasticTable` with `n`
+    /// shards, that holds `key`.
+    #[staticmethod]
+    fn shard_of(key: u64, n: usize) -> PyResult<usize> {
+        check_parts(n)?;
+        Ok(shard_of(hash_key(key), n))
+    }
+
+    /// `key` with `tag` packed into its top `tag_bits` bits, so that one table
+    /// can host several logical keyspaces: namespace `tag` holds the keys
+    /// tagged with it. Raises ValueError if the tag or the key does not fit.
+    #[staticmethod]
+    #[pyo3(signature = (tag, key, tag_bits=8))]
+    fn tag_key(tag: u64, key: u64, tag_bits: u32) -> PyResult<u64> {
+        Namespace::new(tag, tag_bits)?.key(key)
+    }
+
+    /// The `(tag, key)` pair `tag_key` packed into `key`.
+    #[staticmethod]
+    #[pyo3(signature = (key, tag_bits=8))]
+    fn untag_key(key: u64, tag_bits: u32) -> PyResult<(u64, u64)> {
+        split_key(key, tag_bits)
+    }
+
+    /// The keys in namespace `tag`, without the tag, in the order of `keys()`.
+    /// Takes a pass over the table.
+    #[pyo3(signature = (tag, tag_bits=8))]
+    fn namespace_keys(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<Vec<u64>> {
+        self.lock(py)?.namespace_keys(tag, tag_bits)
+    }
+
+    /// The `(key, value)` pairs in namespace `tag`, keys without the tag, in
+    /// the order of `keys()`.
+    #[pyo3(signature = (tag, tag_bits=8))]
+    fn namespace_items(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<Vec<(u64, PyObject)>> {
+        self.lock(py)?.namespace_items(py, tag, tag_bits)
+    }
+
+    /// Remove every key in namespace `tag`, as `delete_many` would. Returns
+    /// how many were removed.
+    #[pyo3(signature = (tag, tag_bits=8))]
+    fn delete_namespace(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<usize> {
+        self.lock(py)?.delete_namespace(py, tag, tag_bits)
+    }
+
+    /// Redistribute the entries across the subarrays, keeping the capacity.
+    ///
+    /// Skewed insertion orders and many deletes can leave some subarrays far
+    /// fuller than the insertion algorithm intends and the table littered with
+    /// tombstones. Rebalancing takes every entry out and inserts it again into
+    /// the emptied subarrays, which restores the intended occupancy profile and
+    /// drops the tombstones, without allocating a larger table. Takes a pass
+    /// over every slot plus an insert per entry.
+    fn rebalance(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.rebalance()
+    }
+
+    /// Remove every entry, keeping the allocated capacity.
+    /// Interning tables also release their pool of distinct values, unless a
+    /// transaction is open: the values it may put back still refer to it.
+    fn clear(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.clear(py)
+    }
+
+    /// Remove entries whose weakly held value has been collected.
+    /// Returns the number of entries removed (always 0 unless the table is weak).
+    fn purge(&self, py: Python<'_>) -> PyResult<usize> {
+        self.lock(py)?.purge(py)
+    }
+
+    /// Keep a Bloom filter over the keys, so most lookups of missing keys are
+    /// answered without probing the subarrays.
+    ///
+    /// The filter is sized for the table's capacity at `bits_per_key` bits per
+    /// slot; at the default of 10 about 1% of misses still probe. It is updated
+    /// by every insert and rebuilt as deleted keys accumulate, and is not saved
+    /// with the table.
+    #[pyo3(signature = (bits_per_key=10))]
+    fn enable_bloom_filter(&self, py: Python<'_>, bits_per_key: usize) -> PyResult<()> {
+        self.lock(py)?.enable_bloom_filter(py, bits_per_key)
+    }
+
+    /// Drop the Bloom filter, if any.
+    fn disable_bloom_filter(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.disable_bloom_filter()
+    }
+
+    /// Bits per slot of the Bloom filter, or None without one.
+    #[getter]
+    fn bloom_filter(&self, py: Python<'_>) -> PyResult<Option<usize>> {
+        self.lock(py)?.bloom_filter()
+    }
+
+    /// Shrink the table once deletes leave its entries filling less than
+    /// `threshold` of the capacity, so a cache that once held many more
+    /// entries gives the memory back.
+    ///
+    /// After a `delete`, `delete_many` or `purge` that leaves the table that
+    /// sparse, its entries move to a new table sized so they fill twice
+    /// `threshold`, but no smaller than `min_capacity` slots, with the GIL
+    /// released. That takes a pass over the slots and an insert per entry,
+    /// and later inserts have only the smaller capacity to fill: pick
+    /// `min_capacity` for the size the table must always accommodate.
+    /// `clear()` keeps the capacity, and no table shrinks while a transaction
+    /// is open. `threshold` must lie in (0, 0.5).
+    #[pyo3(signature = (threshold=0.25, min_capacity=0))]
+    fn enable_auto_shrink(&self, py: Python<'_>, threshold: f64, min_capacity: usize) -> PyResult<()> {
+        self.lock(py)?.enable_auto_shrink(threshold, min_capacity)
+    }
+
+    /// Stop shrinking the table after deletes.
+    fn disable_auto_shrink(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.disable_auto_shrink()
+    }
+
+    /// The `threshold` of `enable_auto_shrink`, or None if the table does not
+    /// shrink.
+    #[getter]
+    fn auto_shrink(&self, py: Python<'_>) -> PyResult<Option<f64>> {
+        self.lock(py)?.auto_shrink()
+    }
+
+    /// Whether probe lengths are recorded for `probe_histograms()` (default: False).
+    /// Recording costs a few atomic increments per insert and lookup; turning
+    /// it off discards the histograms.
+    #[getter]
+    fn track_probes(&self, py: Python<'_>) -> PyResult<bool> {
+        self.lock(py)?.track_probes()
+    }
+
+    #[setter]
+    fn set_track_probes(&self, py: Python<'_>, enabled: bool) -> PyResult<()> {
+        self.lock(py)?.set_track_probes(enabled)
+    }
+
+    /// Whether the insert probe budget adapts to the workload (default: False).
+    ///
+    /// An insert probes a subarray for at most `c * log2(1 / epsilon)^2`
+    /// groups before moving on to the next one, with `c` = 2 by default. While
+    /// adaptive, the table widens `c` when more than 5% of a window of 1024
+    /// inserts run out of budget somewhere, or when an insert fails, and
+    /// narrows it when fewer than 1% do, within 0.25 to 16. `detailed_stats()`
+    /// reports the tuned budget and a recommended `delta` for the workload.
+    /// Turning it off returns to the default budget.
+    #[getter]
+    fn adaptive(&self, py: Python<'_>) -> PyResult<bool> {
+        self.lock(py)?.adaptive()
+    }
+
+    #[setter]
+    fn set_adaptive(&self, py: Python<'_>, enabled: bool) -> PyResult<()> {
+        self.lock(py)?.set_adaptive(enabled)
+    }
+
+    /// Whether inserts place keys by two choices (default: False).
+    ///
+    /// A comparison point for adversarial key sets: every key gets a second
+    /// probe sequence in each subarray, and an insert follows whichever of its
+    /// two sequences starts at the group with more free slots, falling back to
+    /// the other. Lookups probe both sequences, so misses cost about twice as
+    /// much, and inserts of new keys look them up first. Turning it off
+    /// rebalances the table.
+    #[getter]
+    fn two_choices(&self, py: Python<'_>) -> PyResult<bool> {
+        self.lock(py)?.two_choices()
+    }
+
+    #[setter]
+    fn set_two_choices(&self, py: Python<'_>, enabled: bool) -> PyResult<()> {
+        self.lock(py)?.set_two_choices(enabled)
+    }
+
+    /// Whether new keys are placed by Robin Hood displacement (default: False).
+    ///
+    /// Walking its probe sequence in the subarray the insertion algorithm
+    /// picked, a new key takes the slot of the first entry it meets that sits
+    /// fewer probes from the start of its own sequence than the key does,
+    /// and the displaced entry moves on the same way until one reaches a
+    /// free slot. Lookups are unchanged, but entries sit at more even probe
+    /// distances, which trims the probes the longest lookups spend in the
+    /// subarray holding their key, at the cost of slower inserts. Keys stored
+    /// before it was turned on stay put until `rebalance()`. Cannot be
+    /// combined with `two_choices`; "relocations" in `op_stats()` counts the
+    /// entries displaced.
+    #[getter]
+    fn robin_hood(&self, py: Python<'_>) -> PyResult<bool> {
+        self.lock(py)?.robin_hood()
+    }
+
+    #[setter]
+    fn set_robin_hood(&self, py: Python<'_>, enabled: bool) -> PyResult<()> {
+        self.lock(py)?.set_robin_hood(enabled)
+    }
+
+    /// How many stored entries an insert may try to move aside before it
+    /// spills to the next subarray (default: 0, off).
+    ///
+    /// When an insert exhausts its probe budget in a subarray, it tries the
+    /// entries along its probe sequence in turn, and the first one that has a
+    /// free slot within the same budget along its own sequence moves there,
+    /// freeing its slot for the new key. Kicks never cascade, and lookups
+    /// find moved entries as before, so this only trades insert probes for
+    /// fewer keys in the deeper subarrays. `op_stats()` counts the moves as
+    /// "relocations".
+    #[getter]
+    fn cuckoo_kicks(&self, py: Python<'_>) -> PyResult<usize> {
+        self.lock(py)?.cuckoo_kicks()
+    }
+
+    #[setter]
+    fn set_cuckoo_kicks(&self, py: Python<'_>, kicks: usize) -> PyResult<()> {
+        self.lock(py)?.set_cuckoo_kicks(kicks)
+    }
+
+    /// Probe-length histograms recorded since `track_probes` was enabled, or
+    /// None if it is off.
+    ///
+    /// Returns a dict with "insert" and "get" lists, where entry `i` counts the
+    /// operations that used `i` probes in total (the last entry also counts
+    /// longer ones), and a "subarrays" list holding the same two lists for each
+    /// subarray: the inserts placed there and the lookups that found their key
+    /// there, by the probes spent in that subarray. Failed inserts are not counted.
+    fn probe_histograms<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.lock(py)?.probe_histograms(py)
+    }
+
+    /// Counts of the operations applied since the table was created or
+    /// `reset_stats()` was last called.
+    ///
+    /// Returns a dict with "gets", "hits" and "misses" (every key looked up by
+    /// `get`, `get_many` and `contains_many`), "inserts" (successful inserts,
+    /// including "overwrites" that replaced a stored key's value in its slot),
+    /// "failed_inserts" and "relocations" (entries moved to make room for an
+    /// insert, see `cuckoo_kicks` and `robin_hood`). Counting is always on
+    /// and costs two atomic updates per lookup call.
+    fn op_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.op_stats(py)
+    }
+
+    /// Zero the counts of `op_stats()`, the fallthroughs of `detailed_stats()`
+    /// and any probe histograms being recorded.
+    fn reset_stats(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock(py)?.reset_stats()
+    }
+
+    /// Number of distinct values held by the interning pool (0 if not interning).
+    fn interned_count(&self, py: Python<'_>) -> PyResult<usize> {
+        self.lock(py)?.interned_count()
+    }
+
+    /// Number of slots the table was created with.
+    fn capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        self.lock(py)?.capacity()
+    }
+
+    /// Number of entries that can still be inserted before the table holds
+    /// `(1 - delta) * capacity()` of them, the most elastic hashing guarantees
+    /// to place. For a table made by `for_items`, that is `expected_items`.
+    /// Inserts past it may still succeed, but can start failing.
+    fn remaining_capacity(&self, py: Python<'_>) -> PyResult<usize> {
+        self.lock(py)?.remaining_capacity()
+    }
+
+    /// Whether `remaining_capacity()` is 0, so the table should be replaced by
+    /// a larger one before more keys arrive.
+    fn is_full(&self, py: Python<'_>) -> PyResult<bool> {
+        self.lock(py)?.is_full()
+    }
+
+    fn stats(&self, py: Python<'_>) -> PyResult<Vec<(usize, usize, f64)>> {
+        self.lock(py)?.stats()
+    }
+
+    /// Call `callback(key, steps, inserted)` after every insert, to follow the
+    /// decisions of the insertion algorithm on a problematic workload.
+    ///
+    /// `steps` lists the subarrays the insert visited, in order, as
+    /// `(subarray, case, limit, probes)` tuples: the case of the paper's
+    /// analysis that applied (see `detailed_stats`), the groups the insert could
+    /// probe there and the probes it used. When `inserted` is True the key was
+    /// placed in the subarray of the last step; otherwise the insert failed.
+    /// Batch inserts report their keys after the whole batch. An exception raised
+    /// by the callback propagates from the insert, which has already taken
+    /// effect. Pass None to stop tracing. Tracing slows inserts down considerably.
+    #[pyo3(signature = (callback))]
+    fn set_tracer(&self, py: Python<'_>, callback: Option<PyObject>) -> PyResult<()> {
+        self.lock(py)?.set_tracer(callback)
+    }
+
+    /// Call `callback(event, key, old, new)` with every change to the table,
+    /// for change-data-capture without wrapping every call site.
+    ///
+    /// `event` is "insert", "update" or "delete", and `old` and `new` are the
+    /// key's values before and after the change, None where it had none.
+    /// Observers are called in the order they were added, once the call that
+    /// made the change took effect: batch calls report their changes in batch
+    /// order after the whole batch, a `bulk()` block as its buffer is applied,
+    /// and `clear()` a delete per entry. Undoing changes, by rolling back a
+    /// transaction or a failed `insert_all`, is reported as changes too. The
+    /// table is still busy with the call meanwhile, so observers cannot use
+    /// it. An exception raised by an observer propagates from the call, which
+    /// has already taken effect, and skips the calls left. While a table has
+    /// observers, every change costs a lookup of the value it replaces.
+    fn add_observer(&self, py: Python<'_>, callback: PyObject) -> PyResult<()> {
+        self.lock(py)?.add_observer(callback)
+    }
+
+    /// Stop calling `callback` with changes. Raises ValueError if it is not
+    /// an observer of the table.
+    fn remove_observer(&self, py: Python<'_>, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.lock(py)?.remove_observer(callback)
+    }
+
+    /// Get an early warning before inserts start failing: after an insert takes
+    /// the table's load factor to `threshold` or past it, call
+    /// `callback(load_factor, None)`, or issue a `LoadFactorWarning` without a
+    /// callback.
+    ///
+    /// With `subarray_threshold`, the load factor of every subarray is watched
+    /// too, and `callback(load_factor, subarray)` is called for each subarray
+    /// that reaches it. Each alert fires once when its load crosses the
+    /// threshold, and again only after deletes took the load back below it.
+    /// Loads are checked after every insert call, after the whole batch for
+    /// batch inserts and at the end of a `bulk()` block. An exception raised
+    /// by the callback propagates from the insert, which has already taken
+    /// effect. Pass None as `threshold` to remove the alert.
+    #[pyo3(signature = (threshold, callback=None, subarray_threshold=None))]
+    fn set_load_alert(
+        &self,
+        py: Python<'_>,
+        threshold: Option<f64>,
+        callback: Option<PyObject>,
+        subarray_threshold: Option<f64>,
+    ) -> PyResult<()> {
+        self.lock(py)?.set_load_alert(threshold, callback, subarray_threshold)
+    }
+
+    /// The `threshold` of `set_load_alert`, or None without an alert.
+    #[getter]
+    fn load_alert(&self, py: Python<'_>) -> PyResult<Option<f64>> {
+        self.lock(py)?.load_alert()
+    }
+
+    /// Render the table's metrics in the Prometheus text exposition format.
+    ///
+    /// Covers the `op_stats()` counters, occupancy and memory gauges (overall
+    /// and per subarray), and, while `track_probes` is on, the probe histograms.
+    /// Every metric name starts with `prefix`, and every sample carries the
+    /// constant `labels`, such as `{"table": "users"}`. Each call renders
+    /// complete metric families, so tables exposed together need distinct prefixes.
+    #[pyo3(signature = (prefix="elastic_table", labels=None))]
+    fn metrics_text(&self, py: Python<'_>, prefix: &str, labels: Option<BTreeMap<String, String>>) -> PyResult<String> {
+        self.lock(py)?.metrics_text(prefix, labels)
+    }
+
+    /// Compare the probe counts recorded while `track_probes` is on with the
+    /// paper's bounds: O(1) probes amortized and O(log 1/delta) in the worst case.
+    ///
+    /// The bounds are asymptotic, so `tolerance` stands in for their constants:
+    /// the amortized bound is `tolerance` probes and the worst-case bound
+    /// `tolerance * log2(1 / delta)`. Probes are counted in groups of 16 slots.
+    ///
+    /// Returns a dict with "insert_count", "insert_amortized_probes" and
+    /// "insert_worst_case_probes", the same three for "get", the bounds used
+    /// ("log_inv_delta", "amortized_bound", "worst_case_bound"), and a list of
+    /// "deviations" describing every bound exceeded. Lookups of missing keys
+    /// fall outside the paper's analysis and may show up as worst-case deviations.
+    #[pyo3(signature = (tolerance=4.0))]
+    fn probe_bounds<'py>(&self, py: Python<'py>, tolerance: f64) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.probe_bounds(py, tolerance)
+    }
+
+    /// Bytes allocated for the table, broken down by subarray.
+    ///
+    /// Returns a dict with a "subarrays" list holding, per subarray, its
+    /// "slots" (padding included), the "control_bytes", "key_bytes" and
+    /// "value_bytes" they take, their "total_bytes", and the number of
+    /// "references": values held as references to Python objects rather than
+    /// inline. The objects themselves are not measured. "filter_bytes" counts
+    /// the Bloom filter, "order_bytes" the insertion order of an ordered table,
+    /// and "total_bytes" covers everything listed.
+    fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.memory_usage(py)
+    }
+
+    /// Which slots of every subarray are occupied, for visualizing clustering.
+    ///
+    /// Returns one entry per subarray, numbered as in `stats()`, covering its
+    /// slots in order: a numpy bool array, or with `packed`, a `bytes` bitmap
+    /// holding slot `j` in bit `j % 8` of byte `j // 8`, which
+    /// `numpy.unpackbits(..., bitorder="little")` expands. Padding slots are
+    /// left out, and slots of deleted entries count as free.
+    #[pyo3(signature = (packed=false))]
+    fn occupancy(&self, py: Python<'_>, packed: bool) -> PyResult<Vec<PyObject>> {
+        self.lock(py)?.occupancy(py, packed)
+    }
+
+    /// The `(key, value)` pairs stored in subarray `i`, in slot order.
+    ///
+    /// For debugging high spill rates: the keys found in the later, overflow
+    /// subarrays are those the first ones had no room for. Subarrays are
+    /// numbered as in `stats()`; raises IndexError for one the table does not
+    /// have.
+    fn items_in_subarray(&self, py: Python<'_>, i: usize) -> PyResult<Vec<(u64, PyObject)>> {
+        self.lock(py)?.items_in_subarray(py, i)
+    }
+
+    /// A structured version of `stats()`.
+    ///
+    /// Returns a dict with the table's "capacity", "count", "load_factor",
+    /// "delta" and "probe_budget" (the constant `c` of the insert probe budget,
+    /// see `adaptive`), a "tuning" dict while `adaptive` is on, and a
+    /// "subarrays" list with one dict per subarray:
+    ///
+    /// - index, capacity, count, tombstones, load_factor
+    /// - epsilon: the free fraction of the subarray
+    /// - insert_case: which case of the paper's analysis the next insert reaching
+    ///   the subarray falls in: 1 probes a bounded number of groups, 2 skips the
+    ///   nearly full subarray, 3 scans all of it because the next one is nearly full
+    /// - insert_probe_limit: groups that insert may probe (0 when skipping)
+    /// - lookup_probe_limit: groups a lookup probes at most
+    /// - fallthroughs: the inserts that moved on to the next subarray from
+    ///   this one, by the case that sent them on: "exhausted" (case 1, the
+    ///   probe budget ran out), "skipped" (case 2) and "full" (case 3, a scan
+    ///   found no free slot). Passing over an empty subarray is not counted,
+    ///   and `reset_stats()` zeroes them.
+    ///
+    /// Many "exhausted" fallthroughs from subarrays that still have room point
+    /// at keys that cluster, while "skipped" ones from full subarrays are the
+    /// algorithm working as intended, a `delta` too small for the load if they
+    /// end in failed inserts.
+    ///
+    /// The "tuning" dict counts the "inserts" observed, the "fallthroughs"
+    /// among them that ran out of budget in some subarray and the
+    /// "adjustments" made to the budget, and reports the "peak_load" reached,
+    /// the load an insert first failed at as "failure_load" (or None), and a
+    /// "recommended_delta" for tables serving the same workload. That is a
+    /// quarter more slack than the failure left, or, once the table filled to
+    /// `1 - delta` without failing, half of `delta` if the budget did not have
+    /// to grow and `delta` otherwise; it is None before either happens.
+    fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.lock(py)?.detailed_stats(py)
+    }
+
+    /// Check the table's internal invariants.
+    ///
+    /// Verifies that every subarray's counts match its occupied and deleted
+    /// slots, that every stored key is found again from its hash, that no key
+    /// is stored twice and that the Bloom filter, if enabled, admits every
+    /// stored key. Returns a list describing each violation; an empty list
+    /// means the table is consistent. Takes a pass over every slot.
+    fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        self.lock(py)?.validate(py)
+    }
+}
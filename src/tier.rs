@@ -0,0 +1,144 @@
+//! Hot/cold tiering: slots of the first subarrays in memory, the rest in a
+//! memory-mapped file.
+//!
+//! Most entries land in the first subarrays, and the later ones hold overflow
+//! that is probed less often. A tiered region keeps the slots of the first
+//! subarrays in anonymous memory and maps those of the tail subarrays from a
+//! scratch file, so the OS can write cold pages back to the file and drop
+//! them instead of swapping, and a table can grow past RAM gracefully.
+//!
+//! The region is one contiguous range of address space, so the arena indexes
+//! it exactly like any other. Each of the three slot sections is placed so
+//! that its hot/cold boundary falls on a page, and the cold part of each is
+//! mapped over the anonymous memory with `MAP_FIXED`. The file uses the
+//! offsets of the address space and is sparse, so its gaps take no disk
+//! space. Nothing but the table knows where its hot slots are, so the file is
+//! removed with the table.
+
+use std::io;
+use std::path::Path;
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+use crate::map::{Contents, ElasticMap};
+use crate::region::{Layout, Region, COUNTS_LEN, HEADER_LEN};
+
+/// Layout of `slots` arena slots whose first `hot` slots stay in memory.
+/// Each section starts on a fresh page, at the offset that puts its cold
+/// part on a page boundary.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn tiered_layout(slots: usize, hot: usize, page: usize) -> Layout {
+    let place = |from: usize, size: usize| (from.next_multiple_of(page) + size * hot).next_multiple_of(page) - size * hot;
+    let ctrl = place(HEADER_LEN + COUNTS_LEN, 1);
+    let keys = place(ctrl + slots, 8);
+    let values = place(keys + 8 * slots, 8);
+    Layout { slots, ctrl, keys, values, len: (values + 8 * slots).next_multiple_of(page) }
+}
+
+/// Creates an empty map with `capacity` slots whose first `hot_subarrays`
+/// subarrays stay in memory, the others being mapped from a scratch file at
+/// `path`.
+pub(crate) fn create_tiered<V>(path: &Path, capacity: usize, delta: f64, hot_subarrays: usize) -> PyResult<ElasticMap<V>> {
+    if hot_subarrays >= ElasticMap::<V>::subarray_count(capacity) {
+        return Err(PyValueError::new_err("hot_subarrays must leave at least one subarray on disk"));
+    }
+    let slots = ElasticMap::<V>::arena_slots(capacity);
+    let hot = ElasticMap::<V>::leading_slots(capacity, hot_subarrays);
+    let (region, layout) = imp::tiered_region(path, slots, hot)?;
+    Ok(ElasticMap::in_layout(region, layout, capacity, delta, Contents::Empty))
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{self, OpenOptions};
+    use std::os::unix::io::AsRawFd;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// The address space of a tiered region, unmapped, and its file
+    /// removed, on drop.
+    struct Mapping {
+        ptr: *mut libc::c_void,
+        len: usize,
+        path: PathBuf,
+    }
+
+    // SAFETY: the mapping is plain memory, owned by the region.
+    unsafe impl Send for Mapping {}
+    unsafe impl Sync for Mapping {}
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            // SAFETY: the range was mapped by `tiered_region` and nothing
+            // views it once the region is dropped.
+            unsafe { libc::munmap(self.ptr, self.len) };
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    pub(super) fn tiered_region(path: &Path, slots: usize, hot: usize) -> io::Result<(Region, Layout)> {
+        // SAFETY: sysconf has no preconditions.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let layout = tiered_layout(slots, hot, page);
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        file.set_len(layout.len as u64)?;
+
+        // SAFETY: a fresh anonymous mapping aliases nothing.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                layout.len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // From here on, the mapping and the file go away with `mapping`.
+        let mapping = Mapping { ptr, len: layout.len, path: path.to_owned() };
+
+        let sections = [(layout.ctrl, 1), (layout.keys, 8), (layout.values, 8)];
+        for (start, size) in sections {
+            let (cold, end) = (start + size * hot, (start + size * slots).next_multiple_of(page));
+            if cold >= end {
+                continue;
+            }
+            // SAFETY: the range lies inside the anonymous mapping, which it
+            // replaces; no view of the region exists yet.
+            let mapped = unsafe {
+                libc::mmap(
+                    (ptr as *mut u8).add(cold) as *mut libc::c_void,
+                    end - cold,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED | libc::MAP_FIXED,
+                    file.as_raw_fd(),
+                    cold as libc::off_t,
+                )
+            };
+            if mapped == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        // The mappings stay valid after the file is closed.
+        drop(file);
+        // SAFETY: the mapping covers `layout.len` page-aligned bytes and lives
+        // as long as the region.
+        let region = unsafe { Region::new(ptr as *mut u8, layout.len, Box::new(mapping)) }
+            .map_err(io::Error::other)?;
+        Ok((region, layout))
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::*;
+
+    pub(super) fn tiered_region(_path: &Path, _slots: usize, _hot: usize) -> io::Result<(Region, Layout)> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "tiered tables need a Unix system"))
+    }
+}
@@ -0,0 +1,125 @@
+//! Transactions: groups of changes to a table that can be undone together.
+//!
+//! While a transaction is open, the table journals the state every key had
+//! before the transaction first changed it: its value, or its absence. Later
+//! changes to the same key add nothing, so the journal grows with the number
+//! of distinct keys touched rather than the number of changes. Committing
+//! drops the journal; rolling back removes every touched key and puts back
+//! the entries the journal recorded.
+
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::types::{PyAny, PyType};
+
+use crate::map::ElasticMap;
+use crate::table::TableHandle;
+
+/// The state of every key an open transaction changed, from before its
+/// first change.
+pub(crate) struct Journal<V> {
+    before: HashMap<u64, Option<V>>,
+}
+
+impl<V> Journal<V> {
+    pub(crate) fn new() -> Self {
+        Journal { before: HashMap::new() }
+    }
+
+    /// Records the state of `key` ahead of a change, as `previous` returns
+    /// it, unless an earlier change in the transaction already did.
+    pub(crate) fn record(&mut self, key: u64, previous: impl FnOnce() -> Option<V>) {
+        self.before.entry(key).or_insert_with(previous);
+    }
+
+    /// The recorded values of the keys that held one.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.before.values().flatten()
+    }
+
+    /// Returns every touched key of `map` to its recorded state, calling
+    /// `restored` with each key, the value it held and the value put back.
+    pub(crate) fn roll_back(self, map: &mut ElasticMap<V>, mut restored: impl FnMut(u64, Option<V>, Option<&V>)) {
+        // Removing every touched key first leaves room for all the entries to
+        // come back, however the transaction rearranged the slots.
+        let removed: Vec<_> = self.before.keys().map(|&key| map.remove(key)).collect();
+        // An unchanged map yields its entries in the same order every time.
+        for ((key, value), removed) in self.before.into_iter().zip(removed) {
+            restored(key, removed, value.as_ref());
+            if let Some(value) = value {
+                map.restore(key, value);
+            }
+        }
+    }
+}
+
+/// Returned by `table.transaction()`, which opens it.
+#[pyclass]
+pub(crate) struct Transaction {
+    table: TableHandle,
+    finished: bool,
+}
+
+impl Transaction {
+    pub(crate) fn new(table: impl Into<TableHandle>) -> Self {
+        Transaction { table: table.into(), finished: false }
+    }
+
+    fn finish(&mut self, py: Python<'_>, commit: bool) -> PyResult<()> {
+        if self.finished {
+            return Err(PyRuntimeError::new_err("the transaction is already finished"));
+        }
+        match &self.table {
+            TableHandle::Elastic(table) => table.get().lock(py)?.end_transaction(py, commit)?,
+            TableHandle::Int(table) => table.get().lock(py)?.end_transaction(py, commit)?,
+            TableHandle::Float(table) => table.get().lock(py)?.end_transaction(py, commit)?,
+        }
+        self.finished = true;
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Transaction {
+    /// Keep the changes made since the transaction began.
+    fn commit(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.finish(py, true)
+    }
+
+    /// Undo the changes made since the transaction began.
+    fn rollback(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.finish(py, false)
+    }
+
+    /// Whether the transaction was committed or rolled back.
+    #[getter]
+    fn finished(&self) -> bool {
+        self.finished
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        self.table.traverse(&visit)
+    }
+
+    /// Commits if the block completed and rolls back if it raised, unless
+    /// the block already finished the transaction itself.
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        exc_type: Option<&Bound<'_, PyType>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if !self.finished {
+            self.finish(py, exc_type.is_none())?;
+        }
+        Ok(false)
+    }
+}
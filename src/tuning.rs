@@ -0,0 +1,130 @@
+//! Adaptive tuning of the insert probe budget.
+//!
+//! In the first case of the paper's analysis an insert probes at most
+//! `c * log2(1 / epsilon)^2` groups of a subarray before falling through to
+//! the next one. The paper leaves the constant `c` open and the map uses 2 by
+//! default. An adaptive map watches how often inserts fall through and moves
+//! `c` after every window of inserts so that a small share of them does: a
+//! budget that never binds lets inserts probe long runs into crowded
+//! subarrays, and one that binds often pushes keys into the small trailing
+//! subarrays, where lookups have to search longer for them. A failed insert
+//! means the budget was too tight for the workload, so it widens at once.
+//!
+//! The tuner also notes how full the map got and at what load an insert first
+//! failed, from which it recommends a `delta` for tables serving the same
+//! workload.
+
+/// Inserts between two adjustments of the budget.
+const WINDOW: u64 = 1024;
+/// Share of the inserts in a window that may fall through a subarray before
+/// the budget widens, and below which it narrows.
+const FALLTHROUGH_HIGH: f64 = 0.05;
+const FALLTHROUGH_LOW: f64 = 0.01;
+/// Bounds of the budget constant.
+const MIN_BUDGET: f64 = 0.25;
+const MAX_BUDGET: f64 = 16.0;
+/// The budget constant of maps that are not tuned.
+pub(crate) const DEFAULT_BUDGET: f64 = 2.0;
+/// Bounds of the recommended `delta`.
+const MIN_DELTA: f64 = 0.01;
+const MAX_DELTA: f64 = 0.5;
+
+/// What the tuner has observed, as reported by `detailed_stats()`.
+pub(crate) struct Tuning {
+    pub(crate) inserts: u64,
+    pub(crate) fallthroughs: u64,
+    pub(crate) adjustments: u64,
+    pub(crate) peak_load: f64,
+    /// Load factor when an insert first failed.
+    pub(crate) failure_load: Option<f64>,
+    pub(crate) recommended_delta: Option<f64>,
+}
+
+pub(crate) struct Tuner {
+    /// Inserts, and those that fell through a subarray, in the current window.
+    window_inserts: u64,
+    window_fallthroughs: u64,
+    inserts: u64,
+    fallthroughs: u64,
+    adjustments: u64,
+    peak_load: f64,
+    failure_load: Option<f64>,
+}
+
+impl Tuner {
+    pub(crate) fn new() -> Self {
+        Tuner {
+            window_inserts: 0,
+            window_fallthroughs: 0,
+            inserts: 0,
+            fallthroughs: 0,
+            adjustments: 0,
+            peak_load: 0.0,
+            failure_load: None,
+        }
+    }
+
+    /// Records a successful insert that fell through `fallthroughs`
+    /// subarrays, and returns the new budget constant at the end of a window.
+    /// `load` is computed only then.
+    pub(crate) fn inserted(&mut self, fallthroughs: usize, budget: f64, load: impl FnOnce() -> f64) -> Option<f64> {
+        self.window_inserts += 1;
+        self.window_fallthroughs += u64::from(fallthroughs > 0);
+        if self.window_inserts < WINDOW {
+            return None;
+        }
+        let rate = self.window_fallthroughs as f64 / self.window_inserts as f64;
+        self.inserts += self.window_inserts;
+        self.fallthroughs += self.window_fallthroughs;
+        self.window_inserts = 0;
+        self.window_fallthroughs = 0;
+        self.peak_load = self.peak_load.max(load());
+        let tuned = if rate > FALLTHROUGH_HIGH {
+            (budget * 1.5).min(MAX_BUDGET)
+        } else if rate < FALLTHROUGH_LOW {
+            (budget / 1.25).max(MIN_BUDGET)
+        } else {
+            budget
+        };
+        self.adjustments += u64::from(tuned != budget);
+        Some(tuned)
+    }
+
+    /// Records an insert that failed at load factor `load`, and returns the
+    /// widened budget constant.
+    pub(crate) fn failed(&mut self, budget: f64, load: f64) -> f64 {
+        self.peak_load = self.peak_load.max(load);
+        self.failure_load.get_or_insert(load);
+        let tuned = (budget * 2.0).min(MAX_BUDGET);
+        self.adjustments += u64::from(tuned != budget);
+        tuned
+    }
+
+    /// The observations so far, for a map created with `delta`, now at load
+    /// factor `load` and tuned to the budget constant `budget`. `full_load` is
+    /// the load factor of a map holding `1 - delta` of its capacity.
+    ///
+    /// After a failure the recommendation leaves a quarter more slack than
+    /// the load the failure happened at. Otherwise there is one only once the
+    /// map filled to `full_load`: half the slack if the tuned budget ended up
+    /// no wider than the default, since keys were placed easily, and `delta`
+    /// itself if the budget had to grow.
+    pub(crate) fn report(&self, delta: f64, full_load: f64, budget: f64, load: f64) -> Tuning {
+        let peak_load = self.peak_load.max(load);
+        let recommended = match self.failure_load {
+            Some(failure) => Some((1.0 - failure) * 1.25),
+            None if peak_load + 1e-9 >= full_load => {
+                Some(if budget <= DEFAULT_BUDGET { delta / 2.0 } else { delta })
+            }
+            None => None,
+        };
+        Tuning {
+            inserts: self.inserts + self.window_inserts,
+            fallthroughs: self.fallthroughs + self.window_fallthroughs,
+            adjustments: self.adjustments,
+            peak_load,
+            failure_load: self.failure_load,
+            recommended_delta: recommended.map(|delta| delta.clamp(MIN_DELTA, MAX_DELTA)),
+        }
+    }
+}
@@ -0,0 +1,2077 @@
+//! Typed tables storing plain numbers in Rust instead of Python objects.
+//!
+//! `IntTable` and `FloatTable` share one implementation, generated by `typed_table!`,
+//! together with their lookup-only counterparts `FrozenIntTable` and `FrozenFloatTable`.
+
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::types::{PyBytes, PyDict};
+use pyo3::{PyTraverseError, PyVisit};
+use numpy::{IntoPyArray, PyArray1};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::batch::{lookup_result, map_keys, map_keys_with, Batch, Fills, KeyBatch};
+use crate::alert::LoadAlert;
+use crate::bulk::{Bulk, BulkBlock};
+use crate::codec::{decode, encode, Format};
+use crate::distinct::HyperLogLog;
+use crate::hash::{hash_key, hash_keys, shard_of};
+use crate::interop::{
+    arrow_to_numpy, entries_from_json, entries_to_json, numpy_to_arrow, pandas_column, pandas_frame,
+};
+use crate::iter::{TableIterator, Yield};
+use crate::lock::{TableGuard, TableLock};
+use crate::map::{ElasticMap, GrowthPolicy, InsertError};
+use crate::metrics::metrics_text;
+use crate::namespace::{split_key, Namespace};
+use crate::observers::{batch_changes, Change, Observers};
+use crate::perfect::PerfectMap;
+use crate::recorder::{open_recording, Recording};
+use crate::storage::{
+    create_file, create_map, create_segment, load_file, open_file, open_map, open_segment, read_header_file,
+    region_len, save_map, sync_counts, verify_sections, Backing, Header,
+};
+use crate::table::{
+    check_bits_per_key, check_delta, check_load, check_parts, compare_keys, detailed_stats, entry_index,
+    growth_policy, histograms_to_dict, memory_usage, occupancy, op_counts_to_dict, probe_bounds, report_traces, sample_rng,
+    shrink_policy, sizing_for_items, sorted_keys, split_entries, subarray_items, union_capacity, update_entries, AnyTable, Mergeable,
+    OnConflict,
+};
+use crate::tier::create_tiered;
+use crate::transaction::{Journal, Transaction};
+use crate::views::{ItemsView, KeysView, ValuesView};
+use crate::wal::{apply_checkpoints, checkpoint_count, discard_history, log_path, replay, replay_from, Record, Wal};
+
+/// Copies `len` entries into parallel key and value vectors.
+fn export<'a, V: Copy + 'a>(len: usize, entries: impl Iterator<Item = (u64, &'a V)>) -> (Vec<u64>, Vec<V>) {
+    let mut keys = Vec::with_capacity(len);
+    let mut values = Vec::with_capacity(len);
+    for (key, &value) in entries {
+        keys.push(key);
+        values.push(value);
+    }
+    (keys, values)
+}
+
+/// The lookup structure of a frozen table.
+enum FrozenMap<V> {
+    Elastic(Box<ElasticMap<V>>),
+    Perfect(PerfectMap<V>),
+}
+
+impl<V: Copy + Sync> FrozenMap<V> {
+    fn get(&self, key: u64) -> Option<&V> {
+        match self {
+            FrozenMap::Elastic(map) => map.get(key),
+            FrozenMap::Perfect(map) => map.get(key),
+        }
+    }
+
+    /// Maps the value of every key of a lookup batch through `f`, as `map_keys` does.
+    fn map_keys<R, F>(&self, keys: &[u64], f: F) -> Vec<R>
+    where
+        R: Send,
+        F: Fn(Option<&V>) -> R + Sync + Send,
+    {
+        match self {
+            FrozenMap::Elastic(map) => map_keys(map, keys, |key, hash| f(map.get_hashed(key, hash))),
+            FrozenMap::Perfect(map) => {
+                map_keys_with(keys, |hash| map.prefetch(hash), |key, hash| f(map.get_hashed(key, hash)))
+            }
+        }
+    }
+
+    fn export(&self) -> (Vec<u64>, Vec<V>) {
+        match self {
+            FrozenMap::Elastic(map) => export(map.len(), map.iter()),
+            FrozenMap::Perfect(map) => export(map.len(), map.iter()),
+        }
+    }
+
+    fn stats(&self) -> Vec<(usize, usize, f64)> {
+        match self {
+            FrozenMap::Elastic(map) => map.stats(),
+            // A single, completely full array.
+            FrozenMap::Perfect(map) => vec![(0, map.len(), 1.0)],
+        }
+    }
+}
+
+macro_rules! typed_table {
+    ($name:ident, $state:ident, $frozen:ident, $value:ty, $kind:literal, $doc:literal, $frozen_doc:literal) => {
+        #[doc = $doc]
+        #[pyclass(frozen)]
+        pub(crate) struct $name {
+            state: TableLock<$state>,
+        }
+
+        /// The state of a table, behind its lock.
+        pub(crate) struct $state {
+            map: ElasticMap<$value>,
+            backing: Backing,
+            /// Set for tables mapped from a read-only file; every mutation is refused.
+            read_only: bool,
+            /// Change log of the snapshot this table was saved to or loaded from.
+            wal: Option<Wal>,
+            /// Called with the path of every insert; see `set_tracer`.
+            tracer: Option<PyObject>,
+            /// Called with every change; see `add_observer`.
+            observers: Observers,
+            /// Set by `set_load_alert`.
+            alert: Option<LoadAlert>,
+            /// Set inside a `bulk()` block.
+            bulk: Option<Bulk<$value>>,
+            /// Set while a transaction is open.
+            journal: Option<Journal<$value>>,
+            /// Set by `track_distinct_values`.
+            distinct: Option<HyperLogLog>,
+            /// Set by `start_recording`.
+            recording: Option<Recording>,
+        }
+
+        impl From<$state> for $name {
+            fn from(state: $state) -> Self {
+                $name { state: TableLock::new(state) }
+            }
+        }
+
+        impl $name {
+            /// The table's state, waiting for any call on another thread to finish.
+            pub(crate) fn lock(&self, py: Python<'_>) -> PyResult<TableGuard<'_, $state>> {
+                self.state.lock(py)
+            }
+        }
+
+impl $state {
+            fn new(
+                capacity: usize,
+                delta: f64,
+                growth_factor: Option<f64>,
+                grow_at: Option<f64>,
+                max_capacity: Option<usize>,
+            ) -> PyResult<Self> {
+                check_delta(delta)?;
+                let mut map = ElasticMap::new(capacity, delta);
+                map.set_growth(growth_policy(capacity, delta, growth_factor, grow_at, max_capacity)?);
+                Ok($state {
+                    map,
+                    backing: Backing::Heap,
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                })
+            }
+
+            fn for_items(expected_items: usize, load_factor: f64) -> PyResult<Self> {
+                let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+                Self::new(capacity, delta, None, None, None)
+            }
+
+            fn from_numpy(
+                py: Python<'_>,
+                keys: KeyBatch,
+                values: Batch<$value>,
+                load_factor: f64,
+            ) -> PyResult<Self> {
+                let keys = keys.as_slice(py)?;
+                let values = values.as_slice(py)?;
+                let mut table = Self::for_items(keys.len(), load_factor)?;
+                table.insert_slices(py, &keys, &values)?;
+                Ok(table)
+            }
+
+            fn from_arrow(
+                py: Python<'_>,
+                keys: &Bound<'_, PyAny>,
+                values: &Bound<'_, PyAny>,
+                load_factor: f64,
+            ) -> PyResult<Self> {
+                let keys = arrow_to_numpy(keys)?;
+                let values = arrow_to_numpy(values)?;
+                Self::from_numpy(py, keys.extract()?, values.extract()?, load_factor)
+            }
+
+            fn from_pandas(
+                py: Python<'_>,
+                df: &Bound<'_, PyAny>,
+                key_col: &str,
+                value_col: &str,
+                load_factor: f64,
+            ) -> PyResult<Self> {
+                let keys = pandas_column(df, key_col)?;
+                let values = pandas_column(df, value_col)?;
+                Self::from_numpy(py, keys.extract()?, values.extract()?, load_factor)
+            }
+
+            fn create_shared(py: Python<'_>, expected_items: usize, load_factor: f64, name: Option<&str>) -> PyResult<Self> {
+                let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+                let (shm, region) = create_segment(py, name, region_len::<$value>(capacity))?;
+                Ok($state {
+                    map: create_map(region, $kind, capacity, delta),
+                    backing: Backing::SharedMemory(shm),
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                })
+            }
+
+            fn open_shared(py: Python<'_>, name: &str) -> PyResult<Self> {
+                let (shm, region) = open_segment(py, name)?;
+                Ok($state {
+                    map: open_map(region, $kind, true)?,
+                    backing: Backing::SharedMemory(shm),
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                })
+            }
+
+            fn shared_memory(&self, py: Python<'_>) -> Option<PyObject> {
+                match &self.backing {
+                    Backing::SharedMemory(shm) => Some(shm.clone_ref(py)),
+                    _ => None,
+                }
+            }
+
+            fn create_mmap(py: Python<'_>, path: PathBuf, expected_items: usize, load_factor: f64) -> PyResult<Self> {
+                let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+                let (mapped, region) = create_file(py, &path, region_len::<$value>(capacity))?;
+                Ok($state {
+                    map: create_map(region, $kind, capacity, delta),
+                    backing: Backing::File(mapped),
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                })
+            }
+
+            fn open_mmap(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+                let (mapped, region) = open_file(py, &path, true)?;
+                Ok($state {
+                    map: open_map(region, $kind, true)?,
+                    backing: Backing::File(mapped),
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                })
+            }
+
+            fn create_tiered(path: PathBuf, expected_items: usize, load_factor: f64, hot_subarrays: usize) -> PyResult<Self> {
+                let (capacity, delta) = sizing_for_items(expected_items, load_factor)?;
+                Ok($state {
+                    map: create_tiered(&path, capacity, delta, hot_subarrays)?,
+                    backing: Backing::Tiered(hot_subarrays),
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                })
+            }
+
+            fn hot_subarrays(&self) -> Option<usize> {
+                match self.backing {
+                    Backing::Tiered(hot_subarrays) => Some(hot_subarrays),
+                    _ => None,
+                }
+            }
+
+            fn flush(&mut self, py: Python<'_>) -> PyResult<()> {
+                match &self.backing {
+                    Backing::File(mapped) if !self.read_only => {
+                        sync_counts(&mut self.map);
+                        mapped.call_method0(py, "flush")?;
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+
+            fn save(&mut self, py: Python<'_>, path: PathBuf, wal: bool) -> PyResult<()> {
+                let map = &self.map;
+                let id = py.allow_threads(|| {
+                    let id = save_map(map, $kind, &path, |value| value.to_ne_bytes())?;
+                    discard_history(&path)?;
+                    Ok::<_, io::Error>(id)
+                })?;
+
+                if wal || self.wal.as_ref().is_some_and(|current| current.snapshot() == path) {
+                    self.wal = Some(Wal::create(path, id)?);
+                }
+                Ok(())
+            }
+
+            fn checkpoint(&mut self, py: Python<'_>) -> PyResult<u64> {
+                let Some(wal) = &mut self.wal else {
+                    return Err(PyValueError::new_err(
+                        "checkpoint() requires a table saved or loaded with wal=True"
+                    ));
+                };
+                Ok(py.allow_threads(|| wal.checkpoint())?)
+            }
+
+            fn commit(&mut self) -> PyResult<()> {
+                if let Some(wal) = &mut self.wal {
+                    wal.commit()?;
+                }
+                Ok(())
+            }
+
+            fn start_recording(&mut self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+                if self.recording.is_some() {
+                    return Err(PyRuntimeError::new_err("the table is already recording"));
+                }
+                self.apply_buffered(py)?;
+                let entries = self.map.iter().map(|(key, value)| Record::Insert(key, value.to_le_bytes()));
+                self.recording = Some(Recording::create(path, $kind, &self.map, entries)?);
+                Ok(())
+            }
+
+            fn stop_recording(&mut self) -> PyResult<()> {
+                if let Some(mut recording) = self.recording.take() {
+                    recording.flush()?;
+                }
+                Ok(())
+            }
+
+            fn recording(&self) -> Option<PathBuf> {
+                self.recording.as_ref().map(|recording| recording.path().to_owned())
+            }
+
+            fn replay(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+                let (params, reader) = open_recording(&path, $kind)?;
+                let mut table = Self::new(params.capacity, params.delta, None, None, None)?;
+                let map = &mut table.map;
+                map.set_growth(params.growth);
+                map.tune_probes(params.adaptive);
+                py.allow_threads(|| {
+                    replay_from(reader, |record| {
+                        match record {
+                            Record::Insert(key, value) => {
+                                let _ = map.insert(key, <$value>::from_le_bytes(value));
+                            }
+                            Record::Delete(key) => {
+                                map.remove(key);
+                            }
+                            Record::Clear => map.clear(),
+                        }
+                        Ok::<_, io::Error>(())
+                    })
+                })?;
+                Ok(table)
+            }
+
+            fn load(
+                py: Python<'_>,
+                path: PathBuf,
+                mmap: bool,
+                wal: bool,
+                version: Option<u64>,
+                verify: bool,
+            ) -> PyResult<Self> {
+                if wal && version.is_some() {
+                    return Err(PyValueError::new_err("wal=True requires loading the latest version"));
+                }
+                let log = log_path(&path);
+                if mmap {
+                    if wal || version.is_some_and(|version| version > 0) {
+                        return Err(PyValueError::new_err(
+                            "snapshots with checkpoints or a change log cannot be mapped read-only; load them with mmap=False"
+                        ));
+                    }
+                    let (map, backing) = Self::map_snapshot(py, &path, version.is_none(), verify)?;
+                    return Ok($state {
+                        map,
+                        backing,
+                        read_only: true,
+                        wal: None,
+                        tracer: None,
+                        observers: Observers::default(),
+                        alert: None,
+                        bulk: None,
+                        journal: None,
+                        distinct: None,
+                        recording: None,
+                    });
+                }
+                let region = py.allow_threads(|| {
+                    let region = load_file(&path)?;
+                    if verify {
+                        verify_sections(region.bytes(), $kind)?;
+                    }
+                    Ok::<_, PyErr>(region)
+                })?;
+                let Header { id, growth, .. } = Header::read(region.bytes(), $kind)?;
+                let mut table = $state {
+                    map: open_map(region, $kind, true)?,
+                    backing: Backing::Heap,
+                    read_only: false,
+                    wal: None,
+                    tracer: None,
+                    observers: Observers::default(),
+                    alert: None,
+                    bulk: None,
+                    journal: None,
+                    distinct: None,
+                    recording: None,
+                };
+                // A table that grew after it was saved logged more entries than
+                // the snapshot has room for, so replaying may have to grow too.
+                // The saved policy takes over once replay is done.
+                let delta = table.map.delta();
+                table.map.set_growth(Some(GrowthPolicy { factor: 2.0, trigger: 1.0 - delta, max_capacity: None }));
+                let applied = apply_checkpoints(&path, id, version, |record| table.apply(record))?;
+                if let Some(version) = version {
+                    if applied < version {
+                        return Err(PyValueError::new_err(format!("snapshot has no version {version}")));
+                    }
+                    table.map.set_growth(growth);
+                    table.map.reset_counts();
+                    return Ok(table);
+                }
+                let valid = replay(&log, id, |record| table.apply(record))?;
+                table.map.set_growth(growth);
+                if wal {
+                    table.wal = Some(Wal::resume(path, id, applied, valid)?);
+                }
+                // Replayed changes are not operations of the loaded table.
+                table.map.reset_counts();
+                Ok(table)
+            }
+
+            fn freeze(&self, py: Python<'_>, load_factor: f64, perfect: bool) -> PyResult<$frozen> {
+                let source = &self.map;
+                let map = if perfect {
+                    let entries = source.iter().map(|(key, &value)| (key, value)).collect();
+                    let map = py.allow_threads(|| PerfectMap::build(entries))
+                        .ok_or_else(|| PyValueError::new_err("could not build a perfect hash for these keys"))?;
+                    FrozenMap::Perfect(map)
+                } else {
+                    let (capacity, delta) = sizing_for_items(source.len(), load_factor)?;
+                    let map = py.allow_threads(|| {
+                        let mut map = ElasticMap::new(capacity, delta);
+                        for (key, &value) in source.iter() {
+                            map.insert(key, value)?;
+                        }
+                        map.seal();
+                        Ok::<_, InsertError>(map)
+                    })?;
+                    FrozenMap::Elastic(Box::new(map))
+                };
+                Ok($frozen { map, _backing: Backing::Heap })
+            }
+
+            fn read_only(&self) -> bool {
+                self.read_only
+            }
+
+            fn insert(&mut self, py: Python<'_>, key: u64, value: $value) -> PyResult<usize> {
+                self.check_writable()?;
+                if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+                    buffer.push((key, value));
+                    return Ok(0);
+                }
+                self.journal_key(key);
+                let old = (!self.observers.is_empty()).then(|| self.map.get(key).copied()).flatten();
+                let result = self.map.insert(key, value);
+                if result.is_err() {
+                    self.record_failure(key, value)?;
+                }
+                self.report_inserts(py)?;
+                let probes = result?;
+                if let Some(sketch) = &mut self.distinct {
+                    sketch.add(u64::from_le_bytes(value.to_le_bytes()));
+                }
+                self.log([Record::Insert(key, value.to_le_bytes())])?;
+                self.observers.notify(py, [Change { key, old, new: Some(value) }])?;
+                Ok(probes)
+            }
+
+            fn insert_many(&mut self, py: Python<'_>, keys: KeyBatch, values: Batch<$value>) -> PyResult<usize> {
+                let (keys, values) = (keys.as_slice(py)?, values.as_slice(py)?);
+                self.check_writable()?;
+                if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+                    if keys.len() != values.len() {
+                        return Err(PyValueError::new_err("keys and values must have the same length"));
+                    }
+                    buffer.extend(keys.iter().copied().zip(values.iter().copied()));
+                    return Ok(0);
+                }
+                self.insert_slices(py, &keys, &values)
+            }
+
+            fn insert_all(&mut self, py: Python<'_>, keys: KeyBatch, values: Batch<$value>) -> PyResult<usize> {
+                let (keys, values) = (keys.as_slice(py)?, values.as_slice(py)?);
+                self.check_writable()?;
+                // Buffered inserts come first, so they cannot overwrite these later.
+                self.apply_buffered(py)?;
+                let mut undo = Journal::new();
+                for &key in keys.iter() {
+                    undo.record(key, || self.map.get(key).copied());
+                }
+                match self.insert_slices(py, &keys, &values) {
+                    Ok(probes) => Ok(probes),
+                    Err(err) => {
+                        self.roll_back(py, undo)?;
+                        Err(err)
+                    }
+                }
+            }
+
+            fn get(&self, key: u64) -> Option<$value> {
+                let found = self.map.get(key).copied();
+                self.map.count_lookups(1, found.is_some() as usize);
+                found
+            }
+
+            fn delete(&mut self, py: Python<'_>, key: u64) -> PyResult<bool> {
+                self.check_writable()?;
+                self.apply_buffered(py)?;
+                let Some(value) = self.map.remove(key) else { return Ok(false) };
+                if let Some(journal) = &mut self.journal {
+                    journal.record(key, || Some(value));
+                }
+                self.log([Record::Delete(key)])?;
+                self.shrink_if_sparse(py);
+                self.observers.notify(py, [Change { key, old: Some(value), new: None }])?;
+                Ok(true)
+            }
+
+            fn delete_many(&mut self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+                self.check_writable()?;
+                let keys = keys.as_slice(py)?;
+                self.apply_buffered(py)?;
+                self.remove_keys(py, &keys)
+            }
+
+            fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<$value>, mask: bool, defaults: Option<Batch<$value>>) -> PyResult<PyObject> {
+                let keys_buffer = keys.is_buffer();
+                let keys = keys.as_slice(py)?;
+                let fills = Fills::new(py, default, defaults.as_ref(), keys.len())?;
+                let map = &self.map;
+                let (values, hits) = py.allow_threads(|| {
+                    let found = map_keys(map, &keys, |key, hash| map.get_hashed(key, hash).copied());
+                    map.count_lookups(found.len(), found.iter().flatten().count());
+                    let hits: Option<Vec<bool>> = mask.then(|| found.iter().map(Option::is_some).collect());
+                    (found.into_iter().enumerate().map(|(index, value)| value.or_else(|| fills.as_ref().map(|fills| fills.at(index)))).collect::<Vec<_>>(), hits)
+                });
+                lookup_result(py, values, hits, keys_buffer)
+            }
+
+            fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+                let keys = keys.as_slice(py)?;
+                let map = &self.map;
+                let found = py.allow_threads(|| map_keys(map, &keys, |key, hash| map.contains_hashed(key, hash)));
+                map.count_lookups(found.len(), found.iter().filter(|&&hit| hit).count());
+                Ok(found)
+            }
+
+            fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyArray1<u64>>, Bound<'py, PyArray1<$value>>)> {
+                // Surface a missing numpy as ImportError instead of a panic inside the numpy crate.
+                py.import("numpy")?;
+                let (keys, values) = export(self.map.len(), self.map.iter());
+                Ok((keys.into_pyarray(py), values.into_pyarray(py)))
+            }
+
+            fn to_arrow<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+                py.import("pyarrow")?;
+                let (keys, values) = self.to_numpy(py)?;
+                Ok((numpy_to_arrow(keys.as_any())?, numpy_to_arrow(values.as_any())?))
+            }
+
+            fn to_pandas<'py>(&self, py: Python<'py>, key_col: &str, value_col: &str) -> PyResult<Bound<'py, PyAny>> {
+                py.import("pandas")?;
+                let (keys, values) = self.to_numpy(py)?;
+                pandas_frame([(key_col, keys.as_any()), (value_col, values.as_any())])
+            }
+
+            fn traverse(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+                if let Some(tracer) = &self.tracer {
+                    visit.call(tracer)?;
+                }
+                self.observers.traverse(&visit)?;
+                self.alert.as_ref().map_or(Ok(()), |alert| alert.traverse(&visit))
+            }
+
+            fn clear_references(&mut self) {
+                self.tracer = None;
+                self.observers.clear();
+                self.alert = None;
+            }
+
+            fn sample(&self, py: Python<'_>, n: usize, items: bool, seed: Option<u64>) -> PyResult<PyObject> {
+                let sample = self.map.sample(n, &mut sample_rng(seed));
+                if items {
+                    let items: Vec<(u64, $value)> = sample.into_iter().map(|(key, &value)| (key, value)).collect();
+                    Ok(items.into_pyobject(py)?.into_any().unbind())
+                } else {
+                    let keys: Vec<u64> = sample.into_iter().map(|(key, _)| key).collect();
+                    Ok(keys.into_pyobject(py)?.into_any().unbind())
+                }
+            }
+
+            fn entry_at(&self, index: isize) -> PyResult<(u64, $value)> {
+                let index = entry_index(index, self.map.len())?;
+                let (key, &value) = self.map.entry_at(index).expect("index is below the entry count");
+                Ok((key, value))
+            }
+
+            fn entry_range(&self, start: usize, stop: Option<usize>) -> Vec<(u64, $value)> {
+                let stop = stop.unwrap_or(usize::MAX).min(self.map.len());
+                self.map.entry_range(start, stop).map(|(key, &value)| (key, value)).collect()
+            }
+
+            fn subarray_lens(&self) -> Vec<usize> {
+                self.map.subarray_lens()
+            }
+
+            fn to_json(&self, py: Python<'_>, indent: Option<usize>) -> PyResult<String> {
+                let entries = self.map.iter()
+                    .map(|(key, &value)| Ok((key, value.into_pyobject(py)?.into_any().unbind())))
+                    .collect::<PyResult<_>>()?;
+                entries_to_json(py, entries, indent)
+            }
+
+            fn from_json(py: Python<'_>, data: &str, load_factor: f64) -> PyResult<Self> {
+                let mut keys = Vec::new();
+                let mut values = Vec::new();
+                for (key, value) in entries_from_json(py, data)? {
+                    keys.push(key);
+                    values.push(value.extract::<$value>()?);
+                }
+                let mut table = Self::for_items(keys.len(), load_factor)?;
+                table.insert_slices(py, &keys, &values)?;
+                Ok(table)
+            }
+
+            fn to_msgpack<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+                self.encode(py, Format::MsgPack)
+            }
+
+            fn from_msgpack(py: Python<'_>, data: &[u8], load_factor: f64) -> PyResult<Self> {
+                Self::decode(py, Format::MsgPack, data, load_factor)
+            }
+
+            fn to_cbor<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+                self.encode(py, Format::Cbor)
+            }
+
+            fn from_cbor(py: Python<'_>, data: &[u8], load_factor: f64) -> PyResult<Self> {
+                Self::decode(py, Format::Cbor, data, load_factor)
+            }
+
+            fn clear(&mut self, py: Python<'_>) -> PyResult<()> {
+                self.check_writable()?;
+                if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+                    buffer.clear();
+                }
+                if let Some(journal) = &mut self.journal {
+                    for (key, &value) in self.map.iter() {
+                        journal.record(key, || Some(value));
+                    }
+                }
+                let mut changes = Vec::new();
+                if !self.observers.is_empty() {
+                    changes = self.map.iter().map(|(key, &value)| Change { key, old: Some(value), new: None }).collect();
+                }
+                self.map.clear();
+                self.log([Record::Clear])?;
+                self.observers.notify(py, changes)
+            }
+
+            fn split(&self, py: Python<'_>, n: usize) -> PyResult<Vec<Self>> {
+                check_parts(n)?;
+                split_entries(&self.map, n).into_iter()
+                    .map(|(capacity, entries)| {
+                        let mut part = Self::new(capacity, self.map.delta(), None, None, None)?;
+                        let (keys, values) = export(entries.len(), entries.into_iter());
+                        part.insert_slices(py, &keys, &values)?;
+                        Ok(part)
+                    })
+                    .collect()
+            }
+
+            fn namespace_keys(&self, tag: u64, tag_bits: u32) -> PyResult<Vec<u64>> {
+                let namespace = Namespace::new(tag, tag_bits)?;
+                Ok(self.map.iter().filter_map(|(key, _)| namespace.local(key)).collect())
+            }
+
+            fn namespace_items(&self, tag: u64, tag_bits: u32) -> PyResult<Vec<(u64, $value)>> {
+                let namespace = Namespace::new(tag, tag_bits)?;
+                Ok(self.map.iter().filter_map(|(key, &value)| Some((namespace.local(key)?, value))).collect())
+            }
+
+            fn delete_namespace(&mut self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<usize> {
+                self.check_writable()?;
+                let namespace = Namespace::new(tag, tag_bits)?;
+                self.apply_buffered(py)?;
+                let keys: Vec<u64> = self.map.iter().map(|(key, _)| key).filter(|&key| namespace.local(key).is_some()).collect();
+                self.remove_keys(py, &keys)
+            }
+
+            fn rebalance(&mut self, py: Python<'_>) -> PyResult<()> {
+                self.check_writable()?;
+                let map = &mut self.map;
+                py.allow_threads(|| map.rebalance());
+                Ok(())
+            }
+
+            fn enable_bloom_filter(&mut self, py: Python<'_>, bits_per_key: usize) -> PyResult<()> {
+                check_bits_per_key(bits_per_key)?;
+                let map = &mut self.map;
+                py.allow_threads(|| map.enable_filter(bits_per_key));
+                Ok(())
+            }
+
+            fn disable_bloom_filter(&mut self) {
+                self.map.disable_filter();
+            }
+
+            fn bloom_filter(&self) -> Option<usize> {
+                self.map.filter_bits_per_key()
+            }
+
+            fn enable_auto_shrink(&mut self, threshold: f64, min_capacity: usize) -> PyResult<()> {
+                self.check_writable()?;
+                if !matches!(self.backing, Backing::Heap) {
+                    return Err(PyValueError::new_err("tables in shared memory or a mapped file cannot shrink"));
+                }
+                self.map.set_auto_shrink(Some(shrink_policy(threshold, min_capacity)?));
+                Ok(())
+            }
+
+            fn disable_auto_shrink(&mut self) {
+                self.map.set_auto_shrink(None);
+            }
+
+            fn auto_shrink(&self) -> Option<f64> {
+                self.map.auto_shrink().map(|policy| policy.threshold)
+            }
+
+            fn track_probes(&self) -> bool {
+                self.map.histograms().is_some()
+            }
+
+            fn set_track_probes(&mut self, enabled: bool) {
+                self.map.track_probes(enabled);
+            }
+
+            fn adaptive(&self) -> bool {
+                self.map.tunes_probes()
+            }
+
+            fn set_adaptive(&mut self, enabled: bool) {
+                self.map.tune_probes(enabled);
+            }
+
+            fn probe_histograms<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+                self.map.histograms().map(|histograms| histograms_to_dict(py, histograms)).transpose()
+            }
+
+            fn op_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                op_counts_to_dict(py, self.map.op_counts())
+            }
+
+            fn reset_stats(&mut self) {
+                self.map.reset_counts();
+            }
+
+            fn capacity(&self) -> usize {
+                self.map.capacity()
+            }
+
+            fn remaining_capacity(&self) -> usize {
+                self.map.remaining_capacity()
+            }
+
+            fn is_full(&self) -> bool {
+                self.map.remaining_capacity() == 0
+            }
+
+            fn stats(&self) -> Vec<(usize, usize, f64)> {
+                self.map.stats()
+            }
+
+            fn set_tracer(&mut self, callback: Option<PyObject>) {
+                self.map.trace_inserts(callback.is_some());
+                self.tracer = callback;
+            }
+
+            fn add_observer(&mut self, callback: PyObject) {
+                self.observers.add(callback);
+            }
+
+            fn remove_observer(&mut self, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+                self.observers.remove(callback)
+            }
+
+            fn set_load_alert(
+                &mut self,
+                threshold: Option<f64>,
+                callback: Option<PyObject>,
+                subarray_threshold: Option<f64>,
+            ) -> PyResult<()> {
+                self.alert = threshold.map(|threshold| LoadAlert::new(threshold, subarray_threshold, callback)).transpose()?;
+                Ok(())
+            }
+
+            fn load_alert(&self) -> Option<f64> {
+                self.alert.as_ref().map(LoadAlert::threshold)
+            }
+
+            fn track_distinct_values(&mut self, py: Python<'_>, precision: Option<u32>) -> PyResult<()> {
+                self.distinct = precision.map(HyperLogLog::new).transpose()?;
+                if let Some(sketch) = &mut self.distinct {
+                    let map = &self.map;
+                    py.allow_threads(|| map.iter().for_each(|(_, value)| sketch.add(u64::from_le_bytes(value.to_le_bytes()))));
+                }
+                Ok(())
+            }
+
+            fn metrics_text(&self, prefix: &str, labels: Option<BTreeMap<String, String>>) -> PyResult<String> {
+                metrics_text(&self.map, prefix, labels.unwrap_or_default())
+            }
+
+            fn probe_bounds<'py>(&self, py: Python<'py>, tolerance: f64) -> PyResult<Bound<'py, PyDict>> {
+                probe_bounds(py, &self.map, tolerance)
+            }
+
+            fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                memory_usage(py, &self.map, |_| None)
+            }
+
+            fn occupancy(&self, py: Python<'_>, packed: bool) -> PyResult<Vec<PyObject>> {
+                occupancy(py, &self.map, packed)
+            }
+
+            fn items_in_subarray(&self, i: usize) -> PyResult<Vec<(u64, $value)>> {
+                Ok(subarray_items(&self.map, i)?.map(|(key, &value)| (key, value)).collect())
+            }
+
+            fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                let dict = detailed_stats(py, &self.map)?;
+                dict.set_item("distinct_values", self.distinct.as_ref().map(HyperLogLog::estimate))?;
+                Ok(dict)
+            }
+
+            fn validate(&self, py: Python<'_>) -> Vec<String> {
+                py.allow_threads(|| self.map.validate())
+            }
+}
+
+#[pymethods]
+impl $name {
+            /// Create a table with the given slot capacity and delta parameter.
+            /// See ElasticTable for the meaning of the arguments.
+            #[new]
+            #[pyo3(signature = (capacity, delta=0.05, growth_factor=None, grow_at=None, max_capacity=None))]
+            fn new(
+                capacity: usize,
+                delta: f64,
+                growth_factor: Option<f64>,
+                grow_at: Option<f64>,
+                max_capacity: Option<usize>,
+            ) -> PyResult<Self> {
+                $state::new(capacity, delta, growth_factor, grow_at, max_capacity).map(Self::from)
+            }
+
+            /// Create a table sized for `expected_items` at the target load factor.
+            #[staticmethod]
+            #[pyo3(signature = (expected_items, load_factor=0.90))]
+            fn for_items(expected_items: usize, load_factor: f64) -> PyResult<Self> {
+                $state::for_items(expected_items, load_factor).map(Self::from)
+            }
+
+            /// Build a table from parallel key and value arrays.
+            ///
+            /// Args:
+            ///     keys: uint64 numpy array (or sequence of ints)
+            ///     values: numpy array of the table's value type, same length as `keys`
+            ///     load_factor: Target load factor used to size the table (default: 0.90)
+            ///
+            /// Arrays are read in place; no Python object is created per element.
+            #[staticmethod]
+            #[pyo3(signature = (keys, values, load_factor=0.90))]
+            fn from_numpy(py: Python<'_>, keys: KeyBatch, values: Batch<$value>, load_factor: f64) -> PyResult<Self> {
+                $state::from_numpy(py, keys, values, load_factor).map(Self::from)
+            }
+
+            /// Build a table from parallel Arrow key and value arrays.
+            ///
+            /// Args:
+            ///     keys: uint64 pyarrow array or chunked array
+            ///     values: pyarrow array of the table's value type, same length as `keys`
+            ///     load_factor: Target load factor used to size the table (default: 0.90)
+            ///
+            /// Null-free arrays are read in place, as with `from_numpy`; arrays with
+            /// nulls are rejected.
+            #[staticmethod]
+            #[pyo3(signature = (keys, values, load_factor=0.90))]
+            fn from_arrow(
+                py: Python<'_>,
+                keys: &Bound<'_, PyAny>,
+                values: &Bound<'_, PyAny>,
+                load_factor: f64,
+            ) -> PyResult<Self> {
+                $state::from_arrow(py, keys, values, load_factor).map(Self::from)
+            }
+
+            /// Build a table from two columns of a pandas DataFrame.
+            ///
+            /// Args:
+            ///     df: The DataFrame
+            ///     key_col: Name of the uint64 key column
+            ///     value_col: Name of the value column, of the table's value type
+            ///     load_factor: Target load factor used to size the table (default: 0.90)
+            ///
+            /// Columns are read in place, as with `from_numpy`; columns with missing
+            /// values are rejected.
+            #[staticmethod]
+            #[pyo3(signature = (df, key_col, value_col, load_factor=0.90))]
+            fn from_pandas(
+                py: Python<'_>,
+                df: &Bound<'_, PyAny>,
+                key_col: &str,
+                value_col: &str,
+                load_factor: f64,
+            ) -> PyResult<Self> {
+                $state::from_pandas(py, df, key_col, value_col, load_factor).map(Self::from)
+            }
+
+            /// Create an empty table whose slots live in a new shared memory segment.
+            ///
+            /// Args:
+            ///     expected_items: The number of items you plan to store
+            ///     load_factor: Target load factor (default: 0.90)
+            ///     name: Segment name (default: a random name chosen by Python)
+            ///
+            /// Other processes attach to the same slots with `open_shared(name)`.
+            /// The creator should call `table.shared_memory.unlink()` once the
+            /// segment is no longer needed.
+            #[staticmethod]
+            #[pyo3(signature = (expected_items, load_factor=0.90, name=None))]
+            fn create_shared(py: Python<'_>, expected_items: usize, load_factor: f64, name: Option<&str>) -> PyResult<Self> {
+                $state::create_shared(py, expected_items, load_factor, name).map(Self::from)
+            }
+
+            /// Attach to a table created by `create_shared`, without copying it.
+            ///
+            /// Entries are recounted on attach. Writes made through one handle are
+            /// visible in the other processes' lookups, but handles do not
+            /// coordinate: write while no other process is reading, and only from
+            /// the process that created the table.
+            #[staticmethod]
+            fn open_shared(py: Python<'_>, name: &str) -> PyResult<Self> {
+                $state::open_shared(py, name).map(Self::from)
+            }
+
+            /// The `multiprocessing.shared_memory.SharedMemory` holding the slots,
+            /// or None for tables in private memory.
+            #[getter]
+            fn shared_memory(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+                Ok(self.lock(py)?.shared_memory(py))
+            }
+
+            /// Create an empty table whose slots live in a memory-mapped file.
+            ///
+            /// Args:
+            ///     path: File to create (an existing file is overwritten)
+            ///     expected_items: The number of items you plan to store
+            ///     load_factor: Target load factor (default: 0.90)
+            ///
+            /// The OS pages slots in and out on demand, so the table may be larger
+            /// than RAM. Changes reach the file when the OS writes the pages back,
+            /// or at the latest on `flush()`.
+            #[staticmethod]
+            #[pyo3(signature = (path, expected_items, load_factor=0.90))]
+            fn create_mmap(py: Python<'_>, path: PathBuf, expected_items: usize, load_factor: f64) -> PyResult<Self> {
+                $state::create_mmap(py, path, expected_items, load_factor).map(Self::from)
+            }
+
+            /// Reopen a table file written by `create_mmap`, without rebuilding it.
+            #[staticmethod]
+            fn open_mmap(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+                $state::open_mmap(py, path).map(Self::from)
+            }
+
+            /// Create an empty table whose first subarrays stay in memory while
+            /// the tail subarrays live in a memory-mapped scratch file.
+            ///
+            /// Args:
+            ///     path: Scratch file to create (an existing file is overwritten)
+            ///     expected_items: The number of items you plan to store
+            ///     load_factor: Target load factor (default: 0.90)
+            ///     hot_subarrays: Subarrays kept in memory (default: 1)
+            ///
+            /// Later subarrays only hold the overflow of the earlier ones and are
+            /// probed less often, so the OS can write their pages back to the file
+            /// and drop them under memory pressure. The file is removed with the
+            /// table and cannot be reopened.
+            #[staticmethod]
+            #[pyo3(signature = (path, expected_items, load_factor=0.90, hot_subarrays=1))]
+            fn create_tiered(path: PathBuf, expected_items: usize, load_factor: f64, hot_subarrays: usize) -> PyResult<Self> {
+                $state::create_tiered(path, expected_items, load_factor, hot_subarrays).map(Self::from)
+            }
+
+            /// Subarrays kept in memory by a table from `create_tiered`, or None.
+            #[getter]
+            fn hot_subarrays(&self, py: Python<'_>) -> PyResult<Option<usize>> {
+                Ok(self.lock(py)?.hot_subarrays())
+            }
+
+            /// Write modified slots of a memory-mapped table back to its file,
+            /// so that reopening it skips recounting entries. Does nothing for
+            /// other tables.
+            fn flush(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.flush(py)
+            }
+
+            /// Write a snapshot of the table to `path`.
+            ///
+            /// The snapshot uses the same format as `create_mmap` files, so it can
+            /// also be reopened for writing with `open_mmap`. It keeps the table's
+            /// growth settings, which `load` restores.
+            ///
+            /// With `wal=True`, every later change is also appended to a log at
+            /// `<path>.wal`, which `load(path)` replays. A table that already logs
+            /// to that file starts a fresh log, since the snapshot now holds its
+            /// changes.
+            ///
+            /// Saving replaces the log and checkpoints of any snapshot previously
+            /// saved at `path`.
+            #[pyo3(signature = (path, wal=false))]
+            fn save(&self, py: Python<'_>, path: PathBuf, wal: bool) -> PyResult<()> {
+                self.lock(py)?.save(py, path, wal)
+            }
+
+            /// Write the changes logged since the last checkpoint to the next
+            /// checkpoint file, `<path>.v<n>`, and empty the log. Returns `n`.
+            ///
+            /// Each checkpoint holds only the keys changed since the previous one,
+            /// so long-running jobs can checkpoint often without rewriting the
+            /// snapshot. Requires a table that logs its changes (see `save`).
+            fn checkpoint(&self, py: Python<'_>) -> PyResult<u64> {
+                self.lock(py)?.checkpoint(py)
+            }
+
+            /// Versions of the snapshot at `path` that `load` can restore: 0 for the
+            /// snapshot itself, followed by the number of every checkpoint.
+            #[staticmethod]
+            fn versions(path: PathBuf) -> PyResult<Vec<u64>> {
+                let id = read_header_file(&path, $kind)?.id;
+                Ok((0..=checkpoint_count(&path, id)?).collect())
+            }
+
+            /// Force logged changes to disk, so they survive a machine crash;
+            /// changes already survive the process crashing. Does nothing for
+            /// tables without a log.
+            fn commit(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.commit()
+            }
+
+            /// Record every change made to the table from now on to `path`,
+            /// along with its parameters, so that `replay(path)` can repeat them.
+            ///
+            /// The recording starts with the entries the table holds, and
+            /// inserts that fail are recorded too, so a recording started on an
+            /// empty table replays into a table identical slot for slot, for
+            /// reproducing corruption or probe explosions. Raises RuntimeError
+            /// if the table is already recording.
+            fn start_recording(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+                self.lock(py)?.start_recording(py, path)
+            }
+
+            /// Stop recording, handing the recording to the OS. Does nothing
+            /// for a table that is not recording.
+            fn stop_recording(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.stop_recording()
+            }
+
+            /// Path of the recording `start_recording` is writing, or None.
+            #[getter]
+            fn recording(&self, py: Python<'_>) -> PyResult<Option<PathBuf>> {
+                Ok(self.lock(py)?.recording())
+            }
+
+            /// Build a fresh table from a recording written by
+            /// `start_recording`, with the recorded parameters, and repeat the
+            /// recorded changes on it. Inserts that failed when recorded are
+            /// expected to fail again and are skipped; replay stops at a torn
+            /// tail.
+            #[staticmethod]
+            fn replay(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+                $state::replay(py, path).map(Self::from)
+            }
+
+            /// Load a snapshot written by `save`.
+            ///
+            /// Args:
+            ///     path: Snapshot file
+            ///     mmap: Map the file read-only instead of reading it (default: False).
+            ///           Pages are then shared with other processes mapping the
+            ///           file, but the table rejects inserts, deletes and `clear()`.
+            ///     wal: Keep appending changes to the snapshot's log (default: False)
+            ///     version: Restore this version (see `versions`) instead of the latest
+            ///     verify: Check the snapshot's checksums before using it (default:
+            ///             True). Checking reads the whole file; with `mmap=True`,
+            ///             pass False to start in milliseconds regardless of table
+            ///             size, loading pages on first access, on a file known to
+            ///             be intact.
+            ///
+            /// By default the snapshot's checkpoints are applied, followed by the
+            /// changes recorded in `<path>.wal` up to the first record a crash left
+            /// incomplete. An explicit `version` applies checkpoints up to that one
+            /// and ignores the log.
+            ///
+            /// A snapshot written by an incompatible version of this library, or
+            /// one whose header, data or checkpoints fail their checksums, raises
+            /// ValueError.
+            #[staticmethod]
+            #[pyo3(signature = (path, mmap=false, wal=false, version=None, verify=true))]
+            fn load(py: Python<'_>, path: PathBuf, mmap: bool, wal: bool, version: Option<u64>, verify: bool) -> PyResult<Self> {
+                $state::load(py, path, mmap, wal, version, verify).map(Self::from)
+            }
+
+            /// Build a read-only copy of the table tuned for lookups.
+            ///
+            /// The copy is sized for the current entries at `load_factor` and holds
+            /// no tombstones. Each of its subarrays also records the longest probe
+            /// sequence among its keys, so lookups of missing keys stop early and
+            /// empty subarrays are skipped.
+            ///
+            /// With `perfect=True`, the copy instead uses a minimal perfect hash over
+            /// the current keys: entries fill exactly one slot each and every lookup
+            /// inspects a single slot. Building it takes longer, and `load_factor`
+            /// does not apply.
+            #[pyo3(signature = (load_factor=0.90, perfect=false))]
+            fn freeze(&self, py: Python<'_>, load_factor: f64, perfect: bool) -> PyResult<$frozen> {
+                self.lock(py)?.freeze(py, load_factor, perfect)
+            }
+
+            /// Whether the table rejects modification (tables loaded with `mmap=True`).
+            #[getter]
+            fn read_only(&self, py: Python<'_>) -> PyResult<bool> {
+                Ok(self.lock(py)?.read_only())
+            }
+
+            fn insert(&self, py: Python<'_>, key: u64, value: $value) -> PyResult<usize> {
+                self.lock(py)?.insert(py, key, value)
+            }
+
+            /// Insert many key/value pairs in a single call. Returns the total probes used.
+            fn insert_many(&self, py: Python<'_>, keys: KeyBatch, values: Batch<$value>) -> PyResult<usize> {
+                self.lock(py)?.insert_many(py, keys, values)
+            }
+
+            /// Insert many key/value pairs, all or none. See
+            /// `ElasticTable.insert_all`. On a logged table, the pairs taken
+            /// out again are logged as removed.
+            fn insert_all(&self, py: Python<'_>, keys: KeyBatch, values: Batch<$value>) -> PyResult<usize> {
+                self.lock(py)?.insert_all(py, keys, values)
+            }
+
+            fn get(&self, py: Python<'_>, key: u64) -> PyResult<Option<$value>> {
+                Ok(self.lock(py)?.get(key))
+            }
+
+            /// Remove a key from the table. Returns True if the key was present.
+            fn delete(&self, py: Python<'_>, key: u64) -> PyResult<bool> {
+                self.lock(py)?.delete(py, key)
+            }
+
+            /// Remove many keys in a single call. Returns how many were present.
+            fn delete_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<usize> {
+                self.lock(py)?.delete_many(py, keys)
+            }
+
+            /// Look up many keys in a single call, using `default` for missing keys,
+            /// or the matching item of `defaults`, a sequence or array holding one
+            /// value for each key. Large batches are probed in parallel. With
+            /// `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+            #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+            fn get_many(
+                &self,
+                py: Python<'_>,
+                keys: KeyBatch,
+                default: Option<$value>,
+                mask: bool,
+                defaults: Option<Batch<$value>>,
+            ) -> PyResult<PyObject> {
+                self.lock(py)?.get_many(py, keys, default, mask, defaults)
+            }
+
+            /// Check membership for many keys in a single call.
+            /// Large batches are probed in parallel.
+            fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+                self.lock(py)?.contains_many(py, keys)
+            }
+
+            /// Export the contents as a `(keys, values)` pair of numpy arrays.
+            fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyArray1<u64>>, Bound<'py, PyArray1<$value>>)> {
+                self.lock(py)?.to_numpy(py)
+            }
+
+            /// Export the contents as a `(keys, values)` pair of pyarrow arrays,
+            /// which share memory with the arrays `to_numpy()` returns.
+            fn to_arrow<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+                self.lock(py)?.to_arrow(py)
+            }
+
+            /// Export the contents as a pandas DataFrame with one row per entry,
+            /// built around the arrays `to_numpy()` returns.
+            #[pyo3(signature = (key_col="key", value_col="value"))]
+            fn to_pandas<'py>(&self, py: Python<'py>, key_col: &str, value_col: &str) -> PyResult<Bound<'py, PyAny>> {
+                self.lock(py)?.to_pandas(py, key_col, value_col)
+            }
+
+            /// A live, set-like view of the keys, in slot order. Typed tables
+            /// do not keep insertion order. See `ElasticTable.keys`.
+            fn keys(slf: &Bound<'_, Self>) -> KeysView {
+                KeysView::new(slf.clone().unbind())
+            }
+
+            /// A live view of the values, in the order of `keys()`.
+            fn values(slf: &Bound<'_, Self>) -> ValuesView {
+                ValuesView::new(slf.clone().unbind())
+            }
+
+            /// A live, set-like view of the `(key, value)` pairs, in the order of `keys()`.
+            fn items(slf: &Bound<'_, Self>) -> ItemsView {
+                ItemsView::new(slf.clone().unbind())
+            }
+
+            /// Open a transaction, so that a group of changes can be undone
+            /// together. See `ElasticTable.transaction`. A rolled back
+            /// transaction is undone in the change log too.
+            fn transaction(slf: &Bound<'_, Self>) -> PyResult<Transaction> {
+                slf.get().lock(slf.py())?.begin_transaction(slf.py())?;
+                Ok(Transaction::new(slf.clone().unbind()))
+            }
+
+            /// Start a block of inserts, for use as `with table.bulk():`.
+            /// See `ElasticTable.bulk`. Besides tracer calls, a logged table
+            /// hands its change log to the OS once, when the block ends.
+            #[pyo3(signature = (buffer=false))]
+            fn bulk(slf: &Bound<'_, Self>, buffer: bool) -> BulkBlock {
+                BulkBlock::new(slf.clone().unbind(), buffer)
+            }
+
+            /// Iterate over the keys, in the order of `keys()`. Raises
+            /// `RuntimeError` if the table gains or loses entries meanwhile.
+            fn __iter__(slf: &Bound<'_, Self>) -> PyResult<TableIterator> {
+                Ok(TableIterator::new(slf.clone().unbind(), slf.get().lock(slf.py())?.map(), Yield::Keys))
+            }
+
+            /// Reports the tracer, the observers and the load alert's callback
+            /// to the cyclic GC; the values are plain numbers.
+            fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+                // A table in use by a call is not reported, which only delays
+                // collecting a cycle through it.
+                match self.state.try_lock() {
+                    Some(state) => state.traverse(visit),
+                    None => Ok(()),
+                }
+            }
+
+            fn __clear__(&self) {
+                if let Some(mut state) = self.state.try_lock() {
+                    state.clear_references();
+                }
+            }
+
+            /// `n` distinct keys chosen uniformly at random, or `(key, value)`
+            /// pairs with `items=True`. See `ElasticTable.sample`.
+            #[pyo3(signature = (n, items=false, seed=None))]
+            fn sample(&self, py: Python<'_>, n: usize, items: bool, seed: Option<u64>) -> PyResult<PyObject> {
+                self.lock(py)?.sample(py, n, items, seed)
+            }
+
+            /// The entry at position `index` of slot order, as a `(key, value)`
+            /// pair. See `ElasticTable.entry_at`.
+            fn entry_at(&self, py: Python<'_>, index: isize) -> PyResult<(u64, $value)> {
+                self.lock(py)?.entry_at(index)
+            }
+
+            /// The entries at positions `start` up to `stop` of slot order.
+            /// See `ElasticTable.entry_range`.
+            #[pyo3(signature = (start, stop=None))]
+            fn entry_range(&self, py: Python<'_>, start: usize, stop: Option<usize>) -> PyResult<Vec<(u64, $value)>> {
+                Ok(self.lock(py)?.entry_range(start, stop))
+            }
+
+            /// Number of entries in each subarray, in slot order.
+            fn subarray_lens(&self, py: Python<'_>) -> PyResult<Vec<usize>> {
+                Ok(self.lock(py)?.subarray_lens())
+            }
+
+            /// Serialize the contents as a JSON object mapping each key, as a decimal
+            /// string, to its value, in ascending key order.
+            #[pyo3(signature = (indent=None))]
+            fn to_json(&self, py: Python<'_>, indent: Option<usize>) -> PyResult<String> {
+                self.lock(py)?.to_json(py, indent)
+            }
+
+            /// Build a table from a JSON object mapping decimal keys to values, such
+            /// as the output of `to_json()`.
+            #[staticmethod]
+            #[pyo3(signature = (data, load_factor=0.90))]
+            fn from_json(py: Python<'_>, data: &str, load_factor: f64) -> PyResult<Self> {
+                $state::from_json(py, data, load_factor).map(Self::from)
+            }
+
+            /// Encode the contents as a msgpack map from key to value.
+            fn to_msgpack<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+                self.lock(py)?.to_msgpack(py)
+            }
+
+            /// Build a table from a msgpack map from non-negative integer keys to values,
+            /// such as the output of `to_msgpack()`.
+            #[staticmethod]
+            #[pyo3(signature = (data, load_factor=0.90))]
+            fn from_msgpack(py: Python<'_>, data: &[u8], load_factor: f64) -> PyResult<Self> {
+                $state::from_msgpack(py, data, load_factor).map(Self::from)
+            }
+
+            /// Encode the contents as a CBOR map from key to value.
+            fn to_cbor<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+                self.lock(py)?.to_cbor(py)
+            }
+
+            /// Build a table from a CBOR map from non-negative integer keys to values,
+            /// such as the output of `to_cbor()`.
+            #[staticmethod]
+            #[pyo3(signature = (data, load_factor=0.90))]
+            fn from_cbor(py: Python<'_>, data: &[u8], load_factor: f64) -> PyResult<Self> {
+                $state::from_cbor(py, data, load_factor).map(Self::from)
+            }
+
+            /// Remove every entry, keeping the allocated capacity.
+            fn clear(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.clear(py)
+            }
+
+            /// Insert every entry of `other`, a table of the same class, a mapping
+            /// with int keys or an iterable of `(key, value)` pairs. See
+            /// ElasticTable.merge for `on_conflict`.
+            #[pyo3(signature = (other, on_conflict="replace"))]
+            fn merge(slf: &Bound<'_, Self>, other: &Bound<'_, PyAny>, on_conflict: &str) -> PyResult<()> {
+                let on_conflict = OnConflict::parse(on_conflict)?;
+                // Every key conflicts with itself, and replacing or keeping it changes nothing.
+                if other.is(slf) && !matches!(on_conflict, OnConflict::Error) {
+                    return Ok(());
+                }
+                let (mut keys, mut values) = $state::merge_source(other)?;
+                let mut table = slf.get().lock(slf.py())?;
+                on_conflict.apply(&table.map, &mut keys, &mut values)?;
+                table.insert_slices(slf.py(), &keys, &values)?;
+                Ok(())
+            }
+
+            /// `table | other`: a new table holding the entries of both, with the
+            /// values of `other` for keys both hold. It has the same `delta` and
+            /// enough capacity for both tables.
+            fn __or__(slf: &Bound<'_, Self>, other: Mergeable<'_, Self>) -> PyResult<Py<Self>> {
+                let py = slf.py();
+                let (keys, values) = $state::merge_source(other.as_any())?;
+                let this = slf.get().lock(py)?;
+                let capacity = union_capacity(&this.map, this.map.len() + keys.len());
+                let mut table = $state::new(capacity, this.map.delta(), None, None, None)?;
+                let (own_keys, own_values) = export(this.map.len(), this.map.iter());
+                drop(this);
+                table.insert_slices(py, &own_keys, &own_values)?;
+                table.insert_slices(py, &keys, &values)?;
+                Py::new(py, Self::from(table))
+            }
+
+            /// `table |= other`: `merge(other)` in place.
+            fn __ior__(slf: &Bound<'_, Self>, other: &Bound<'_, PyAny>) -> PyResult<()> {
+                Self::merge(slf, other, "replace")
+            }
+
+            /// Keys of this table that `other`, a table of any class, also holds,
+            /// in ascending order. See ElasticTable.intersection_keys.
+            fn intersection_keys(&self, py: Python<'_>, other: AnyTable<'_>) -> PyResult<Vec<u64>> {
+                let keys = sorted_keys(py, &self.lock(py)?.map);
+                compare_keys(py, keys, &other, true)
+            }
+
+            /// Keys of this table that `other` does not hold, in ascending order.
+            fn difference_keys(&self, py: Python<'_>, other: AnyTable<'_>) -> PyResult<Vec<u64>> {
+                let keys = sorted_keys(py, &self.lock(py)?.map);
+                compare_keys(py, keys, &other, false)
+            }
+
+            /// Partition the entries into `n` new tables by key hash. See
+            /// ElasticTable.split; the parts keep this table's `delta`.
+            fn split(&self, py: Python<'_>, n: usize) -> PyResult<Vec<Py<Self>>> {
+                let parts = self.lock(py)?.split(py, n)?;
+                parts.into_iter().map(|part| Py::new(py, Self::from(part))).collect()
+            }
+
+            /// The part of `split(n)` that holds `key`. See ElasticTable.shard_of.
+            #[staticmethod]
+            fn shard_of(key: u64, n: usize) -> PyResult<usize> {
+                check_parts(n)?;
+                Ok(shard_of(hash_key(key), n))
+            }
+
+            /// `key` with `tag` packed into its top `tag_bits` bits. See
+            /// ElasticTable.tag_key.
+            #[staticmethod]
+            #[pyo3(signature = (tag, key, tag_bits=8))]
+            fn tag_key(tag: u64, key: u64, tag_bits: u32) -> PyResult<u64> {
+                Namespace::new(tag, tag_bits)?.key(key)
+            }
+
+            /// The `(tag, key)` pair `tag_key` packed into `key`.
+            #[staticmethod]
+            #[pyo3(signature = (key, tag_bits=8))]
+            fn untag_key(key: u64, tag_bits: u32) -> PyResult<(u64, u64)> {
+                split_key(key, tag_bits)
+            }
+
+            /// The keys in namespace `tag`, without the tag, in the order of `keys()`.
+            #[pyo3(signature = (tag, tag_bits=8))]
+            fn namespace_keys(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<Vec<u64>> {
+                self.lock(py)?.namespace_keys(tag, tag_bits)
+            }
+
+            /// The `(key, value)` pairs in namespace `tag`, keys without the tag.
+            #[pyo3(signature = (tag, tag_bits=8))]
+            fn namespace_items(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<Vec<(u64, $value)>> {
+                self.lock(py)?.namespace_items(tag, tag_bits)
+            }
+
+            /// Remove every key in namespace `tag`, as `delete_many` would.
+            /// Returns how many were removed.
+            #[pyo3(signature = (tag, tag_bits=8))]
+            fn delete_namespace(&self, py: Python<'_>, tag: u64, tag_bits: u32) -> PyResult<usize> {
+                self.lock(py)?.delete_namespace(py, tag, tag_bits)
+            }
+
+            /// Redistribute the entries across the subarrays, keeping the capacity.
+            /// See ElasticTable.rebalance. The GIL is released meanwhile.
+            fn rebalance(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.rebalance(py)
+            }
+
+            /// Keep a Bloom filter over the keys, so most lookups of missing keys
+            /// are answered without probing. See ElasticTable.enable_bloom_filter.
+            #[pyo3(signature = (bits_per_key=10))]
+            fn enable_bloom_filter(&self, py: Python<'_>, bits_per_key: usize) -> PyResult<()> {
+                self.lock(py)?.enable_bloom_filter(py, bits_per_key)
+            }
+
+            /// Drop the Bloom filter, if any.
+            fn disable_bloom_filter(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.disable_bloom_filter();
+                Ok(())
+            }
+
+            /// Bits per slot of the Bloom filter, or None without one.
+            #[getter]
+            fn bloom_filter(&self, py: Python<'_>) -> PyResult<Option<usize>> {
+                Ok(self.lock(py)?.bloom_filter())
+            }
+
+            /// Shrink the table once deletes leave it sparse. See
+            /// ElasticTable.enable_auto_shrink. Tables in shared memory or a
+            /// mapped file cannot shrink.
+            #[pyo3(signature = (threshold=0.25, min_capacity=0))]
+            fn enable_auto_shrink(&self, py: Python<'_>, threshold: f64, min_capacity: usize) -> PyResult<()> {
+                self.lock(py)?.enable_auto_shrink(threshold, min_capacity)
+            }
+
+            /// Stop shrinking the table after deletes.
+            fn disable_auto_shrink(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.disable_auto_shrink();
+                Ok(())
+            }
+
+            /// The `threshold` of `enable_auto_shrink`, or None if the table does
+            /// not shrink.
+            #[getter]
+            fn auto_shrink(&self, py: Python<'_>) -> PyResult<Option<f64>> {
+                Ok(self.lock(py)?.auto_shrink())
+            }
+
+            /// Whether probe lengths are recorded for `probe_histograms()` (default:
+            /// False). See ElasticTable.track_probes.
+            #[getter]
+            fn track_probes(&self, py: Python<'_>) -> PyResult<bool> {
+                Ok(self.lock(py)?.track_probes())
+            }
+
+            #[setter]
+            fn set_track_probes(&self, py: Python<'_>, enabled: bool) -> PyResult<()> {
+                self.lock(py)?.set_track_probes(enabled);
+                Ok(())
+            }
+
+            /// Whether the insert probe budget adapts to the workload (default:
+            /// False). See ElasticTable.adaptive.
+            #[getter]
+            fn adaptive(&self, py: Python<'_>) -> PyResult<bool> {
+                Ok(self.lock(py)?.adaptive())
+            }
+
+            #[setter]
+            fn set_adaptive(&self, py: Python<'_>, enabled: bool) -> PyResult<()> {
+                self.lock(py)?.set_adaptive(enabled);
+                Ok(())
+            }
+
+            /// Probe-length histograms recorded since `track_probes` was enabled, or
+            /// None if it is off. See ElasticTable.probe_histograms.
+            fn probe_histograms<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+                self.lock(py)?.probe_histograms(py)
+            }
+
+            /// Counts of the operations applied since the table was created or loaded,
+            /// or `reset_stats()` was last called. See ElasticTable.op_stats.
+            fn op_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                self.lock(py)?.op_stats(py)
+            }
+
+            /// Zero the counts of `op_stats()`, the fallthroughs of `detailed_stats()`
+            /// and any probe histograms being recorded.
+            fn reset_stats(&self, py: Python<'_>) -> PyResult<()> {
+                self.lock(py)?.reset_stats();
+                Ok(())
+            }
+
+            /// Number of slots the table was created with.
+            fn capacity(&self, py: Python<'_>) -> PyResult<usize> {
+                Ok(self.lock(py)?.capacity())
+            }
+
+            /// See `ElasticTable.remaining_capacity`.
+            fn remaining_capacity(&self, py: Python<'_>) -> PyResult<usize> {
+                Ok(self.lock(py)?.remaining_capacity())
+            }
+
+            /// See `ElasticTable.is_full`.
+            fn is_full(&self, py: Python<'_>) -> PyResult<bool> {
+                Ok(self.lock(py)?.is_full())
+            }
+
+            fn stats(&self, py: Python<'_>) -> PyResult<Vec<(usize, usize, f64)>> {
+                Ok(self.lock(py)?.stats())
+            }
+
+            /// Call `callback(key, steps, inserted)` after every insert. See
+            /// ElasticTable.set_tracer.
+            #[pyo3(signature = (callback))]
+            fn set_tracer(&self, py: Python<'_>, callback: Option<PyObject>) -> PyResult<()> {
+                self.lock(py)?.set_tracer(callback);
+                Ok(())
+            }
+
+            /// Call `callback(event, key, old, new)` with every change to the
+            /// table. See ElasticTable.add_observer.
+            fn add_observer(&self, py: Python<'_>, callback: PyObject) -> PyResult<()> {
+                self.lock(py)?.add_observer(callback);
+                Ok(())
+            }
+
+            /// Stop calling `callback` with changes. Raises ValueError if it
+            /// is not an observer of the table.
+            fn remove_observer(&self, py: Python<'_>, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+                self.lock(py)?.remove_observer(callback)
+            }
+
+            /// Call `callback(load_factor, subarray)`, or issue a
+            /// `LoadFactorWarning`, once the load factor crosses `threshold`.
+            /// See ElasticTable.set_load_alert.
+            #[pyo3(signature = (threshold, callback=None, subarray_threshold=None))]
+            fn set_load_alert(
+                &self,
+                py: Python<'_>,
+                threshold: Option<f64>,
+                callback: Option<PyObject>,
+                subarray_threshold: Option<f64>,
+            ) -> PyResult<()> {
+                self.lock(py)?.set_load_alert(threshold, callback, subarray_threshold)
+            }
+
+            /// The `threshold` of `set_load_alert`, or None without an alert.
+            #[getter]
+            fn load_alert(&self, py: Python<'_>) -> PyResult<Option<f64>> {
+                Ok(self.lock(py)?.load_alert())
+            }
+
+            /// Estimate the number of distinct values inserted, reported as
+            /// "distinct_values" by `detailed_stats()`.
+            ///
+            /// A HyperLogLog sketch of `2**precision` bytes (4 to 18, default
+            /// 14 for 16 KiB and a typical error of 0.8%) takes in the values
+            /// stored now and every value inserted from then on. Values stay
+            /// counted once overwritten or deleted. `None` stops the estimate.
+            #[pyo3(signature = (precision=Some(14)))]
+            fn track_distinct_values(&self, py: Python<'_>, precision: Option<u32>) -> PyResult<()> {
+                self.lock(py)?.track_distinct_values(py, precision)
+            }
+
+            /// Render the table's metrics in the Prometheus text exposition format.
+            /// See ElasticTable.metrics_text.
+            #[pyo3(signature = (prefix="elastic_table", labels=None))]
+            fn metrics_text(&self, py: Python<'_>, prefix: &str, labels: Option<BTreeMap<String, String>>) -> PyResult<String> {
+                self.lock(py)?.metrics_text(prefix, labels)
+            }
+
+            /// Compare the probe counts recorded while `track_probes` is on with the
+            /// paper's bounds. See ElasticTable.probe_bounds.
+            #[pyo3(signature = (tolerance=4.0))]
+            fn probe_bounds<'py>(&self, py: Python<'py>, tolerance: f64) -> PyResult<Bound<'py, PyDict>> {
+                self.lock(py)?.probe_bounds(py, tolerance)
+            }
+
+            /// Bytes allocated for the table, broken down by subarray. See
+            /// ElasticTable.memory_usage; values are stored inline, so there are no
+            /// "references". Mapped tables count the bytes of their mapping.
+            fn memory_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                self.lock(py)?.memory_usage(py)
+            }
+
+            /// Which slots of every subarray are occupied. See ElasticTable.occupancy.
+            #[pyo3(signature = (packed=false))]
+            fn occupancy(&self, py: Python<'_>, packed: bool) -> PyResult<Vec<PyObject>> {
+                self.lock(py)?.occupancy(py, packed)
+            }
+
+            /// The `(key, value)` pairs stored in subarray `i`, in slot order.
+            /// See ElasticTable.items_in_subarray.
+            fn items_in_subarray(&self, py: Python<'_>, i: usize) -> PyResult<Vec<(u64, $value)>> {
+                self.lock(py)?.items_in_subarray(i)
+            }
+
+            /// A structured version of `stats()`. See ElasticTable.detailed_stats;
+            /// with `track_distinct_values`, "distinct_values" is the estimated
+            /// number of distinct values inserted, and None without it.
+            fn detailed_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                self.lock(py)?.detailed_stats(py)
+            }
+
+            /// Check the table's internal invariants. See ElasticTable.validate.
+            fn validate(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+                Ok(self.lock(py)?.validate(py))
+            }
+}
+
+        impl $state {
+            pub(crate) fn map(&self) -> &ElasticMap<$value> {
+                &self.map
+            }
+
+            /// The value stored under `key`, without counting a lookup.
+            pub(crate) fn value_of(&self, py: Python<'_>, key: u64) -> PyResult<Option<PyObject>> {
+                self.map.get(key).map(|&value| value.into_py_any(py)).transpose()
+            }
+
+            /// Maps the snapshot at `path` read-only. With `latest`, refuses snapshots
+            /// whose checkpoints or log would be left out.
+            fn map_snapshot(
+                py: Python<'_>,
+                path: &Path,
+                latest: bool,
+                verify: bool,
+            ) -> PyResult<(ElasticMap<$value>, Backing)> {
+                if latest {
+                    let id = read_header_file(path, $kind)?.id;
+                    let logged = log_path(path).metadata().is_ok_and(|meta| meta.len() > 0);
+                    if logged || checkpoint_count(path, id)? > 0 {
+                        return Err(PyValueError::new_err(
+                            "snapshots with checkpoints or a change log cannot be mapped read-only; load them with mmap=False"
+                        ));
+                    }
+                }
+                let (mapped, region) = open_file(py, path, false)?;
+                if verify {
+                    py.allow_threads(|| verify_sections(region.bytes(), $kind))?;
+                }
+                Ok((open_map(region, $kind, false)?, Backing::File(mapped)))
+            }
+
+            fn encode<'py>(&self, py: Python<'py>, format: Format) -> PyResult<Bound<'py, PyBytes>> {
+                let map = &self.map;
+                let bytes = py.allow_threads(|| {
+                    encode(format, map.len(), map.iter().map(|(key, &value)| (key, value)))
+                })?;
+                Ok(PyBytes::new(py, &bytes))
+            }
+
+            fn decode(py: Python<'_>, format: Format, data: &[u8], load_factor: f64) -> PyResult<Self> {
+                let (keys, values) = py.allow_threads(|| decode::<$value>(format, data))?;
+                let mut table = Self::for_items(keys.len(), load_factor)?;
+                table.insert_slices(py, &keys, &values)?;
+                Ok(table)
+            }
+
+            /// The entries of `other`, a table of this class or a dict with int keys.
+            fn merge_source(other: &Bound<'_, PyAny>) -> PyResult<(Vec<u64>, Vec<$value>)> {
+                if let Ok(table) = other.downcast::<$name>() {
+                    let table = table.get().lock(other.py())?;
+                    Ok(export(table.map.len(), table.map.iter()))
+                } else {
+                    let entries = update_entries(other)?
+                        .into_iter()
+                        .map(|(key, value)| Ok((key.extract::<u64>()?, value.extract::<$value>()?)))
+                        .collect::<PyResult<Vec<_>>>()?;
+                    Ok(entries.into_iter().unzip())
+                }
+            }
+
+            fn check_writable(&self) -> PyResult<()> {
+                if self.read_only {
+                    return Err(PyTypeError::new_err("table is read-only"));
+                }
+                Ok(())
+            }
+
+            /// Bulk insert shared by `insert_many` and `from_numpy`; probes without the GIL.
+            fn insert_slices(&mut self, py: Python<'_>, keys: &[u64], values: &[$value]) -> PyResult<usize> {
+                self.check_writable()?;
+                if keys.len() != values.len() {
+                    return Err(PyValueError::new_err("keys and values must have the same length"));
+                }
+
+                for &key in keys {
+                    self.journal_key(key);
+                }
+                let mut changes = Vec::new();
+                if !self.observers.is_empty() {
+                    changes = batch_changes(keys, values.iter().copied(), |key| self.map.get(key).copied(), |&value| value);
+                }
+                let (map, distinct) = (&mut self.map, &mut self.distinct);
+                let mut inserted = 0;
+                let result = py.allow_threads(|| {
+                    let hashes = hash_keys(keys);
+                    let mut total_probes = 0;
+                    for ((&key, &hash), &value) in keys.iter().zip(&hashes).zip(values) {
+                        total_probes += map.insert_hashed(key, hash, value)?;
+                        inserted += 1;
+                    }
+                    Ok::<_, InsertError>(total_probes)
+                });
+                if let Some(sketch) = distinct {
+                    values[..inserted].iter().for_each(|value| sketch.add(u64::from_le_bytes(value.to_le_bytes())));
+                }
+                // Pairs inserted before a failure stay in the table, so they are logged too.
+                let records = keys.iter().zip(values).take(inserted);
+                self.log(records.map(|(&key, value)| Record::Insert(key, value.to_le_bytes())))?;
+                if result.is_err() {
+                    self.record_failure(keys[inserted], values[inserted])?;
+                }
+                self.report_inserts(py)?;
+                changes.truncate(inserted);
+                self.observers.notify(py, changes)?;
+                Ok(result?)
+            }
+
+            /// Removes `keys`, for `delete_many` and `delete_namespace`,
+            /// returning how many were present. Probes without the GIL.
+            fn remove_keys(&mut self, py: Python<'_>, keys: &[u64]) -> PyResult<usize> {
+                let map = &mut self.map;
+                let removed: Vec<(u64, $value)> = py.allow_threads(|| {
+                    let hashes = hash_keys(keys);
+                    keys.iter().zip(&hashes)
+                        .filter_map(|(&key, &hash)| Some((key, map.remove_hashed(key, hash)?)))
+                        .collect()
+                });
+                if let Some(journal) = &mut self.journal {
+                    for &(key, value) in &removed {
+                        journal.record(key, || Some(value));
+                    }
+                }
+                self.log(removed.iter().map(|&(key, _)| Record::Delete(key)))?;
+                self.shrink_if_sparse(py);
+                self.observers.notify(py, removed.iter().map(|&(key, value)| Change { key, old: Some(value), new: None }))?;
+                Ok(removed.len())
+            }
+
+            /// Records the current state of `key` in the journal of the open
+            /// transaction, if any, ahead of a change to it.
+            fn journal_key(&mut self, key: u64) {
+                if let Some(journal) = &mut self.journal {
+                    journal.record(key, || self.map.get(key).copied());
+                }
+            }
+
+            /// Gives up capacity after removals if auto-shrink calls for it, except
+            /// while a transaction is open. See `ElasticTable.shrink_if_sparse`.
+            fn shrink_if_sparse(&mut self, py: Python<'_>) {
+                if self.journal.is_none() {
+                    let map = &mut self.map;
+                    py.allow_threads(|| map.shrink_if_sparse());
+                }
+            }
+
+            pub(crate) fn begin_transaction(&mut self, py: Python<'_>) -> PyResult<()> {
+                self.check_writable()?;
+                if self.journal.is_some() {
+                    return Err(PyRuntimeError::new_err("the table already has an open transaction"));
+                }
+                // Inserts buffered before the transaction are not part of it.
+                self.apply_buffered(py)?;
+                self.journal = Some(Journal::new());
+                Ok(())
+            }
+
+            /// Closes the open transaction, undoing its changes unless
+            /// `commit`. The undoing is logged like any other change.
+            pub(crate) fn end_transaction(&mut self, py: Python<'_>, commit: bool) -> PyResult<()> {
+                let Some(journal) = self.journal.take() else { return Ok(()) };
+                if commit {
+                    return Ok(());
+                }
+                if let Some(buffer) = self.bulk.as_mut().and_then(Bulk::buffer) {
+                    buffer.clear();
+                }
+                self.roll_back(py, journal)
+            }
+
+            /// Returns the keys `journal` recorded to their recorded state,
+            /// logging the change and telling the observers.
+            fn roll_back(&mut self, py: Python<'_>, journal: Journal<$value>) -> PyResult<()> {
+                let (mut records, mut changes) = (Vec::new(), Vec::new());
+                journal.roll_back(&mut self.map, |key, removed, value| {
+                    records.push(Record::Delete(key));
+                    records.extend(value.map(|value| Record::Insert(key, value.to_le_bytes())));
+                    changes.push(Change { key, old: removed, new: value.copied() });
+                });
+                self.log(records)?;
+                self.observers.notify(py, changes)
+            }
+
+            /// Calls the tracer with the inserts traced since the last call and
+            /// checks the load alert, unless a `bulk()` block defers that to its end.
+            fn report_inserts(&mut self, py: Python<'_>) -> PyResult<()> {
+                if self.bulk.is_some() {
+                    return Ok(());
+                }
+                report_traces(py, &mut self.map, &self.tracer)?;
+                check_load(py, &self.map, &mut self.alert)
+            }
+
+            pub(crate) fn begin_bulk(&mut self, buffer: bool) -> PyResult<()> {
+                if self.bulk.is_some() {
+                    return Err(PyRuntimeError::new_err("the table is already in a bulk() block"));
+                }
+                self.bulk = Some(Bulk::new(buffer));
+                Ok(())
+            }
+
+            /// Inserts what the current `bulk()` block has buffered so far.
+            fn apply_buffered(&mut self, py: Python<'_>) -> PyResult<()> {
+                let Some(bulk) = &mut self.bulk else { return Ok(()) };
+                let (keys, values) = bulk.take();
+                if !keys.is_empty() {
+                    self.insert_slices(py, &keys, &values)?;
+                }
+                Ok(())
+            }
+
+            /// Ends the `bulk()` block: inserts what it buffered, makes the
+            /// tracer calls it deferred and hands the change log to the OS.
+            pub(crate) fn end_bulk(&mut self, py: Python<'_>) -> PyResult<()> {
+                let applied = self.apply_buffered(py);
+                self.bulk = None;
+                applied?;
+                self.report_inserts(py)?;
+                self.flush_logs()
+            }
+
+            /// Appends `records` to the change log, if the table keeps one.
+            fn log(&mut self, records: impl IntoIterator<Item = Record>) -> PyResult<()> {
+                if self.wal.is_none() && self.recording.is_none() {
+                    return Ok(());
+                }
+                for record in records {
+                    if let Some(wal) = &mut self.wal {
+                        wal.append(record)?;
+                    }
+                    if let Some(recording) = &mut self.recording {
+                        recording.append(record)?;
+                    }
+                }
+                // A `bulk()` block flushes once, when it ends.
+                if self.bulk.is_none() {
+                    self.flush_logs()?;
+                }
+                Ok(())
+            }
+
+            /// Hands the change log and the recording, if any, to the OS.
+            fn flush_logs(&mut self) -> PyResult<()> {
+                if let Some(wal) = &mut self.wal {
+                    wal.flush()?;
+                }
+                if let Some(recording) = &mut self.recording {
+                    recording.flush()?;
+                }
+                Ok(())
+            }
+
+            /// Records an insert that failed, which left the table unchanged,
+            /// so that a replay attempts it too.
+            fn record_failure(&mut self, key: u64, value: $value) -> PyResult<()> {
+                if let Some(recording) = &mut self.recording {
+                    recording.append(Record::Insert(key, value.to_le_bytes()))?;
+                    if self.bulk.is_none() {
+                        recording.flush()?;
+                    }
+                }
+                Ok(())
+            }
+
+            /// Re-applies a change read from the log.
+            fn apply(&mut self, record: Record) -> PyResult<()> {
+                match record {
+                    Record::Insert(key, value) => {
+                        self.map.insert(key, <$value>::from_le_bytes(value))?;
+                    }
+                    Record::Delete(key) => {
+                        self.map.remove(key);
+                    }
+                    Record::Clear => self.map.clear(),
+                }
+                Ok(())
+            }
+        }
+
+        #[doc = $frozen_doc]
+        ///
+        /// Frozen tables only offer lookups, so any number of threads can share
+        /// one without locking.
+        #[pyclass(frozen)]
+        pub(crate) struct $frozen {
+            map: FrozenMap<$value>,
+            /// Keeps the mapping or segment holding the slots alive.
+            _backing: Backing,
+        }
+
+        #[pymethods]
+        impl $frozen {
+            /// Map a snapshot written by `save` for serving lookups.
+            ///
+            /// The file is mapped read-only, so every process that opens it (for
+            /// example each worker of a pre-forking web server) shares one physical
+            /// copy through the page cache. The snapshot's checksums are checked
+            /// first, which reads the whole file; with `verify=False`, opening
+            /// instead takes milliseconds regardless of table size. Snapshots
+            /// with checkpoints or a change log are refused; load and save them
+            /// again to fold those in.
+            #[staticmethod]
+            #[pyo3(signature = (path, verify=true))]
+            fn open(py: Python<'_>, path: PathBuf, verify: bool) -> PyResult<Self> {
+                let (map, backing) = $state::map_snapshot(py, &path, true, verify)?;
+                Ok($frozen { map: FrozenMap::Elastic(Box::new(map)), _backing: backing })
+            }
+
+            /// Attach to the shared memory segment of a table created with
+            /// `create_shared`, for lookups only. The segment must not be modified
+            /// while it is attached.
+            #[staticmethod]
+            fn open_shared(py: Python<'_>, name: &str) -> PyResult<Self> {
+                let (shm, region) = open_segment(py, name)?;
+                Ok($frozen {
+                    map: FrozenMap::Elastic(Box::new(open_map(region, $kind, false)?)),
+                    _backing: Backing::SharedMemory(shm),
+                })
+            }
+
+            fn get(&self, key: u64) -> Option<$value> {
+                self.map.get(key).copied()
+            }
+
+            /// Look up many keys in a single call, using `default` for missing keys,
+            /// or the matching item of `defaults`, a sequence or array holding one
+            /// value for each key. Large batches are probed in parallel. With
+            /// `mask`, returns a `(values, found)` pair; see ElasticTable.get_many.
+            #[pyo3(signature = (keys, default=None, mask=false, defaults=None))]
+            fn get_many(&self, py: Python<'_>, keys: KeyBatch, default: Option<$value>, mask: bool, defaults: Option<Batch<$value>>) -> PyResult<PyObject> {
+                let keys_buffer = keys.is_buffer();
+                let keys = keys.as_slice(py)?;
+                let fills = Fills::new(py, default, defaults.as_ref(), keys.len())?;
+                let found = py.allow_threads(|| self.map.map_keys(&keys, |value| value.copied()));
+                let hits = mask.then(|| found.iter().map(Option::is_some).collect());
+                let values: Vec<Option<$value>> = found.into_iter().enumerate().map(|(index, value)| value.or_else(|| fills.as_ref().map(|fills| fills.at(index)))).collect();
+                lookup_result(py, values, hits, keys_buffer)
+            }
+
+            /// Check membership for many keys in a single call.
+            /// Large batches are probed in parallel.
+            fn contains_many(&self, py: Python<'_>, keys: KeyBatch) -> PyResult<Vec<bool>> {
+                let keys = keys.as_slice(py)?;
+                Ok(py.allow_threads(|| self.map.map_keys(&keys, |value| value.is_some())))
+            }
+
+            /// Export the contents as a `(keys, values)` pair of numpy arrays.
+            fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyArray1<u64>>, Bound<'py, PyArray1<$value>>)> {
+                py.import("numpy")?;
+                let (keys, values) = self.map.export();
+                Ok((keys.into_pyarray(py), values.into_pyarray(py)))
+            }
+
+            fn stats(&self) -> Vec<(usize, usize, f64)> {
+                self.map.stats()
+            }
+        }
+    };
+}
+
+typed_table!(
+    IntTable,
+    IntTableState,
+    FrozenIntTable,
+    i64,
+    1,
+    "An elastic hash table mapping u64 keys to int64 values stored in Rust.",
+    "A read-only IntTable."
+);
+typed_table!(
+    FloatTable,
+    FloatTableState,
+    FrozenFloatTable,
+    f64,
+    2,
+    "An elastic hash table mapping u64 keys to float64 values stored in Rust.",
+    "A read-only FloatTable."
+);
@@ -0,0 +1,182 @@
+//! Compact storage for the values of an `ElasticTable`.
+//!
+//! A `PyValue` is one machine word. Object references are stored as the pointer
+//! itself (always 8-byte aligned, so the low bits are zero); `None`, booleans and
+//! ints that fit in 61 bits are encoded inline in the same word with a tag in the
+//! low three bits. Inline values hold no reference, so storing millions of flags
+//! or counters costs no refcount traffic, and they become Python objects only
+//! when read back.
+//!
+//! Tables created with `intern=True` also keep an `Interner`: every other value
+//! is stored once in its pool and slots hold only its index, so millions of keys
+//! mapping to a handful of distinct values share a handful of objects.
+
+use std::mem::ManuallyDrop;
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::types::{PyBool, PyDict, PyInt, PyTuple};
+
+const TAG_BITS: u32 = 3;
+const TAG_MASK: u64 = (1 << TAG_BITS) - 1;
+
+const TAG_INT: u64 = 1;
+const TAG_NONE: u64 = 2;
+const TAG_FALSE: u64 = 3;
+const TAG_TRUE: u64 = 4;
+const TAG_INTERNED: u64 = 5;
+
+/// Inline ints are stored shifted left by `TAG_BITS`, so they must fit in 61 bits.
+const INLINE_INT_MIN: i64 = i64::MIN >> TAG_BITS;
+const INLINE_INT_MAX: i64 = i64::MAX >> TAG_BITS;
+
+pub(crate) struct PyValue(Repr);
+
+/// Either the tagged word or, when the tag is zero, an owned object reference.
+union Repr {
+    bits: u64,
+    obj: ManuallyDrop<PyObject>,
+}
+
+impl PyValue {
+    /// Encodes `value`, storing it inline when possible. Subclasses of int and
+    /// bool are kept as references so their type survives the round trip.
+    pub(crate) fn new(value: &Bound<'_, PyAny>) -> Self {
+        if value.is_none() {
+            return Self::inline(TAG_NONE);
+        }
+        if let Ok(flag) = value.downcast_exact::<PyBool>() {
+            return Self::inline(if flag.is_true() { TAG_TRUE } else { TAG_FALSE });
+        }
+        if value.is_exact_instance_of::<PyInt>() {
+            if let Ok(int) = value.extract::<i64>() {
+                if (INLINE_INT_MIN..=INLINE_INT_MAX).contains(&int) {
+                    return Self::inline(((int << TAG_BITS) as u64) | TAG_INT);
+                }
+            }
+        }
+        PyValue(Repr { obj: ManuallyDrop::new(value.clone().unbind()) })
+    }
+
+    /// Like `new`, but values that cannot be stored inline are stored in `interner`.
+    pub(crate) fn new_interned(value: &Bound<'_, PyAny>, interner: &mut Interner) -> PyResult<Self> {
+        let encoded = Self::new(value);
+        if encoded.tag() != 0 {
+            return Ok(encoded);
+        }
+        match interner.index_of(value)? {
+            Some(index) => Ok(Self::inline(((index as u64) << TAG_BITS) | TAG_INTERNED)),
+            None => Ok(encoded),
+        }
+    }
+
+    fn inline(bits: u64) -> Self {
+        PyValue(Repr { bits })
+    }
+
+    fn tag(&self) -> u64 {
+        // SAFETY: both variants are a single initialized word.
+        unsafe { self.0.bits & TAG_MASK }
+    }
+
+    /// A second handle to the same value; inline values are simply copied.
+    pub(crate) fn clone_ref(&self, py: Python<'_>) -> Self {
+        match self.as_object() {
+            Some(obj) => PyValue(Repr { obj: ManuallyDrop::new(obj.clone_ref(py)) }),
+            // SAFETY: a non-zero tag means `bits` is the live variant.
+            None => Self::inline(unsafe { self.0.bits }),
+        }
+    }
+
+    /// The object reference held by this value, if it is not encoded inline.
+    pub(crate) fn as_object(&self) -> Option<&PyObject> {
+        // SAFETY: a zero tag means `obj` is the live variant.
+        (self.tag() == 0).then(|| unsafe { &*self.0.obj })
+    }
+
+    /// Reports the object this value holds, if any, to the cyclic GC.
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        self.as_object().map_or(Ok(()), |obj| visit.call(obj))
+    }
+
+    /// Returns the stored value as a Python object. `pool` is the interner's pool,
+    /// or empty for tables that do not intern.
+    pub(crate) fn to_object(&self, py: Python<'_>, pool: &[PyObject]) -> PyObject {
+        // SAFETY: the tag identifies which variant is live.
+        unsafe {
+            match self.tag() {
+                0 => self.0.obj.clone_ref(py),
+                TAG_INT => {
+                    let int = (self.0.bits as i64) >> TAG_BITS;
+                    int.into_pyobject(py).unwrap().into_any().unbind()
+                }
+                TAG_INTERNED => pool[(self.0.bits >> TAG_BITS) as usize].clone_ref(py),
+                TAG_NONE => py.None(),
+                TAG_FALSE => PyBool::new(py, false).to_owned().into_any().unbind(),
+                _ => PyBool::new(py, true).to_owned().into_any().unbind(),
+            }
+        }
+    }
+}
+
+impl Drop for PyValue {
+    fn drop(&mut self) {
+        if self.tag() == 0 {
+            // SAFETY: a zero tag means `obj` is the live variant; it is dropped once.
+            // `Py`'s own drop defers the decref if the GIL is not held.
+            unsafe { ManuallyDrop::drop(&mut self.0.obj) };
+        }
+    }
+}
+
+/// Pool of distinct values shared by the slots of an interning table.
+///
+/// Values are matched by type and equality, so `2**70` and `2.0**70` stay
+/// distinct. Entries are never evicted while the table is in use; `clear()`
+/// on the table releases the whole pool.
+pub(crate) struct Interner {
+    lookup: Py<PyDict>,
+    values: Vec<PyObject>,
+}
+
+impl Interner {
+    pub(crate) fn new(py: Python<'_>) -> Self {
+        Interner {
+            lookup: PyDict::new(py).unbind(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Index of `value` in the pool, adding it if new. Returns `None` for
+    /// unhashable values, which the caller stores as a plain reference.
+    fn index_of(&mut self, value: &Bound<'_, PyAny>) -> PyResult<Option<usize>> {
+        let py = value.py();
+        let key = PyTuple::new(py, [value.get_type().into_any(), value.clone()])?;
+        if key.hash().is_err() {
+            return Ok(None);
+        }
+        let lookup = self.lookup.bind(py);
+        if let Some(index) = lookup.get_item(&key)? {
+            return Ok(Some(index.extract()?));
+        }
+        let index = self.values.len();
+        lookup.set_item(key, index)?;
+        self.values.push(value.clone().unbind());
+        Ok(Some(index))
+    }
+
+    pub(crate) fn values(&self) -> &[PyObject] {
+        &self.values
+    }
+
+    /// Reports the pool to the cyclic GC.
+    pub(crate) fn traverse(&self, visit: &PyVisit<'_>) -> Result<(), PyTraverseError> {
+        visit.call(&self.lookup)?;
+        self.values.iter().try_for_each(|value| visit.call(value))
+    }
+
+    /// Number of distinct values in the pool.
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+}
@@ -0,0 +1,194 @@
+//! Live views of a table's keys, values and items, like a dict's.
+//!
+//! A view holds the table rather than a copy of its entries, so it reflects
+//! every later change, and iterating over it walks the table in place (see
+//! `iter`). Key and item views are set-like: `&`, `|`, `-` and `^` accept any
+//! iterable and return a `set`, and comparisons treat the view as the set of
+//! its elements. The views are registered with `collections.abc`, so
+//! `isinstance(table.keys(), collections.abc.KeysView)` holds.
+
+use pyo3::prelude::*;
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::basic::CompareOp;
+use pyo3::types::{PyAny, PyList, PySet, PyTuple};
+
+use crate::iter::{TableIterator, ViewedTable, Yield};
+
+/// `repr` of a view: its class name around the list of its elements.
+fn view_repr(view: &Bound<'_, PyAny>, name: &str) -> PyResult<String> {
+    let elements = view.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+    Ok(format!("{name}({})", PyList::new(view.py(), elements)?.repr()?))
+}
+
+/// The elements of a view as a new set.
+fn as_set<'py>(view: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PySet>> {
+    let set = PySet::empty(view.py())?;
+    for element in view.try_iter()? {
+        set.add(element?)?;
+    }
+    Ok(set)
+}
+
+/// Whether a key view of `table` contains `key`; anything but a u64 is not a key.
+fn has_key(table: &ViewedTable, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+    let Ok(key) = key.extract::<u64>() else { return Ok(false) };
+    Ok(table.value(py, key)?.is_some())
+}
+
+/// Whether an item view of `table` contains `item`, a `(key, value)` pair
+/// whose value compares equal to the one stored under the key.
+fn has_item(table: &ViewedTable, py: Python<'_>, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+    let Ok(item) = item.downcast::<PyTuple>() else { return Ok(false) };
+    if item.len() != 2 {
+        return Ok(false);
+    }
+    let Ok(key) = item.get_item(0)?.extract::<u64>() else { return Ok(false) };
+    match table.value(py, key)? {
+        Some(value) => value.bind(py).eq(item.get_item(1)?),
+        None => Ok(false),
+    }
+}
+
+macro_rules! set_view {
+    ($name:ident, $yields:expr, $contains:ident, $doc:literal) => {
+        #[doc = $doc]
+        #[pyclass]
+        pub(crate) struct $name {
+            table: ViewedTable,
+        }
+
+        impl $name {
+            pub(crate) fn new(table: impl Into<ViewedTable>) -> Self {
+                $name { table: table.into() }
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            fn __len__(&self, py: Python<'_>) -> PyResult<usize> {
+                self.table.len(py)
+            }
+
+            fn __iter__(&self, py: Python<'_>) -> PyResult<TableIterator> {
+                self.table.iter(py, $yields)
+            }
+
+            fn __contains__(&self, py: Python<'_>, element: &Bound<'_, PyAny>) -> PyResult<bool> {
+                $contains(&self.table, py, element)
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+                view_repr(slf.as_any(), stringify!($name))
+            }
+
+            /// Compares as the set of the view's elements, like a dict view.
+            fn __richcmp__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>, op: CompareOp) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.rich_compare(other, op)
+            }
+
+            fn __and__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("intersection", (other,))
+            }
+
+            fn __rand__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("intersection", (other,))
+            }
+
+            fn __or__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("union", (other,))
+            }
+
+            fn __ror__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("union", (other,))
+            }
+
+            fn __sub__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("difference", (other,))
+            }
+
+            fn __rsub__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(other)?.call_method1("difference", (slf,))
+            }
+
+            fn __xor__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("symmetric_difference", (other,))
+            }
+
+            fn __rxor__<'py>(slf: &Bound<'py, Self>, other: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+                as_set(slf.as_any())?.call_method1("symmetric_difference", (other,))
+            }
+
+            /// Whether the view and `other` have no element in common.
+            fn isdisjoint(&self, py: Python<'_>, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+                for element in other.try_iter()? {
+                    if $contains(&self.table, py, &element?)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+
+            fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+                self.table.traverse(&visit)
+            }
+        }
+    };
+}
+
+set_view!(KeysView, Yield::Keys, has_key, "Returned by `table.keys()`: a live, set-like view of the keys.");
+set_view!(
+    ItemsView,
+    Yield::Items,
+    has_item,
+    "Returned by `table.items()`: a live, set-like view of the `(key, value)` pairs."
+);
+
+/// Returned by `table.values()`: a live view of the values. Like a dict's,
+/// it is not set-like, since values need not be unique or hashable.
+#[pyclass]
+pub(crate) struct ValuesView {
+    table: ViewedTable,
+}
+
+impl ValuesView {
+    pub(crate) fn new(table: impl Into<ViewedTable>) -> Self {
+        ValuesView { table: table.into() }
+    }
+}
+
+#[pymethods]
+impl ValuesView {
+    fn __len__(&self, py: Python<'_>) -> PyResult<usize> {
+        self.table.len(py)
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<TableIterator> {
+        self.table.iter(py, Yield::Values)
+    }
+
+    fn __contains__(slf: &Bound<'_, Self>, value: &Bound<'_, PyAny>) -> PyResult<bool> {
+        for element in slf.try_iter()? {
+            if element?.eq(value)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        view_repr(slf.as_any(), "ValuesView")
+    }
+
+    fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        self.table.traverse(&visit)
+    }
+}
+
+/// Registers the views as virtual subclasses of the `collections.abc` views.
+pub(crate) fn register_abcs(py: Python<'_>) -> PyResult<()> {
+    let abc = py.import("collections.abc")?;
+    abc.getattr("KeysView")?.call_method1("register", (py.get_type::<KeysView>(),))?;
+    abc.getattr("ItemsView")?.call_method1("register", (py.get_type::<ItemsView>(),))?;
+    abc.getattr("ValuesView")?.call_method1("register", (py.get_type::<ValuesView>(),))?;
+    Ok(())
+}
@@ -0,0 +1,361 @@
+//! Append-only change log kept next to a typed table snapshot.
+//!
+//! Every insert, delete and clear of a logged table is appended to
+//! `<snapshot>.wal` as a fixed-size record protected by a CRC-32:
+//!
+//! ```text
+//! | op (1) | key (8) | value (8) | crc32 of the first 17 bytes (4) |
+//! ```
+//!
+//! The records follow a small header naming the snapshot the log belongs to:
+//!
+//! ```text
+//! | magic (8) | snapshot id (8) | crc32 of the above (4) | 0 (4) | records |
+//! ```
+//!
+//! Records are handed to the OS at the end of every call and forced to disk by
+//! `commit()`. Loading the snapshot replays the log in order and stops at the
+//! first incomplete or corrupted record, which is where a crash cut it off.
+//! Replaying is idempotent, so a log that is only partly reflected in the
+//! snapshot is still applied correctly. A log whose header names another
+//! snapshot is ignored: saving renames the new snapshot into place before it
+//! removes the old log, so a crash in between leaves a log of changes the new
+//! snapshot already holds, or that were discarded with the old one.
+//!
+//! `checkpoint()` folds the log into a numbered checkpoint file next to the
+//! snapshot and starts the log afresh. Checkpoint `n` is stored in
+//! `<snapshot>.v<n>` and holds one record per key changed since checkpoint
+//! `n - 1`, after a small header:
+//!
+//! ```text
+//! | magic (8) | snapshot id (8) | version (8) | record count (8) | crc32 of the above (4) | 0 (4) | records |
+//! ```
+//!
+//! The snapshot id ties checkpoints to the snapshot they were taken on, so
+//! files left over from an older snapshot at the same path are ignored.
+//! Checkpoints are written to a temporary file and renamed into place, so any
+//! other damage means the file was corrupted and is reported instead of being
+//! skipped.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+
+use crate::errors::CorruptedDataError;
+use crate::storage::crc32;
+
+const RECORD_LEN: usize = 21;
+
+/// Identifies a change log.
+const LOG_MAGIC: [u8; 8] = *b"ELHLOG\0\0";
+const LOG_HEADER_LEN: usize = 24;
+
+/// Identifies a checkpoint file.
+const CHECKPOINT_MAGIC: [u8; 8] = *b"ELHDELTA";
+const CHECKPOINT_HEADER_LEN: usize = 40;
+
+const OP_INSERT: u8 = 1;
+const OP_DELETE: u8 = 2;
+const OP_CLEAR: u8 = 3;
+
+/// One logged change. Values are stored as little-endian bytes.
+#[derive(Clone, Copy)]
+pub(crate) enum Record {
+    Insert(u64, [u8; 8]),
+    Delete(u64),
+    Clear,
+}
+
+impl Record {
+    pub(crate) fn encode(&self) -> [u8; RECORD_LEN] {
+        let (op, key, value) = match *self {
+            Record::Insert(key, value) => (OP_INSERT, key, value),
+            Record::Delete(key) => (OP_DELETE, key, [0; 8]),
+            Record::Clear => (OP_CLEAR, 0, [0; 8]),
+        };
+        let mut bytes = [0; RECORD_LEN];
+        bytes[0] = op;
+        bytes[1..9].copy_from_slice(&key.to_le_bytes());
+        bytes[9..17].copy_from_slice(&value);
+        let crc = crc32(0, &bytes[..17]);
+        bytes[17..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RECORD_LEN]) -> Option<Record> {
+        if crc32(0, &bytes[..17]).to_le_bytes() != bytes[17..] {
+            return None;
+        }
+        let key = u64::from_le_bytes(bytes[1..9].try_into().unwrap());
+        match bytes[0] {
+            OP_INSERT => Some(Record::Insert(key, bytes[9..17].try_into().unwrap())),
+            OP_DELETE => Some(Record::Delete(key)),
+            OP_CLEAR => Some(Record::Clear),
+            _ => None,
+        }
+    }
+}
+
+/// Path of the log belonging to `snapshot`.
+pub(crate) fn log_path(snapshot: &Path) -> PathBuf {
+    with_suffix(snapshot, ".wal")
+}
+
+/// Path of checkpoint `version` of `snapshot`.
+fn checkpoint_path(snapshot: &Path, version: u64) -> PathBuf {
+    with_suffix(snapshot, &format!(".v{version}"))
+}
+
+fn with_suffix(snapshot: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(snapshot.as_os_str());
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Reads the records of checkpoint `version` of the snapshot with id `id`.
+/// Returns `None` if the checkpoint is missing or belongs to another snapshot.
+fn read_checkpoint(snapshot: &Path, id: u64, version: u64) -> PyResult<Option<Vec<Record>>> {
+    let bytes = match fs::read(checkpoint_path(snapshot, version)) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let corrupted = || CorruptedDataError::new_err(format!("checkpoint {version} is corrupted"));
+    let crc_at = CHECKPOINT_HEADER_LEN - 8;
+    if bytes.len() < CHECKPOINT_HEADER_LEN
+        || bytes[..8] != CHECKPOINT_MAGIC
+        || crc32(0, &bytes[..crc_at]).to_le_bytes() != bytes[crc_at..crc_at + 4]
+    {
+        return Err(corrupted());
+    }
+    let word = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+    if word(8) != id {
+        return Ok(None);
+    }
+    let (body, count) = (&bytes[CHECKPOINT_HEADER_LEN..], word(24));
+    if word(16) != version || body.len() as u64 != count * RECORD_LEN as u64 {
+        return Err(corrupted());
+    }
+    let records = body.chunks_exact(RECORD_LEN).map(|chunk| Record::decode(chunk.try_into().unwrap()));
+    records.collect::<Option<_>>().map(Some).ok_or_else(corrupted)
+}
+
+/// Number of checkpoints taken on the snapshot with id `id`: checkpoints
+/// `1..=n` are all present.
+pub(crate) fn checkpoint_count(snapshot: &Path, id: u64) -> PyResult<u64> {
+    let mut count = 0;
+    while read_checkpoint(snapshot, id, count + 1)?.is_some() {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Applies checkpoints `1..=version` of the snapshot with id `id` in order,
+/// or every checkpoint if `version` is `None`. Returns the last one applied.
+pub(crate) fn apply_checkpoints(
+    snapshot: &Path,
+    id: u64,
+    version: Option<u64>,
+    mut apply: impl FnMut(Record) -> PyResult<()>,
+) -> PyResult<u64> {
+    let mut applied = 0;
+    while version.is_none_or(|version| applied < version) {
+        let Some(records) = read_checkpoint(snapshot, id, applied + 1)? else { break };
+        for record in records {
+            apply(record)?;
+        }
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+/// Removes the log and checkpoints of whatever snapshot was last saved at
+/// `snapshot`, before a new one replaces it.
+pub(crate) fn discard_history(snapshot: &Path) -> io::Result<()> {
+    let remove = |path: PathBuf| match fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        result => result.map(|()| true),
+    };
+    remove(log_path(snapshot))?;
+    let mut version = 1;
+    while remove(checkpoint_path(snapshot, version))? {
+        version += 1;
+    }
+    Ok(())
+}
+
+/// Header of a log belonging to the snapshot with id `id`.
+fn log_header(id: u64) -> [u8; LOG_HEADER_LEN] {
+    let mut header = [0; LOG_HEADER_LEN];
+    header[..8].copy_from_slice(&LOG_MAGIC);
+    header[8..16].copy_from_slice(&id.to_le_bytes());
+    let crc = crc32(0, &header[..16]);
+    header[16..20].copy_from_slice(&crc.to_le_bytes());
+    header
+}
+
+/// Applies the valid records of the log at `path` in order, if it exists and
+/// belongs to the snapshot with id `id`. Returns the length of the valid
+/// prefix in bytes, header included, or 0 if the log was ignored.
+pub(crate) fn replay<E: From<io::Error>>(
+    path: &Path,
+    id: u64,
+    apply: impl FnMut(Record) -> Result<(), E>,
+) -> Result<u64, E> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err.into()),
+    };
+    let mut reader = BufReader::new(file);
+    let mut header = [0; LOG_HEADER_LEN];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(0),
+        Err(err) => return Err(err.into()),
+    }
+    // A torn header is only left by a crash while the log was being started,
+    // before any record reached it.
+    if header != log_header(id) {
+        return Ok(0);
+    }
+    Ok(LOG_HEADER_LEN as u64 + replay_from(reader, apply)?)
+}
+
+/// Applies the valid records read from `reader`, in order, like `replay`.
+pub(crate) fn replay_from<E: From<io::Error>>(mut reader: impl Read, mut apply: impl FnMut(Record) -> Result<(), E>) -> Result<u64, E> {
+    let mut valid = 0;
+    let mut bytes = [0; RECORD_LEN];
+    loop {
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let Some(record) = Record::decode(&bytes) else { break };
+        apply(record)?;
+        valid += RECORD_LEN as u64;
+    }
+    Ok(valid)
+}
+
+/// An open log that records are appended to.
+pub(crate) struct Wal {
+    out: BufWriter<File>,
+    snapshot: PathBuf,
+    /// Id of the snapshot the log belongs to.
+    id: u64,
+    /// Number of checkpoints taken on the snapshot so far.
+    version: u64,
+}
+
+impl Wal {
+    /// Starts an empty log for the snapshot at `snapshot` with id `id`,
+    /// replacing any existing one.
+    pub(crate) fn create(snapshot: PathBuf, id: u64) -> io::Result<Wal> {
+        Self::resume(snapshot, id, 0, 0)
+    }
+
+    /// Continues the log of `snapshot` after its first `valid` bytes, as
+    /// returned by `replay`, dropping any torn tail left by a crash. A log
+    /// with no valid bytes is started afresh. `version` checkpoints have been
+    /// taken.
+    pub(crate) fn resume(snapshot: PathBuf, id: u64, version: u64, valid: u64) -> io::Result<Wal> {
+        // Append mode keeps writes at the end even after `reset` truncates the file.
+        let file = OpenOptions::new().create(true).append(true).open(log_path(&snapshot))?;
+        file.set_len(valid)?;
+        let mut out = BufWriter::new(file);
+        if valid == 0 {
+            out.write_all(&log_header(id))?;
+            out.flush()?;
+        }
+        Ok(Wal { out, snapshot, id, version })
+    }
+
+    /// Path of the snapshot the log belongs to.
+    pub(crate) fn snapshot(&self) -> &Path {
+        &self.snapshot
+    }
+
+    pub(crate) fn append(&mut self, record: Record) -> io::Result<()> {
+        self.out.write_all(&record.encode())
+    }
+
+    /// Hands buffered records to the OS, so they survive the process crashing.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Forces every record to disk, so they survive the machine crashing.
+    pub(crate) fn commit(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.out.get_ref().sync_data()
+    }
+
+    /// Writes the changes logged since the last checkpoint to the next
+    /// checkpoint file and empties the log. Returns the new checkpoint's version.
+    pub(crate) fn checkpoint(&mut self) -> io::Result<u64> {
+        self.out.flush()?;
+        // Only the last change to each key matters, unless a clear came after it.
+        let mut cleared = false;
+        let mut changes = BTreeMap::new();
+        replay(&log_path(&self.snapshot), self.id, |record| {
+            match record {
+                Record::Insert(key, value) => {
+                    changes.insert(key, Some(value));
+                }
+                Record::Delete(key) => {
+                    changes.insert(key, None);
+                }
+                Record::Clear => {
+                    cleared = true;
+                    changes.clear();
+                }
+            }
+            Ok::<_, io::Error>(())
+        })?;
+        let records = cleared.then_some(Record::Clear).into_iter().chain(
+            changes.into_iter().map(|(key, value)| match value {
+                Some(value) => Record::Insert(key, value),
+                None => Record::Delete(key),
+            }),
+        );
+
+        let version = self.version + 1;
+        let path = checkpoint_path(&self.snapshot, version);
+        let tmp = with_suffix(&path, ".tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        let mut body = Vec::new();
+        for record in records {
+            body.extend_from_slice(&record.encode());
+        }
+        let mut header = Vec::with_capacity(CHECKPOINT_HEADER_LEN);
+        header.extend_from_slice(&CHECKPOINT_MAGIC);
+        for word in [self.id, version, (body.len() / RECORD_LEN) as u64] {
+            header.extend_from_slice(&word.to_le_bytes());
+        }
+        let crc = crc32(0, &header);
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.resize(CHECKPOINT_HEADER_LEN, 0);
+        out.write_all(&header)?;
+        out.write_all(&body)?;
+        out.into_inner()?.sync_all()?;
+        fs::rename(&tmp, &path)?;
+
+        self.version = version;
+        self.reset()?;
+        Ok(version)
+    }
+
+    /// Discards every record, after their changes reached a new snapshot.
+    fn reset(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        let file = self.out.get_mut();
+        file.set_len(LOG_HEADER_LEN as u64)?;
+        file.sync_data()
+    }
+}
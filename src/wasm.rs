@@ -0,0 +1,106 @@
+//! JavaScript bindings for the typed tables, behind the `wasm` feature.
+//!
+//! Built for `wasm32-unknown-unknown` with `wasm-bindgen`, the module exports
+//! `IntTable` and `FloatTable` classes over the same maps as the Python
+//! classes of those names. Their methods follow `Map` where they overlap
+//! (`get`, `has`, `delete`, `size`, `clear`). Keys are `BigInt`s, as are the
+//! values of an `IntTable`; batch methods take and return `BigUint64Array`s
+//! and typed arrays of the values, so large lookup tables load without a
+//! call per entry.
+
+use wasm_bindgen::prelude::*;
+
+use crate::map::ElasticMap;
+
+/// Validates `delta`, which defaults to 0.05.
+fn delta_or_default(delta: Option<f64>) -> Result<f64, JsError> {
+    let delta = delta.unwrap_or(0.05);
+    if !(delta > 0.0 && delta < 1.0) {
+        return Err(JsError::new("delta must be between 0 and 1"));
+    }
+    Ok(delta)
+}
+
+macro_rules! wasm_table {
+    ($name:ident, $js_name:literal, $value:ty) => {
+        #[wasm_bindgen(js_name = $js_name)]
+        pub struct $name {
+            map: ElasticMap<$value>,
+        }
+
+        #[wasm_bindgen(js_class = $js_name)]
+        impl $name {
+            /// Creates a table with `capacity` slots and elasticity `delta`
+            /// (default 0.05), which holds up to `(1 - delta) * capacity` entries.
+            #[wasm_bindgen(constructor)]
+            pub fn new(capacity: usize, delta: Option<f64>) -> Result<$name, JsError> {
+                Ok($name { map: ElasticMap::new(capacity, delta_or_default(delta)?) })
+            }
+
+            /// Creates a table sized for `expectedItems` at `loadFactor`
+            /// (default 0.90).
+            #[wasm_bindgen(js_name = forItems)]
+            pub fn for_items(expected_items: usize, load_factor: Option<f64>) -> Result<$name, JsError> {
+                let load_factor = load_factor.unwrap_or(0.90);
+                if load_factor <= 0.5 || load_factor >= 0.99 {
+                    return Err(JsError::new("load_factor must be between 0.5 and 0.99"));
+                }
+                let capacity = (expected_items as f64 / load_factor).ceil() as usize;
+                Self::new(capacity, Some(1.0 - load_factor))
+            }
+
+            /// Inserts or updates `key`. Throws if a new key cannot be placed.
+            pub fn insert(&mut self, key: u64, value: $value) -> Result<(), JsError> {
+                self.map.insert(key, value).map(|_| ()).map_err(|err| JsError::new(&err.to_string()))
+            }
+
+            /// Inserts `keys[i]` with `values[i]` for every `i`. Throws at the
+            /// first key that cannot be placed, keeping the ones before it.
+            #[wasm_bindgen(js_name = insertMany)]
+            pub fn insert_many(&mut self, keys: &[u64], values: &[$value]) -> Result<(), JsError> {
+                if keys.len() != values.len() {
+                    return Err(JsError::new("keys and values must have the same length"));
+                }
+                keys.iter().zip(values).try_for_each(|(&key, &value)| self.insert(key, value))
+            }
+
+            pub fn get(&self, key: u64) -> Option<$value> {
+                self.map.get(key).copied()
+            }
+
+            /// The value of every key of `keys`, or `missing` for the keys
+            /// that are not stored.
+            #[wasm_bindgen(js_name = getMany)]
+            pub fn get_many(&self, keys: &[u64], missing: $value) -> Vec<$value> {
+                keys.iter().map(|&key| self.map.get(key).copied().unwrap_or(missing)).collect()
+            }
+
+            pub fn has(&self, key: u64) -> bool {
+                self.map.get(key).is_some()
+            }
+
+            /// Removes `key`, returning whether it was stored.
+            pub fn delete(&mut self, key: u64) -> bool {
+                self.map.remove(key).is_some()
+            }
+
+            pub fn clear(&mut self) {
+                self.map.clear();
+            }
+
+            #[wasm_bindgen(getter)]
+            pub fn size(&self) -> usize {
+                self.map.len()
+            }
+
+            /// Number of slots the table was created with.
+            #[wasm_bindgen(getter)]
+            pub fn capacity(&self) -> usize {
+                self.map.capacity()
+            }
+        }
+    };
+}
+
+wasm_table!(WasmIntTable, "IntTable", i64);
+wasm_table!(WasmFloatTable, "FloatTable", f64);
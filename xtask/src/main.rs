@@ -0,0 +1,35 @@
+//! Maintenance tasks for the repository, run with `cargo run -p xtask -- <task>`.
+//!
+//! - `stubs` regenerates the type stub `rb_elastic_hash.pyi`.
+//! - `stubs --check` fails if the checked-in stub is out of date, without
+//!   writing it.
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+mod stubs;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().expect("xtask/ sits in the repository");
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["stubs"] => {
+            let path = stubs::path(root);
+            fs::write(&path, stubs::render_stub(root)).unwrap_or_else(|err| panic!("cannot write {}: {err}", path.display()));
+            ExitCode::SUCCESS
+        }
+        ["stubs", "--check"] => {
+            let path = stubs::path(root);
+            if fs::read_to_string(&path).ok() == Some(stubs::render_stub(root)) {
+                return ExitCode::SUCCESS;
+            }
+            eprintln!("{} is out of date; run `cargo run -p xtask -- stubs`", path.display());
+            ExitCode::FAILURE
+        }
+        _ => {
+            eprintln!("usage: cargo run -p xtask -- stubs [--check]");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,645 @@
+//! Generates the type stub `rb_elastic_hash.pyi` from the `#[pymethods]`
+//! blocks in `src/`, so IDEs and type checkers know every class's methods.
+//! maturin puts a stub named after the module that sits next to Cargo.toml
+//! into the wheel, together with a `py.typed` marker.
+//!
+//! Signatures come from `#[pyo3(signature = ...)]` where one is given and
+//! from the Rust parameters otherwise. Rust types map to the Python types
+//! PyO3 converts them from and to; anything else is typed `Any`. Tables
+//! defined by a `macro_rules!` macro are found by expanding its invocations.
+//! Items behind `#[cfg]` are kept if they are built with the crate's default
+//! features, which are the ones the wheel is built with, on this host.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Group, TokenStream, TokenTree};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, FnArg, GenericArgument, ImplItem, ImplItemFn, Item, Lit, Meta, Pat, PathArguments, ReturnType, Token, Type};
+
+/// The module name, which is also the stub's file name.
+const MODULE: &str = "rb_elastic_hash";
+
+/// Methods PyO3 uses for garbage collection, which Python code never calls.
+const HIDDEN: &[&str] = &["__traverse__", "__clear__"];
+
+/// A single-arm `macro_rules!` macro: the names of its `$` parameters and its body.
+struct Macro {
+    params: Vec<String>,
+    body: TokenStream,
+}
+
+#[derive(Default)]
+struct Class {
+    doc: Vec<String>,
+    methods: Vec<Method>,
+}
+
+#[derive(PartialEq)]
+enum Kind {
+    Instance,
+    Static,
+    Class,
+    New,
+    Getter,
+    Setter,
+}
+
+struct Method {
+    name: String,
+    kind: Kind,
+    params: Vec<Param>,
+    ret: Option<Type>,
+    doc: Vec<String>,
+}
+
+/// A parameter, or the bare `*` and `/` markers, which have no type.
+struct Param {
+    text: String,
+    ty: Option<Type>,
+    default: Option<String>,
+}
+
+struct Exception {
+    name: String,
+    base: String,
+    doc: String,
+}
+
+#[derive(Default)]
+struct Module {
+    classes: BTreeMap<String, Class>,
+    exceptions: Vec<Exception>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Position {
+    Argument,
+    Return,
+}
+
+/// The stub file of the crate whose Cargo.toml is in `root`.
+pub fn path(root: &Path) -> PathBuf {
+    root.join(format!("{MODULE}.pyi"))
+}
+
+/// Renders the stub of the crate whose Cargo.toml is in `root`.
+pub fn render_stub(root: &Path) -> String {
+    let cfg = Cfg::for_crate(root);
+    let src = root.join("src");
+    let mut files: Vec<_> = fs::read_dir(&src)
+        .expect("cannot list src/")
+        .map(|entry| entry.expect("cannot list src/").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "rs"))
+        .collect();
+    files.sort();
+
+    let mut module = Module::default();
+    for path in &files {
+        let source = fs::read_to_string(path).unwrap_or_else(|err| panic!("cannot read {}: {err}", path.display()));
+        let file = syn::parse_file(&source).unwrap_or_else(|err| panic!("cannot parse {}: {err}", path.display()));
+        collect(file.items, &mut HashMap::new(), &mut module, &cfg);
+    }
+    let lib = fs::read_to_string(src.join("lib.rs")).expect("cannot read src/lib.rs");
+    let registered: Vec<&str> = lib.split("add_class::<").skip(1).filter_map(|rest| rest.split('>').next()).collect();
+    render(&module, &registered)
+}
+
+/// What `#[cfg]` predicates are evaluated against: the crate's default
+/// features and the host's target.
+struct Cfg {
+    features: Vec<String>,
+}
+
+impl Cfg {
+    /// Reads the `default` list of the `[features]` table of `root/Cargo.toml`,
+    /// following features that enable other features.
+    fn for_crate(root: &Path) -> Self {
+        let manifest = fs::read_to_string(root.join("Cargo.toml")).expect("cannot read Cargo.toml");
+        let mut table = HashMap::new();
+        let mut in_features = false;
+        for line in manifest.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_features = line == "[features]";
+            } else if let (true, false, Some((name, list))) = (in_features, line.starts_with('#'), line.split_once('=')) {
+                let items: Vec<String> = list.trim().trim_start_matches('[').trim_end_matches(']')
+                    .split(',')
+                    .map(|item| item.trim().trim_matches('"').to_string())
+                    .filter(|item| !item.is_empty() && !item.contains(':') && !item.contains('/'))
+                    .collect();
+                table.insert(name.trim().to_string(), items);
+            }
+        }
+        let mut features = Vec::new();
+        let mut pending = table.get("default").cloned().unwrap_or_default();
+        while let Some(feature) = pending.pop() {
+            if !features.contains(&feature) {
+                pending.extend(table.get(&feature).cloned().unwrap_or_default());
+                features.push(feature);
+            }
+        }
+        Cfg { features }
+    }
+
+    /// Whether every `#[cfg]` on an item holds.
+    fn enabled(&self, attrs: &[Attribute]) -> bool {
+        attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .all(|attr| attr.parse_args::<Meta>().map_or(true, |meta| self.holds(&meta)))
+    }
+
+    fn holds(&self, meta: &Meta) -> bool {
+        match meta {
+            Meta::Path(path) => path.get_ident().is_some_and(|ident| ident == std::env::consts::FAMILY),
+            Meta::NameValue(pair) => {
+                let Expr::Lit(syn::ExprLit { lit: Lit::Str(value), .. }) = &pair.value else { return true };
+                let Some(key) = pair.path.get_ident() else { return true };
+                let value = value.value();
+                match key.to_string().as_str() {
+                    "feature" => self.features.contains(&value),
+                    "target_os" => value == std::env::consts::OS,
+                    "target_arch" => value == std::env::consts::ARCH,
+                    "target_family" => value == std::env::consts::FAMILY,
+                    _ => false,
+                }
+            }
+            Meta::List(list) => {
+                let Ok(nested) = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated) else { return true };
+                if list.path.is_ident("not") {
+                    !nested.iter().all(|meta| self.holds(meta))
+                } else if list.path.is_ident("any") {
+                    nested.iter().any(|meta| self.holds(meta))
+                } else {
+                    nested.iter().all(|meta| self.holds(meta))
+                }
+            }
+        }
+    }
+}
+
+fn collect(items: Vec<Item>, macros: &mut HashMap<String, Macro>, module: &mut Module, cfg: &Cfg) {
+    for item in items {
+        match item {
+            Item::Struct(item) if has_attr(&item.attrs, "pyclass") && cfg.enabled(&item.attrs) => {
+                module.classes.entry(item.ident.to_string()).or_default().doc = doc_lines(&item.attrs);
+            }
+            Item::Impl(item) if has_attr(&item.attrs, "pymethods") && cfg.enabled(&item.attrs) => {
+                let Type::Path(self_ty) = &*item.self_ty else { continue };
+                let Some(name) = self_ty.path.segments.last().map(|segment| segment.ident.to_string()) else { continue };
+                let methods = item.items.iter().filter_map(|item| match item {
+                    ImplItem::Fn(function) => method(function, cfg),
+                    _ => None,
+                });
+                module.classes.entry(name).or_default().methods.extend(methods);
+            }
+            Item::Macro(item) => {
+                let mac = &item.mac;
+                if mac.path.is_ident("macro_rules") {
+                    if let (Some(ident), Some(rules)) = (&item.ident, single_rule(mac.tokens.clone())) {
+                        macros.insert(ident.to_string(), rules);
+                    }
+                } else if mac.path.is_ident("create_exception") {
+                    let args = split_commas(mac.tokens.clone());
+                    if let [_, name, base, rest @ ..] = args.as_slice() {
+                        let doc = rest.first().and_then(|doc| syn::parse2::<syn::LitStr>(doc.clone()).ok());
+                        module.exceptions.push(Exception {
+                            name: name.to_string(),
+                            base: base.to_string().trim_start_matches("Py").to_string(),
+                            doc: doc.map(|doc| doc.value()).unwrap_or_default(),
+                        });
+                    }
+                } else if let Some(rules) = mac.path.get_ident().and_then(|ident| macros.get(&ident.to_string())) {
+                    let bindings: HashMap<_, _> = rules.params.iter().cloned().zip(split_commas(mac.tokens.clone())).collect();
+                    if let Ok(expanded) = syn::parse2::<syn::File>(substitute(rules.body.clone(), &bindings)) {
+                        collect(expanded.items, macros, module, cfg);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads a `macro_rules!` body of the form `($a:ident, $b:ty) => { ... }`.
+fn single_rule(tokens: TokenStream) -> Option<Macro> {
+    let mut tokens = tokens.into_iter();
+    let Some(TokenTree::Group(pattern)) = tokens.next() else { return None };
+    let body = tokens.find_map(|token| match token {
+        TokenTree::Group(group) => Some(group.stream()),
+        _ => None,
+    })?;
+    let mut params = Vec::new();
+    let mut pattern = pattern.stream().into_iter();
+    while let Some(token) = pattern.next() {
+        if matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '$') {
+            if let Some(TokenTree::Ident(ident)) = pattern.next() {
+                params.push(ident.to_string());
+            }
+        }
+    }
+    Some(Macro { params, body })
+}
+
+/// Replaces every `$name` in `body` with the tokens bound to `name`.
+fn substitute(body: TokenStream, bindings: &HashMap<String, TokenStream>) -> TokenStream {
+    let mut out = TokenStream::new();
+    let mut tokens = body.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == '$' => {
+                let bound = match tokens.peek() {
+                    Some(TokenTree::Ident(ident)) => bindings.get(&ident.to_string()),
+                    _ => None,
+                };
+                match bound {
+                    Some(bound) => {
+                        out.extend(bound.clone());
+                        tokens.next();
+                    }
+                    None => out.extend([token]),
+                }
+            }
+            TokenTree::Group(group) => {
+                let mut replaced = Group::new(group.delimiter(), substitute(group.stream(), bindings));
+                replaced.set_span(group.span());
+                out.extend([TokenTree::Group(replaced)]);
+            }
+            other => out.extend([other]),
+        }
+    }
+    out
+}
+
+/// Splits a token stream at its top-level commas.
+fn split_commas(tokens: TokenStream) -> Vec<TokenStream> {
+    let mut parts = vec![TokenStream::new()];
+    for token in tokens {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => parts.push(TokenStream::new()),
+            _ => parts.last_mut().expect("never empty").extend([token]),
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(name))
+}
+
+/// The lines of an item's doc comment.
+fn doc_lines(attrs: &[Attribute]) -> Vec<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(pair) => match &pair.value {
+                Expr::Lit(syn::ExprLit { lit: Lit::Str(text), .. }) => Some(text.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .flat_map(|text| text.lines().map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end().to_string()).collect::<Vec<_>>())
+        .collect();
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |end| end + 1);
+    lines[start..end].to_vec()
+}
+
+fn method(function: &ImplItemFn, cfg: &Cfg) -> Option<Method> {
+    let attrs = &function.attrs;
+    let mut name = function.sig.ident.to_string();
+    if HIDDEN.contains(&name.as_str()) || !cfg.enabled(attrs) {
+        return None;
+    }
+    let kind = if has_attr(attrs, "new") {
+        Kind::New
+    } else if has_attr(attrs, "staticmethod") {
+        Kind::Static
+    } else if has_attr(attrs, "classmethod") {
+        Kind::Class
+    } else if has_attr(attrs, "getter") {
+        Kind::Getter
+    } else if has_attr(attrs, "setter") {
+        Kind::Setter
+    } else {
+        Kind::Instance
+    };
+
+    let mut signature = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("pyo3")) {
+        let Meta::List(list) = &attr.meta else { continue };
+        for option in split_commas(list.tokens.clone()) {
+            let tokens: Vec<TokenTree> = option.into_iter().collect();
+            match tokens.as_slice() {
+                [TokenTree::Ident(key), TokenTree::Punct(_), TokenTree::Group(group)] if key == "signature" => {
+                    signature = Some(group.stream());
+                }
+                [TokenTree::Ident(key), TokenTree::Punct(_), TokenTree::Literal(value)] if key == "name" => {
+                    name = value.to_string().trim_matches('"').to_string();
+                }
+                _ => {}
+            }
+        }
+    }
+    name = match kind {
+        Kind::Getter => name.strip_prefix("get_").unwrap_or(&name).to_string(),
+        Kind::Setter => name.strip_prefix("set_").unwrap_or(&name).to_string(),
+        _ => name,
+    };
+
+    // The receiver, `Python` tokens and the `slf`/`cls` parameter are not
+    // Python-visible arguments.
+    let mut arguments: Vec<(String, Type)> = Vec::new();
+    for (index, input) in function.sig.inputs.iter().enumerate() {
+        let FnArg::Typed(typed) = input else { continue };
+        let Pat::Ident(ident) = &*typed.pat else { continue };
+        let ident = ident.ident.to_string();
+        if is_python(&typed.ty) || (index == 0 && (ident == "slf" || ident == "cls")) {
+            continue;
+        }
+        arguments.push((ident, (*typed.ty).clone()));
+    }
+    let type_of = |name: &str| arguments.iter().find(|(argument, _)| argument == name).map(|(_, ty)| ty.clone());
+    let params = match signature {
+        Some(signature) => split_commas(signature)
+            .into_iter()
+            .map(|part| {
+                let tokens: Vec<TokenTree> = part.into_iter().collect();
+                let stars = tokens.iter().take_while(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == '*')).count();
+                match &tokens[stars..] {
+                    [] => Param { text: tokens.iter().map(ToString::to_string).collect(), ty: None, default: None },
+                    [TokenTree::Ident(ident), rest @ ..] => {
+                        let default = rest.get(1..).map(|default| python_literal(&default.iter().map(ToString::to_string).collect::<String>()));
+                        let ident = ident.to_string();
+                        Param { text: format!("{}{ident}", "*".repeat(stars)), ty: type_of(&ident), default }
+                    }
+                    _ => Param { text: part_text(&tokens), ty: None, default: None },
+                }
+            })
+            .collect(),
+        None => arguments.into_iter().map(|(name, ty)| Param { text: name, ty: Some(ty), default: None }).collect(),
+    };
+
+    let mut ret = match &function.sig.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some((**ty).clone()),
+    };
+    if name == "__next__" {
+        // Returning None ends the iteration.
+        ret = ret.map(|ty| inner_option(&unwrap_result(&ty)).unwrap_or(ty));
+    }
+    Some(Method { name, kind, params, ret, doc: doc_lines(attrs) })
+}
+
+fn part_text(tokens: &[TokenTree]) -> String {
+    tokens.iter().map(ToString::to_string).collect()
+}
+
+/// A signature default as Python source: literals as they are, and `...`
+/// for anything computed.
+fn python_literal(default: &str) -> String {
+    match default {
+        "None" => "None".to_string(),
+        "true" => "True".to_string(),
+        "false" => "False".to_string(),
+        _ if default.starts_with('"') || default.replace('_', "").parse::<f64>().is_ok() => default.to_string(),
+        _ => "...".to_string(),
+    }
+}
+
+fn is_python(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Python"))
+}
+
+/// The type arguments of the last segment of a path type.
+fn type_args(segment: &syn::PathSegment) -> Vec<&Type> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+fn unwrap_result(ty: &Type) -> Type {
+    match last_segment(ty) {
+        Some(segment) if segment.ident == "PyResult" || segment.ident == "Result" => {
+            type_args(segment).first().map_or_else(|| ty.clone(), |inner| (*inner).clone())
+        }
+        _ => ty.clone(),
+    }
+}
+
+fn inner_option(ty: &Type) -> Option<Type> {
+    let segment = last_segment(ty).filter(|segment| segment.ident == "Option")?;
+    type_args(segment).first().map(|inner| (*inner).clone())
+}
+
+/// The Python type PyO3 accepts (at `Position::Argument`) or produces (at
+/// `Position::Return`) for the Rust type `ty` in a method of `class`.
+fn python_type(ty: &Type, position: Position, class: &str, names: &HashMap<String, String>) -> String {
+    let convert = |ty: &Type| python_type(ty, position, class, names);
+    match ty {
+        Type::Reference(reference) => return convert(&reference.elem),
+        Type::Paren(paren) => return convert(&paren.elem),
+        Type::Group(group) => return convert(&group.elem),
+        Type::Slice(slice) => {
+            return match last_segment(&slice.elem) {
+                Some(segment) if segment.ident == "u8" => "bytes".to_string(),
+                _ => format!("Sequence[{}]", convert(&slice.elem)),
+            }
+        }
+        Type::Tuple(tuple) if tuple.elems.is_empty() => return "None".to_string(),
+        Type::Tuple(tuple) => return format!("tuple[{}]", tuple.elems.iter().map(convert).collect::<Vec<_>>().join(", ")),
+        _ => {}
+    }
+    let Some(segment) = last_segment(ty) else { return "Any".to_string() };
+    let args = type_args(segment);
+    let arg = |index: usize| args.get(index).map_or_else(|| "Any".to_string(), |ty| convert(ty));
+    let ident = segment.ident.to_string();
+    match ident.as_str() {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => "int".to_string(),
+        "f32" | "f64" => "float".to_string(),
+        "bool" => "bool".to_string(),
+        "String" | "str" | "char" => "str".to_string(),
+        "PathBuf" | "Path" if position == Position::Argument => "str | os.PathLike[str]".to_string(),
+        "PathBuf" | "Path" => "str".to_string(),
+        "Option" => format!("{} | None", arg(0)),
+        "Vec" if args.first().and_then(|arg| last_segment(arg)).is_some_and(|segment| segment.ident == "u8") => "bytes".to_string(),
+        "Vec" if position == Position::Argument => format!("Sequence[{}]", arg(0)),
+        "Vec" => format!("list[{}]", arg(0)),
+        "HashMap" | "BTreeMap" => format!("dict[{}, {}]", arg(0), arg(1)),
+        "HashSet" | "BTreeSet" => format!("set[{}]", arg(0)),
+        "PyResult" | "Result" | "Py" | "Bound" | "Borrowed" | "PyRef" | "PyRefMut" | "Cow" | "Box" => arg(0),
+        "PyAny" | "PyObject" => "Any".to_string(),
+        "PyDict" => "dict[Any, Any]".to_string(),
+        "PyList" => "list[Any]".to_string(),
+        "PyTuple" => "tuple[Any, ...]".to_string(),
+        "PyBytes" => "bytes".to_string(),
+        "PyString" => "str".to_string(),
+        "PyType" => "type".to_string(),
+        "PyFloat" => "float".to_string(),
+        "PyInt" => "int".to_string(),
+        "PyBool" => "bool".to_string(),
+        "PySet" => "set[Any]".to_string(),
+        "PyFrozenSet" => "frozenset[Any]".to_string(),
+        "PyIterator" => "Iterator[Any]".to_string(),
+        "PySequence" => "Sequence[Any]".to_string(),
+        "PyMapping" => "Mapping[Any, Any]".to_string(),
+        "Self" => class.to_string(),
+        // The crate's own argument types, which implement FromPyObject by hand.
+        "Batch" => format!("Iterable[{}]", arg(0)),
+        "KeyBatch" => "Iterable[int]".to_string(),
+        "Mergeable" => format!("{} | dict[Any, Any]", arg(0)),
+        "AnyTable" => "ElasticTable | IntTable | FloatTable".to_string(),
+        _ => names.get(&ident).cloned().unwrap_or_else(|| "Any".to_string()),
+    }
+}
+
+fn render(module: &Module, registered: &[&str]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by `cargo run -p xtask -- stubs` from the #[pymethods] blocks in src/; do not edit.\n\n");
+    out.push_str("import os\n");
+    out.push_str("from typing import Any, Generic, Iterable, Iterator, Mapping, Sequence, TypeVar\n\n");
+    out.push_str("_K = TypeVar(\"_K\")\n_V = TypeVar(\"_V\")\n");
+
+    for exception in &module.exceptions {
+        let _ = writeln!(out, "\n\nclass {}({}):", exception.name, exception.base);
+        let doc: Vec<String> = exception.doc.lines().map(str::to_string).collect();
+        write_doc(&mut out, &doc, "    ");
+    }
+
+    // The module's classes in the order lib.rs adds them, then the classes
+    // only handed out by methods, such as iterators and context managers.
+    let mut order: Vec<&str> = registered.iter().copied().filter(|name| module.classes.contains_key(*name)).collect();
+    order.extend(module.classes.keys().map(String::as_str).filter(|name| !registered.contains(name)));
+    // Classes the module does not export get a private name, so a type
+    // checker does not accept importing them.
+    let names: HashMap<String, String> = order
+        .iter()
+        .map(|&name| (name.to_string(), if registered.contains(&name) { name.to_string() } else { format!("_{name}") }))
+        .collect();
+    for rust_name in order {
+        let class = &module.classes[rust_name];
+        let name = &names[rust_name];
+        let generic = class.methods.iter().any(|method| method.name == "__class_getitem__");
+        let bases = if generic { "(Generic[_K, _V])" } else { "" };
+        let _ = writeln!(out, "\n\nclass {name}{bases}:");
+        let mut body = String::new();
+        if !class.doc.is_empty() {
+            write_doc(&mut body, &class.doc, "    ");
+        }
+        for method in &class.methods {
+            // Generic[...] already provides subscription to type checkers.
+            if method.kind == Kind::Setter || (generic && method.name == "__class_getitem__") {
+                continue;
+            }
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            write_method(&mut body, method, name, &names);
+            if method.kind == Kind::Getter {
+                let setter = class.methods.iter().find(|setter| setter.kind == Kind::Setter && setter.name == method.name);
+                if let Some(setter) = setter {
+                    write_method(&mut body, setter, name, &names);
+                }
+            }
+        }
+        if body.is_empty() {
+            body.push_str("    ...\n");
+        }
+        out.push_str(&body);
+    }
+    out
+}
+
+fn write_method(out: &mut String, method: &Method, class: &str, names: &HashMap<String, String>) {
+    let (decorator, receiver, name) = match method.kind {
+        Kind::Instance => (None, Some("self"), method.name.clone()),
+        Kind::New => (None, Some("self"), "__init__".to_string()),
+        Kind::Static => (Some("@staticmethod".to_string()), None, method.name.clone()),
+        Kind::Class => (Some("@classmethod".to_string()), Some("cls"), method.name.clone()),
+        Kind::Getter => (Some("@property".to_string()), Some("self"), method.name.clone()),
+        Kind::Setter => (Some(format!("@{}.setter", method.name)), Some("self"), method.name.clone()),
+    };
+    let mut params: Vec<String> = receiver.into_iter().map(str::to_string).collect();
+    for param in &method.params {
+        let mut text = param.text.clone();
+        if let Some(ty) = &param.ty {
+            // `*args` and `**kwargs` are typed by their elements, not by the
+            // tuple and dict PyO3 collects them into.
+            let ty = if text.starts_with('*') { "Any".to_string() } else { python_type(ty, Position::Argument, class, names) };
+            let _ = write!(text, ": {ty}");
+        }
+        if let Some(default) = &param.default {
+            let _ = write!(text, " = {default}");
+        }
+        params.push(text);
+    }
+    let in_place = name.starts_with("__i") && !matches!(name.as_str(), "__init__" | "__iter__");
+    let ret = match (&method.kind, &method.ret) {
+        (Kind::New | Kind::Setter, _) => "None".to_string(),
+        // PyO3 returns `self` from in-place operators that return ().
+        _ if in_place && method.ret.as_ref().is_none_or(|ty| python_type(&unwrap_result(ty), Position::Return, class, names) == "None") => {
+            class.to_string()
+        }
+        (_, None) => "None".to_string(),
+        (_, Some(ty)) => python_type(ty, Position::Return, class, names),
+    };
+    if name == "__richcmp__" {
+        // PyO3 serves all six comparisons from `__richcmp__`.
+        let operators = ["__eq__", "__ne__", "__lt__", "__le__", "__gt__", "__ge__"];
+        let defs: Vec<String> = operators.iter().map(|operator| format!("    def {operator}(self, other: Any) -> {ret}: ...\n")).collect();
+        out.push_str(&defs.join("\n"));
+        return;
+    }
+    if let Some(decorator) = decorator {
+        let _ = writeln!(out, "    {decorator}");
+    }
+    let _ = write!(out, "    def {name}({}) -> {ret}:", params.join(", "));
+    if method.doc.is_empty() || method.kind == Kind::Setter {
+        out.push_str(" ...\n");
+    } else {
+        out.push('\n');
+        write_doc(out, &method.doc, "        ");
+    }
+}
+
+fn write_doc(out: &mut String, lines: &[String], indent: &str) {
+    let escaped: Vec<String> = lines.iter().map(|line| line.replace('\\', "\\\\").replace("\"\"\"", "\\\"\\\"\\\"")).collect();
+    match escaped.as_slice() {
+        [] => {
+            let _ = writeln!(out, "{indent}...");
+        }
+        [line] => {
+            let _ = writeln!(out, "{indent}\"\"\"{line}\"\"\"");
+        }
+        [first, rest @ ..] => {
+            let _ = writeln!(out, "{indent}\"\"\"{first}");
+            for line in rest {
+                if line.is_empty() {
+                    out.push('\n');
+                } else {
+                    let _ = writeln!(out, "{indent}{line}");
+                }
+            }
+            let _ = writeln!(out, "{indent}\"\"\"");
+        }
+    }
+}